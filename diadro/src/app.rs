@@ -1,27 +1,112 @@
-use crate::graph::Graphics;
-use crate::ws::{MousePosition, RequestInfo, WsMessages};
+use crate::graph::{
+    remove_stale_cursors, update_remote_cursor, Alignment, GraphFigure, Graphics, RectFigure,
+    RemoteCursors,
+};
+use crate::ws::{enqueue_offline, MessageEncoding, MousePosition, RequestInfo, WsMessages};
 use chrono::{DateTime, Duration, Utc};
 use eframe::egui;
 use eframe::egui::Vec2;
 use uuid::Uuid;
-use {std::cell::RefCell, std::rc::Rc};
+use {std::cell::Cell, std::cell::RefCell, std::collections::VecDeque, std::rc::Rc};
 
 // ! For WASM only
 #[cfg(target_arch = "wasm32")]
+use crate::auth::LoginState;
+#[cfg(target_arch = "wasm32")]
 use wasm_sockets::EventClient;
 
+/// Size of the diagram overview window content
+const MINIMAP_SIZE: Vec2 = Vec2::new(200., 150.);
+
+/// Name of the page created on start
+const DEFAULT_PAGE: &str = "Page 1";
+/// Identifier of the page created on start, the same for all clients of the board
+const DEFAULT_PAGE_ID: &str = "default";
+
+/// Number of simulation steps of automatic layout
+const LAYOUT_ITERATIONS: u32 = 100;
+/// Repulsion and attraction factors of automatic layout, connected figures tend to be
+/// 100 points apart
+const LAYOUT_REPULSION: f32 = 1e6;
+const LAYOUT_ATTRACTION: f32 = 1.;
+
+/// Page of the diagram, messages of the board users refer page by its identifier
+struct Page {
+    id: String,
+    name: String,
+    graphics: Graphics,
+}
+
+impl Page {
+    fn new(id: String, name: String) -> Self {
+        Self {
+            id,
+            name,
+            graphics: Graphics::default(),
+        }
+    }
+}
+
+/// Kind of the file chosen by user
+#[derive(Clone, Copy)]
+enum FileKind {
+    /// Pages saved by File→Save
+    Diagram,
+    /// draw.io document imported into the active page
+    DrawIo,
+}
+
+impl FileKind {
+    /// Name and extensions of the file dialog filter
+    fn filter(self) -> (&'static str, &'static [&'static str]) {
+        match self {
+            FileKind::Diagram => ("Diagram", &["json"]),
+            FileKind::DrawIo => ("draw.io", &["drawio", "xml"]),
+        }
+    }
+}
+
 pub struct TemplateApp {
     #[allow(dead_code)]
     id: String,
-    plot: Graphics,
+    /// Diagram pages
+    pages: Vec<Page>,
+    /// Index of the page shown in central panel
+    active_page: usize,
+    /// Index of the page being renamed and its edited name
+    renamed_page: Option<(usize, String)>,
     ctx: Option<egui::Context>,
     packet_start: Option<DateTime<Utc>>,
     packet: Vec<WsMessages>,
     incoming_messages: Rc<RefCell<Vec<WsMessages>>>,
+    /// Encoding of sent messages, switched after server acknowledges `WsMessages::Hello`
+    encoding: Rc<Cell<MessageEncoding>>,
+    /// Messages which could not be sent, they are sent when connection is established
+    offline_queue: Rc<RefCell<VecDeque<WsMessages>>>,
+    /// Cursors of other users connected to the board
+    remote_cursors: RemoteCursors,
+    /// Show diagram overview in the corner of canvas
+    show_minimap: bool,
+    /// Show sidebar with saved views
+    show_bookmarks: bool,
+    /// Show window with diagram title and description
+    show_about: bool,
+    /// Presentation mode: menu and tool windows are hidden, diagram is read-only.
+    /// Escape leaves the mode
+    presentation: bool,
 
     #[cfg(target_arch = "wasm32")]
     /// ! For WASM Only
     client: Rc<RefCell<Option<EventClient>>>,
+
+    #[cfg(target_arch = "wasm32")]
+    /// ! For WASM Only
+    login: Rc<RefCell<LoginState>>,
+
+    #[cfg(target_arch = "wasm32")]
+    /// Kind and content of the file chosen by user, file is read asynchronously
+    /// ! For WASM Only
+    imported: Rc<RefCell<Option<(FileKind, String)>>>,
 }
 
 impl Default for TemplateApp {
@@ -29,11 +114,23 @@ impl Default for TemplateApp {
     fn default() -> Self {
         Self {
             id: Uuid::new_v4().to_string(),
-            plot: Graphics::default(),
+            pages: vec![Page::new(
+                DEFAULT_PAGE_ID.to_owned(),
+                DEFAULT_PAGE.to_owned(),
+            )],
+            active_page: 0,
+            renamed_page: None,
             ctx: None,
             packet_start: None,
             packet: Default::default(),
             incoming_messages: Rc::new(RefCell::new(Default::default())),
+            encoding: Default::default(),
+            offline_queue: Default::default(),
+            remote_cursors: Default::default(),
+            show_minimap: false,
+            show_bookmarks: false,
+            show_about: false,
+            presentation: false,
         }
     }
 
@@ -41,22 +138,45 @@ impl Default for TemplateApp {
     fn default() -> Self {
         Self {
             id: Uuid::new_v4().to_string(),
-            plot: Graphics::default(),
+            pages: vec![Page::new(
+                DEFAULT_PAGE_ID.to_owned(),
+                DEFAULT_PAGE.to_owned(),
+            )],
+            active_page: 0,
+            renamed_page: None,
             ctx: None,
             packet_start: None,
             packet: vec![],
             client: Rc::new(RefCell::new(None)),
             incoming_messages: Default::default(),
+            encoding: Default::default(),
+            offline_queue: Default::default(),
+            remote_cursors: Default::default(),
+            show_minimap: false,
+            show_bookmarks: false,
+            show_about: false,
+            presentation: false,
+            login: Default::default(),
+            imported: Default::default(),
         }
     }
 }
 
 /// Implies web-socket communications
 impl TemplateApp {
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn new(_cc: &eframe::CreationContext<'_>) -> Self {
         Default::default()
     }
 
+    #[cfg(target_arch = "wasm32")]
+    pub fn new(_cc: &eframe::CreationContext<'_>) -> Self {
+        let app: Self = Default::default();
+        // Complete login if the page is opened after redirect from OAuth2 provider
+        crate::auth::restore_login(app.login.clone());
+        app
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     /// web-socket processing threaad for not desktop application
     /// ! for desktop only code
@@ -79,28 +199,67 @@ impl TemplateApp {
     }
 
     #[cfg(not(target_arch = "wasm32"))]
-    /// Send web socket message for Desktop application
+    /// Send web socket message for Desktop application. Desktop application has no connection
+    /// yet, so messages are kept in offline queue
     /// ! for desktop only code
-    fn send(&self, _message: &str) {
-        todo!();
+    fn send(&self, messages: Vec<WsMessages>) {
+        enqueue_offline(&mut self.offline_queue.borrow_mut(), messages);
     }
 
     #[cfg(target_arch = "wasm32")]
-    /// Send web-socket message for WASM application
+    /// Send web-socket messages for WASM application using negotiated encoding. Messages are
+    /// kept in offline queue if socket is not connected
     /// ! for WASM only
-    fn send(&self, message: &str) {
-        if let Some(client) = self.client.borrow().as_ref() {
-            match client.send_string(message) {
+    fn send(&self, messages: Vec<WsMessages>) {
+        let client = self.client.borrow();
+        let connected = client
+            .as_ref()
+            .filter(|client| *client.status.borrow() == wasm_sockets::ConnectionStatus::Connected);
+
+        if let Some(client) = connected {
+            let result = match self.encoding.get() {
+                MessageEncoding::Json => match crate::ws::to_json(&messages) {
+                    Ok(message) => client.send_string(&message),
+                    Err(err) => {
+                        tracing::error!("Error serializing messages: {:?}", err);
+                        return;
+                    }
+                },
+                MessageEncoding::Bincode => match crate::ws::to_bincode(&messages) {
+                    Ok(data) => client.send_binary(data),
+                    Err(err) => {
+                        tracing::error!("Error encoding messages: {:?}", err);
+                        return;
+                    }
+                },
+            };
+
+            match result {
                 Ok(_) => tracing::debug!("WebSocket message sent successfully"),
-                Err(err) => tracing::error!("Error sending ws message: {:?}", err),
+                Err(err) => {
+                    tracing::error!("Error sending ws message: {:?}", err);
+                    enqueue_offline(&mut self.offline_queue.borrow_mut(), messages);
+                }
             }
+        } else {
+            enqueue_offline(&mut self.offline_queue.borrow_mut(), messages);
         }
     }
 
     /// Send message to web-socket using buffer
     /// Works in both desktop and WASM build because update in egui called enough offen to
     /// avoid special timers to send buffered data
-    fn send_buffered(&mut self, message: WsMessages) {
+    fn send_buffered(&mut self, mut message: WsMessages) {
+        // Server drops messages with already received identifiers, so frames replayed after
+        // network failures are not applied twice. Mouse positions are frequent and harmless
+        // to replay, so they are not identified
+        if !matches!(message, WsMessages::MousePosition(_)) {
+            if let Some(rq) = message.request_info_mut() {
+                rq.message_id
+                    .get_or_insert_with(|| Uuid::new_v4().to_string());
+            }
+        }
+
         match self.packet_start {
             None => {
                 self.packet.push(message);
@@ -109,15 +268,8 @@ impl TemplateApp {
             Some(start) if Utc::now() - start > Duration::microseconds(100) => {
                 if !self.packet.is_empty() {
                     tracing::info!("Packet: {}", self.packet.len());
-                    match serde_json::to_string(&self.packet) {
-                        Ok(msg) => {
-                            tracing::debug!("Sending messages");
-                            self.send(&msg);
-                        }
-                        Err(err) => tracing::error!("Error serializing messages: {:?}", err),
-                    }
-
-                    self.packet.clear();
+                    let packet = std::mem::take(&mut self.packet);
+                    self.send(packet);
                 }
 
                 self.packet.push(message);
@@ -134,9 +286,16 @@ impl TemplateApp {
         if self.client.borrow().is_none() {
             tracing::debug!("Starting websocket commincation inside WASM");
 
+            let token = match &*self.login.borrow() {
+                LoginState::LoggedIn { access_token, .. } => {
+                    format!("?token={}", urlencoding::encode(access_token))
+                }
+                _ => String::new(),
+            };
+
             let window = match web_sys::window() {
                 Some(wnd) => format!(
-                    "{}://{}:{}/ws/{}",
+                    "{}://{}:{}/ws/{}{}",
                     match wnd.location().protocol() {
                         Ok(proto) if proto.as_str() == "http:" => "ws",
                         Ok(proto) if proto.as_str() == "https:" => "wss",
@@ -147,9 +306,10 @@ impl TemplateApp {
                     },
                     wnd.location().hostname().unwrap(),
                     wnd.location().port().unwrap(),
-                    self.id
+                    self.id,
+                    token
                 ),
-                None => format!("ws://127.0.0.1:8081/ws/{}", self.id),
+                None => format!("ws://127.0.0.1:8081/ws/{}{}", self.id, token),
             };
 
             tracing::info!("WS location: {}", &window);
@@ -159,10 +319,36 @@ impl TemplateApp {
                 tracing::error!("{:#?}", error);
             })));
 
-            client.set_on_connection(Some(Box::new(|client: &wasm_sockets::EventClient| {
+            let offline_queue = self.offline_queue.clone();
+            client.set_on_connection(Some(Box::new(move |client: &wasm_sockets::EventClient| {
                 tracing::info!("{:#?}", client.status);
-                tracing::info!("Sending message...");
-                client.send_string("Hello, World!").unwrap();
+                tracing::info!("Sending handshake...");
+                // Messages are sent as json until server acknowledges preferred encoding,
+                // encoding is negotiated after server accepts protocol version
+                match crate::ws::to_json(&[WsMessages::Handshake {
+                    version: crate::ws::PROTOCOL_VERSION,
+                }]) {
+                    Ok(handshake) => {
+                        if let Err(err) = client.send_string(&handshake) {
+                            tracing::error!("Error sending handshake: {:?}", err);
+                        }
+                    }
+                    Err(err) => tracing::error!("Error serializing handshake: {}", err),
+                }
+
+                // Replay messages queued while socket was not connected
+                let queued: Vec<WsMessages> = offline_queue.borrow_mut().drain(..).collect();
+                if !queued.is_empty() {
+                    tracing::info!("Sending {} queued messages", queued.len());
+                    match crate::ws::to_json(&queued) {
+                        Ok(message) => {
+                            if let Err(err) = client.send_string(&message) {
+                                tracing::error!("Error sending queued messages: {:?}", err);
+                            }
+                        }
+                        Err(err) => tracing::error!("Error serializing queued messages: {}", err),
+                    }
+                }
             })));
 
             let clone_cl = self.client.clone();
@@ -172,17 +358,35 @@ impl TemplateApp {
             })));
 
             let incoming_messages = self.incoming_messages.clone();
+            let encoding = self.encoding.clone();
             client.set_on_message(Some(Box::new(
                 move |client: &wasm_sockets::EventClient, message: wasm_sockets::Message| {
-                    match message {
+                    let messages = match message {
                         wasm_sockets::Message::Text(text) => {
-                            let m = text.trim();
-                            match serde_json::from_str::<Vec<WsMessages>>(m) {
-                                Ok(v) => incoming_messages.borrow_mut().extend(v),
-                                Err(err) => tracing::error!("{}", err),
+                            crate::ws::from_json(text.trim()).map_err(|err| err.to_string())
+                        }
+                        wasm_sockets::Message::Binary(data) => {
+                            crate::ws::from_bincode(&data).map_err(|err| err.to_string())
+                        }
+                    };
+
+                    match messages {
+                        Ok(messages) => {
+                            for msg in messages.iter() {
+                                match msg {
+                                    WsMessages::HandshakeAck { .. } => send_hello(client),
+                                    WsMessages::HelloAck { encoding: name } => {
+                                        // Unknown encoding means that json is still used
+                                        encoding.set(
+                                            MessageEncoding::from_name(name).unwrap_or_default(),
+                                        );
+                                    }
+                                    _ => {}
+                                }
                             }
+                            incoming_messages.borrow_mut().extend(messages)
                         }
-                        _ => tracing::error!("Unknown message incoming"),
+                        Err(err) => tracing::error!("{}", err),
                     }
                 },
             )));
@@ -192,6 +396,426 @@ impl TemplateApp {
     }
 }
 
+#[cfg(target_arch = "wasm32")]
+/// Ask server for binary encoding of the messages, sent after server accepts protocol version
+/// ! for WASM only
+fn send_hello(client: &EventClient) {
+    match crate::ws::to_json(&[WsMessages::Hello {
+        preferred_encoding: MessageEncoding::Bincode.name().to_owned(),
+    }]) {
+        Ok(hello) => {
+            if let Err(err) = client.send_string(&hello) {
+                tracing::error!("Error sending hello: {:?}", err);
+            }
+        }
+        Err(err) => tracing::error!("Error serializing hello: {}", err),
+    }
+}
+
+/// Implies saving exported diagrams
+impl TemplateApp {
+    #[cfg(not(target_arch = "wasm32"))]
+    /// Save exported file into current directory
+    /// ! for desktop only code
+    fn save_file(&self, name: &str, data: Vec<u8>) {
+        match std::fs::write(name, data) {
+            Ok(_) => tracing::info!("Diagram exported to {}", name),
+            Err(err) => tracing::error!("Error saving file {}: {}", name, err),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    /// Desktop application does not require login
+    /// ! for desktop only code
+    fn login_ui(&self, _ui: &mut egui::Ui) {}
+
+    #[cfg(target_arch = "wasm32")]
+    /// Show login button or logged in user
+    /// ! for WASM only
+    fn login_ui(&self, ui: &mut egui::Ui) {
+        let state = self.login.borrow().clone();
+        match state {
+            LoginState::NeedsLogin => {
+                if ui.button("Login").clicked() {
+                    match crate::auth::login() {
+                        Ok(state) => {
+                            self.login.replace(state);
+                        }
+                        Err(err) => tracing::error!("Error starting login: {:?}", err),
+                    }
+                }
+            }
+            LoginState::Pending { .. } => {
+                ui.label("Logging in...");
+            }
+            LoginState::LoggedIn { user_id, .. } => {
+                ui.label(user_id);
+            }
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    /// Download exported file using browser
+    /// ! for WASM only
+    fn save_file(&self, name: &str, data: Vec<u8>) {
+        use eframe::wasm_bindgen::JsCast;
+
+        let download = || -> Result<(), eframe::wasm_bindgen::JsValue> {
+            let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(data.as_slice()));
+            let blob = web_sys::Blob::new_with_u8_array_sequence(&parts)?;
+            let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+            let document = web_sys::window()
+                .and_then(|wnd| wnd.document())
+                .ok_or_else(|| eframe::wasm_bindgen::JsValue::from_str("No document"))?;
+            let anchor = document
+                .create_element("a")?
+                .dyn_into::<web_sys::HtmlAnchorElement>()?;
+            anchor.set_href(&url);
+            anchor.set_download(name);
+            anchor.click();
+            web_sys::Url::revoke_object_url(&url)
+        };
+
+        if let Err(err) = download() {
+            tracing::error!("Error downloading file {}: {:?}", name, err);
+        }
+    }
+}
+
+/// Implies multi-page documents
+impl TemplateApp {
+    /// Diagram of the active page
+    fn plot(&self) -> &Graphics {
+        &self.pages[self.active_page].graphics
+    }
+
+    /// Mutable diagram of the active page
+    fn plot_mut(&mut self) -> &mut Graphics {
+        &mut self.pages[self.active_page].graphics
+    }
+
+    /// Request information of the messages sent by application
+    fn request_info(&self) -> RequestInfo {
+        RequestInfo {
+            board: "Main".to_string(),
+            user: self.id.clone(),
+            message_id: None,
+            page_id: None,
+        }
+    }
+
+    /// Draw tabs of the pages. Click on tab shows the page, double click starts renaming,
+    /// "+" appends blank page. Added pages are sent to the board users, each user switches
+    /// pages separately
+    fn pages_ui(&mut self, ui: &mut egui::Ui) {
+        let mut switched = None;
+        let mut renamed = None;
+        let mut rename_done = false;
+        for (idx, page) in self.pages.iter_mut().enumerate() {
+            match &mut self.renamed_page {
+                Some((page, new_name)) if *page == idx => {
+                    let response = ui.text_edit_singleline(new_name);
+                    if response.lost_focus() {
+                        let new_name = new_name.trim();
+                        if !new_name.is_empty() {
+                            page.name = new_name.to_owned();
+                        }
+                        rename_done = true;
+                    } else if !response.has_focus() {
+                        response.request_focus();
+                    }
+                }
+                _ => {
+                    let response = ui.selectable_label(idx == self.active_page, page.name.as_str());
+                    if response.double_clicked() {
+                        renamed = Some((idx, page.name.clone()));
+                    } else if response.clicked() {
+                        switched = Some(idx);
+                    }
+                }
+            }
+        }
+
+        if rename_done {
+            self.renamed_page = None;
+        }
+
+        if renamed.is_some() {
+            self.renamed_page = renamed;
+        }
+
+        if ui.button("+").on_hover_text("Add page").clicked() {
+            let page_id = Uuid::new_v4().to_string();
+            let page_name = format!("Page {}", self.pages.len() + 1);
+            self.pages
+                .push(Page::new(page_id.clone(), page_name.clone()));
+            switched = Some(self.pages.len() - 1);
+            self.send_buffered(WsMessages::AddPage {
+                rq: self.request_info(),
+                page_id,
+                page_name,
+            });
+        }
+
+        if let Some(page) = switched {
+            self.active_page = page;
+        }
+    }
+
+    /// Apply pages added by other users of the board. Messages of other pages are applied to
+    /// them at once, messages of the active page are left for the canvas
+    fn receive_pages(&mut self) {
+        let incoming = std::mem::take(&mut *self.incoming_messages.borrow_mut());
+        let mut active = Vec::with_capacity(incoming.len());
+        for mut msg in incoming {
+            if let WsMessages::AddPage {
+                page_id, page_name, ..
+            } = &msg
+            {
+                if !self.pages.iter().any(|page| page.id == *page_id) {
+                    self.pages
+                        .push(Page::new(page_id.clone(), page_name.clone()));
+                }
+                continue;
+            }
+
+            let page_id = msg.request_info_mut().and_then(|rq| rq.page_id.clone());
+            match page_id {
+                Some(page_id) => match self.pages.iter().position(|page| page.id == page_id) {
+                    Some(page) if page == self.active_page => active.push(msg),
+                    Some(page) => self.pages[page]
+                        .graphics
+                        .apply_messages(std::slice::from_ref(&msg)),
+                    None => tracing::warn!("Message of unknown page {} is dropped", page_id),
+                },
+                None => {
+                    // Selection cleared by server when user leaves is not bound to a page
+                    if let WsMessages::SelectionChange { .. } = msg {
+                        for (idx, page) in self.pages.iter_mut().enumerate() {
+                            if idx != self.active_page {
+                                page.graphics.apply_messages(std::slice::from_ref(&msg));
+                            }
+                        }
+                    }
+                    active.push(msg);
+                }
+            }
+        }
+        self.incoming_messages.borrow_mut().extend(active);
+    }
+
+    /// Replace all pages by the ones saved by File→Save. First page takes place of the page
+    /// created on start, other pages are added to the board
+    fn load_pages(&mut self, json: &str) {
+        let pages = match Graphics::pages_from_json(json) {
+            Ok(pages) if !pages.is_empty() => pages,
+            Ok(_) => {
+                tracing::error!("Error loading pages: file has no pages");
+                return;
+            }
+            Err(err) => {
+                tracing::error!("Error loading pages: {}", err);
+                return;
+            }
+        };
+
+        self.pages.clear();
+        for (idx, (name, graphics)) in pages.into_iter().enumerate() {
+            let id = if idx == 0 {
+                DEFAULT_PAGE_ID.to_owned()
+            } else {
+                let page_id = Uuid::new_v4().to_string();
+                self.send_buffered(WsMessages::AddPage {
+                    rq: self.request_info(),
+                    page_id: page_id.clone(),
+                    page_name: name.clone(),
+                });
+                page_id
+            };
+            self.pages.push(Page { id, name, graphics });
+        }
+        self.active_page = 0;
+        self.renamed_page = None;
+        tracing::info!("Diagram loaded");
+    }
+}
+
+/// Implies opening saved diagrams and importing draw.io documents
+impl TemplateApp {
+    /// Replace current diagram by the imported one
+    fn load_drawio(&mut self, xml: &str) {
+        match crate::import::import_drawio(xml) {
+            Ok(plot) => {
+                *self.plot_mut() = plot;
+                tracing::info!("Diagram imported");
+            }
+            Err(err) => tracing::error!("Error importing diagram: {}", err),
+        }
+    }
+
+    /// Load content of the file chosen by user
+    fn load_file(&mut self, kind: FileKind, content: &str) {
+        match kind {
+            FileKind::Diagram => self.load_pages(content),
+            FileKind::DrawIo => self.load_drawio(content),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    /// Choose file of the given kind using file dialog and load it
+    /// ! for desktop only code
+    fn import_file(&mut self, kind: FileKind) {
+        let (name, extensions) = kind.filter();
+        let path = match rfd::FileDialog::new()
+            .add_filter(name, extensions)
+            .pick_file()
+        {
+            Some(path) => path,
+            None => return,
+        };
+
+        match std::fs::read_to_string(&path) {
+            Ok(content) => self.load_file(kind, &content),
+            Err(err) => tracing::error!("Error reading file {}: {}", path.display(), err),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    /// Files are imported synchronously
+    /// ! for desktop only code
+    fn poll_import(&mut self) {}
+
+    #[cfg(target_arch = "wasm32")]
+    /// Choose file of the given kind using browser file input. File is read asynchronously,
+    /// diagram is replaced by `poll_import`
+    /// ! for WASM only
+    fn import_file(&mut self, kind: FileKind) {
+        let imported = self.imported.clone();
+        let ctx = self.ctx.clone();
+        let (name, extensions) = kind.filter();
+        wasm_bindgen_futures::spawn_local(async move {
+            let file = match rfd::AsyncFileDialog::new()
+                .add_filter(name, extensions)
+                .pick_file()
+                .await
+            {
+                Some(file) => file,
+                None => return,
+            };
+
+            match String::from_utf8(file.read().await) {
+                Ok(content) => {
+                    imported.replace(Some((kind, content)));
+                    if let Some(ctx) = ctx {
+                        ctx.request_repaint();
+                    }
+                }
+                Err(err) => tracing::error!("Error reading file {}: {}", file.file_name(), err),
+            }
+        });
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    /// Import file content read by `import_file`
+    /// ! for WASM only
+    fn poll_import(&mut self) {
+        let imported = self.imported.borrow_mut().take();
+        if let Some((kind, content)) = imported {
+            self.load_file(kind, &content);
+        }
+    }
+}
+
+/// Implies copying exported diagrams into clipboard
+impl TemplateApp {
+    #[cfg(not(target_arch = "wasm32"))]
+    /// Put diagram as svg text into clipboard
+    /// ! for desktop only code
+    fn copy_svg(&self) {
+        let svg = self.plot().export_svg();
+        match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(svg)) {
+            Ok(_) => tracing::info!("Diagram copied as SVG"),
+            Err(err) => tracing::error!("Error copying SVG: {}", err),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    /// Put diagram as image into clipboard
+    /// ! for desktop only code
+    fn copy_image(&self) {
+        let (width, height, pixels) = match self.plot().export_rgba() {
+            Some(image) => image,
+            None => return,
+        };
+
+        let image = arboard::ImageData {
+            width,
+            height,
+            bytes: pixels.into(),
+        };
+
+        match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_image(image)) {
+            Ok(_) => tracing::info!("Diagram copied as image"),
+            Err(err) => tracing::error!("Error copying image: {}", err),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    /// Put diagram as svg text into clipboard
+    /// ! for WASM only
+    fn copy_svg(&self) {
+        let clipboard = match web_sys::window().and_then(|wnd| wnd.navigator().clipboard()) {
+            Some(clipboard) => clipboard,
+            None => {
+                tracing::error!("Clipboard is not available");
+                return;
+            }
+        };
+
+        let promise = clipboard.write_text(&self.plot().export_svg());
+        wasm_bindgen_futures::spawn_local(async move {
+            match wasm_bindgen_futures::JsFuture::from(promise).await {
+                Ok(_) => tracing::info!("Diagram copied as SVG"),
+                Err(err) => tracing::error!("Error copying SVG: {:?}", err),
+            }
+        });
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    /// Put diagram as png image into clipboard
+    /// ! for WASM only
+    fn copy_image(&self) {
+        use eframe::wasm_bindgen::JsValue;
+
+        let copy = || -> Result<js_sys::Promise, JsValue> {
+            let clipboard = web_sys::window()
+                .and_then(|wnd| wnd.navigator().clipboard())
+                .ok_or_else(|| JsValue::from_str("Clipboard is not available"))?;
+
+            let png = self.plot().export_png();
+            let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(png.as_slice()));
+            let mut options = web_sys::BlobPropertyBag::new();
+            options.type_("image/png");
+            let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options)?;
+
+            let items = js_sys::Object::new();
+            js_sys::Reflect::set(&items, &JsValue::from_str("image/png"), &blob)?;
+            let item = web_sys::ClipboardItem::new_with_record_from_str_to_blob_promise(&items)?;
+            Ok(clipboard.write(&js_sys::Array::of1(&item)))
+        };
+
+        match copy() {
+            Ok(promise) => wasm_bindgen_futures::spawn_local(async move {
+                match wasm_bindgen_futures::JsFuture::from(promise).await {
+                    Ok(_) => tracing::info!("Diagram copied as image"),
+                    Err(err) => tracing::error!("Error copying image: {:?}", err),
+                }
+            }),
+            Err(err) => tracing::error!("Error copying image: {:?}", err),
+        }
+    }
+}
+
 impl eframe::App for TemplateApp {
     /// Called each time the UI needs repainting, which may be many times per second.
     /// Put your widgets into a `SidePanel`, `TopPanel`, `CentralPanel`, `Window` or `Area`.
@@ -202,36 +826,291 @@ impl eframe::App for TemplateApp {
 
         // Send mouse position
         if let Some(pos) = ctx.input().pointer.hover_pos() {
+            let rq = RequestInfo {
+                page_id: Some(self.pages[self.active_page].id.clone()),
+                ..self.request_info()
+            };
             self.send_buffered(WsMessages::MousePosition(MousePosition {
-                rq: RequestInfo {
-                    board: "Main".to_string(),
-                    user: self.id.clone(),
-                },
+                rq,
                 position: pos,
             }));
         }
 
         self.start_read_ws(ctx);
+        self.poll_import();
+        self.receive_pages();
+
+        // Ctrl-Shift-C copies diagram as SVG, Ctrl-Alt-C copies diagram as image
+        let (copy_svg, copy_image) = {
+            let input = ctx.input();
+            let copy = input.modifiers.command && input.key_pressed(egui::Key::C);
+            (
+                copy && input.modifiers.shift && !input.modifiers.alt,
+                copy && input.modifiers.alt && !input.modifiers.shift,
+            )
+        };
+
+        if copy_svg {
+            self.copy_svg();
+        }
+
+        if copy_image {
+            self.copy_image();
+        }
+
+        if self.presentation && ctx.input().key_pressed(egui::Key::Escape) {
+            self.presentation = false;
+        }
+
+        egui::TopBottomPanel::top("menu").show_animated(ctx, !self.presentation, |ui| {
+            egui::menu::bar(ui, |ui| {
+                ui.menu_button("File", |ui| {
+                    if ui.button("Save").clicked() {
+                        let pages = self
+                            .pages
+                            .iter()
+                            .map(|page| (page.name.as_str(), &page.graphics));
+                        match Graphics::pages_to_json(pages) {
+                            Ok(json) => self.save_file("diagram.json", json.into_bytes()),
+                            Err(err) => tracing::error!("Error saving pages: {}", err),
+                        }
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Open").clicked() {
+                        self.import_file(FileKind::Diagram);
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Import").clicked() {
+                        self.import_file(FileKind::DrawIo);
+                        ui.close_menu();
+                    }
+                });
+
+                ui.menu_button("Edit", |ui| {
+                    if ui.button("Copy as SVG").clicked() {
+                        self.copy_svg();
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Copy as Image").clicked() {
+                        self.copy_image();
+                        ui.close_menu();
+                    }
+                });
+
+                ui.menu_button("Export", |ui| {
+                    if ui.button("XLSX").clicked() {
+                        self.save_file("diagram.xlsx", self.plot().export_xlsx());
+                        ui.close_menu();
+                    }
+
+                    if ui.button("DOT").clicked() {
+                        self.save_file("diagram.dot", self.plot().to_dot().into_bytes());
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Mermaid").clicked() {
+                        self.save_file("diagram.mmd", self.plot().to_mermaid().into_bytes());
+                        ui.close_menu();
+                    }
+
+                    if ui.button("draw.io").clicked() {
+                        self.save_file(
+                            "diagram.drawio",
+                            crate::import::export_drawio(self.plot()).into_bytes(),
+                        );
+                        ui.close_menu();
+                    }
+                });
+
+                // Alignment makes sense for several figures only
+                if self.plot().selected_count() > 1 {
+                    ui.menu_button("Align", |ui| {
+                        for (alignment, label) in [
+                            (Alignment::Left, "Left"),
+                            (Alignment::CenterH, "Center horizontally"),
+                            (Alignment::Right, "Right"),
+                            (Alignment::Top, "Top"),
+                            (Alignment::CenterV, "Center vertically"),
+                            (Alignment::Bottom, "Bottom"),
+                        ] {
+                            if ui.button(label).clicked() {
+                                self.plot_mut().align_selected(alignment);
+                                ui.close_menu();
+                            }
+                        }
+
+                        ui.separator();
+                        if ui.button("Distribute horizontally").clicked() {
+                            self.plot_mut().distribute_horizontal();
+                            ui.close_menu();
+                        }
+
+                        if ui.button("Distribute vertically").clicked() {
+                            self.plot_mut().distribute_vertical();
+                            ui.close_menu();
+                        }
+                    });
+                }
+
+                if ui.button("Layout").clicked() {
+                    self.plot_mut().force_directed_layout(
+                        LAYOUT_ITERATIONS,
+                        LAYOUT_REPULSION,
+                        LAYOUT_ATTRACTION,
+                    );
+                }
+
+                let has_tool = self.plot().has_tool();
+                if ui
+                    .selectable_label(has_tool, "▭")
+                    .on_hover_text("Drag on canvas places rectangle instead of selecting")
+                    .clicked()
+                {
+                    let tool: Option<Box<dyn GraphFigure>> = if has_tool {
+                        None
+                    } else {
+                        Some(Box::new(RectFigure::default()))
+                    };
+                    self.plot_mut().select_tool(tool);
+                }
+
+                if ui.selectable_label(self.show_minimap, "Minimap").clicked() {
+                    self.show_minimap = !self.show_minimap;
+                }
+
+                if ui
+                    .selectable_label(self.show_bookmarks, "Bookmarks")
+                    .clicked()
+                {
+                    self.show_bookmarks = !self.show_bookmarks;
+                }
+
+                if ui.selectable_label(self.show_about, "About").clicked() {
+                    self.show_about = !self.show_about;
+                }
+
+                let read_only = self.plot().read_only();
+                if ui
+                    .selectable_label(read_only, if read_only { "🔒" } else { "🔓" })
+                    .on_hover_text("Forbid diagram changes")
+                    .clicked()
+                {
+                    self.plot_mut().set_read_only(!read_only);
+                }
+
+                if ui
+                    .button("Present")
+                    .on_hover_text("Hide menu and windows, press Escape to leave")
+                    .clicked()
+                {
+                    self.presentation = true;
+                    self.plot_mut().set_read_only(true);
+                }
+
+                ui.with_layout(egui::Layout::right_to_left(), |ui| self.login_ui(ui));
+            });
+        });
+
+        egui::TopBottomPanel::top("pages").show_animated(ctx, !self.presentation, |ui| {
+            ui.horizontal(|ui| self.pages_ui(ui));
+        });
+
+        egui::SidePanel::right("bookmarks").show_animated(
+            ctx,
+            self.show_bookmarks && !self.presentation,
+            |ui| {
+                ui.heading("Bookmarks");
+                self.plot_mut().bookmarks_ui(ui);
+            },
+        );
+
+        egui::SidePanel::right("properties").show_animated(ctx, !self.presentation, |ui| {
+            ui.heading("Properties");
+            self.plot_mut().show_properties_panel(ui);
+        });
+
+        if self.show_about && !self.presentation {
+            let mut open = true;
+            egui::Window::new("About this diagram")
+                .open(&mut open)
+                .resizable(false)
+                .show(ctx, |ui| self.plot_mut().about_ui(ui));
+            self.show_about = open;
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             egui::warn_if_debug_build(ui);
 
-            egui::Window::new("Window").show(ctx, |ui| {
-                ui.label("Windows can be moved by dragging them.");
-                ui.label("They are automatically sized based on contents.");
-                ui.label("You can turn on resizing and scrolling if you like.");
-                ui.label("You would normally chose either panels OR windows.");
-            });
+            // Tool windows are hidden in presentation mode
+            if !self.presentation {
+                egui::Window::new("Window").show(ctx, |ui| {
+                    ui.label("Windows can be moved by dragging them.");
+                    ui.label("They are automatically sized based on contents.");
+                    ui.label("You can turn on resizing and scrolling if you like.");
+                    ui.label("You would normally chose either panels OR windows.");
+                });
+
+                egui::Window::new("Statistics").show(ctx, |ui| {
+                    let (figures, edges) = self.plot().statistics();
+                    ui.label(format!("Figures: {}", figures));
+                    ui.label(format!("Edges: {}", edges));
+                    if let Some(len) = self.plot().highlighted_path_len() {
+                        ui.label(format!("Path length: {}", len));
+                    }
+                });
+            }
 
-            // let incoming_message = self.incoming_messages.borrow();
-            let msg = self.plot.ui(ui, self.incoming_messages.borrow());
-            if !msg.inner.is_empty() {
-                match serde_json::to_string(&msg.inner) {
-                    Ok(s) => self.send(&s),
-                    Err(err) => tracing::error!("Error serializing: {}", err),
+            // Accumulate cursors of other users
+            let now = Utc::now();
+            for msg in self.incoming_messages.borrow().iter() {
+                if let WsMessages::MousePosition(mp) = msg {
+                    if mp.rq.user != self.id {
+                        update_remote_cursor(
+                            &mut self.remote_cursors,
+                            &mp.rq.user,
+                            mp.position,
+                            now,
+                        );
+                    }
+                }
+            }
+            remove_stale_cursors(&mut self.remote_cursors, now);
+
+            let page = &mut self.pages[self.active_page];
+            let page_id = page.id.clone();
+            let msg = page
+                .graphics
+                .ui(ui, self.incoming_messages.borrow(), &self.remote_cursors);
+            for mut message in msg.inner {
+                if let Some(rq) = message.request_info_mut() {
+                    rq.board = "Main".to_string();
+                    rq.user = self.id.clone();
+                    rq.page_id = Some(page_id.clone());
                 }
+                self.send_buffered(message);
             }
 
             self.incoming_messages.borrow_mut().clear();
+
+            if self.show_minimap {
+                egui::Window::new("Minimap")
+                    .anchor(egui::Align2::RIGHT_BOTTOM, [-10., -10.])
+                    .collapsible(false)
+                    .resizable(false)
+                    .show(ctx, |ui| {
+                        let (response, painter) =
+                            ui.allocate_painter(MINIMAP_SIZE, egui::Sense::click());
+                        self.plot().render_to_painter(&painter, response.rect);
+                        if let Some(pos) = response.interact_pointer_pos() {
+                            if response.clicked() {
+                                self.plot_mut().minimap_clicked(response.rect, pos);
+                            }
+                        }
+                    });
+            }
         });
     }
 
@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+/// Authentication state of the client
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoginState {
+    /// User is not logged in
+    NeedsLogin,
+    /// Authorization code is requested from the provider
+    Pending {
+        code_verifier: String,
+        state: String,
+    },
+    /// Access token received
+    LoggedIn {
+        access_token: String,
+        user_id: String,
+    },
+}
+
+impl Default for LoginState {
+    fn default() -> Self {
+        LoginState::NeedsLogin
+    }
+}
+
+/// OAuth2 provider configuration. Injected into the page as `window.DIADRO_CONFIG.oauth`
+#[derive(Clone, Debug, Deserialize)]
+pub struct OAuthConfig {
+    pub authorize_url: String,
+    pub token_url: String,
+    pub client_id: String,
+    pub redirect_uri: String,
+    #[serde(default)]
+    pub scope: String,
+}
+
+/// Token endpoint response
+#[derive(Debug, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    #[serde(default)]
+    pub id_token: Option<String>,
+    #[serde(default)]
+    pub user_id: Option<String>,
+}
+
+impl TokenResponse {
+    /// Convert token response into logged in state. User identifier is taken from `user_id` field
+    /// or from `sub` claim of the id token
+    pub fn into_login_state(self) -> LoginState {
+        let user_id = self
+            .user_id
+            .or_else(|| self.id_token.as_deref().and_then(subject_from_id_token))
+            .unwrap_or_default();
+
+        LoginState::LoggedIn {
+            access_token: self.access_token,
+            user_id,
+        }
+    }
+}
+
+#[inline]
+fn base64_url(data: &[u8]) -> String {
+    base64::encode_config(data, base64::URL_SAFE_NO_PAD)
+}
+
+/// Generate url safe random string from given number of random bytes. Predictable values
+/// would break login protection, so there is no fallback if random source fails
+fn random_string(bytes: usize) -> Result<String, getrandom::Error> {
+    let mut data = vec![0u8; bytes];
+    getrandom::getrandom(&mut data)?;
+    Ok(base64_url(&data))
+}
+
+/// Generate PKCE code verifier, 43 characters long
+pub fn generate_code_verifier() -> Result<String, getrandom::Error> {
+    random_string(32)
+}
+
+/// Generate random state used to protect against CSRF
+pub fn generate_state() -> Result<String, getrandom::Error> {
+    random_string(16)
+}
+
+/// Compute PKCE S256 code challenge for the verifier
+pub fn code_challenge(code_verifier: &str) -> String {
+    base64_url(&Sha256::digest(code_verifier.as_bytes()))
+}
+
+/// Parse parameters from url fragment or query string
+/// ### Arguments
+/// * params - fragment or query string, leading `#` or `?` is skipped
+pub fn parse_params(params: &str) -> HashMap<String, String> {
+    params
+        .trim_start_matches(|c| c == '#' || c == '?')
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .filter_map(|(key, value)| {
+            urlencoding::decode(value)
+                .ok()
+                .map(|value| (key.to_owned(), value.into_owned()))
+        })
+        .collect()
+}
+
+/// Remove authorization response parameters from the query string, other parameters are kept
+/// ### Arguments
+/// * query - query string with leading `?` or empty
+/// ### Return
+/// query string with leading `?` or empty string if no parameters left
+pub fn strip_auth_params(query: &str) -> String {
+    let params: Vec<&str> = query
+        .trim_start_matches('?')
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .filter(|pair| {
+            let key = pair.split_once('=').map_or(*pair, |(key, _)| key);
+            !matches!(key, "code" | "state" | "session_state")
+        })
+        .collect();
+    if params.is_empty() {
+        String::new()
+    } else {
+        format!("?{}", params.join("&"))
+    }
+}
+
+/// Extract `sub` claim from id token. Signature is not validated, the token is received
+/// directly from the token endpoint
+fn subject_from_id_token(token: &str) -> Option<String> {
+    let payload = token.split('.').nth(1)?;
+    let data = base64::decode_config(payload, base64::URL_SAFE_NO_PAD).ok()?;
+    serde_json::from_slice::<serde_json::Value>(&data)
+        .ok()?
+        .get("sub")?
+        .as_str()
+        .map(str::to_owned)
+}
+
+impl OAuthConfig {
+    /// Build url of the provider authorization page
+    pub fn authorize_url(&self, code_challenge: &str, state: &str) -> String {
+        format!(
+            "{}?response_type=code&client_id={}&redirect_uri={}&scope={}&state={}&code_challenge={}&code_challenge_method=S256",
+            self.authorize_url,
+            urlencoding::encode(&self.client_id),
+            urlencoding::encode(&self.redirect_uri),
+            urlencoding::encode(&self.scope),
+            urlencoding::encode(state),
+            urlencoding::encode(code_challenge),
+        )
+    }
+
+    /// Build form encoded body of the token request
+    pub fn token_request_body(&self, code: &str, code_verifier: &str) -> String {
+        format!(
+            "grant_type=authorization_code&code={}&redirect_uri={}&client_id={}&code_verifier={}",
+            urlencoding::encode(code),
+            urlencoding::encode(&self.redirect_uri),
+            urlencoding::encode(&self.client_id),
+            urlencoding::encode(code_verifier),
+        )
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub use self::web::{login, restore_login};
+
+/// Browser specific part of the login flow
+/// ! For WASM only
+#[cfg(target_arch = "wasm32")]
+mod web {
+    use std::{cell::RefCell, rc::Rc};
+
+    use eframe::wasm_bindgen::JsValue;
+
+    use super::{
+        code_challenge, generate_code_verifier, generate_state, parse_params, strip_auth_params,
+        LoginState, OAuthConfig, TokenResponse,
+    };
+
+    const VERIFIER_KEY: &str = "diadro.pkce.verifier";
+    const STATE_KEY: &str = "diadro.pkce.state";
+
+    /// Read OAuth2 configuration injected into the page
+    pub fn config() -> Option<OAuthConfig> {
+        let window = web_sys::window()?;
+        let config = js_sys::Reflect::get(&window, &JsValue::from_str("DIADRO_CONFIG")).ok()?;
+        let oauth = js_sys::Reflect::get(&config, &JsValue::from_str("oauth")).ok()?;
+        let json = js_sys::JSON::stringify(&oauth).ok()?.as_string()?;
+        match serde_json::from_str(&json) {
+            Ok(config) => Some(config),
+            Err(err) => {
+                tracing::error!("Wrong OAuth2 configuration: {}", err);
+                None
+            }
+        }
+    }
+
+    fn session_storage() -> Result<web_sys::Storage, JsValue> {
+        web_sys::window()
+            .ok_or_else(|| JsValue::from_str("No window"))?
+            .session_storage()?
+            .ok_or_else(|| JsValue::from_str("No session storage"))
+    }
+
+    /// Start login: store PKCE verifier and redirect to the provider authorization page
+    pub fn login() -> Result<LoginState, JsValue> {
+        let config = config().ok_or_else(|| JsValue::from_str("OAuth2 is not configured"))?;
+        let random_error = |err: getrandom::Error| JsValue::from_str(&err.to_string());
+        let code_verifier = generate_code_verifier().map_err(random_error)?;
+        let state = generate_state().map_err(random_error)?;
+
+        // Verifier must survive the redirect
+        let storage = session_storage()?;
+        storage.set_item(VERIFIER_KEY, &code_verifier)?;
+        storage.set_item(STATE_KEY, &state)?;
+
+        let url = config.authorize_url(&code_challenge(&code_verifier), &state);
+        web_sys::window()
+            .ok_or_else(|| JsValue::from_str("No window"))?
+            .location()
+            .assign(&url)?;
+
+        Ok(LoginState::Pending {
+            code_verifier,
+            state,
+        })
+    }
+
+    /// Complete login after redirect back from the provider. Authorization code is taken from
+    /// url fragment (or query) and exchanged to the access token in background
+    pub fn restore_login(login: Rc<RefCell<LoginState>>) {
+        let location = match web_sys::window() {
+            Some(window) => window.location(),
+            None => return,
+        };
+
+        let mut params = parse_params(&location.hash().unwrap_or_default());
+        if !params.contains_key("code") {
+            params = parse_params(&location.search().unwrap_or_default());
+        }
+
+        let code = match params.remove("code") {
+            Some(code) => code,
+            None => return,
+        };
+
+        let (code_verifier, state) = match session_storage().and_then(|storage| {
+            let verifier = storage.get_item(VERIFIER_KEY)?;
+            let state = storage.get_item(STATE_KEY)?;
+            storage.remove_item(VERIFIER_KEY)?;
+            storage.remove_item(STATE_KEY)?;
+            Ok((verifier, state))
+        }) {
+            Ok((Some(verifier), Some(state))) => (verifier, state),
+            Ok(_) => {
+                tracing::error!("No PKCE verifier stored, login must be restarted");
+                return;
+            }
+            Err(err) => {
+                tracing::error!("Error reading session storage: {:?}", err);
+                return;
+            }
+        };
+
+        if params.get("state") != Some(&state) {
+            tracing::error!("OAuth2 state mismatch, login rejected");
+            return;
+        }
+
+        // Remove authorization code from the address bar, the code can be exchanged only once
+        let _ = location.set_hash("");
+        clear_auth_params(&location);
+
+        let config = match config() {
+            Some(config) => config,
+            None => return,
+        };
+
+        login.replace(LoginState::Pending {
+            code_verifier: code_verifier.clone(),
+            state,
+        });
+
+        wasm_bindgen_futures::spawn_local(async move {
+            match exchange_code(&config, &code, &code_verifier).await {
+                Ok(state) => {
+                    login.replace(state);
+                }
+                Err(err) => {
+                    tracing::error!("Error exchanging authorization code: {}", err);
+                    login.replace(LoginState::NeedsLogin);
+                }
+            }
+        });
+    }
+
+    /// Replace current url with the one without authorization response query parameters.
+    /// History entry is replaced, so the page is not reloaded
+    fn clear_auth_params(location: &web_sys::Location) {
+        let search = location.search().unwrap_or_default();
+        let clean = strip_auth_params(&search);
+        if clean == search {
+            return;
+        }
+
+        let url = format!("{}{}", location.pathname().unwrap_or_default(), clean);
+        let result = web_sys::window()
+            .ok_or_else(|| JsValue::from_str("No window"))
+            .and_then(|window| window.history())
+            .and_then(|history| history.replace_state_with_url(&JsValue::NULL, "", Some(&url)));
+        if let Err(err) = result {
+            tracing::error!("Error removing authorization code from url: {:?}", err);
+        }
+    }
+
+    /// Exchange authorization code to access token
+    async fn exchange_code(
+        config: &OAuthConfig,
+        code: &str,
+        code_verifier: &str,
+    ) -> Result<LoginState, String> {
+        let response = gloo_net::http::Request::post(&config.token_url)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(config.token_request_body(code, code_verifier))
+            .map_err(|err| err.to_string())?
+            .send()
+            .await
+            .map_err(|err| err.to_string())?;
+
+        if !response.ok() {
+            return Err(format!("Token endpoint returned {}", response.status()));
+        }
+
+        response
+            .json::<TokenResponse>()
+            .await
+            .map(TokenResponse::into_login_state)
+            .map_err(|err| err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        code_challenge, generate_code_verifier, parse_params, strip_auth_params, OAuthConfig,
+        TokenResponse,
+    };
+    use crate::auth::LoginState;
+
+    #[test]
+    fn test_code_challenge() {
+        // Example from RFC 7636, Appendix B
+        assert_eq!(
+            code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+
+        let verifier = generate_code_verifier().unwrap();
+        assert_eq!(verifier.len(), 43);
+        assert_ne!(verifier, generate_code_verifier().unwrap());
+    }
+
+    #[test]
+    fn test_strip_auth_params() {
+        assert_eq!(strip_auth_params("?code=abc&state=xyz"), "");
+        assert_eq!(
+            strip_auth_params("?board=Main&code=abc&state=xyz&page=2"),
+            "?board=Main&page=2"
+        );
+        assert_eq!(strip_auth_params(""), "");
+    }
+
+    #[test]
+    fn test_parse_params() {
+        let params = parse_params("#code=abc%20d&state=xyz");
+        assert_eq!(params.get("code").map(String::as_str), Some("abc d"));
+        assert_eq!(params.get("state").map(String::as_str), Some("xyz"));
+        assert!(parse_params("").is_empty());
+    }
+
+    #[test]
+    fn test_authorize_url() {
+        let config = OAuthConfig {
+            authorize_url: "https://auth.example.com/authorize".to_owned(),
+            token_url: "https://auth.example.com/token".to_owned(),
+            client_id: "diadro".to_owned(),
+            redirect_uri: "https://diadro.example.com/".to_owned(),
+            scope: "openid profile".to_owned(),
+        };
+
+        let url = config.authorize_url("challenge", "state");
+        assert!(url.starts_with("https://auth.example.com/authorize?response_type=code"));
+        assert!(url.contains("redirect_uri=https%3A%2F%2Fdiadro.example.com%2F"));
+        assert!(url.contains("scope=openid%20profile"));
+        assert!(url.contains("code_challenge_method=S256"));
+    }
+
+    #[test]
+    fn test_token_response_subject() {
+        // {"sub":"user-1"}
+        let response = TokenResponse {
+            access_token: "token".to_owned(),
+            id_token: Some("e30.eyJzdWIiOiJ1c2VyLTEifQ.sig".to_owned()),
+            user_id: None,
+        };
+
+        assert_eq!(
+            response.into_login_state(),
+            LoginState::LoggedIn {
+                access_token: "token".to_owned(),
+                user_id: "user-1".to_owned()
+            }
+        );
+    }
+}
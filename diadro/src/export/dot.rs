@@ -0,0 +1,119 @@
+use std::cell::RefCell;
+
+use crate::graph::{id_to_string, ConnectionPoint, Graphics};
+
+/// Quote string for Graphviz DOT format
+fn dot_quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Graphviz shape of the figure type
+#[inline]
+fn dot_shape(type_name: &str) -> &'static str {
+    match type_name {
+        "ellipse" => "ellipse",
+        "diamond" => "diamond",
+        "cylinder" => "cylinder",
+        _ => "rect",
+    }
+}
+
+/// Implies export into Graphviz DOT format
+impl Graphics {
+    /// Export diagram into Graphviz DOT format. Edges not connected to figures on both ends
+    /// are skipped
+    /// ### Return
+    /// directed graph description
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph G {\n");
+        for figure in self.figures() {
+            let figure = RefCell::borrow(figure);
+            dot.push_str(&format!(
+                "    {} [label={}, shape={}];\n",
+                dot_quote(&id_to_string(figure.id())),
+                dot_quote(&figure.label()),
+                dot_shape(figure.type_name())
+            ));
+        }
+
+        for edge in self.edges() {
+            let figure_id = |cp: &Option<ConnectionPoint>| {
+                cp.as_ref()
+                    .map(|cp| dot_quote(&id_to_string(RefCell::borrow(cp.get_figure()).id())))
+            };
+            if let (Some(start), Some(end)) = (
+                figure_id(edge.get_start_connection()),
+                figure_id(edge.get_end_connection()),
+            ) {
+                dot.push_str(&format!("    {} -> {};\n", start, end));
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use eframe::emath::{pos2, Rect};
+    use regex::Regex;
+
+    use crate::graph::{
+        ArrowFigure, ConnectionPoint, EllipseFigure, GraphFigure, Graphics, RectFigure,
+    };
+
+    #[test]
+    fn test_to_dot() {
+        let mut graphics = Graphics::default();
+        let figures: Vec<Rc<RefCell<Box<dyn GraphFigure>>>> = vec![
+            Rc::new(RefCell::new(Box::new(RectFigure::new(
+                graphics.generate_id(),
+                Rect::from_min_max(pos2(0., 0.), pos2(100., 50.)),
+                String::new(),
+            )))),
+            Rc::new(RefCell::new(Box::new(EllipseFigure::new(
+                graphics.generate_id(),
+                Rect::from_min_max(pos2(200., 0.), pos2(300., 50.)),
+                "Say \"hi\"".to_owned(),
+            )))),
+        ];
+        for figure in &figures {
+            graphics.add_figure(Rc::clone(figure));
+        }
+
+        let mut edge = ArrowFigure::new([pos2(100., 25.), pos2(200., 25.)], graphics.generate_id());
+        edge.connect_start(ConnectionPoint::new(Rc::clone(&figures[0]), 0));
+        edge.connect_end(ConnectionPoint::new(Rc::clone(&figures[1]), 0));
+        graphics.add_edge(edge);
+
+        let dot = graphics.to_dot();
+        let lines: Vec<_> = dot.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], "digraph G {");
+        assert_eq!(lines[4], "}");
+
+        // Quoted identifiers may contain escaped quotes
+        let id = r#""(?:[^"\\]|\\.)*""#;
+        let node = Regex::new(&format!(r"^    {id} \[label={id}, shape=\w+\];$", id = id)).unwrap();
+        let edge = Regex::new(&format!(r"^    {id} -> {id};$", id = id)).unwrap();
+        assert!(node.is_match(lines[1]));
+        assert!(lines[1].ends_with("shape=rect];"));
+        assert!(node.is_match(lines[2]));
+        assert!(lines[2].contains(r#"label="Say \"hi\"", shape=ellipse"#));
+        assert!(edge.is_match(lines[3]));
+    }
+}
@@ -0,0 +1,146 @@
+use std::cell::RefCell;
+
+use crate::graph::{id_to_string, ConnectionPoint, GraphFigure, Graphics};
+
+/// Mermaid node identifier. Identifiers are prefixed to start with a letter
+#[inline]
+fn node_id(figure: &dyn GraphFigure) -> String {
+    format!("n{}", id_to_string(figure.id()))
+}
+
+/// Escape special characters of the label using HTML entities
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '|' => escaped.push_str("&#124;"),
+            '\n' => escaped.push_str("<br>"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Node definition using shape of the figure type
+fn node(figure: &dyn GraphFigure) -> String {
+    let (open, close) = match figure.type_name() {
+        "diamond" => ("{", "}"),
+        "ellipse" => ("((", "))"),
+        "cylinder" => ("[(", ")]"),
+        _ => ("[", "]"),
+    };
+    format!(
+        "{}{}\"{}\"{}",
+        node_id(figure),
+        open,
+        escape(&figure.label()),
+        close
+    )
+}
+
+/// Implies export into Mermaid.js format
+impl Graphics {
+    /// Export diagram into Mermaid.js flowchart. Labels are quoted, so they can contain
+    /// any text. Edges not connected to figures on both ends are skipped
+    /// ### Return
+    /// flowchart description
+    pub fn to_mermaid(&self) -> String {
+        let mut mermaid = String::from("flowchart TD\n");
+        for figure in self.figures() {
+            mermaid.push_str(&format!("    {}\n", node(RefCell::borrow(figure).as_ref())));
+        }
+
+        for edge in self.edges() {
+            let figure_id = |cp: &Option<ConnectionPoint>| {
+                cp.as_ref()
+                    .map(|cp| node_id(RefCell::borrow(cp.get_figure()).as_ref()))
+            };
+            let (start, end) = match (
+                figure_id(edge.get_start_connection()),
+                figure_id(edge.get_end_connection()),
+            ) {
+                (Some(start), Some(end)) => (start, end),
+                _ => continue,
+            };
+
+            match edge.label() {
+                label if label.is_empty() => {
+                    mermaid.push_str(&format!("    {} --> {}\n", start, end))
+                }
+                label => mermaid.push_str(&format!(
+                    "    {} -->|\"{}\"| {}\n",
+                    start,
+                    escape(&label),
+                    end
+                )),
+            }
+        }
+
+        mermaid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use eframe::emath::{pos2, Rect};
+    use regex::Regex;
+
+    use crate::graph::{
+        ArrowFigure, ConnectionPoint, DiamondFigure, EllipseFigure, GraphFigure, Graphics,
+        RectFigure,
+    };
+
+    #[test]
+    fn test_to_mermaid() {
+        let mut graphics = Graphics::default();
+        let rect = Rect::from_min_max(pos2(0., 0.), pos2(100., 50.));
+        let figures: Vec<Rc<RefCell<Box<dyn GraphFigure>>>> = vec![
+            Rc::new(RefCell::new(Box::new(RectFigure::new(
+                graphics.generate_id(),
+                rect,
+                "Start".to_owned(),
+            )))),
+            Rc::new(RefCell::new(Box::new(DiamondFigure::new(
+                graphics.generate_id(),
+                rect,
+                "x < 10".to_owned(),
+            )))),
+            Rc::new(RefCell::new(Box::new(EllipseFigure::new(
+                graphics.generate_id(),
+                rect,
+                "End".to_owned(),
+            )))),
+        ];
+        for figure in &figures {
+            graphics.add_figure(Rc::clone(figure));
+        }
+
+        for (start, end, label) in [(0, 1, ""), (1, 2, "yes")] {
+            let mut edge = ArrowFigure::new([pos2(0., 0.), pos2(1., 1.)], graphics.generate_id());
+            edge.connect_start(ConnectionPoint::new(Rc::clone(&figures[start]), 0));
+            edge.connect_end(ConnectionPoint::new(Rc::clone(&figures[end]), 0));
+            edge.set_label(label);
+            graphics.add_edge(edge);
+        }
+
+        let mermaid = graphics.to_mermaid();
+        let lines: Vec<_> = mermaid.lines().collect();
+        assert_eq!(lines[0], "flowchart TD");
+
+        let node = Regex::new(r#"^    n\w+(\[|\{|\(\()"[^"]*"(\]|\}|\)\))$"#).unwrap();
+        let edge = Regex::new(r#"^    n\w+ -->(\|"[^"]*"\|)? n\w+$"#).unwrap();
+        assert_eq!(lines.iter().filter(|line| node.is_match(line)).count(), 3);
+        assert_eq!(lines.iter().filter(|line| edge.is_match(line)).count(), 2);
+        assert_eq!(lines.len(), 6);
+
+        assert!(lines[2].ends_with(r#"{"x &lt; 10"}"#));
+        assert!(lines[3].ends_with(r#"(("End"))"#));
+        assert!(lines[5].contains(r#"-->|"yes"|"#));
+    }
+}
@@ -0,0 +1,229 @@
+use std::{cell::RefCell, cmp::Ordering};
+
+use eframe::emath::{Rect, Vec2};
+
+use super::{commands::Command, graphics::GraphicsData, Graphics};
+
+/// Reference line used to align selected figures
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Alignment {
+    /// Left edges are aligned to the leftmost edge
+    Left,
+    /// Horizontal centers are aligned to the center of selection
+    CenterH,
+    /// Right edges are aligned to the rightmost edge
+    Right,
+    /// Top edges are aligned to the topmost edge
+    Top,
+    /// Vertical centers are aligned to the center of selection
+    CenterV,
+    /// Bottom edges are aligned to the bottommost edge
+    Bottom,
+}
+
+impl Alignment {
+    /// Offset moving figure rectangle to the reference line of the bounds
+    fn offset(&self, bounds: Rect, rect: Rect) -> Vec2 {
+        match self {
+            Alignment::Left => Vec2::new(bounds.min.x - rect.min.x, 0.),
+            Alignment::CenterH => Vec2::new(bounds.center().x - rect.center().x, 0.),
+            Alignment::Right => Vec2::new(bounds.max.x - rect.max.x, 0.),
+            Alignment::Top => Vec2::new(0., bounds.min.y - rect.min.y),
+            Alignment::CenterV => Vec2::new(0., bounds.center().y - rect.center().y),
+            Alignment::Bottom => Vec2::new(0., bounds.max.y - rect.max.y),
+        }
+    }
+}
+
+impl GraphicsData {
+    /// Indexes and rectangles of the selected figures
+    fn selected_rects(&self) -> Vec<(usize, Rect)> {
+        self.selected_figure_indices
+            .iter()
+            .filter_map(|idx| {
+                self.figures
+                    .get(*idx)
+                    .map(|fig| (*idx, RefCell::borrow(fig).rect()))
+            })
+            .collect()
+    }
+
+    /// Move figures by offsets and store moves in history as one command
+    /// ### Arguments
+    /// * offsets - indexes of the figures with offsets
+    fn move_figures(&mut self, offsets: Vec<(usize, Vec2)>) {
+        let commands = offsets
+            .into_iter()
+            .filter(|(_, offset)| *offset != Vec2::ZERO)
+            .filter_map(|(idx, offset)| {
+                let mut figure = RefCell::borrow_mut(self.figures.get(idx)?);
+                let from = figure.rect().min;
+                figure.move_to(from + offset, from);
+                Some(Command::MoveFigure {
+                    id: figure.id(),
+                    from,
+                    to: from + offset,
+                })
+            })
+            .collect();
+        self.push_user_commands(commands);
+    }
+
+    /// Place selected figures one after another with equal gaps. The first and the last figures
+    /// along the axis stay in place
+    /// ### Arguments
+    /// * axis - function returning start and size of the rectangle along the axis
+    /// * offset - function converting offset along the axis into vector
+    fn distribute(&mut self, axis: impl Fn(&Rect) -> (f32, f32), offset: impl Fn(f32) -> Vec2) {
+        let mut rects = self.selected_rects();
+        if rects.len() < 3 {
+            return;
+        }
+
+        rects
+            .sort_by(|(_, a), (_, b)| axis(a).0.partial_cmp(&axis(b).0).unwrap_or(Ordering::Equal));
+        let start = rects
+            .iter()
+            .map(|(_, rect)| axis(rect).0)
+            .fold(f32::MAX, f32::min);
+        let end = rects
+            .iter()
+            .map(|(_, rect)| axis(rect).0 + axis(rect).1)
+            .fold(f32::MIN, f32::max);
+        let sizes: f32 = rects.iter().map(|(_, rect)| axis(rect).1).sum();
+        let gap = (end - start - sizes) / (rects.len() - 1) as f32;
+
+        let mut pos = start;
+        let offsets = rects
+            .into_iter()
+            .map(|(idx, rect)| {
+                let (min, size) = axis(&rect);
+                let moved = (idx, offset(pos - min));
+                pos += size + gap;
+                moved
+            })
+            .collect();
+        self.move_figures(offsets);
+    }
+}
+
+/// Implies alignment of the selected figures
+impl Graphics {
+    /// Return number of selected figures
+    pub fn selected_count(&self) -> usize {
+        self.graphics_data.selected_figure_indices.len()
+    }
+
+    /// Align selected figures by the reference line of their bounding rectangle.
+    /// Nothing is changed if less than two figures are selected
+    /// ### Arguments
+    /// * alignment - reference line
+    pub fn align_selected(&mut self, alignment: Alignment) {
+        let data = &mut self.graphics_data;
+        let rects = data.selected_rects();
+        if rects.len() < 2 {
+            return;
+        }
+
+        let bounds = rects
+            .iter()
+            .fold(Rect::NOTHING, |bounds, (_, rect)| bounds.union(*rect));
+        let offsets = rects
+            .into_iter()
+            .map(|(idx, rect)| (idx, alignment.offset(bounds, rect)))
+            .collect();
+        data.move_figures(offsets);
+    }
+
+    /// Evenly space selected figures horizontally, leftmost and rightmost figures stay in place
+    pub fn distribute_horizontal(&mut self) {
+        self.graphics_data
+            .distribute(|rect| (rect.min.x, rect.width()), |dx| Vec2::new(dx, 0.));
+    }
+
+    /// Evenly space selected figures vertically, topmost and bottommost figures stay in place
+    pub fn distribute_vertical(&mut self) {
+        self.graphics_data
+            .distribute(|rect| (rect.min.y, rect.height()), |dy| Vec2::new(0., dy));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use eframe::emath::{pos2, Rect};
+
+    use super::Alignment;
+    use crate::graph::{shapes::GraphUi, CommandStack, GraphFigure, Graphics, RectFigure};
+
+    fn graphics(rects: &[Rect]) -> Graphics {
+        let mut graphics = Graphics::default();
+        for rect in rects {
+            let data = &mut graphics.graphics_data;
+            let id = data.generate_id();
+            let figure = RectFigure::new(id, *rect, "".to_owned());
+            data.add_figure(Rc::new(RefCell::new(Box::new(figure))));
+        }
+        graphics
+            .graphics_data
+            .select_by_rect(Rect::from_min_max(pos2(-1000., -1000.), pos2(1000., 1000.)));
+        graphics
+    }
+
+    fn rects(graphics: &Graphics) -> Vec<Rect> {
+        graphics
+            .figures()
+            .iter()
+            .map(|fig| RefCell::borrow(fig).rect())
+            .collect()
+    }
+
+    #[test]
+    fn test_align_selected() {
+        let mut graphics = graphics(&[
+            Rect::from_min_max(pos2(10., 0.), pos2(110., 50.)),
+            Rect::from_min_max(pos2(200., 100.), pos2(240., 120.)),
+        ]);
+
+        graphics.align_selected(Alignment::Left);
+        let aligned = rects(&graphics);
+        assert_eq!(aligned[0].min.x, 10.);
+        assert_eq!(aligned[1].min, pos2(10., 100.));
+
+        graphics.align_selected(Alignment::CenterV);
+        let aligned = rects(&graphics);
+        assert_eq!(aligned[0].center().y, 60.);
+        assert_eq!(aligned[1].center().y, 60.);
+
+        graphics.align_selected(Alignment::Right);
+        assert_eq!(rects(&graphics)[1].max.x, 110.);
+
+        // Each alignment is undone as one command
+        assert!(graphics.graphics_data.undo());
+        assert_eq!(rects(&graphics)[1].max.x, 50.);
+    }
+
+    #[test]
+    fn test_distribute() {
+        let mut graphics = graphics(&[
+            Rect::from_min_max(pos2(0., 0.), pos2(20., 20.)),
+            Rect::from_min_max(pos2(200., 60.), pos2(240., 80.)),
+            Rect::from_min_max(pos2(30., 10.), pos2(40., 20.)),
+        ]);
+
+        // Total width is 240, figures take 70, two gaps are 85 each
+        graphics.distribute_horizontal();
+        let distributed = rects(&graphics);
+        assert_eq!(distributed[0].min.x, 0.);
+        assert_eq!(distributed[2].min.x, 105.);
+        assert_eq!(distributed[1].min.x, 200.);
+
+        // Total height is 80, figures take 50, two gaps are 15 each
+        graphics.distribute_vertical();
+        let distributed = rects(&graphics);
+        assert_eq!(distributed[0].min.y, 0.);
+        assert_eq!(distributed[2].min.y, 35.);
+        assert_eq!(distributed[1].min.y, 60.);
+    }
+}
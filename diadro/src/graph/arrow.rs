@@ -1,15 +1,21 @@
-use std::{borrow::Cow, cell::RefCell, f32::consts::PI, rc::Rc};
+use std::{
+    borrow::Cow, cell::RefCell, cmp::Ordering, collections::HashMap, f32::consts::PI, rc::Rc,
+};
 
 use eframe::{
-    egui::{CursorIcon, Id, PointerButton, Ui},
+    egui::{CursorIcon, Id, Painter, PointerButton, Ui},
     emath::{Pos2, Rect, Vec2},
-    epaint::{PathShape, Shape},
+    epaint::{Color32, CubicBezierShape, PathShape, Shape, Stroke},
 };
+use serde::{Deserialize, Serialize};
 
-use crate::graph::pos_by_angle;
+use crate::rgraph::CanvasCommand;
 
 use super::{
+    dash::{apply_dash, DashPattern, DEFAULT_DASH_LENGTH},
+    routing::{orthogonal_route, EdgeRouting},
     shapes::{FigureBasics, SelectMode, SELECT_MODE_NONE, SELECT_MODE_SELECTED},
+    text::TextOps,
     utils::{PointMath, TwoPosLine},
     GraphFigure, Zoom,
 };
@@ -42,29 +48,368 @@ impl ConnectionPoint {
     pub fn get_figure(&self) -> &Rc<RefCell<Box<dyn GraphFigure>>> {
         &self.figure
     }
+
+    /// Return index within figure's connection points array
+    pub fn connection_point(&self) -> usize {
+        self.connection_point
+    }
+}
+
+/// Serializable form of the connection point. Figure is referenced by identifier
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConnectionRef {
+    /// Identifier of the connected figure
+    pub figure: Id,
+    /// Index within figure's connection points array
+    pub connection_point: usize,
+}
+
+impl From<&ConnectionPoint> for ConnectionRef {
+    fn from(cp: &ConnectionPoint) -> Self {
+        Self {
+            figure: cp.figure.borrow().id(),
+            connection_point: cp.connection_point,
+        }
+    }
+}
+
+impl ConnectionRef {
+    /// Resolve reference into connection point
+    /// ### Arguments
+    /// * figures - all figures of the diagram by identifiers
+    /// ### Return
+    /// connection point or None if figure is not found
+    pub fn resolve(
+        &self,
+        figures: &HashMap<Id, Rc<RefCell<Box<dyn GraphFigure>>>>,
+    ) -> Option<ConnectionPoint> {
+        figures
+            .get(&self.figure)
+            .map(|figure| ConnectionPoint::new(Rc::clone(figure), self.connection_point))
+    }
+}
+
+/// Length of the arrow head along the line
+const HEAD_LENGTH: f32 = 15.;
+/// Half of the arrow head width
+const HEAD_HALF_WIDTH: f32 = 6.;
+/// Number of points approximating circle arrow head
+const HEAD_CIRCLE_POINTS: usize = 12;
+/// Size of the box the edge label is fitted in, at zoom factor 1
+const LABEL_SIZE: Vec2 = Vec2::new(100., 30.);
+/// Maximum distance from the line end to start dragging this end
+const ENDPOINT_MARGIN: f32 = 8.;
+/// Maximum distance from the waypoint to start dragging it
+const WAYPOINT_MARGIN: f32 = 5.;
+
+/// Distance at which edge connected to the same figure at both ends loops out of the figure
+pub const LOOP_RADIUS: f32 = 20.;
+
+/// Maximum distance between the self-loop curve and its flattened line
+const LOOP_TOLERANCE: f32 = 0.5;
+
+/// Direction from the figure center to its connection point, directed up if the point
+/// is at the center
+/// ### Arguments
+/// * center - center of the figure
+/// * point - connection point
+pub fn outward_normal(center: Pos2, point: Pos2) -> Vec2 {
+    let normal = (point - center).normalized();
+    if normal.is_finite() && normal != Vec2::ZERO {
+        normal
+    } else {
+        -Vec2::Y
+    }
+}
+
+/// Defines what is changed while edge is dragged
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ArrowDragMode {
+    /// Edge is not dragged
+    None,
+    /// Start point follows the cursor
+    Start,
+    /// End point follows the cursor
+    End,
+    /// Whole line is moved, contains last cursor position
+    Move(Pos2),
+    /// Waypoint with the index follows the cursor
+    MoveWaypoint(usize),
+    /// Waypoint with the index was inserted by double click on the line, it is moved by
+    /// the next drag started over it
+    AddWaypoint(usize),
+}
+
+/// Style of the arrow head drawn at the edge's end
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArrowHeadStyle {
+    /// No head
+    None,
+    /// Two wings without fill
+    Open,
+    /// Filled triangle
+    Filled,
+    /// Filled diamond
+    Diamond,
+    /// Filled circle
+    Circle,
+    /// Diamond outline
+    OpenDiamond,
+}
+
+impl Default for ArrowHeadStyle {
+    fn default() -> Self {
+        ArrowHeadStyle::None
+    }
+}
+
+/// Outline of the arrow head
+struct HeadShape {
+    points: Vec<Pos2>,
+    closed: bool,
+    filled: bool,
+}
+
+/// Compute outline of the arrow head
+/// ### Arguments
+/// * style - head style
+/// * line - line segment, head is placed at its end
+/// ### Return
+/// None if there is nothing to draw
+fn head_shape(style: ArrowHeadStyle, line: [Pos2; 2]) -> Option<HeadShape> {
+    let [from, tip] = line;
+    if from == tip {
+        return None;
+    }
+
+    let dir = (tip - from).normalized();
+    let normal = dir.rot90();
+    let back = tip - dir * HEAD_LENGTH;
+    let middle = tip - dir * HEAD_LENGTH / 2.;
+    let wings = [
+        back + normal * HEAD_HALF_WIDTH,
+        back - normal * HEAD_HALF_WIDTH,
+    ];
+    let diamond = vec![
+        tip,
+        middle + normal * HEAD_HALF_WIDTH,
+        back,
+        middle - normal * HEAD_HALF_WIDTH,
+    ];
+
+    let (points, closed, filled) = match style {
+        ArrowHeadStyle::None => return None,
+        ArrowHeadStyle::Open => (vec![wings[0], tip, wings[1]], false, false),
+        ArrowHeadStyle::Filled => (vec![tip, wings[0], wings[1]], true, true),
+        ArrowHeadStyle::Diamond => (diamond, true, true),
+        ArrowHeadStyle::OpenDiamond => (diamond, true, false),
+        ArrowHeadStyle::Circle => {
+            let radius = HEAD_LENGTH / 3.;
+            let center = tip - dir * radius;
+            let points = (0..HEAD_CIRCLE_POINTS)
+                .map(|i| {
+                    let angle = 2. * PI * i as f32 / HEAD_CIRCLE_POINTS as f32;
+                    center + Vec2::angled(angle) * radius
+                })
+                .collect();
+            (points, true, true)
+        }
+    };
+
+    Some(HeadShape {
+        points,
+        closed,
+        filled,
+    })
+}
+
+/// Draw arrow head at the end of the line
+/// ### Arguments
+/// * style - head style
+/// * line - line segment, head is placed at its end
+/// * painter - painter to draw with
+/// * stroke - line stroke, filled heads use its color
+pub fn draw_head(style: ArrowHeadStyle, line: [Pos2; 2], painter: &Painter, stroke: Stroke) {
+    if let Some(head) = head_shape(style, line) {
+        let shape = match head {
+            HeadShape {
+                points,
+                filled: true,
+                ..
+            } => Shape::convex_polygon(points, stroke.color, stroke),
+            HeadShape {
+                points,
+                closed: true,
+                ..
+            } => Shape::Path(PathShape::closed_line(points, stroke)),
+            HeadShape { points, .. } => Shape::Path(PathShape::line(points, stroke)),
+        };
+        painter.add(shape);
+    }
+}
+
+/// Convert arrow head into drawing commands
+fn head_commands(style: ArrowHeadStyle, line: [Pos2; 2], stroke: Stroke) -> Option<CanvasCommand> {
+    head_shape(style, line).map(|head| {
+        if head.filled {
+            CanvasCommand::FillPolygon(head.points, stroke.color)
+        } else {
+            CanvasCommand::DrawPath {
+                points: head.points,
+                closed: head.closed,
+                fill: Color32::TRANSPARENT,
+                stroke,
+            }
+        }
+    })
 }
 
 /// Defines edge figure
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(into = "ArrowData", from = "ArrowData")]
 #[allow(dead_code)]
 pub struct ArrowFigure {
     /// Identifier
     id: Id,
     /// Start and end position
     line: TwoPosLine,
+    /// Intermediate points the line goes through from start to end
+    waypoints: Vec<Pos2>,
     wing_size: f32,
     size: f32,
 
-    start_arrow: bool,
-    end_arrow: bool,
+    /// Head drawn at the start of the edge
+    start_head_style: ArrowHeadStyle,
+    /// Head drawn at the end of the edge
+    end_head_style: ArrowHeadStyle,
 
     zoom_factor: f32,
     scroll_delta: Vec2,
     fb: FigureBasics,
-    text: Option<Cow<'static, str>>,
+    /// Text drawn in the middle of the line
+    label: Option<TextOps>,
+    /// Label is edited
+    label_edit: bool,
     selected: bool,
     start_figure: Option<ConnectionPoint>,
     end_figure: Option<ConnectionPoint>,
+    /// Defines how line is drawn between start and end points
+    routing: EdgeRouting,
+    drag_mode: ArrowDragMode,
+    /// Start and end points used to connect other edges, refreshed when the edge is changed or drawn
+    connection_points: Vec<Pos2>,
+    /// Text shown when cursor is over the edge
+    tooltip: Option<String>,
+}
+
+/// Serializable form of the edge. Connected figures are stored as identifiers
+#[derive(Clone, Serialize, Deserialize)]
+pub(super) struct ArrowData {
+    id: Id,
+    line: TwoPosLine,
+    #[serde(default)]
+    waypoints: Vec<Pos2>,
+    wing_size: f32,
+    size: f32,
+    /// Kept to read diagrams saved before head styles were added
+    #[serde(default)]
+    start_arrow: bool,
+    #[serde(default)]
+    end_arrow: bool,
+    #[serde(default)]
+    start_head_style: Option<ArrowHeadStyle>,
+    #[serde(default)]
+    end_head_style: Option<ArrowHeadStyle>,
+    zoom_factor: f32,
+    scroll_delta: Vec2,
+    fb: FigureBasics,
+    /// Kept to read diagrams saved before labels were added
+    #[serde(default)]
+    text: Option<Cow<'static, str>>,
+    #[serde(default)]
+    label: Option<TextOps>,
+    start_figure: Option<ConnectionRef>,
+    end_figure: Option<ConnectionRef>,
+    #[serde(default)]
+    routing: EdgeRouting,
+    #[serde(default)]
+    tooltip: Option<String>,
+}
+
+impl ArrowData {
+    /// Restore edge and connect it to figures
+    /// ### Arguments
+    /// * figures - all figures of the diagram by identifiers
+    pub(super) fn into_arrow(
+        self,
+        figures: &HashMap<Id, Rc<RefCell<Box<dyn GraphFigure>>>>,
+    ) -> ArrowFigure {
+        ArrowFigure {
+            id: self.id,
+            line: self.line,
+            waypoints: self.waypoints,
+            wing_size: self.wing_size,
+            size: self.size,
+            start_head_style: self.start_head_style.unwrap_or(if self.start_arrow {
+                ArrowHeadStyle::Filled
+            } else {
+                ArrowHeadStyle::None
+            }),
+            end_head_style: self.end_head_style.unwrap_or(if self.end_arrow {
+                ArrowHeadStyle::Filled
+            } else {
+                ArrowHeadStyle::None
+            }),
+            zoom_factor: self.zoom_factor,
+            scroll_delta: self.scroll_delta,
+            fb: self.fb,
+            label: self.label.or_else(|| self.text.map(TextOps::new)),
+            label_edit: false,
+            selected: false,
+            start_figure: self.start_figure.and_then(|cr| cr.resolve(figures)),
+            end_figure: self.end_figure.and_then(|cr| cr.resolve(figures)),
+            routing: self.routing,
+            drag_mode: ArrowDragMode::None,
+            connection_points: vec![],
+            tooltip: self.tooltip,
+        }
+        .with_connection_points()
+    }
+}
+
+impl From<ArrowFigure> for ArrowData {
+    fn from(arrow: ArrowFigure) -> Self {
+        Self {
+            start_figure: arrow.start_figure.as_ref().map(ConnectionRef::from),
+            end_figure: arrow.end_figure.as_ref().map(ConnectionRef::from),
+            id: arrow.id,
+            line: arrow.line,
+            waypoints: arrow.waypoints,
+            wing_size: arrow.wing_size,
+            size: arrow.size,
+            start_arrow: arrow.start_arrow(),
+            end_arrow: arrow.end_arrow(),
+            start_head_style: Some(arrow.start_head_style),
+            end_head_style: Some(arrow.end_head_style),
+            zoom_factor: arrow.zoom_factor,
+            scroll_delta: arrow.scroll_delta,
+            fb: arrow.fb,
+            text: arrow
+                .label
+                .as_ref()
+                .map(|label| Cow::Owned(label.text().to_owned())),
+            label: arrow.label,
+            routing: arrow.routing,
+            tooltip: arrow.tooltip,
+        }
+    }
+}
+
+/// Standalone edge can not be connected to figures, connections are resolved by diagram
+impl From<ArrowData> for ArrowFigure {
+    fn from(data: ArrowData) -> Self {
+        data.into_arrow(&HashMap::new())
+    }
 }
 
 impl std::fmt::Debug for ArrowFigure {
@@ -72,12 +417,13 @@ impl std::fmt::Debug for ArrowFigure {
         f.debug_struct("ArrowFigure")
             .field("id", &self.id)
             .field("line", &self.line)
+            .field("waypoints", &self.waypoints)
             .field("wing_size", &self.wing_size)
             .field("size", &self.size)
             .field("zoom_factor", &self.zoom_factor)
             .field("scroll_delta", &self.scroll_delta)
             .field("fb", &self.fb)
-            .field("text", &self.text)
+            .field("label", &self.label)
             .field("selected", &self.selected)
             .field("start_figure", &self.start_figure.is_some())
             .field("end_figure", &self.end_figure.is_some())
@@ -85,60 +431,116 @@ impl std::fmt::Debug for ArrowFigure {
     }
 }
 
+/// Zero-length arrow at origin
+impl Default for ArrowFigure {
+    fn default() -> Self {
+        Self::new(TwoPosLine::default(), Id::null())
+    }
+}
+
 #[allow(dead_code)]
 impl ArrowFigure {
     pub fn new(line: impl Into<TwoPosLine>, id: Id) -> Self {
         Self {
             id,
             line: line.into(),
+            waypoints: vec![],
             wing_size: 20.,
             size: 15.,
-            start_arrow: false,
-            end_arrow: true,
+            start_head_style: ArrowHeadStyle::None,
+            end_head_style: ArrowHeadStyle::Filled,
             zoom_factor: 1.,
             scroll_delta: Vec2::ZERO,
             fb: Default::default(),
-            text: None,
+            label: None,
+            label_edit: false,
             selected: false,
             start_figure: None,
             end_figure: None,
+            routing: EdgeRouting::default(),
+            drag_mode: ArrowDragMode::None,
+            connection_points: vec![],
+            tooltip: None,
         }
+        .with_connection_points()
+    }
+
+    /// Compute connection points of the constructed edge
+    fn with_connection_points(mut self) -> Self {
+        self.update_connection_points();
+        self
+    }
+
+    /// Store current start and end points as connection points
+    fn update_connection_points(&mut self) {
+        self.connection_points = vec![self.compute_start_point(), self.compute_end_point()];
     }
 
     pub fn start_arrow(&self) -> bool {
-        self.start_arrow
+        self.start_head_style != ArrowHeadStyle::None
     }
 
     pub fn end_arrow(&self) -> bool {
-        self.end_arrow
+        self.end_head_style != ArrowHeadStyle::None
     }
 
+    /// Turn start head on (filled) or off
     pub fn set_start_arrow(&mut self, flag: bool) {
-        self.start_arrow = flag;
+        self.start_head_style = if flag {
+            ArrowHeadStyle::Filled
+        } else {
+            ArrowHeadStyle::None
+        };
     }
 
+    /// Turn end head on (filled) or off
     pub fn set_end_arrow(&mut self, flag: bool) {
-        self.end_arrow = flag;
+        self.end_head_style = if flag {
+            ArrowHeadStyle::Filled
+        } else {
+            ArrowHeadStyle::None
+        };
+    }
+
+    pub fn start_head_style(&self) -> ArrowHeadStyle {
+        self.start_head_style
+    }
+
+    pub fn end_head_style(&self) -> ArrowHeadStyle {
+        self.end_head_style
+    }
+
+    pub fn set_start_head_style(&mut self, style: ArrowHeadStyle) {
+        self.start_head_style = style;
+    }
+
+    pub fn set_end_head_style(&mut self, style: ArrowHeadStyle) {
+        self.end_head_style = style;
     }
 
     pub fn connect_start(&mut self, figure: ConnectionPoint) {
         self.start_figure = Some(figure);
+        self.update_connection_points();
     }
 
     pub fn disconnect_start(&mut self) {
         self.start_figure = None;
+        self.update_connection_points();
     }
 
     pub fn connect_end(&mut self, figure: ConnectionPoint) {
         self.end_figure = Some(figure);
+        self.update_connection_points();
     }
 
     pub fn disconnect_end(&mut self) {
         self.end_figure = None;
+        self.update_connection_points();
     }
 
     pub fn set_end_pos(&mut self, pos: Pos2) {
         self.line.move_to(pos);
+        self.update_connection_points();
     }
 
     pub fn get_start_connection(&self) -> &Option<ConnectionPoint> {
@@ -149,6 +551,184 @@ impl ArrowFigure {
         &self.end_figure
     }
 
+    pub fn stroke(&self) -> Stroke {
+        self.fb.stroke
+    }
+
+    pub fn stroke_pattern(&self) -> DashPattern {
+        self.fb.stroke_pattern
+    }
+
+    pub fn set_stroke_pattern(&mut self, pattern: DashPattern) {
+        self.fb.stroke_pattern = pattern;
+    }
+
+    /// Label drawn in the middle of the line
+    fn new_label(text: impl Into<Cow<'static, str>>) -> TextOps {
+        TextOps::new(text).pading(2.)
+    }
+
+    pub fn label_text(&self) -> Option<&str> {
+        self.label.as_ref().map(|label| label.text())
+    }
+
+    pub fn set_label(&mut self, text: &'static str) {
+        self.label = Some(Self::new_label(text));
+    }
+
+    pub fn set_label_owned(&mut self, text: String) {
+        self.label = Some(Self::new_label(text));
+    }
+
+    pub fn routing(&self) -> EdgeRouting {
+        self.routing
+    }
+
+    pub fn set_routing(&mut self, routing: EdgeRouting) {
+        self.routing = routing;
+    }
+
+    pub fn waypoints(&self) -> &[Pos2] {
+        &self.waypoints
+    }
+
+    pub fn set_waypoints(&mut self, waypoints: Vec<Pos2>) {
+        self.waypoints = waypoints;
+    }
+
+    /// Start point, waypoints and end point the line goes through
+    fn control_points(&self) -> Vec<Pos2> {
+        std::iter::once(self.compute_start_point())
+            .chain(self.waypoints.iter().copied())
+            .chain(std::iter::once(self.compute_end_point()))
+            .collect()
+    }
+
+    /// Insert waypoint into the segment between control points nearest to the point
+    /// ### Arguments
+    /// * pos - position of the new waypoint
+    /// ### Return
+    /// index of the inserted waypoint
+    fn insert_waypoint(&mut self, pos: Pos2) -> usize {
+        let index = self
+            .control_points()
+            .windows(2)
+            .map(|segment| {
+                TwoPosLine::new([segment[0], segment[1]])
+                    .closest_point(pos)
+                    .distance(pos)
+            })
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+            .map(|(index, _)| index)
+            .unwrap_or_default();
+        self.waypoints.insert(index, pos);
+        index
+    }
+
+    /// Find waypoint under cursor
+    /// ### Arguments
+    /// * pos - cursor position
+    /// ### Return
+    /// index of the waypoint or None if cursor is not near any waypoint
+    pub fn waypoint_at(&self, pos: Pos2) -> Option<usize> {
+        self.waypoints
+            .iter()
+            .position(|waypoint| pos.over(*waypoint, WAYPOINT_MARGIN))
+    }
+
+    /// Remove waypoint, the line goes straight between neighbour control points
+    /// ### Arguments
+    /// * index - index of the waypoint, out of range indexes are ignored
+    pub fn remove_waypoint(&mut self, index: usize) {
+        if index < self.waypoints.len() {
+            self.waypoints.remove(index);
+        }
+    }
+
+    /// Control points of the curve drawn when both ends are connected to the same figure.
+    /// Curve loops out of the figure along the outward normals of the connection points
+    /// ### Return
+    /// cubic bezier points or None if the edge is not a self-loop
+    fn self_loop(&self) -> Option<[Pos2; 4]> {
+        // Waypoints define the path of the loop
+        if !self.waypoints.is_empty() {
+            return None;
+        }
+
+        let center = match (&self.start_figure, &self.end_figure) {
+            (Some(start), Some(end)) if Rc::ptr_eq(start.get_figure(), end.get_figure()) => {
+                start.get_figure().borrow().rect().center()
+            }
+            _ => return None,
+        };
+
+        let (start, end) = (self.compute_start_point(), self.compute_end_point());
+        let start_normal = outward_normal(center, start);
+        let end_normal = outward_normal(center, end);
+        Some([
+            start,
+            start + start_normal * 2. * LOOP_RADIUS + start_normal.rot90() * LOOP_RADIUS,
+            end + end_normal * 2. * LOOP_RADIUS - end_normal.rot90() * LOOP_RADIUS,
+            end,
+        ])
+    }
+
+    /// Self-loop curve with the edge stroke
+    fn self_loop_shape(&self) -> Option<CubicBezierShape> {
+        self.self_loop().map(|points| {
+            CubicBezierShape::from_points_stroke(
+                points,
+                false,
+                Color32::TRANSPARENT,
+                self.fb.stroke,
+            )
+        })
+    }
+
+    /// Compute points of the line between start and end points through waypoints according
+    /// to routing. Self-loop is returned as flattened curve
+    pub fn route_points(&self) -> Vec<Pos2> {
+        if let Some(curve) = self.self_loop_shape() {
+            return curve.flatten(Some(LOOP_TOLERANCE));
+        }
+
+        let points = self.control_points();
+        match self.routing {
+            EdgeRouting::Straight => points,
+            EdgeRouting::Orthogonal => {
+                // Route must go around connected figures
+                let avoid: Vec<Rect> = [&self.start_figure, &self.end_figure]
+                    .iter()
+                    .filter_map(|cp| cp.as_ref())
+                    .map(|cp| cp.get_figure().borrow().bounding_rect())
+                    .collect();
+                let mut route = vec![points[0]];
+                for segment in points.windows(2) {
+                    // Each part starts at the end of the previous one
+                    route.extend(
+                        orthogonal_route(segment[0], segment[1], &avoid)
+                            .into_iter()
+                            .skip(1),
+                    );
+                }
+                route
+            }
+        }
+    }
+
+    /// First and last segments of the line directed to the line ends, used to draw arrow heads
+    fn head_lines(points: &[Pos2]) -> ([Pos2; 2], [Pos2; 2]) {
+        match points {
+            [first, second, ..] => {
+                let [from, to] = [points[points.len() - 2], points[points.len() - 1]];
+                ([*second, *first], [from, to])
+            }
+            [point] => ([*point, *point], [*point, *point]),
+            [] => ([Pos2::ZERO; 2], [Pos2::ZERO; 2]),
+        }
+    }
+
     /// Function compute nearest point on the rectangle's edges centers for the given point.
     /// ### Arguments
     /// * `rect` - rectangle to check
@@ -197,48 +777,38 @@ impl ArrowFigure {
         }
     }
 
-    #[inline]
-    fn arrow_for_line(&self, angle_grad: f32, distance: f32) -> Vec<Pos2> {
-        let line_angle = self.line.angle();
-        let rotate = PI;
-
-        // line_angle - angle + 180
-        let angle = angle_grad * PI / 180.;
-        let left_angle = line_angle + angle + rotate;
-        let left_pos = pos_by_angle(self.line.end(), left_angle, distance);
-        let right_angle = line_angle - angle + rotate;
-        let right_pos = pos_by_angle(self.line.end(), right_angle, distance);
-        let center_pos = self.line.point_from_end(distance / 1.5);
-        vec![
-            self.line.end(),
-            left_pos,
-            center_pos,
-            right_pos,
-            self.line.end(),
-        ]
-    }
-
     /// Drawing lines between two points: start and end. To determine start and end points there are
     /// self.start_figure and self.end_figure must be used.
     /// Function must use only vertical and hozintal lines to draw
     /// Draw only line, do not drawing arrows
     fn compute_lines_points(&mut self, zoom_factor: f32, scroll_delta: Vec2) -> Vec<Pos2> {
         // Compute line's start and end points
-        self.line = self.line.zoom(zoom_factor / self.zoom_factor);
+        let ratio = zoom_factor / self.zoom_factor;
+        self.line = self.line.zoom(ratio);
+        self.waypoints
+            .iter_mut()
+            .for_each(|waypoint| *waypoint = waypoint.zoom(ratio));
         self.zoom_factor = zoom_factor;
         if self.scroll_delta != scroll_delta {
             self.line = self.line.translate(scroll_delta);
+            self.waypoints
+                .iter_mut()
+                .for_each(|waypoint| *waypoint += scroll_delta);
             self.scroll_delta = scroll_delta;
         }
 
         // Compute real line's start and end points
         self.line
             .set_points([self.compute_start_point(), self.compute_end_point()]);
-        self.line.into_points().to_vec()
+        self.route_points()
     }
 }
 
 impl GraphFigure for ArrowFigure {
+    fn clone_box(&self) -> Box<dyn GraphFigure> {
+        Box::new(self.clone())
+    }
+
     fn set_id(&mut self, id: Id) {
         self.id = id;
     }
@@ -250,14 +820,44 @@ impl GraphFigure for ArrowFigure {
     fn draw(&mut self, ui: &mut Ui, zoom_factor: f32, scroll_delta: Vec2) {
         // Compute start and end points if defined start and end connections
         let line_points = self.compute_lines_points(zoom_factor, scroll_delta);
-        ui.painter()
-            .add(Shape::Path(PathShape::line(line_points, self.fb.stroke)));
+        let (start_line, end_line) = Self::head_lines(&line_points);
+        match self.self_loop_shape() {
+            Some(curve) if self.fb.stroke_pattern == DashPattern::Solid => {
+                ui.painter().add(Shape::CubicBezier(curve));
+            }
+            _ => {
+                for segment in apply_dash(&line_points, self.fb.stroke_pattern, DEFAULT_DASH_LENGTH)
+                {
+                    ui.painter().add(Shape::LineSegment {
+                        points: segment,
+                        stroke: self.fb.stroke,
+                    });
+                }
+            }
+        }
 
-        ui.painter().add(Shape::convex_polygon(
-            self.arrow_for_line(15., 20.).to_vec(),
-            self.fb.fill_color,
+        draw_head(
+            self.start_head_style,
+            start_line,
+            ui.painter(),
             self.fb.stroke,
-        ));
+        );
+        draw_head(self.end_head_style, end_line, ui.painter(), self.fb.stroke);
+
+        if let Some(label) = &mut self.label {
+            let label_rect = Rect::from_center_size(self.line.center(), LABEL_SIZE * zoom_factor);
+            label.draw(
+                label_rect,
+                ui,
+                self.id,
+                self.fb.stroke.color,
+                self.fb.fill.base_color(),
+                &mut self.label_edit,
+            );
+        }
+
+        // Connected figures could be moved since the last frame
+        self.update_connection_points();
     }
 
     fn select(&mut self, selected: SelectMode) {
@@ -265,7 +865,11 @@ impl GraphFigure for ArrowFigure {
     }
 
     fn contains(&self, point: Pos2) -> Option<CursorIcon> {
-        if point.in_line(self.line.into_points(), 2.) {
+        if self
+            .route_points()
+            .windows(2)
+            .any(|segment| point.in_line([segment[0], segment[1]], 2.))
+        {
             Some(CursorIcon::Grab)
         } else {
             None
@@ -283,35 +887,392 @@ impl GraphFigure for ArrowFigure {
     fn move_to(&mut self, pos: Pos2, drag_started: Pos2) {
         let offset = pos - drag_started;
         self.line = self.line.translate(offset);
+        self.waypoints
+            .iter_mut()
+            .for_each(|waypoint| *waypoint += offset);
+        self.update_connection_points();
     }
 
-    fn drag_start(&mut self, _hover_pos: Pos2, _button: PointerButton, _zoom_factor: f32) {
-        todo!()
+    /// Start dragging waypoint or one of the line ends if cursor is near it, otherwise the
+    /// whole line. Dragged end is disconnected from the figure
+    fn drag_start(&mut self, hover_pos: Pos2, _button: PointerButton, _zoom_factor: f32) {
+        let added = match self.drag_mode {
+            ArrowDragMode::AddWaypoint(index) => self
+                .waypoints
+                .get(index)
+                .filter(|waypoint| hover_pos.over(**waypoint, WAYPOINT_MARGIN))
+                .map(|_| index),
+            _ => None,
+        };
+        self.drag_mode = if let Some(index) = added.or_else(|| self.waypoint_at(hover_pos)) {
+            ArrowDragMode::MoveWaypoint(index)
+        } else if hover_pos.distance(self.compute_start_point()) <= ENDPOINT_MARGIN {
+            self.line
+                .set_points([self.compute_start_point(), self.compute_end_point()]);
+            self.disconnect_start();
+            ArrowDragMode::Start
+        } else if hover_pos.distance(self.compute_end_point()) <= ENDPOINT_MARGIN {
+            self.line
+                .set_points([self.compute_start_point(), self.compute_end_point()]);
+            self.disconnect_end();
+            ArrowDragMode::End
+        } else {
+            ArrowDragMode::Move(hover_pos)
+        };
     }
 
-    fn dragged_by(&mut self, _hover_pos: Pos2, _button: PointerButton) {
-        todo!()
+    fn dragged_by(&mut self, hover_pos: Pos2, _button: PointerButton) {
+        match self.drag_mode {
+            ArrowDragMode::None => {}
+            ArrowDragMode::Start => self.line.set_points([hover_pos, self.line.end()]),
+            ArrowDragMode::End => self.line.move_to(hover_pos),
+            ArrowDragMode::Move(drag_started) => {
+                self.move_to(hover_pos, drag_started);
+                self.drag_mode = ArrowDragMode::Move(hover_pos);
+            }
+            ArrowDragMode::MoveWaypoint(index) | ArrowDragMode::AddWaypoint(index) => {
+                if let Some(waypoint) = self.waypoints.get_mut(index) {
+                    *waypoint = hover_pos;
+                }
+            }
+        }
+        self.update_connection_points();
     }
 
-    fn drag_released(&mut self, _hover_pos: Pos2, _button: PointerButton) {
-        todo!()
+    fn drag_released(&mut self, hover_pos: Pos2, button: PointerButton) {
+        self.dragged_by(hover_pos, button);
+        self.drag_mode = ArrowDragMode::None;
     }
 
+    /// Start label editing, label is created if edge has not it yet
     fn double_click(&mut self) {
-        todo!()
+        if self.label.is_none() {
+            self.label = Some(Self::new_label("Label"));
+        }
+        self.label_edit = true;
+    }
+
+    /// Remove waypoint if it is double clicked, add waypoint if the line is double clicked
+    /// between waypoints, otherwise start label editing
+    fn double_click_at(&mut self, pos: Pos2) {
+        if let Some(index) = self.waypoint_at(pos) {
+            self.remove_waypoint(index);
+            self.drag_mode = ArrowDragMode::None;
+        } else if self.contains(pos).is_some() {
+            let index = self.insert_waypoint(pos);
+            self.drag_mode = ArrowDragMode::AddWaypoint(index);
+        } else {
+            self.double_click();
+        }
     }
 
     fn rect(&self) -> Rect {
-        todo!()
+        let mut points = vec![self.line.start(), self.line.end()];
+        points.extend_from_slice(&self.waypoints);
+        Rect::from_points(&points)
     }
 
     fn connection_points(&self) -> &Vec<Pos2> {
-        todo!()
+        &self.connection_points
+    }
+
+    fn type_name(&self) -> &'static str {
+        "arrow"
+    }
+
+    fn label(&self) -> String {
+        self.label
+            .as_ref()
+            .map(|label| label.text().to_owned())
+            .unwrap_or_default()
+    }
+
+    fn tooltip(&self) -> Option<&str> {
+        self.tooltip.as_deref()
+    }
+
+    fn set_tooltip(&mut self, tooltip: Option<String>) {
+        self.tooltip = tooltip;
+    }
+
+    fn fill_color(&self) -> Color32 {
+        self.fb.fill.base_color()
+    }
+
+    fn to_canvas_commands(&self) -> Vec<CanvasCommand> {
+        let points = self.route_points();
+        let (start_line, end_line) = Self::head_lines(&points);
+        apply_dash(&points, self.fb.stroke_pattern, DEFAULT_DASH_LENGTH)
+            .into_iter()
+            .map(|segment| CanvasCommand::DrawLine(segment, self.fb.stroke))
+            .chain(head_commands(
+                self.start_head_style,
+                start_line,
+                self.fb.stroke,
+            ))
+            .chain(head_commands(self.end_head_style, end_line, self.fb.stroke))
+            .collect()
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use eframe::{
+        egui::{Id, PointerButton},
+        emath::{pos2, Rect},
+    };
+
+    use super::{head_shape, ArrowDragMode, ArrowFigure, ArrowHeadStyle, ConnectionPoint};
+    use crate::graph::DashPattern;
+    use crate::graph::{EdgeRouting, GraphFigure, RectFigure};
+
     #[test]
     fn test_arrow_figure_compute_nearest_point() {}
+
+    #[test]
+    fn test_arrow_orthogonal_routing() {
+        let figure = |id: u64, rect: Rect| -> Rc<RefCell<Box<dyn GraphFigure>>> {
+            Rc::new(RefCell::new(Box::new(RectFigure::new(
+                Id::new(id),
+                rect,
+                "".to_owned(),
+            ))))
+        };
+        let source = figure(1, Rect::from_min_max(pos2(0., 0.), pos2(100., 50.)));
+        let target = figure(2, Rect::from_min_max(pos2(200., 100.), pos2(300., 150.)));
+
+        let mut arrow = ArrowFigure::new([pos2(0., 0.), pos2(0., 0.)], Id::new(3));
+        // Right center of the source and left center of the target
+        arrow.connect_start(ConnectionPoint::new(source, 6));
+        arrow.connect_end(ConnectionPoint::new(target, 14));
+        assert_eq!(arrow.route_points().len(), 2);
+
+        arrow.set_routing(EdgeRouting::Orthogonal);
+        let points = arrow.route_points();
+        assert_eq!(points.first(), Some(&pos2(100., 25.)));
+        assert_eq!(points.last(), Some(&pos2(200., 125.)));
+        assert!(points
+            .windows(2)
+            .all(|s| s[0].x == s[1].x || s[0].y == s[1].y));
+        assert_eq!(arrow.to_canvas_commands().len(), points.len());
+    }
+
+    #[test]
+    fn test_head_styles() {
+        let line = [pos2(0., 0.), pos2(100., 0.)];
+        assert!(head_shape(ArrowHeadStyle::None, line).is_none());
+
+        let head = head_shape(ArrowHeadStyle::Open, line).unwrap();
+        assert!(!head.closed);
+        assert_eq!(head.points[1], pos2(100., 0.));
+
+        let head = head_shape(ArrowHeadStyle::OpenDiamond, line).unwrap();
+        assert!(head.closed && !head.filled);
+        assert_eq!(head.points.len(), 4);
+
+        let head = head_shape(ArrowHeadStyle::Circle, line).unwrap();
+        assert!(head.filled);
+        assert!(head.points.iter().all(|p| p.x <= 100.001 && p.x >= 89.999));
+
+        let mut arrow = ArrowFigure::new(line, Id::new(1));
+        arrow.set_start_head_style(ArrowHeadStyle::Open);
+        arrow.set_end_head_style(ArrowHeadStyle::None);
+        assert!(arrow.start_arrow() && !arrow.end_arrow());
+        assert_eq!(arrow.to_canvas_commands().len(), 2);
+    }
+
+    #[test]
+    fn test_dashed_arrow() {
+        let mut arrow = ArrowFigure::new([pos2(0., 0.), pos2(100., 0.)], Id::new(1));
+        assert_eq!(arrow.to_canvas_commands().len(), 2);
+
+        arrow.set_stroke_pattern(DashPattern::Dashed);
+        // Dashes of 8 with gaps of 4 and the arrow head
+        assert_eq!(arrow.to_canvas_commands().len(), 10);
+    }
+
+    #[test]
+    fn test_drag_ends() {
+        let mut arrow = ArrowFigure::new([pos2(0., 0.), pos2(100., 0.)], Id::new(1));
+        assert_eq!(
+            arrow.rect(),
+            Rect::from_min_max(pos2(0., 0.), pos2(100., 0.))
+        );
+
+        arrow.drag_start(pos2(2., 1.), PointerButton::Primary, 1.);
+        assert_eq!(arrow.drag_mode, ArrowDragMode::Start);
+        arrow.dragged_by(pos2(10., 10.), PointerButton::Primary);
+        arrow.drag_released(pos2(20., 20.), PointerButton::Primary);
+        assert_eq!(arrow.drag_mode, ArrowDragMode::None);
+        assert_eq!(arrow.route_points(), vec![pos2(20., 20.), pos2(100., 0.)]);
+
+        arrow.drag_start(pos2(99., 0.), PointerButton::Primary, 1.);
+        assert_eq!(arrow.drag_mode, ArrowDragMode::End);
+        arrow.drag_released(pos2(100., 50.), PointerButton::Primary);
+        assert_eq!(arrow.route_points(), vec![pos2(20., 20.), pos2(100., 50.)]);
+        assert_eq!(
+            arrow.rect(),
+            Rect::from_min_max(pos2(20., 20.), pos2(100., 50.))
+        );
+    }
+
+    #[test]
+    fn test_drag_move() {
+        let mut arrow = ArrowFigure::new([pos2(0., 0.), pos2(100., 0.)], Id::new(1));
+        arrow.drag_start(pos2(50., 0.), PointerButton::Primary, 1.);
+        assert_eq!(arrow.drag_mode, ArrowDragMode::Move(pos2(50., 0.)));
+        arrow.dragged_by(pos2(55., 10.), PointerButton::Primary);
+        arrow.drag_released(pos2(60., 20.), PointerButton::Primary);
+        assert_eq!(arrow.route_points(), vec![pos2(10., 20.), pos2(110., 20.)]);
+
+        // Release without drag start does nothing
+        arrow.drag_released(pos2(0., 0.), PointerButton::Primary);
+        assert_eq!(arrow.route_points(), vec![pos2(10., 20.), pos2(110., 20.)]);
+    }
+
+    #[test]
+    fn test_waypoints() {
+        let mut arrow = ArrowFigure::new([pos2(0., 0.), pos2(100., 0.)], Id::new(1));
+        arrow.double_click_at(pos2(50., 1.));
+        assert_eq!(arrow.waypoints(), &[pos2(50., 1.)]);
+        assert_eq!(arrow.drag_mode, ArrowDragMode::AddWaypoint(0));
+        assert!(!arrow.label_edit);
+        assert_eq!(
+            arrow.route_points(),
+            vec![pos2(0., 0.), pos2(50., 1.), pos2(100., 0.)]
+        );
+
+        // New waypoint is inserted into the clicked segment
+        arrow.double_click_at(pos2(75., 0.));
+        assert_eq!(arrow.waypoints(), &[pos2(50., 1.), pos2(75., 0.)]);
+
+        arrow.drag_start(pos2(52., 2.), PointerButton::Primary, 1.);
+        assert_eq!(arrow.drag_mode, ArrowDragMode::MoveWaypoint(0));
+        arrow.dragged_by(pos2(50., 30.), PointerButton::Primary);
+        arrow.drag_released(pos2(50., 50.), PointerButton::Primary);
+        assert_eq!(arrow.drag_mode, ArrowDragMode::None);
+        assert_eq!(
+            arrow.route_points(),
+            vec![pos2(0., 0.), pos2(50., 50.), pos2(75., 0.), pos2(100., 0.)]
+        );
+        assert_eq!(
+            arrow.rect(),
+            Rect::from_min_max(pos2(0., 0.), pos2(100., 50.))
+        );
+
+        // Double click away from the line edits label
+        arrow.double_click_at(pos2(200., 200.));
+        assert!(arrow.label_edit);
+        assert_eq!(arrow.waypoints().len(), 2);
+
+        // Waypoints are moved with the line and saved
+        arrow.drag_start(pos2(25., 25.), PointerButton::Primary, 1.);
+        arrow.drag_released(pos2(35., 25.), PointerButton::Primary);
+        let json = serde_json::to_string(&arrow).unwrap();
+        let restored: ArrowFigure = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.waypoints(), &[pos2(60., 50.), pos2(85., 0.)]);
+
+        arrow.set_routing(EdgeRouting::Orthogonal);
+        let points = arrow.route_points();
+        assert!(points
+            .windows(2)
+            .all(|s| s[0].x == s[1].x || s[0].y == s[1].y));
+        assert!(points.contains(&pos2(60., 50.)));
+
+        // Double click on waypoint removes it
+        arrow.double_click_at(pos2(85., 1.));
+        assert_eq!(arrow.waypoints(), &[pos2(60., 50.)]);
+    }
+
+    #[test]
+    fn test_drag_connected_end() {
+        let figure: Rc<RefCell<Box<dyn GraphFigure>>> =
+            Rc::new(RefCell::new(Box::new(RectFigure::new(
+                Id::new(1),
+                Rect::from_min_max(pos2(0., 0.), pos2(100., 50.)),
+                "".to_owned(),
+            ))));
+        let mut arrow = ArrowFigure::new([pos2(0., 0.), pos2(300., 300.)], Id::new(2));
+        arrow.connect_start(ConnectionPoint::new(figure, 6));
+        let start = arrow.compute_start_point();
+
+        arrow.drag_start(start, PointerButton::Primary, 1.);
+        assert!(arrow.get_start_connection().is_none());
+        arrow.drag_released(pos2(150., 150.), PointerButton::Primary);
+        assert_eq!(
+            arrow.route_points(),
+            vec![pos2(150., 150.), pos2(300., 300.)]
+        );
+    }
+
+    #[test]
+    fn test_self_loop() {
+        let figure: Rc<RefCell<Box<dyn GraphFigure>>> =
+            Rc::new(RefCell::new(Box::new(RectFigure::new(
+                Id::new(1),
+                Rect::from_min_max(pos2(0., 0.), pos2(100., 50.)),
+                "".to_owned(),
+            ))));
+        let mut arrow = ArrowFigure::new([pos2(0., 0.), pos2(300., 300.)], Id::new(2));
+        arrow.connect_start(ConnectionPoint::new(Rc::clone(&figure), 6));
+        assert!(arrow.self_loop().is_none());
+        arrow.connect_end(ConnectionPoint::new(Rc::clone(&figure), 6));
+
+        let curve = arrow.self_loop_shape().unwrap();
+        let start = arrow.compute_start_point();
+        assert_eq!(curve.points[0], start);
+        assert_eq!(curve.points[3], start);
+
+        // Loop goes out of the figure
+        let top = curve.sample(0.5);
+        assert!(!figure.borrow().rect().contains(top));
+        assert!(arrow.contains(top).is_some());
+        assert!(arrow.contains(pos2(50., 25.)).is_none());
+    }
+
+    #[test]
+    fn test_label() {
+        let mut arrow = ArrowFigure::new([pos2(0., 0.), pos2(100., 0.)], Id::new(1));
+        assert_eq!(arrow.label(), "");
+
+        arrow.double_click();
+        assert!(arrow.label_edit);
+        assert_eq!(arrow.label_text(), Some("Label"));
+
+        arrow.set_label_owned("depends on".to_owned());
+        let json = serde_json::to_string(&arrow).unwrap();
+        let restored: ArrowFigure = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.label(), "depends on");
+        assert!(!restored.label_edit);
+    }
+
+    #[test]
+    fn test_connection_points() {
+        let mut arrow = ArrowFigure::new([pos2(10., 20.), pos2(110., 70.)], Id::new(1));
+        assert_eq!(
+            arrow.connection_points(),
+            &vec![pos2(10., 20.), pos2(110., 70.)]
+        );
+
+        // Connected end follows the figure
+        let figure: Rc<RefCell<Box<dyn GraphFigure>>> =
+            Rc::new(RefCell::new(Box::new(RectFigure::new(
+                Id::new(2),
+                Rect::from_min_max(pos2(200., 200.), pos2(300., 250.)),
+                "".to_owned(),
+            ))));
+        arrow.connect_end(ConnectionPoint::new(Rc::clone(&figure), 0));
+        assert_eq!(arrow.connection_points()[1], pos2(200., 200.));
+    }
+
+    #[test]
+    fn test_default_arrow() {
+        let arrow = ArrowFigure::default();
+        assert_eq!(arrow.line.start(), pos2(0., 0.));
+        assert_eq!(arrow.line.end(), pos2(0., 0.));
+        assert_eq!(arrow.id(), Id::null());
+        assert!(arrow.end_arrow());
+    }
 }
@@ -0,0 +1,140 @@
+use eframe::{
+    egui::{Button, Ui},
+    emath::Vec2,
+};
+use serde::{Deserialize, Serialize};
+
+use super::Graphics;
+
+/// Saved canvas view which can be restored by name
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ViewBookmark {
+    pub name: String,
+    pub scroll_delta: Vec2,
+    pub zoom_factor: f32,
+}
+
+/// Implies named views of the diagram
+impl Graphics {
+    /// Save current view. Bookmark with the same name is replaced
+    pub fn save_bookmark(&mut self, name: String) {
+        let bookmark = ViewBookmark {
+            name,
+            scroll_delta: self.graphics_data.scroll_delta,
+            zoom_factor: self.graphics_data.zoom_factor,
+        };
+
+        let bookmarks = &mut self.graphics_data.bookmarks;
+        match bookmarks
+            .iter_mut()
+            .find(|saved| saved.name == bookmark.name)
+        {
+            Some(saved) => *saved = bookmark,
+            None => bookmarks.push(bookmark),
+        }
+    }
+
+    /// Restore view saved by `save_bookmark`
+    /// ### Return
+    /// false if there is no bookmark with the given name
+    pub fn restore_bookmark(&mut self, name: &str) -> bool {
+        let data = &mut self.graphics_data;
+        match data.bookmarks.iter().find(|saved| saved.name == name) {
+            Some(bookmark) => {
+                data.scroll_delta = bookmark.scroll_delta;
+                data.zoom_factor = bookmark.zoom_factor;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove bookmark by name
+    pub fn remove_bookmark(&mut self, name: &str) {
+        self.graphics_data
+            .bookmarks
+            .retain(|bookmark| bookmark.name != name);
+    }
+
+    /// Return saved views
+    pub fn bookmarks(&self) -> &[ViewBookmark] {
+        &self.graphics_data.bookmarks
+    }
+
+    /// Draw list of bookmarks. Click on bookmark restores the view,
+    /// current view is saved under the entered name
+    pub fn bookmarks_ui(&mut self, ui: &mut Ui) {
+        let mut restored = None;
+        let mut removed = None;
+        for bookmark in self.bookmarks() {
+            ui.horizontal(|ui| {
+                if ui.button(&bookmark.name).clicked() {
+                    restored = Some(bookmark.name.clone());
+                }
+
+                if ui.small_button("🗑").on_hover_text("Remove").clicked() {
+                    removed = Some(bookmark.name.clone());
+                }
+            });
+        }
+
+        if let Some(name) = restored {
+            self.restore_bookmark(&name);
+        }
+
+        if let Some(name) = removed {
+            self.remove_bookmark(&name);
+        }
+
+        ui.separator();
+        ui.text_edit_singleline(&mut self.graphics_data.bookmark_name);
+        let name = self.graphics_data.bookmark_name.trim().to_owned();
+        if ui
+            .add_enabled(!name.is_empty(), Button::new("Save view"))
+            .clicked()
+        {
+            self.save_bookmark(name);
+            self.graphics_data.bookmark_name.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eframe::emath::vec2;
+
+    use crate::graph::Graphics;
+
+    #[test]
+    fn test_bookmarks() {
+        let mut graphics = Graphics::default();
+        graphics.set_scroll_delta(vec2(100., 50.));
+        graphics.save_bookmark("Overview".to_owned());
+        graphics.set_scroll_delta(vec2(-20., 0.));
+        graphics.graphics_data.zoom_factor = 2.;
+        graphics.save_bookmark("Details".to_owned());
+
+        assert!(graphics.restore_bookmark("Overview"));
+        assert_eq!(graphics.graphics_data.scroll_delta, vec2(100., 50.));
+        assert_eq!(graphics.graphics_data.zoom_factor, 1.);
+        assert!(!graphics.restore_bookmark("Missing"));
+
+        // Saving under existing name replaces the view
+        graphics.save_bookmark("Details".to_owned());
+        assert_eq!(graphics.bookmarks().len(), 2);
+        assert_eq!(graphics.bookmarks()[1].zoom_factor, 1.);
+
+        // Bookmarks survive save/load cycle
+        let restored = Graphics::from_json(&graphics.to_json().unwrap()).unwrap();
+        assert_eq!(restored.bookmarks(), graphics.bookmarks());
+
+        graphics.remove_bookmark("Overview");
+        assert_eq!(graphics.bookmarks().len(), 1);
+        assert_eq!(graphics.bookmarks()[0].name, "Details");
+
+        // Diagrams saved without bookmarks are loaded
+        let json =
+            r#"{"last_id":0,"zoom_factor":1.0,"scroll_delta":[0.0,0.0],"figures":[],"edges":[]}"#;
+        assert!(Graphics::from_json(json).unwrap().bookmarks().is_empty());
+    }
+}
@@ -0,0 +1,299 @@
+use std::{cell::RefCell, rc::Rc};
+
+use eframe::{
+    egui::Id,
+    emath::{Pos2, Rect},
+};
+
+use super::{ArrowFigure, GraphFigure};
+
+type FigureRef = Rc<RefCell<Box<dyn GraphFigure>>>;
+
+/// Reversible diagram mutation
+#[derive(Clone)]
+pub enum Command {
+    /// Figure was added to the end of figures list
+    AddFigure(FigureRef),
+    /// Figure was removed from given position of figures list
+    RemoveFigure { index: usize, figure: FigureRef },
+    /// Figure was moved, positions are left top corners of the figure
+    MoveFigure { id: Id, from: Pos2, to: Pos2 },
+    /// Figure was resized
+    ResizeFigure {
+        id: Id,
+        from_rect: Rect,
+        to_rect: Rect,
+    },
+    /// Edge was added to the end of edges list
+    AddEdge(ArrowFigure),
+    /// Edge was removed from given position of edges list
+    RemoveEdge { index: usize, edge: ArrowFigure },
+    /// Edges were removed from given positions of edges list, positions are in ascending order
+    RemoveEdges(Vec<(usize, ArrowFigure)>),
+    /// Edge was dragged or its waypoints were edited, contains edge before and after change
+    ChangeEdge { from: ArrowFigure, to: ArrowFigure },
+    /// Figures were wrapped into group added to the end of figures list. Members are listed
+    /// in ascending order of their positions before grouping
+    Group {
+        group: FigureRef,
+        members: Vec<(usize, FigureRef)>,
+    },
+    /// Group at given position was dissolved into its members
+    Ungroup { index: usize, group: FigureRef },
+    /// Figure was moved from one position of figures list to another, changing drawing order
+    Reorder { from: usize, to: usize },
+    /// Several commands performed as one user action, e.g. moving of all selected figures
+    Batch(Vec<Command>),
+}
+
+/// Undo/redo operations over diagram history
+pub trait CommandStack {
+    /// Store performed command in history. Redo stack is cleared
+    fn push_command(&mut self, command: Command);
+    /// Revert last performed command
+    /// ### Return
+    /// true if there was a command to undo
+    fn undo(&mut self) -> bool;
+    /// Perform again last reverted command
+    /// ### Return
+    /// true if there was a command to redo
+    fn redo(&mut self) -> bool;
+}
+
+/// Find figure by identifier
+#[inline]
+fn find_figure(figures: &[FigureRef], id: Id) -> Option<&FigureRef> {
+    figures.iter().find(|fig| RefCell::borrow(fig).id() == id)
+}
+
+/// Remove figure from the list comparing by pointer
+fn remove_figure(figures: &mut Vec<FigureRef>, figure: &FigureRef) {
+    if let Some(idx) = figures.iter().position(|fig| Rc::ptr_eq(fig, figure)) {
+        figures.remove(idx);
+    }
+}
+
+/// Move figure to another position of the list
+fn move_figure(figures: &mut Vec<FigureRef>, from: usize, to: usize) {
+    if from < figures.len() {
+        let figure = figures.remove(from);
+        figures.insert(to.min(figures.len()), figure);
+    }
+}
+
+/// Return members of the group figure
+fn group_members(group: &FigureRef) -> Vec<FigureRef> {
+    RefCell::borrow(group)
+        .as_group()
+        .map(|group| group.members().clone())
+        .unwrap_or_default()
+}
+
+/// Remove edge from the list comparing by identifier
+fn remove_edge(edges: &mut Vec<ArrowFigure>, edge: &ArrowFigure) {
+    if let Some(idx) = edges.iter().position(|e| e.id() == edge.id()) {
+        edges.remove(idx);
+    }
+}
+
+/// Replace edge with the same identifier
+fn replace_edge(edges: &mut [ArrowFigure], edge: &ArrowFigure) {
+    if let Some(e) = edges.iter_mut().find(|e| e.id() == edge.id()) {
+        *e = edge.clone();
+    }
+}
+
+impl Command {
+    /// Revert command
+    /// ### Arguments
+    /// * figures - figures of the diagram
+    /// * edges - edges of the diagram
+    pub fn undo(&self, figures: &mut Vec<FigureRef>, edges: &mut Vec<ArrowFigure>) {
+        match self {
+            Command::AddFigure(figure) => remove_figure(figures, figure),
+            Command::RemoveFigure { index, figure } => {
+                figures.insert((*index).min(figures.len()), Rc::clone(figure))
+            }
+            Command::MoveFigure { id, from, to } => {
+                if let Some(figure) = find_figure(figures, *id) {
+                    RefCell::borrow_mut(figure).move_to(*from, *to);
+                }
+            }
+            Command::ResizeFigure { id, from_rect, .. } => {
+                if let Some(figure) = find_figure(figures, *id) {
+                    RefCell::borrow_mut(figure).set_rect(*from_rect);
+                }
+            }
+            Command::Group { group, members } => {
+                remove_figure(figures, group);
+                for (index, member) in members {
+                    figures.insert((*index).min(figures.len()), Rc::clone(member));
+                }
+            }
+            Command::Ungroup { index, group } => {
+                for member in group_members(group) {
+                    remove_figure(figures, &member);
+                }
+                figures.insert((*index).min(figures.len()), Rc::clone(group));
+            }
+            Command::AddEdge(edge) => remove_edge(edges, edge),
+            Command::RemoveEdge { index, edge } => {
+                edges.insert((*index).min(edges.len()), edge.clone())
+            }
+            Command::RemoveEdges(removed) => {
+                for (index, edge) in removed {
+                    edges.insert((*index).min(edges.len()), edge.clone());
+                }
+            }
+            Command::ChangeEdge { from, .. } => replace_edge(edges, from),
+            Command::Reorder { from, to } => move_figure(figures, *to, *from),
+            Command::Batch(commands) => {
+                for command in commands.iter().rev() {
+                    command.undo(figures, edges);
+                }
+            }
+        }
+    }
+
+    /// Perform command again after it was reverted
+    /// ### Arguments
+    /// * figures - figures of the diagram
+    /// * edges - edges of the diagram
+    pub fn redo(&self, figures: &mut Vec<FigureRef>, edges: &mut Vec<ArrowFigure>) {
+        match self {
+            Command::AddFigure(figure) => figures.push(Rc::clone(figure)),
+            Command::RemoveFigure { figure, .. } => remove_figure(figures, figure),
+            Command::MoveFigure { id, from, to } => {
+                if let Some(figure) = find_figure(figures, *id) {
+                    RefCell::borrow_mut(figure).move_to(*to, *from);
+                }
+            }
+            Command::ResizeFigure { id, to_rect, .. } => {
+                if let Some(figure) = find_figure(figures, *id) {
+                    RefCell::borrow_mut(figure).set_rect(*to_rect);
+                }
+            }
+            Command::Group { group, members } => {
+                for (_, member) in members {
+                    remove_figure(figures, member);
+                }
+                figures.push(Rc::clone(group));
+            }
+            Command::Ungroup { group, .. } => {
+                if let Some(idx) = figures.iter().position(|fig| Rc::ptr_eq(fig, group)) {
+                    figures.splice(idx..=idx, group_members(group));
+                }
+            }
+            Command::AddEdge(edge) => edges.push(edge.clone()),
+            Command::RemoveEdge { edge, .. } => remove_edge(edges, edge),
+            Command::RemoveEdges(removed) => {
+                for (_, edge) in removed {
+                    remove_edge(edges, edge);
+                }
+            }
+            Command::ChangeEdge { to, .. } => replace_edge(edges, to),
+            Command::Reorder { from, to } => move_figure(figures, *from, *to),
+            Command::Batch(commands) => {
+                for command in commands {
+                    command.redo(figures, edges);
+                }
+            }
+        }
+    }
+
+    /// Build command describing figure's geometry change
+    /// ### Arguments
+    /// * id - figure identifier
+    /// * from_rect - figure rectangle before change
+    /// * to_rect - figure rectangle after change
+    /// ### Return
+    /// move command if only position was changed, resize command if size was changed
+    /// and None if figure was not changed
+    pub fn from_rect_change(id: Id, from_rect: Rect, to_rect: Rect) -> Option<Command> {
+        if from_rect == to_rect {
+            None
+        } else if from_rect.size() == to_rect.size() {
+            Some(Command::MoveFigure {
+                id,
+                from: from_rect.min,
+                to: to_rect.min,
+            })
+        } else {
+            Some(Command::ResizeFigure {
+                id,
+                from_rect,
+                to_rect,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use eframe::{
+        egui::Id,
+        emath::{pos2, Rect},
+    };
+
+    use super::Command;
+    use crate::graph::{ArrowFigure, GraphFigure, RectFigure};
+
+    fn rect_figure(id: u64, rect: Rect) -> Rc<RefCell<Box<dyn GraphFigure>>> {
+        Rc::new(RefCell::new(Box::new(RectFigure::new(
+            Id::new(id),
+            rect,
+            "".to_owned(),
+        ))))
+    }
+
+    #[test]
+    fn test_command_undo_redo() {
+        let from_rect = Rect::from_min_max(pos2(0., 0.), pos2(100., 50.));
+        let figure = rect_figure(1, from_rect);
+        let mut figures = vec![figure.clone()];
+        let mut edges = vec![];
+
+        let moved = from_rect.translate([10., 20.].into());
+        let command = Command::from_rect_change(Id::new(1), from_rect, moved).unwrap();
+        assert!(matches!(command, Command::MoveFigure { .. }));
+        command.redo(&mut figures, &mut edges);
+        assert_eq!(RefCell::borrow(&figure).rect(), moved);
+        command.undo(&mut figures, &mut edges);
+        assert_eq!(RefCell::borrow(&figure).rect(), from_rect);
+
+        let resized = Rect::from_min_max(pos2(0., 0.), pos2(150., 50.));
+        let command = Command::from_rect_change(Id::new(1), from_rect, resized).unwrap();
+        assert!(matches!(command, Command::ResizeFigure { .. }));
+        command.redo(&mut figures, &mut edges);
+        assert_eq!(RefCell::borrow(&figure).rect(), resized);
+        command.undo(&mut figures, &mut edges);
+        assert_eq!(RefCell::borrow(&figure).rect(), from_rect);
+        assert!(Command::from_rect_change(Id::new(1), from_rect, from_rect).is_none());
+
+        let command = Command::Batch(vec![
+            Command::RemoveFigure {
+                index: 0,
+                figure: figure.clone(),
+            },
+            Command::AddEdge(ArrowFigure::new([pos2(0., 0.), pos2(10., 10.)], Id::new(2))),
+        ]);
+        command.redo(&mut figures, &mut edges);
+        assert!(figures.is_empty());
+        assert_eq!(edges.len(), 1);
+        command.undo(&mut figures, &mut edges);
+        assert!(Rc::ptr_eq(&figures[0], &figure));
+        assert!(edges.is_empty());
+
+        let from = ArrowFigure::new([pos2(0., 0.), pos2(100., 0.)], Id::new(3));
+        let mut to = from.clone();
+        to.set_waypoints(vec![pos2(50., 50.)]);
+        let mut edges = vec![to.clone()];
+        let command = Command::ChangeEdge { from, to };
+        command.undo(&mut figures, &mut edges);
+        assert!(edges[0].waypoints().is_empty());
+        command.redo(&mut figures, &mut edges);
+        assert_eq!(edges[0].waypoints(), &[pos2(50., 50.)]);
+    }
+}
@@ -0,0 +1,136 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use eframe::{
+    egui::Painter,
+    emath::{Align2, Pos2, Vec2},
+    epaint::{color::Hsva, Color32, FontId},
+};
+
+/// Time after the last update when remote cursor is removed, milliseconds
+pub const REMOTE_CURSOR_TTL_MS: i64 = 3000;
+
+/// Radius of the remote cursor dot
+const REMOTE_CURSOR_RADIUS: f32 = 4.;
+
+/// Cursor of another user connected to the same board
+#[derive(Clone, Debug)]
+pub struct RemoteCursor {
+    /// Last received cursor position
+    pub position: Pos2,
+    /// Time when position was received
+    pub updated: DateTime<Utc>,
+}
+
+/// Remote cursors by user identifier
+pub type RemoteCursors = HashMap<String, RemoteCursor>;
+
+/// Store new cursor position of the user
+/// ### Arguments
+/// * cursors - known remote cursors
+/// * user - user identifier
+/// * position - cursor position
+/// * now - time when position was received
+pub fn update_remote_cursor(
+    cursors: &mut RemoteCursors,
+    user: &str,
+    position: Pos2,
+    now: DateTime<Utc>,
+) {
+    cursors.insert(
+        user.to_owned(),
+        RemoteCursor {
+            position,
+            updated: now,
+        },
+    );
+}
+
+/// Remove cursors which were not updated during `REMOTE_CURSOR_TTL_MS`
+pub fn remove_stale_cursors(cursors: &mut RemoteCursors, now: DateTime<Utc>) {
+    cursors.retain(|_, cursor| opacity(cursor, now) > 0.);
+}
+
+/// Compute cursor opacity, it fades out linearly until cursor becomes stale
+/// ### Return
+/// opacity in range [0; 1], 0 means cursor is stale
+fn opacity(cursor: &RemoteCursor, now: DateTime<Utc>) -> f32 {
+    let age = now - cursor.updated;
+    let ttl = Duration::milliseconds(REMOTE_CURSOR_TTL_MS);
+    if age >= ttl {
+        0.
+    } else {
+        1. - age.num_milliseconds().max(0) as f32 / REMOTE_CURSOR_TTL_MS as f32
+    }
+}
+
+/// Compute color of the user's cursor. Color depends on user identifier only,
+/// so all clients show the same user with the same color
+pub fn cursor_color(user: &str) -> Color32 {
+    let mut hasher = DefaultHasher::new();
+    user.hash(&mut hasher);
+    let hue = (hasher.finish() % 360) as f32 / 360.;
+    Hsva::new(hue, 0.8, 0.9, 1.).into()
+}
+
+/// Draw labeled dot for each remote cursor
+/// ### Arguments
+/// * painter - painter to draw with
+/// * cursors - remote cursors to draw
+/// * now - current time, used to fade out old cursors
+pub fn draw_remote_cursors(painter: &Painter, cursors: &RemoteCursors, now: DateTime<Utc>) {
+    for (user, cursor) in cursors {
+        let opacity = opacity(cursor, now);
+        if opacity <= 0. {
+            continue;
+        }
+
+        let color = cursor_color(user).linear_multiply(opacity);
+        painter.circle_filled(cursor.position, REMOTE_CURSOR_RADIUS, color);
+        painter.text(
+            cursor.position + Vec2::splat(REMOTE_CURSOR_RADIUS),
+            Align2::LEFT_TOP,
+            user,
+            FontId::proportional(8.),
+            color,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use eframe::emath::pos2;
+
+    use super::{cursor_color, remove_stale_cursors, update_remote_cursor, RemoteCursors};
+
+    #[test]
+    fn test_remote_cursors() {
+        let now = Utc::now();
+        let mut cursors = RemoteCursors::new();
+        update_remote_cursor(&mut cursors, "alice", pos2(1., 2.), now);
+        update_remote_cursor(
+            &mut cursors,
+            "bob",
+            pos2(3., 4.),
+            now - Duration::seconds(4),
+        );
+        update_remote_cursor(&mut cursors, "alice", pos2(5., 6.), now);
+
+        remove_stale_cursors(&mut cursors, now);
+        assert_eq!(cursors.len(), 1);
+        assert_eq!(cursors["alice"].position, pos2(5., 6.));
+
+        remove_stale_cursors(&mut cursors, now + Duration::seconds(3));
+        assert!(cursors.is_empty());
+    }
+
+    #[test]
+    fn test_cursor_color() {
+        assert_eq!(cursor_color("alice"), cursor_color("alice"));
+        assert_ne!(cursor_color("alice"), cursor_color("bob"));
+    }
+}
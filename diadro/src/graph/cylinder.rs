@@ -0,0 +1,520 @@
+use eframe::{
+    egui::{CursorIcon, Id, PointerButton, Ui},
+    emath::pos2,
+    epaint::{Color32, CubicBezierShape, FontFamily, Pos2, Rect, Shape, Stroke, Vec2},
+};
+use serde::{Deserialize, Serialize};
+
+use super::persist::FigureKind;
+use super::shapes::{
+    draw_selection_border, resize_direction, zoom_rect, FigureBasics, SelectMode,
+    SELECT_MODE_HOVER, SELECT_MODE_NONE, SELECT_MODE_SELECTED,
+};
+use super::text::TextOps;
+use super::utils::PointMath;
+use super::{DragMode, GraphFigure};
+use crate::rgraph::{CanvasCommand, Figure};
+
+/// Tolerance to detect cursor over the border or connection point
+const MARGIN: f32 = 10.;
+/// Vertical radius of the caps relative to the figure height
+const CAP_RATIO: f32 = 0.1;
+/// Distance from the ends to the control points of the bezier curve approximating
+/// quarter of the ellipse, relative to the radius
+const BEZIER_KAPPA: f32 = 0.552_284_8;
+/// Tolerance used to flatten bezier curves into polylines
+const FLATTEN_TOLERANCE: f32 = 0.5;
+
+/// Cylinder with elliptic caps. Used as database or storage node
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CylinderFigure {
+    id: Id,
+    rect: Rect,
+    fb: FigureBasics,
+    #[serde(skip)]
+    selected: SelectMode,
+    zoom_factor: f32,
+    scroll_delta: Vec2,
+    #[serde(skip)]
+    drag_mode: DragMode,
+    text: TextOps,
+    #[serde(skip)]
+    text_edit: bool,
+    #[serde(skip)]
+    connection_points: Vec<Pos2>,
+    /// Offset of the selection border dash pattern
+    #[serde(skip)]
+    selection_offset: f32,
+}
+
+impl Default for CylinderFigure {
+    fn default() -> Self {
+        Self {
+            id: Id::new(1),
+            rect: Rect {
+                min: Pos2::ZERO,
+                max: Pos2::ZERO,
+            },
+            selected: SELECT_MODE_NONE,
+            zoom_factor: 1.0,
+            scroll_delta: Vec2::ZERO,
+            drag_mode: DragMode::Extend,
+            text: TextOps::new("Database"),
+            text_edit: false,
+            fb: Default::default(),
+            connection_points: Default::default(),
+            selection_offset: 0.,
+        }
+    }
+}
+
+/// Radius of the caps inscribed into rectangle
+#[inline]
+fn cap_radius(rect: Rect) -> Vec2 {
+    Vec2::new(rect.width() / 2., rect.height() * CAP_RATIO)
+}
+
+/// Centers of the top and bottom caps
+#[inline]
+fn cap_centers(rect: Rect) -> [Pos2; 2] {
+    let radius = cap_radius(rect);
+    [
+        pos2(rect.center().x, rect.top() + radius.y),
+        pos2(rect.center().x, rect.bottom() - radius.y),
+    ]
+}
+
+/// Bezier curves approximating quarters of the ellipse. Quarters go clockwise on screen
+/// starting from the right point: right-bottom, bottom-left, left-top, top-right
+fn ellipse_quarters(center: Pos2, radius: Vec2) -> [[Pos2; 4]; 4] {
+    let k = radius * BEZIER_KAPPA;
+    let (rx, ry) = (radius.x, radius.y);
+    [
+        [
+            center + Vec2::new(rx, 0.),
+            center + Vec2::new(rx, k.y),
+            center + Vec2::new(k.x, ry),
+            center + Vec2::new(0., ry),
+        ],
+        [
+            center + Vec2::new(0., ry),
+            center + Vec2::new(-k.x, ry),
+            center + Vec2::new(-rx, k.y),
+            center + Vec2::new(-rx, 0.),
+        ],
+        [
+            center + Vec2::new(-rx, 0.),
+            center + Vec2::new(-rx, -k.y),
+            center + Vec2::new(-k.x, -ry),
+            center + Vec2::new(0., -ry),
+        ],
+        [
+            center + Vec2::new(0., -ry),
+            center + Vec2::new(k.x, -ry),
+            center + Vec2::new(rx, -k.y),
+            center + Vec2::new(rx, 0.),
+        ],
+    ]
+}
+
+/// Outline of the cylinder: upper half of the top cap, right side, lower half
+/// of the bottom cap and left side. Outline is convex and not closed
+fn cylinder_outline(rect: Rect) -> Vec<Pos2> {
+    let radius = cap_radius(rect);
+    let [top, bottom] = cap_centers(rect);
+    let [right_bottom, bottom_left, _, _] = ellipse_quarters(bottom, radius);
+    let [_, _, left_top, top_right] = ellipse_quarters(top, radius);
+
+    let mut outline: Vec<Pos2> = [left_top, top_right, right_bottom, bottom_left]
+        .iter()
+        .flat_map(|points| {
+            CubicBezierShape::from_points_stroke(
+                *points,
+                false,
+                Color32::TRANSPARENT,
+                Stroke::none(),
+            )
+            .flatten(Some(FLATTEN_TOLERANCE))
+        })
+        .collect();
+    outline.dedup();
+    outline
+}
+
+/// Check if point lies inside the ellipse
+#[inline]
+fn in_ellipse(point: Pos2, center: Pos2, radius: Vec2) -> bool {
+    if radius.x <= 0. || radius.y <= 0. {
+        return false;
+    }
+
+    let delta = point - center;
+    (delta.x / radius.x).powi(2) + (delta.y / radius.y).powi(2) <= 1.
+}
+
+impl GraphFigure for CylinderFigure {
+    fn clone_box(&self) -> Box<dyn GraphFigure> {
+        Box::new(self.clone())
+    }
+
+    fn set_id(&mut self, id: Id) {
+        self.id = id;
+    }
+
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn draw(&mut self, ui: &mut Ui, zoom_factor: f32, scroll_delta: Vec2) {
+        self.zoom(zoom_factor, scroll_delta);
+
+        let bg_color = match self.selected {
+            x if x & SELECT_MODE_HOVER > 0 => self.fb.selected_fill_color,
+            _ => self.fb.fill.base_color(),
+        };
+
+        ui.painter().add(Shape::convex_polygon(
+            cylinder_outline(self.rect),
+            bg_color,
+            self.fb.stroke,
+        ));
+
+        // Front half of the top cap, back half is a part of the outline
+        let [top, _] = cap_centers(self.rect);
+        let [right_bottom, bottom_left, _, _] = ellipse_quarters(top, cap_radius(self.rect));
+        for points in [right_bottom, bottom_left] {
+            ui.painter()
+                .add(Shape::CubicBezier(CubicBezierShape::from_points_stroke(
+                    points,
+                    false,
+                    Color32::TRANSPARENT,
+                    self.fb.stroke,
+                )));
+        }
+
+        self.text.draw(
+            self.text_rect(),
+            ui,
+            self.id(),
+            Color32::BLUE,
+            bg_color,
+            &mut self.text_edit,
+        );
+
+        if self.selected & SELECT_MODE_SELECTED > 0 {
+            self.draw_selection_border(ui);
+        }
+    }
+
+    fn select(&mut self, selected: SelectMode) {
+        self.selected = selected;
+    }
+
+    fn contains(&self, point: Pos2) -> Option<CursorIcon> {
+        if self
+            .connection_points
+            .iter()
+            .any(|cp| point.over(*cp, MARGIN / 2.))
+        {
+            return Some(CursorIcon::Crosshair);
+        }
+
+        if let Some((icon, _)) = self.resize_direction(point) {
+            return Some(icon);
+        }
+
+        if self.inside(point) {
+            Some(CursorIcon::Default)
+        } else {
+            None
+        }
+    }
+
+    fn selected(&self) -> SelectMode {
+        self.selected
+    }
+
+    fn move_to(&mut self, pos: Pos2, drag_started: Pos2) {
+        let offset = pos - drag_started;
+        self.rect = self.rect.translate(offset);
+        for point in &mut self.connection_points {
+            *point += offset;
+        }
+    }
+
+    fn drag_start(&mut self, hover_pos: Pos2, _button: PointerButton, zoom_factor: f32) {
+        match self.selected {
+            x if x & SELECT_MODE_HOVER > 0 || x & SELECT_MODE_SELECTED > 0 => {
+                self.drag_mode = match self.resize_direction(hover_pos) {
+                    Some((_, drag_mode)) => drag_mode,
+                    None => DragMode::Move(hover_pos),
+                };
+            }
+            _ => {
+                self.drag_mode = DragMode::Extend;
+                self.rect = Rect::from_two_pos(hover_pos, hover_pos);
+            }
+        }
+
+        self.zoom_factor = zoom_factor;
+    }
+
+    fn dragged_by(&mut self, hover_pos: Pos2, _button: PointerButton) {
+        match self.drag_mode {
+            DragMode::Move(drag_started) => {
+                self.move_to(hover_pos, drag_started);
+                self.drag_mode = DragMode::Move(hover_pos);
+            }
+            drag_mode => {
+                drag_mode.resize(&mut self.rect, hover_pos);
+                self.compute_connection_points();
+            }
+        }
+    }
+
+    fn drag_released(&mut self, hover_pos: Pos2, button: PointerButton) {
+        self.dragged_by(hover_pos, button);
+    }
+
+    fn double_click(&mut self) {
+        self.text_edit = true;
+    }
+
+    fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    fn set_rect(&mut self, rect: Rect) {
+        self.rect = rect;
+        self.compute_connection_points();
+    }
+
+    fn connection_points(&self) -> &Vec<Pos2> {
+        &self.connection_points
+    }
+
+    fn type_name(&self) -> &'static str {
+        "cylinder"
+    }
+
+    fn label(&self) -> String {
+        self.text.text().to_owned()
+    }
+
+    fn set_text(&mut self, s: String) {
+        self.text.replace_text(s);
+    }
+
+    fn text_edited(&self) -> bool {
+        self.text_edit
+    }
+
+    fn font_family(&self) -> Option<FontFamily> {
+        Some(self.text.font_family().clone())
+    }
+
+    fn set_font_family(&mut self, family: FontFamily) {
+        self.text.set_font_family(family);
+    }
+
+    fn fill_color(&self) -> Color32 {
+        self.fb.fill.base_color()
+    }
+
+    fn to_canvas_commands(&self) -> Vec<CanvasCommand> {
+        let [top, _] = cap_centers(self.rect);
+        let [right_bottom, bottom_left, _, _] = ellipse_quarters(top, cap_radius(self.rect));
+        let rim = [right_bottom, bottom_left]
+            .iter()
+            .flat_map(|points| {
+                CubicBezierShape::from_points_stroke(
+                    *points,
+                    false,
+                    Color32::TRANSPARENT,
+                    Stroke::none(),
+                )
+                .flatten(Some(FLATTEN_TOLERANCE))
+            })
+            .collect();
+
+        vec![
+            CanvasCommand::DrawPath {
+                points: cylinder_outline(self.rect),
+                closed: true,
+                fill: self.fb.fill.base_color(),
+                stroke: self.fb.stroke,
+            },
+            CanvasCommand::DrawPath {
+                points: rim,
+                closed: false,
+                fill: Color32::TRANSPARENT,
+                stroke: self.fb.stroke,
+            },
+            CanvasCommand::DrawText {
+                pos: self.text_rect().left_top(),
+                text: self.text.text().to_owned(),
+                font_size: self.text.font_size(),
+                color: Color32::BLUE,
+            },
+        ]
+    }
+
+    fn set_selection_offset(&mut self, offset: f32) {
+        self.selection_offset = offset;
+    }
+
+    fn to_kind(&self) -> Option<FigureKind> {
+        Some(FigureKind::Cylinder(self.clone()))
+    }
+}
+
+impl CylinderFigure {
+    /// Construct figure with given position and text
+    pub fn new(id: Id, rect: Rect, text: String) -> Self {
+        let mut figure = Self {
+            id,
+            rect,
+            text: TextOps::new(text),
+            ..Default::default()
+        };
+        figure.compute_connection_points();
+        figure
+    }
+
+    fn zoom(&mut self, zoom_factor: f32, scroll_delta: Vec2) {
+        self.rect = zoom_rect(
+            self.rect,
+            (self.zoom_factor, self.scroll_delta),
+            (zoom_factor, scroll_delta),
+        );
+        self.zoom_factor = zoom_factor;
+        self.scroll_delta = scroll_delta;
+        self.compute_connection_points();
+    }
+
+    /// Four connection points on the top cap: top, right, front, left.
+    /// Four points on the sides: left upper and lower, right upper and lower
+    fn compute_connection_points(&mut self) {
+        if self.rect.size() == Vec2::ZERO {
+            return;
+        }
+
+        let radius = cap_radius(self.rect);
+        let [top, bottom] = cap_centers(self.rect);
+        let side = |x: f32, ratio: f32| pos2(x, top.y + (bottom.y - top.y) * ratio);
+        self.connection_points = vec![
+            top - Vec2::new(0., radius.y),
+            top + Vec2::new(radius.x, 0.),
+            top + Vec2::new(0., radius.y),
+            top - Vec2::new(radius.x, 0.),
+            side(self.rect.left(), 1. / 3.),
+            side(self.rect.left(), 2. / 3.),
+            side(self.rect.right(), 1. / 3.),
+            side(self.rect.right(), 2. / 3.),
+        ];
+    }
+
+    /// Part of the body below the top cap, used to place text
+    fn text_rect(&self) -> Rect {
+        let radius = cap_radius(self.rect);
+        Rect::from_min_max(
+            pos2(self.rect.left(), self.rect.top() + radius.y * 2.),
+            self.rect.max,
+        )
+    }
+
+    /// Check if point lies inside the body or inside one of the caps. Body and bounding
+    /// rectangles of the caps are checked by `Figure::contains_in_rect`, caps are then
+    /// clipped by the ellipse
+    fn inside(&self, point: Pos2) -> bool {
+        let radius = cap_radius(self.rect);
+        let [top, bottom] = cap_centers(self.rect);
+        let body = Rect::from_x_y_ranges(self.rect.x_range(), top.y..=bottom.y);
+        let in_cap = |center: Pos2| {
+            Figure::contains_in_rect(Rect::from_center_size(center, radius * 2.), point, 0.)
+                .is_some()
+                && in_ellipse(point, center, radius)
+        };
+
+        Figure::contains_in_rect(body, point, 0.).is_some() || in_cap(top) || in_cap(bottom)
+    }
+
+    /// Detect if point is near the border of the bounding rectangle and compute resize cursor
+    /// and drag mode
+    fn resize_direction(&self, point: Pos2) -> Option<(CursorIcon, DragMode)> {
+        resize_direction(self.rect, point, MARGIN)
+    }
+
+    /// Draw animated dashed border around selected figure
+    fn draw_selection_border(&self, ui: &mut Ui) {
+        let mut border = cylinder_outline(self.rect.expand(3.));
+        if let Some(first) = border.first().copied() {
+            border.push(first);
+        }
+
+        draw_selection_border(ui, &border, self.selection_offset, self.fb.selected_stroke);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eframe::{
+        egui::{CursorIcon, PointerButton},
+        emath::{pos2, Rect},
+    };
+
+    use super::CylinderFigure;
+    use crate::graph::{shapes::SELECT_MODE_SELECTED, GraphFigure};
+
+    fn cylinder() -> CylinderFigure {
+        let mut figure = CylinderFigure::default();
+        figure.drag_start(pos2(0., 0.), PointerButton::Primary, 1.);
+        figure.drag_released(pos2(100., 200.), PointerButton::Primary);
+        figure
+    }
+
+    #[test]
+    fn test_cylinder_contains() {
+        let figure = cylinder();
+        assert_eq!(figure.connection_points().len(), 8);
+        assert_eq!(figure.connection_points()[0], pos2(50., 0.));
+        assert_eq!(figure.connection_points()[2], pos2(50., 40.));
+
+        assert_eq!(figure.contains(pos2(50., 100.)), Some(CursorIcon::Default));
+        // Inside the caps
+        assert_eq!(figure.contains(pos2(50., 10.)), Some(CursorIcon::Default));
+        assert_eq!(figure.contains(pos2(50., 190.)), Some(CursorIcon::Default));
+        // Corner of the bounding rect is outside the caps, but is used to resize figure
+        assert_eq!(figure.contains(pos2(2., 2.)), Some(CursorIcon::ResizeNwSe));
+        assert_eq!(figure.contains(pos2(8., 193.)), None);
+        assert_eq!(figure.contains(pos2(150., 100.)), None);
+        assert_eq!(
+            figure.contains(pos2(99., 100.)),
+            Some(CursorIcon::ResizeHorizontal)
+        );
+    }
+
+    #[test]
+    fn test_cylinder_resize() {
+        let mut figure = cylinder();
+        figure.select(SELECT_MODE_SELECTED);
+
+        // Drag bottom side
+        figure.drag_start(pos2(50., 199.), PointerButton::Primary, 1.);
+        figure.drag_released(pos2(50., 299.), PointerButton::Primary);
+        assert_eq!(
+            figure.rect(),
+            Rect::from_min_max(pos2(0., 0.), pos2(100., 299.))
+        );
+
+        // Drag inside moves figure
+        figure.drag_start(pos2(50., 150.), PointerButton::Primary, 1.);
+        figure.drag_released(pos2(60., 150.), PointerButton::Primary);
+        assert_eq!(
+            figure.rect(),
+            Rect::from_min_max(pos2(10., 0.), pos2(110., 299.))
+        );
+        assert_eq!(figure.connection_points()[0], pos2(60., 0.));
+    }
+}
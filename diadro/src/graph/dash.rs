@@ -0,0 +1,135 @@
+use eframe::emath::Pos2;
+use serde::{Deserialize, Serialize};
+
+use super::utils::dashed_path;
+
+/// Default length of the dash, other pattern elements are computed from it
+pub const DEFAULT_DASH_LENGTH: f32 = 8.;
+/// Shorter dash lengths are increased to it, so number of drawn segments stays limited
+const MIN_DASH_LENGTH: f32 = 1.;
+
+/// Pattern of the stroke line
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DashPattern {
+    /// Continuous line
+    Solid,
+    /// Dashes separated by gaps
+    Dashed,
+    /// Dots separated by gaps
+    Dotted,
+    /// Dashes and dots separated by gaps
+    DashDot,
+}
+
+impl Default for DashPattern {
+    fn default() -> Self {
+        DashPattern::Solid
+    }
+}
+
+impl DashPattern {
+    /// Drawn parts of the pattern
+    /// ### Arguments
+    /// * segment_len - dash length
+    /// ### Return
+    /// length and offset from the pattern start of each drawn part with the pattern period,
+    /// None for solid line
+    fn parts(&self, segment_len: f32) -> Option<(Vec<(f32, f32)>, f32)> {
+        let dot = (segment_len / 5.).max(1.);
+        match self {
+            DashPattern::Solid => None,
+            DashPattern::Dashed => Some((vec![(segment_len, 0.)], segment_len * 1.5)),
+            DashPattern::Dotted => Some((vec![(dot, 0.)], dot + segment_len / 2.)),
+            DashPattern::DashDot => {
+                let gap = segment_len / 3.;
+                Some((
+                    vec![(segment_len, 0.), (dot, segment_len + gap)],
+                    segment_len + dot + 2. * gap,
+                ))
+            }
+        }
+    }
+}
+
+/// Split polyline into segments to draw according to dash pattern. Pattern continues
+/// over polyline corners
+/// ### Arguments
+/// * points - polyline points
+/// * pattern - dash pattern
+/// * segment_len - dash length, not positive length draws solid line
+/// ### Return
+/// line segments to draw grouped by pattern parts, polyline segments for solid line
+pub fn apply_dash(points: &[Pos2], pattern: DashPattern, segment_len: f32) -> Vec<[Pos2; 2]> {
+    let segments = |polyline: &[Pos2]| -> Vec<[Pos2; 2]> {
+        polyline.windows(2).map(|s| [s[0], s[1]]).collect()
+    };
+    if !segment_len.is_finite() || segment_len <= 0. {
+        return segments(points);
+    }
+
+    match pattern.parts(segment_len.max(MIN_DASH_LENGTH)) {
+        Some((parts, period)) => parts
+            .iter()
+            .flat_map(|(len, offset)| dashed_path(points, *len, period - len, -offset))
+            .flat_map(|dash| segments(&dash))
+            .collect(),
+        None => segments(points),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eframe::emath::pos2;
+
+    use super::{apply_dash, DashPattern};
+
+    #[test]
+    fn test_apply_dash() {
+        let points = [pos2(0., 0.), pos2(20., 0.), pos2(20., 40.)];
+        assert_eq!(
+            apply_dash(&points, DashPattern::Solid, 8.),
+            vec![[points[0], points[1]], [points[1], points[2]]]
+        );
+
+        // Dash 10, gap 5: the second dash starts on the first segment and ends on the second one
+        assert_eq!(
+            apply_dash(&points, DashPattern::Dashed, 10.),
+            vec![
+                [pos2(0., 0.), pos2(10., 0.)],
+                [pos2(15., 0.), pos2(20., 0.)],
+                [pos2(20., 0.), pos2(20., 5.)],
+                [pos2(20., 10.), pos2(20., 20.)],
+                [pos2(20., 25.), pos2(20., 35.)],
+            ]
+        );
+
+        let dots = apply_dash(&points, DashPattern::Dotted, 10.);
+        assert!(dots.iter().all(|[a, b]| a.distance(*b) <= 2.001));
+
+        // Dot follows the dash after the gap
+        let dash_dot = apply_dash(&points, DashPattern::DashDot, 10.);
+        assert_eq!(dash_dot[0], [pos2(0., 0.), pos2(10., 0.)]);
+        assert!(dash_dot
+            .iter()
+            .any(|[a, b]| a.distance(pos2(10. + 10. / 3., 0.)) < 1e-3
+                && (a.distance(*b) - 2.).abs() < 1e-3));
+    }
+
+    #[test]
+    fn test_apply_dash_short_length() {
+        let points = [pos2(0., 0.), pos2(100., 0.)];
+        assert_eq!(
+            apply_dash(&points, DashPattern::Dashed, 0.),
+            vec![[points[0], points[1]]]
+        );
+        assert_eq!(
+            apply_dash(&points, DashPattern::Dotted, f32::NAN),
+            vec![[points[0], points[1]]]
+        );
+
+        // Too short dashes are drawn with minimal length
+        let dashes = apply_dash(&points, DashPattern::Dashed, 1e-30);
+        assert!((66..=68).contains(&dashes.len()));
+        assert!(dashes[0][1].distance(pos2(1., 0.)) < 1e-3);
+    }
+}
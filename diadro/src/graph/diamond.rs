@@ -0,0 +1,439 @@
+use eframe::{
+    egui::{CursorIcon, Id, PointerButton, Ui},
+    epaint::{Color32, FontFamily, Pos2, Rect, Shape, Vec2},
+};
+use serde::{Deserialize, Serialize};
+
+use super::persist::FigureKind;
+use super::shapes::{
+    draw_selection_border, zoom_rect, FigureBasics, SelectMode, SELECT_MODE_HOVER,
+    SELECT_MODE_NONE, SELECT_MODE_SELECTED,
+};
+use super::text::TextOps;
+use super::utils::PointMath;
+use super::{DragMode, GraphFigure};
+use crate::rgraph::{CanvasCommand, PointAlgoritm};
+
+/// Tolerance to detect cursor over the border or connection point
+const MARGIN: f32 = 10.;
+
+/// Rhombus inscribed into bounding rectangle. Used as decision node in flowcharts
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiamondFigure {
+    id: Id,
+    rect: Rect,
+    fb: FigureBasics,
+    #[serde(skip)]
+    selected: SelectMode,
+    zoom_factor: f32,
+    scroll_delta: Vec2,
+    #[serde(skip)]
+    drag_mode: DragMode,
+    text: TextOps,
+    #[serde(skip)]
+    text_edit: bool,
+    #[serde(skip)]
+    connection_points: Vec<Pos2>,
+    /// Offset of the selection border dash pattern
+    #[serde(skip)]
+    selection_offset: f32,
+}
+
+impl Default for DiamondFigure {
+    fn default() -> Self {
+        Self {
+            id: Id::new(1),
+            rect: Rect {
+                min: Pos2::ZERO,
+                max: Pos2::ZERO,
+            },
+            selected: SELECT_MODE_NONE,
+            zoom_factor: 1.0,
+            scroll_delta: Vec2::ZERO,
+            drag_mode: DragMode::Extend,
+            text: TextOps::new("Decision"),
+            text_edit: false,
+            fb: Default::default(),
+            connection_points: Default::default(),
+            selection_offset: 0.,
+        }
+    }
+}
+
+/// Vertices of the rhombus inscribed into rectangle: top, right, bottom, left
+#[inline]
+fn diamond_points(rect: Rect) -> [Pos2; 4] {
+    [
+        rect.center_top(),
+        rect.right_center(),
+        rect.center_bottom(),
+        rect.left_center(),
+    ]
+}
+
+/// Closed polygon of the rhombus, first point is repeated at the end
+#[inline]
+fn diamond_path(rect: Rect) -> Vec<Pos2> {
+    let points = diamond_points(rect);
+    vec![points[0], points[1], points[2], points[3], points[0]]
+}
+
+impl GraphFigure for DiamondFigure {
+    fn clone_box(&self) -> Box<dyn GraphFigure> {
+        Box::new(self.clone())
+    }
+
+    fn set_id(&mut self, id: Id) {
+        self.id = id;
+    }
+
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn draw(&mut self, ui: &mut Ui, zoom_factor: f32, scroll_delta: Vec2) {
+        self.zoom(zoom_factor, scroll_delta);
+
+        let bg_color = match self.selected {
+            x if x & SELECT_MODE_HOVER > 0 => self.fb.selected_fill_color,
+            _ => self.fb.fill.base_color(),
+        };
+
+        ui.painter().add(Shape::convex_polygon(
+            diamond_points(self.rect).to_vec(),
+            bg_color,
+            self.fb.stroke,
+        ));
+
+        self.text.draw(
+            self.text_rect(),
+            ui,
+            self.id(),
+            Color32::BLUE,
+            bg_color,
+            &mut self.text_edit,
+        );
+
+        if self.selected & SELECT_MODE_SELECTED > 0 {
+            self.draw_selection_border(ui);
+        }
+    }
+
+    fn select(&mut self, selected: SelectMode) {
+        self.selected = selected;
+    }
+
+    fn contains(&self, point: Pos2) -> Option<CursorIcon> {
+        if self
+            .connection_points
+            .iter()
+            .any(|cp| point.over(*cp, MARGIN / 2.))
+        {
+            return Some(CursorIcon::Crosshair);
+        }
+
+        if let Some((icon, _)) = self.resize_direction(point) {
+            return Some(icon);
+        }
+
+        if self.inside(point) {
+            Some(CursorIcon::Default)
+        } else {
+            None
+        }
+    }
+
+    fn selected(&self) -> SelectMode {
+        self.selected
+    }
+
+    fn move_to(&mut self, pos: Pos2, drag_started: Pos2) {
+        let offset = pos - drag_started;
+        self.rect = self.rect.translate(offset);
+        for point in &mut self.connection_points {
+            *point += offset;
+        }
+    }
+
+    fn drag_start(&mut self, hover_pos: Pos2, _button: PointerButton, zoom_factor: f32) {
+        match self.selected {
+            x if x & SELECT_MODE_HOVER > 0 || x & SELECT_MODE_SELECTED > 0 => {
+                self.drag_mode = match self.resize_direction(hover_pos) {
+                    Some((_, drag_mode)) => drag_mode,
+                    None => DragMode::Move(hover_pos),
+                };
+            }
+            _ => {
+                self.drag_mode = DragMode::Extend;
+                self.rect = Rect::from_two_pos(hover_pos, hover_pos);
+            }
+        }
+
+        self.zoom_factor = zoom_factor;
+    }
+
+    fn dragged_by(&mut self, hover_pos: Pos2, _button: PointerButton) {
+        match self.drag_mode {
+            DragMode::Move(drag_started) => {
+                self.move_to(hover_pos, drag_started);
+                self.drag_mode = DragMode::Move(hover_pos);
+            }
+            drag_mode => {
+                drag_mode.resize(&mut self.rect, hover_pos);
+                self.compute_connection_points();
+            }
+        }
+    }
+
+    fn drag_released(&mut self, hover_pos: Pos2, button: PointerButton) {
+        self.dragged_by(hover_pos, button);
+    }
+
+    fn double_click(&mut self) {
+        self.text_edit = true;
+    }
+
+    fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    fn set_rect(&mut self, rect: Rect) {
+        self.rect = rect;
+        self.compute_connection_points();
+    }
+
+    fn connection_points(&self) -> &Vec<Pos2> {
+        &self.connection_points
+    }
+
+    fn type_name(&self) -> &'static str {
+        "diamond"
+    }
+
+    fn label(&self) -> String {
+        self.text.text().to_owned()
+    }
+
+    fn set_text(&mut self, s: String) {
+        self.text.replace_text(s);
+    }
+
+    fn text_edited(&self) -> bool {
+        self.text_edit
+    }
+
+    fn font_family(&self) -> Option<FontFamily> {
+        Some(self.text.font_family().clone())
+    }
+
+    fn set_font_family(&mut self, family: FontFamily) {
+        self.text.set_font_family(family);
+    }
+
+    fn fill_color(&self) -> Color32 {
+        self.fb.fill.base_color()
+    }
+
+    fn to_canvas_commands(&self) -> Vec<CanvasCommand> {
+        let path = diamond_path(self.rect);
+        let mut commands = vec![CanvasCommand::FillPolygon(
+            path[..4].to_vec(),
+            self.fb.fill.base_color(),
+        )];
+        commands.extend(
+            path.windows(2)
+                .map(|line| CanvasCommand::DrawLine([line[0], line[1]], self.fb.stroke)),
+        );
+        commands.push(CanvasCommand::DrawText {
+            pos: self.text_rect().left_top(),
+            text: self.text.text().to_owned(),
+            font_size: self.text.font_size(),
+            color: Color32::BLUE,
+        });
+        commands
+    }
+
+    fn set_selection_offset(&mut self, offset: f32) {
+        self.selection_offset = offset;
+    }
+
+    fn to_kind(&self) -> Option<FigureKind> {
+        Some(FigureKind::Diamond(self.clone()))
+    }
+}
+
+impl DiamondFigure {
+    /// Construct figure with given position and text
+    pub fn new(id: Id, rect: Rect, text: String) -> Self {
+        let mut figure = Self {
+            id,
+            rect,
+            text: TextOps::new(text),
+            ..Default::default()
+        };
+        figure.compute_connection_points();
+        figure
+    }
+
+    fn zoom(&mut self, zoom_factor: f32, scroll_delta: Vec2) {
+        self.rect = zoom_rect(
+            self.rect,
+            (self.zoom_factor, self.scroll_delta),
+            (zoom_factor, scroll_delta),
+        );
+        self.zoom_factor = zoom_factor;
+        self.scroll_delta = scroll_delta;
+        self.compute_connection_points();
+    }
+
+    /// Connection points are the vertices of the rhombus: top, right, bottom, left
+    fn compute_connection_points(&mut self) {
+        if self.rect.size() != Vec2::ZERO {
+            self.connection_points = diamond_points(self.rect).to_vec();
+        }
+    }
+
+    /// Rectangle inscribed into the rhombus, used to place text
+    fn text_rect(&self) -> Rect {
+        Rect::from_center_size(self.rect.center(), self.rect.size() / 2.)
+    }
+
+    /// Check if point lies inside the rhombus
+    fn inside(&self, point: Pos2) -> bool {
+        let radius = self.rect.size() / 2.;
+        if radius.x <= 0. || radius.y <= 0. {
+            return false;
+        }
+
+        let delta = point - self.rect.center();
+        delta.x.abs() / radius.x + delta.y.abs() / radius.y <= 1.
+    }
+
+    /// Detect if point is near the border and compute resize cursor and drag mode.
+    /// Vertices resize the figure along one axis, sides - along both axes
+    fn resize_direction(&self, point: Pos2) -> Option<(CursorIcon, DragMode)> {
+        let path = diamond_path(self.rect);
+        if !point.belong_path(&path, MARGIN / 2.) {
+            return None;
+        }
+
+        let [top, right, bottom, left] = diamond_points(self.rect);
+        let vertex = [
+            (top, CursorIcon::ResizeVertical, DragMode::ResizeTtoB(point)),
+            (
+                right,
+                CursorIcon::ResizeHorizontal,
+                DragMode::ResizeRtoL(point),
+            ),
+            (
+                bottom,
+                CursorIcon::ResizeVertical,
+                DragMode::ResizeBtoT(point),
+            ),
+            (
+                left,
+                CursorIcon::ResizeHorizontal,
+                DragMode::ResizeLtoR(point),
+            ),
+        ]
+        .into_iter()
+        .find(|(vertex, _, _)| point.over(*vertex, MARGIN))
+        .map(|(_, icon, drag_mode)| (icon, drag_mode));
+
+        vertex.or_else(|| {
+            let side = [
+                (CursorIcon::ResizeNeSw, DragMode::ResizeTRtoBL(point)),
+                (CursorIcon::ResizeNwSe, DragMode::ResizeBRtoTL(point)),
+                (CursorIcon::ResizeNeSw, DragMode::ResizeBLtoTR(point)),
+                (CursorIcon::ResizeNwSe, DragMode::ResizeTLtoBR(point)),
+            ];
+            path.windows(2)
+                .position(|line| point.belong_line(&[line[0], line[1]], MARGIN / 2.))
+                .map(|idx| side[idx])
+        })
+    }
+
+    /// Draw animated dashed border around selected figure
+    fn draw_selection_border(&self, ui: &mut Ui) {
+        let border = diamond_path(self.rect.expand(3.));
+        draw_selection_border(ui, &border, self.selection_offset, self.fb.selected_stroke);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eframe::{
+        egui::{CursorIcon, PointerButton},
+        emath::{pos2, Rect},
+    };
+
+    use super::DiamondFigure;
+    use crate::graph::{shapes::SELECT_MODE_SELECTED, GraphFigure};
+
+    fn diamond() -> DiamondFigure {
+        let mut figure = DiamondFigure::default();
+        figure.drag_start(pos2(0., 0.), PointerButton::Primary, 1.);
+        figure.drag_released(pos2(200., 100.), PointerButton::Primary);
+        figure
+    }
+
+    #[test]
+    fn test_diamond_contains() {
+        let figure = diamond();
+        assert_eq!(
+            figure.connection_points(),
+            &vec![
+                pos2(100., 0.),
+                pos2(200., 50.),
+                pos2(100., 100.),
+                pos2(0., 50.)
+            ]
+        );
+
+        assert_eq!(figure.contains(pos2(100., 50.)), Some(CursorIcon::Default));
+        // Corner of the bounding rect is outside the rhombus
+        assert_eq!(figure.contains(pos2(10., 10.)), None);
+        assert_eq!(figure.contains(pos2(101., 1.)), Some(CursorIcon::Crosshair));
+        // Middle of the top right side
+        assert_eq!(
+            figure.contains(pos2(150., 25.)),
+            Some(CursorIcon::ResizeNeSw)
+        );
+        assert_eq!(
+            figure.contains(pos2(50., 25.)),
+            Some(CursorIcon::ResizeNwSe)
+        );
+    }
+
+    #[test]
+    fn test_diamond_resize() {
+        let mut figure = diamond();
+        figure.select(SELECT_MODE_SELECTED);
+
+        // Drag right vertex
+        figure.drag_start(pos2(200., 50.), PointerButton::Primary, 1.);
+        figure.drag_released(pos2(240., 60.), PointerButton::Primary);
+        assert_eq!(
+            figure.rect(),
+            Rect::from_min_max(pos2(0., 0.), pos2(240., 100.))
+        );
+        assert_eq!(figure.connection_points()[1], pos2(240., 50.));
+
+        // Drag bottom left side
+        figure.drag_start(pos2(60., 75.), PointerButton::Primary, 1.);
+        figure.drag_released(pos2(-10., 120.), PointerButton::Primary);
+        assert_eq!(
+            figure.rect(),
+            Rect::from_min_max(pos2(-10., 0.), pos2(240., 120.))
+        );
+
+        // Drag inside moves figure
+        figure.drag_start(pos2(115., 60.), PointerButton::Primary, 1.);
+        figure.drag_released(pos2(125., 60.), PointerButton::Primary);
+        assert_eq!(
+            figure.rect(),
+            Rect::from_min_max(pos2(0., 0.), pos2(250., 120.))
+        );
+    }
+}
@@ -0,0 +1,444 @@
+use std::f32::consts::{PI, TAU};
+
+use eframe::{
+    egui::{CursorIcon, Id, PointerButton, Ui},
+    epaint::{Color32, FontFamily, Pos2, Rect, Shape, Vec2},
+};
+use serde::{Deserialize, Serialize};
+
+use super::persist::FigureKind;
+use super::shapes::{
+    draw_selection_border, zoom_rect, FigureBasics, SelectMode, SELECT_MODE_HOVER,
+    SELECT_MODE_NONE, SELECT_MODE_SELECTED,
+};
+use super::text::TextOps;
+use super::utils::PointMath;
+use super::{DragMode, GraphFigure};
+use crate::rgraph::CanvasCommand;
+
+/// Tolerance to detect cursor over the perimeter or connection point
+const MARGIN: f32 = 10.;
+/// Number of connection points evenly placed around the perimeter
+const CONNECTION_POINTS: usize = 12;
+/// Number of segments used to draw the ellipse
+const SEGMENTS: usize = 64;
+
+/// Ellipse inscribed into bounding rectangle. Circle if rectangle is a square
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EllipseFigure {
+    id: Id,
+    rect: Rect,
+    fb: FigureBasics,
+    #[serde(skip)]
+    selected: SelectMode,
+    zoom_factor: f32,
+    scroll_delta: Vec2,
+    #[serde(skip)]
+    drag_mode: DragMode,
+    text: TextOps,
+    #[serde(skip)]
+    text_edit: bool,
+    #[serde(skip)]
+    connection_points: Vec<Pos2>,
+    /// Offset of the selection border dash pattern
+    #[serde(skip)]
+    selection_offset: f32,
+    /// Text shown when cursor is over the figure
+    tooltip: Option<String>,
+}
+
+impl Default for EllipseFigure {
+    fn default() -> Self {
+        Self {
+            id: Id::new(1),
+            rect: Rect {
+                min: Pos2::ZERO,
+                max: Pos2::ZERO,
+            },
+            selected: SELECT_MODE_NONE,
+            zoom_factor: 1.0,
+            scroll_delta: Vec2::ZERO,
+            drag_mode: DragMode::Extend,
+            text: TextOps::new("New figure"),
+            text_edit: false,
+            fb: Default::default(),
+            connection_points: Default::default(),
+            selection_offset: 0.,
+            tooltip: None,
+        }
+    }
+}
+
+/// Compute point on the ellipse inscribed into rectangle
+/// ### Arguments
+/// * rect - bounding rectangle
+/// * angle - angle in radians, counted clockwise from the positive x axis
+#[inline]
+fn ellipse_point(rect: Rect, angle: f32) -> Pos2 {
+    rect.center()
+        + Vec2::new(
+            rect.width() / 2. * angle.cos(),
+            rect.height() / 2. * angle.sin(),
+        )
+}
+
+/// Compute closed polyline approximating the ellipse inscribed into rectangle
+fn ellipse_points(rect: Rect, segments: usize) -> Vec<Pos2> {
+    (0..=segments)
+        .map(|idx| ellipse_point(rect, TAU * idx as f32 / segments as f32))
+        .collect()
+}
+
+impl GraphFigure for EllipseFigure {
+    fn clone_box(&self) -> Box<dyn GraphFigure> {
+        Box::new(self.clone())
+    }
+
+    fn set_id(&mut self, id: Id) {
+        self.id = id;
+    }
+
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn draw(&mut self, ui: &mut Ui, zoom_factor: f32, scroll_delta: Vec2) {
+        self.zoom(zoom_factor, scroll_delta);
+
+        let bg_color = match self.selected {
+            x if x & SELECT_MODE_HOVER > 0 => self.fb.selected_fill_color,
+            _ => self.fb.fill.base_color(),
+        };
+
+        let mut points = ellipse_points(self.rect, SEGMENTS);
+        points.pop();
+        ui.painter()
+            .add(Shape::convex_polygon(points, bg_color, self.fb.stroke));
+
+        self.text.draw(
+            self.text_rect(),
+            ui,
+            self.id(),
+            Color32::BLUE,
+            bg_color,
+            &mut self.text_edit,
+        );
+
+        if self.selected & SELECT_MODE_SELECTED > 0 {
+            self.draw_selection_border(ui);
+        }
+    }
+
+    fn select(&mut self, selected: SelectMode) {
+        self.selected = selected;
+    }
+
+    fn contains(&self, point: Pos2) -> Option<CursorIcon> {
+        if self
+            .connection_points
+            .iter()
+            .any(|cp| point.over(*cp, MARGIN / 2.))
+        {
+            return Some(CursorIcon::Crosshair);
+        }
+
+        if let Some((icon, _)) = self.resize_direction(point) {
+            return Some(icon);
+        }
+
+        if self.normalized_distance(point) <= 1. {
+            Some(CursorIcon::Default)
+        } else {
+            None
+        }
+    }
+
+    fn selected(&self) -> SelectMode {
+        self.selected
+    }
+
+    fn move_to(&mut self, pos: Pos2, drag_started: Pos2) {
+        let offset = pos - drag_started;
+        self.rect = self.rect.translate(offset);
+        for point in &mut self.connection_points {
+            *point += offset;
+        }
+    }
+
+    fn drag_start(&mut self, hover_pos: Pos2, _button: PointerButton, zoom_factor: f32) {
+        match self.selected {
+            x if x & SELECT_MODE_HOVER > 0 || x & SELECT_MODE_SELECTED > 0 => {
+                self.drag_mode = match self.resize_direction(hover_pos) {
+                    Some((_, drag_mode)) => drag_mode,
+                    None => DragMode::Move(hover_pos),
+                };
+            }
+            _ => {
+                self.drag_mode = DragMode::Extend;
+                self.rect = Rect::from_two_pos(hover_pos, hover_pos);
+            }
+        }
+
+        self.zoom_factor = zoom_factor;
+    }
+
+    fn dragged_by(&mut self, hover_pos: Pos2, _button: PointerButton) {
+        match self.drag_mode {
+            DragMode::Move(drag_started) => {
+                self.move_to(hover_pos, drag_started);
+                self.drag_mode = DragMode::Move(hover_pos);
+            }
+            drag_mode => {
+                drag_mode.resize(&mut self.rect, hover_pos);
+                self.compute_connection_points();
+            }
+        }
+    }
+
+    fn drag_released(&mut self, hover_pos: Pos2, button: PointerButton) {
+        self.dragged_by(hover_pos, button);
+    }
+
+    fn double_click(&mut self) {
+        self.text_edit = true;
+    }
+
+    fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    fn set_rect(&mut self, rect: Rect) {
+        self.rect = rect;
+        self.compute_connection_points();
+    }
+
+    fn connection_points(&self) -> &Vec<Pos2> {
+        &self.connection_points
+    }
+
+    fn type_name(&self) -> &'static str {
+        "ellipse"
+    }
+
+    fn label(&self) -> String {
+        self.text.text().to_owned()
+    }
+
+    fn set_text(&mut self, s: String) {
+        self.text.replace_text(s);
+    }
+
+    fn text_edited(&self) -> bool {
+        self.text_edit
+    }
+
+    fn font_family(&self) -> Option<FontFamily> {
+        Some(self.text.font_family().clone())
+    }
+
+    fn set_font_family(&mut self, family: FontFamily) {
+        self.text.set_font_family(family);
+    }
+
+    fn tooltip(&self) -> Option<&str> {
+        self.tooltip.as_deref()
+    }
+
+    fn set_tooltip(&mut self, tooltip: Option<String>) {
+        self.tooltip = tooltip;
+    }
+
+    fn fill_color(&self) -> Color32 {
+        self.fb.fill.base_color()
+    }
+
+    fn to_canvas_commands(&self) -> Vec<CanvasCommand> {
+        let points = ellipse_points(self.rect, SEGMENTS);
+        let mut commands = vec![CanvasCommand::FillPolygon(
+            points[..SEGMENTS].to_vec(),
+            self.fb.fill.base_color(),
+        )];
+        commands.extend(
+            points
+                .windows(2)
+                .map(|line| CanvasCommand::DrawLine([line[0], line[1]], self.fb.stroke)),
+        );
+        commands.push(CanvasCommand::DrawText {
+            pos: self.text_rect().left_top(),
+            text: self.text.text().to_owned(),
+            font_size: self.text.font_size(),
+            color: Color32::BLUE,
+        });
+        commands
+    }
+
+    fn set_selection_offset(&mut self, offset: f32) {
+        self.selection_offset = offset;
+    }
+
+    fn to_kind(&self) -> Option<FigureKind> {
+        Some(FigureKind::Ellipse(self.clone()))
+    }
+}
+
+impl EllipseFigure {
+    /// Construct figure with given position and text
+    pub fn new(id: Id, rect: Rect, text: String) -> Self {
+        let mut figure = Self {
+            id,
+            rect,
+            text: TextOps::new(text),
+            ..Default::default()
+        };
+        figure.compute_connection_points();
+        figure
+    }
+
+    fn zoom(&mut self, zoom_factor: f32, scroll_delta: Vec2) {
+        self.rect = zoom_rect(
+            self.rect,
+            (self.zoom_factor, self.scroll_delta),
+            (zoom_factor, scroll_delta),
+        );
+        self.zoom_factor = zoom_factor;
+        self.scroll_delta = scroll_delta;
+        self.compute_connection_points();
+    }
+
+    /// Twelve points evenly placed around the perimeter, starting from the rightmost point
+    fn compute_connection_points(&mut self) {
+        if self.rect.size() != Vec2::ZERO {
+            self.connection_points = (0..CONNECTION_POINTS)
+                .map(|idx| ellipse_point(self.rect, TAU * idx as f32 / CONNECTION_POINTS as f32))
+                .collect();
+        }
+    }
+
+    /// Rectangle inscribed into the ellipse, used to place text
+    fn text_rect(&self) -> Rect {
+        Rect::from_center_size(
+            self.rect.center(),
+            self.rect.size() * std::f32::consts::FRAC_1_SQRT_2,
+        )
+    }
+
+    /// Distance from center to the point, normalized by ellipse radii: 1 means the point is on
+    /// the perimeter, less than 1 - inside the ellipse
+    fn normalized_distance(&self, point: Pos2) -> f32 {
+        let radius = self.rect.size() / 2.;
+        if radius.x <= 0. || radius.y <= 0. {
+            return f32::INFINITY;
+        }
+
+        let delta = point - self.rect.center();
+        ((delta.x / radius.x).powi(2) + (delta.y / radius.y).powi(2)).sqrt()
+    }
+
+    /// Detect if point is near the perimeter and compute resize cursor and drag mode.
+    /// Resize direction depends on the octant of the perimeter the point is near to
+    fn resize_direction(&self, point: Pos2) -> Option<(CursorIcon, DragMode)> {
+        let radius = self.rect.size() / 2.;
+        let tolerance = MARGIN / 2. / radius.x.min(radius.y).max(1.);
+        if (self.normalized_distance(point) - 1.).abs() > tolerance {
+            return None;
+        }
+
+        let delta = point - self.rect.center();
+        let angle = (delta.y / radius.y).atan2(delta.x / radius.x);
+        // Octant index counted clockwise from the right side
+        let octant = ((angle + TAU + PI / 8.) / (PI / 4.)) as usize % 8;
+        Some(match octant {
+            0 => (CursorIcon::ResizeHorizontal, DragMode::ResizeRtoL(point)),
+            1 => (CursorIcon::ResizeNwSe, DragMode::ResizeBRtoTL(point)),
+            2 => (CursorIcon::ResizeVertical, DragMode::ResizeBtoT(point)),
+            3 => (CursorIcon::ResizeNeSw, DragMode::ResizeBLtoTR(point)),
+            4 => (CursorIcon::ResizeHorizontal, DragMode::ResizeLtoR(point)),
+            5 => (CursorIcon::ResizeNwSe, DragMode::ResizeTLtoBR(point)),
+            6 => (CursorIcon::ResizeVertical, DragMode::ResizeTtoB(point)),
+            _ => (CursorIcon::ResizeNeSw, DragMode::ResizeTRtoBL(point)),
+        })
+    }
+
+    /// Draw animated dashed border around selected figure
+    fn draw_selection_border(&self, ui: &mut Ui) {
+        let border = ellipse_points(self.rect.expand(3.), SEGMENTS);
+        draw_selection_border(ui, &border, self.selection_offset, self.fb.selected_stroke);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eframe::{
+        egui::{CursorIcon, PointerButton},
+        emath::{pos2, Rect},
+    };
+
+    use super::EllipseFigure;
+    use crate::graph::{
+        shapes::{SELECT_MODE_NONE, SELECT_MODE_SELECTED},
+        GraphFigure,
+    };
+
+    fn ellipse() -> EllipseFigure {
+        let mut figure = EllipseFigure::default();
+        figure.drag_start(pos2(0., 0.), PointerButton::Primary, 1.);
+        figure.drag_released(pos2(200., 100.), PointerButton::Primary);
+        figure
+    }
+
+    #[test]
+    fn test_ellipse_connection_points() {
+        let figure = ellipse();
+        let points = figure.connection_points();
+        assert_eq!(points.len(), 12);
+        assert!(points[0].distance(pos2(200., 50.)) < 0.01);
+        assert!(points[3].distance(pos2(100., 100.)) < 0.01);
+        assert!(points[6].distance(pos2(0., 50.)) < 0.01);
+        assert!(points[9].distance(pos2(100., 0.)) < 0.01);
+    }
+
+    #[test]
+    fn test_ellipse_contains() {
+        let figure = ellipse();
+        assert_eq!(figure.contains(pos2(100., 50.)), Some(CursorIcon::Default));
+        // Corner of the bounding rect is outside the ellipse
+        assert_eq!(figure.contains(pos2(5., 5.)), None);
+        assert_eq!(figure.contains(pos2(300., 50.)), None);
+        // Connection point
+        assert_eq!(
+            figure.contains(pos2(199., 50.)),
+            Some(CursorIcon::Crosshair)
+        );
+        // Perimeter near the right side, between connection points
+        assert_eq!(
+            figure.contains(pos2(196.5, 62.)),
+            Some(CursorIcon::ResizeHorizontal)
+        );
+    }
+
+    #[test]
+    fn test_ellipse_resize() {
+        let mut figure = ellipse();
+        figure.select(SELECT_MODE_SELECTED);
+
+        // Drag top side of the ellipse up
+        figure.drag_start(pos2(85., 1.), PointerButton::Primary, 1.);
+        figure.drag_released(pos2(85., -20.), PointerButton::Primary);
+        assert_eq!(
+            figure.rect(),
+            Rect::from_min_max(pos2(0., -20.), pos2(200., 100.))
+        );
+
+        // Drag inside moves the figure
+        figure.drag_start(pos2(100., 40.), PointerButton::Primary, 1.);
+        figure.drag_released(pos2(110., 50.), PointerButton::Primary);
+        assert_eq!(
+            figure.rect(),
+            Rect::from_min_max(pos2(10., -10.), pos2(210., 110.))
+        );
+        assert!(figure.connection_points()[0].distance(pos2(210., 50.)) < 0.01);
+
+        figure.select(SELECT_MODE_NONE);
+    }
+}
@@ -0,0 +1,267 @@
+use std::cell::RefCell;
+
+use eframe::{
+    egui::Id,
+    emath::{Pos2, Rect},
+    epaint::Color32,
+};
+use resvg::{tiny_skia, usvg};
+use rust_xlsxwriter::{Workbook, XlsxError};
+
+use super::{GraphFigure, Graphics};
+use crate::rgraph::{CanvasCommand, SvgRenderer};
+
+/// Margin added to the right and bottom of exported image
+const EXPORT_MARGIN: f32 = 10.;
+
+/// Convert figure identifier to string
+#[inline]
+pub(crate) fn id_to_string(id: Id) -> String {
+    format!("{:?}", id)
+}
+
+/// Convert string produced by `id_to_string` back to figure identifier
+/// ### Return
+/// None if string is not a formatted identifier
+pub(super) fn id_from_string(s: &str) -> Option<Id> {
+    let value = u64::from_str_radix(s, 16).ok()?;
+    // Identifier is serialized as its value
+    serde_json::from_value(value.into()).ok()
+}
+
+/// Convert color to hex string #RRGGBBAA
+#[inline]
+fn color_to_hex(color: Color32) -> String {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    format!("#{:02X}{:02X}{:02X}{:02X}", r, g, b, a)
+}
+
+/// Implies diagram export functions
+impl Graphics {
+    /// Export diagram into xlsx workbook with two sheets: "Nodes" and "Edges"
+    /// ### Return
+    /// raw xlsx bytes or empty vector if workbook cannot be built
+    pub fn export_xlsx(&self) -> Vec<u8> {
+        match self.build_xlsx() {
+            Ok(data) => data,
+            Err(err) => {
+                tracing::error!("Error exporting xlsx: {}", err);
+                vec![]
+            }
+        }
+    }
+
+    /// Convert all figures and edges into drawing commands
+    fn canvas_commands(&self) -> Vec<CanvasCommand> {
+        let data = &self.graphics_data;
+        data.figures
+            .iter()
+            .flat_map(|figure| RefCell::borrow(figure).to_canvas_commands())
+            .chain(data.edges.iter().flat_map(|edge| edge.to_canvas_commands()))
+            .collect()
+    }
+
+    /// Compute rectangle contained all figures and edges
+    pub(super) fn bounding_rect(&self) -> Rect {
+        let data = &self.graphics_data;
+        data.figures
+            .iter()
+            .map(|figure| RefCell::borrow(figure).bounding_rect())
+            .chain(data.edges.iter().map(|edge| {
+                Rect::from_two_pos(edge.compute_start_point(), edge.compute_end_point())
+            }))
+            .fold(Rect::from_min_max(Pos2::ZERO, Pos2::ZERO), Rect::union)
+    }
+
+    /// Export diagram into svg document. Figures are drawn in screen coordinates, canvas scroll
+    /// and zoom are reverted by the transformation of the root group
+    /// ### Return
+    /// svg document as string
+    pub fn export_svg(&self) -> String {
+        let data = &self.graphics_data;
+        let scale = 1. / data.zoom_factor;
+        let rect = self.bounding_rect().translate(-data.scroll_delta);
+        SvgRenderer::new(
+            rect.max.x * scale + EXPORT_MARGIN,
+            rect.max.y * scale + EXPORT_MARGIN,
+        )
+        .transform(-data.scroll_delta, scale)
+        .render(&self.canvas_commands())
+    }
+
+    /// Rasterize exported svg document
+    fn rasterize(&self) -> Option<tiny_skia::Pixmap> {
+        let svg = self.export_svg();
+        let tree = match usvg::Tree::from_str(&svg, &usvg::Options::default()) {
+            Ok(tree) => tree,
+            Err(err) => {
+                tracing::error!("Error parsing exported svg: {}", err);
+                return None;
+            }
+        };
+
+        let size = tree.size().to_int_size();
+        let mut pixmap = tiny_skia::Pixmap::new(size.width(), size.height())?;
+        resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+        Some(pixmap)
+    }
+
+    /// Export diagram into png image
+    /// ### Return
+    /// png bytes or empty vector if image cannot be rendered
+    pub fn export_png(&self) -> Vec<u8> {
+        match self.rasterize().map(|pixmap| pixmap.encode_png()) {
+            Some(Ok(data)) => data,
+            Some(Err(err)) => {
+                tracing::error!("Error encoding png: {}", err);
+                vec![]
+            }
+            None => vec![],
+        }
+    }
+
+    /// Export diagram into raw image
+    /// ### Return
+    /// width, height and not premultiplied RGBA pixels
+    pub fn export_rgba(&self) -> Option<(usize, usize, Vec<u8>)> {
+        self.rasterize().map(|pixmap| {
+            let pixels = pixmap
+                .pixels()
+                .iter()
+                .flat_map(|pixel| {
+                    let color = pixel.demultiply();
+                    [color.red(), color.green(), color.blue(), color.alpha()]
+                })
+                .collect();
+            (pixmap.width() as usize, pixmap.height() as usize, pixels)
+        })
+    }
+
+    fn build_xlsx(&self) -> Result<Vec<u8>, XlsxError> {
+        let mut workbook = Workbook::new();
+
+        let nodes = workbook.add_worksheet();
+        nodes.set_name("Nodes")?;
+        for (col, header) in [
+            "Id",
+            "Label",
+            "Type",
+            "X",
+            "Y",
+            "Width",
+            "Height",
+            "FillColor",
+        ]
+        .iter()
+        .enumerate()
+        {
+            nodes.write_string(0, col as u16, *header)?;
+        }
+
+        for (idx, figure) in self.graphics_data.figures.iter().enumerate() {
+            let figure = RefCell::borrow(figure);
+            let row = idx as u32 + 1;
+            let rect = figure.rect();
+            nodes.write_string(row, 0, id_to_string(figure.id()))?;
+            nodes.write_string(row, 1, figure.label())?;
+            nodes.write_string(row, 2, figure.type_name())?;
+            nodes.write_number(row, 3, rect.min.x)?;
+            nodes.write_number(row, 4, rect.min.y)?;
+            nodes.write_number(row, 5, rect.width())?;
+            nodes.write_number(row, 6, rect.height())?;
+            nodes.write_string(row, 7, color_to_hex(figure.fill_color()))?;
+        }
+
+        let edges = workbook.add_worksheet();
+        edges.set_name("Edges")?;
+        for (col, header) in ["Id", "FromId", "ToId", "Label", "Style"]
+            .iter()
+            .enumerate()
+        {
+            edges.write_string(0, col as u16, *header)?;
+        }
+
+        for (idx, edge) in self.graphics_data.edges.iter().enumerate() {
+            let row = idx as u32 + 1;
+            let figure_id = |cp: &Option<super::arrow::ConnectionPoint>| {
+                cp.as_ref()
+                    .map(|cp| id_to_string(RefCell::borrow(cp.get_figure()).id()))
+                    .unwrap_or_default()
+            };
+            let stroke = edge.stroke();
+
+            edges.write_string(row, 0, id_to_string(edge.id()))?;
+            edges.write_string(row, 1, figure_id(edge.get_start_connection()))?;
+            edges.write_string(row, 2, figure_id(edge.get_end_connection()))?;
+            edges.write_string(row, 3, edge.label())?;
+            edges.write_string(
+                row,
+                4,
+                format!(
+                    "strokeWidth={};strokeColor={}",
+                    stroke.width,
+                    color_to_hex(stroke.color)
+                ),
+            )?;
+        }
+
+        workbook.save_to_buffer()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, io::Cursor, rc::Rc};
+
+    use calamine::{open_workbook_from_rs, Reader, Xlsx};
+    use eframe::{
+        egui::PointerButton,
+        emath::{pos2, Pos2},
+    };
+
+    use crate::graph::{shapes::GraphUi, GraphFigure, Graphics, RectFigure};
+
+    fn add_rect(graphics: &mut Graphics, start: Pos2, end: Pos2) {
+        let mut figure = RectFigure::default();
+        figure.set_id(graphics.graphics_data.generate_id());
+        figure.drag_start(start, PointerButton::Primary, 1.);
+        figure.drag_released(end, PointerButton::Primary);
+        graphics
+            .graphics_data
+            .add_figure(Rc::new(RefCell::new(Box::new(figure))));
+    }
+
+    #[test]
+    fn test_export_xlsx() {
+        let mut graphics = Graphics::default();
+        add_rect(&mut graphics, pos2(0., 0.), pos2(100., 50.));
+        add_rect(&mut graphics, pos2(200., 0.), pos2(300., 50.));
+
+        let data = graphics.export_xlsx();
+        assert!(!data.is_empty());
+
+        let mut workbook: Xlsx<_> = open_workbook_from_rs(Cursor::new(data)).unwrap();
+        let nodes = workbook.worksheet_range("Nodes").unwrap();
+        assert_eq!(nodes.height(), 3);
+        let edges = workbook.worksheet_range("Edges").unwrap();
+        assert_eq!(edges.height(), 1);
+    }
+
+    #[test]
+    fn test_export_svg_and_png() {
+        let mut graphics = Graphics::default();
+        add_rect(&mut graphics, pos2(0., 0.), pos2(100., 50.));
+
+        let svg = graphics.export_svg();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains(r#"width="110""#));
+        assert!(svg.contains("<rect"));
+
+        let png = graphics.export_png();
+        assert!(png.starts_with(b"\x89PNG"));
+
+        let (width, height, pixels) = graphics.export_rgba().unwrap();
+        assert_eq!((width, height), (110, 60));
+        assert_eq!(pixels.len(), width * height * 4);
+    }
+}
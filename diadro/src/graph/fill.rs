@@ -0,0 +1,214 @@
+use std::f32::consts::FRAC_PI_2;
+
+use eframe::{
+    emath::{Pos2, Rect, Vec2},
+    epaint::{Color32, Mesh},
+};
+use serde::{Deserialize, Serialize};
+
+/// Number of segments used to approximate rounded corner
+const CORNER_SEGMENTS: usize = 4;
+
+/// Defines how figure's interior is filled
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FillStyle {
+    /// One color fill
+    Solid(Color32),
+    /// Color changes from start to end along the direction given by angle.
+    /// Angle 0 means from left to right, 90 - from top to bottom
+    LinearGradient {
+        start_color: Color32,
+        end_color: Color32,
+        angle_deg: f32,
+    },
+    /// Color changes from center of the figure to its border
+    RadialGradient {
+        center_color: Color32,
+        edge_color: Color32,
+    },
+}
+
+impl Default for FillStyle {
+    fn default() -> Self {
+        FillStyle::Solid(Color32::from_rgba_premultiplied(100, 100, 50, 50))
+    }
+}
+
+/// Linear interpolation between two colors
+fn lerp_color(from: Color32, to: Color32, t: f32) -> Color32 {
+    let t = t.max(0.).min(1.);
+    let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    Color32::from_rgba_premultiplied(
+        lerp(from.r(), to.r()),
+        lerp(from.g(), to.g()),
+        lerp(from.b(), to.b()),
+        lerp(from.a(), to.a()),
+    )
+}
+
+/// Compute outline of the rectangle with rounded corners
+/// ### Arguments
+/// * rect - rectangle
+/// * radius - corners radius
+/// ### Return
+/// outline points clockwise
+pub fn rounded_rect_outline(rect: Rect, radius: f32) -> Vec<Pos2> {
+    let radius = radius
+        .min(rect.width() / 2.)
+        .min(rect.height() / 2.)
+        .max(0.);
+    let corners = [
+        (rect.right_bottom() - Vec2::splat(radius), 0.),
+        (rect.left_bottom() + Vec2::new(radius, -radius), FRAC_PI_2),
+        (rect.left_top() + Vec2::splat(radius), 2. * FRAC_PI_2),
+        (
+            rect.right_top() + Vec2::new(-radius, radius),
+            3. * FRAC_PI_2,
+        ),
+    ];
+
+    corners
+        .iter()
+        .flat_map(|(center, start_angle)| {
+            (0..=CORNER_SEGMENTS).map(move |i| {
+                let angle = start_angle + FRAC_PI_2 * i as f32 / CORNER_SEGMENTS as f32;
+                *center + Vec2::angled(angle) * radius
+            })
+        })
+        .collect()
+}
+
+impl FillStyle {
+    /// Color used where only one color is supported, e.g. in exports
+    pub fn base_color(&self) -> Color32 {
+        match self {
+            FillStyle::Solid(color) => *color,
+            FillStyle::LinearGradient { start_color, .. } => *start_color,
+            FillStyle::RadialGradient { center_color, .. } => *center_color,
+        }
+    }
+
+    /// Return true if fill is gradient and must be drawn with mesh
+    pub fn is_gradient(&self) -> bool {
+        !matches!(self, FillStyle::Solid(_))
+    }
+
+    /// Compute fill color in the given point
+    /// ### Arguments
+    /// * rect - rectangle of the filled figure
+    /// * pos - point inside the rectangle
+    pub fn color_at(&self, rect: Rect, pos: Pos2) -> Color32 {
+        match self {
+            FillStyle::Solid(color) => *color,
+            FillStyle::LinearGradient {
+                start_color,
+                end_color,
+                angle_deg,
+            } => {
+                let direction = Vec2::angled(angle_deg.to_radians());
+                // Half length of the rectangle projection onto gradient direction
+                let half =
+                    (rect.width() * direction.x.abs() + rect.height() * direction.y.abs()) / 2.;
+                let t = if half > 0. {
+                    (pos - rect.center()).dot(direction) / (2. * half) + 0.5
+                } else {
+                    0.
+                };
+                lerp_color(*start_color, *end_color, t)
+            }
+            FillStyle::RadialGradient {
+                center_color,
+                edge_color,
+            } => {
+                let half = rect.size() / 2.;
+                let offset = pos - rect.center();
+                let t = if half.x > 0. && half.y > 0. {
+                    Vec2::new(offset.x / half.x, offset.y / half.y).length()
+                } else {
+                    0.
+                };
+                lerp_color(*center_color, *edge_color, t)
+            }
+        }
+    }
+
+    /// Build mesh filling convex outline. Triangles fan from the center of the rectangle,
+    /// each vertex is colored by the fill style
+    /// ### Arguments
+    /// * rect - rectangle of the filled figure
+    /// * outline - convex outline of the figure
+    pub fn mesh(&self, rect: Rect, outline: &[Pos2]) -> Mesh {
+        let mut mesh = Mesh::default();
+        if outline.len() < 3 {
+            return mesh;
+        }
+
+        let center = rect.center();
+        mesh.colored_vertex(center, self.color_at(rect, center));
+        for point in outline {
+            mesh.colored_vertex(*point, self.color_at(rect, *point));
+        }
+
+        let count = outline.len() as u32;
+        for i in 0..count {
+            mesh.add_triangle(0, i + 1, (i + 1) % count + 1);
+        }
+
+        mesh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eframe::{
+        emath::{pos2, Rect},
+        epaint::Color32,
+    };
+
+    use super::{rounded_rect_outline, FillStyle};
+
+    #[test]
+    fn test_gradient_colors() {
+        let rect = Rect::from_min_max(pos2(0., 0.), pos2(100., 50.));
+        let linear = FillStyle::LinearGradient {
+            start_color: Color32::BLACK,
+            end_color: Color32::WHITE,
+            angle_deg: 0.,
+        };
+        assert_eq!(linear.color_at(rect, rect.left_center()), Color32::BLACK);
+        assert_eq!(linear.color_at(rect, rect.right_center()), Color32::WHITE);
+        assert_eq!(
+            linear.color_at(rect, rect.center()),
+            Color32::from_rgb(128, 128, 128)
+        );
+
+        let vertical = FillStyle::LinearGradient {
+            start_color: Color32::BLACK,
+            end_color: Color32::WHITE,
+            angle_deg: 90.,
+        };
+        assert_eq!(vertical.color_at(rect, rect.left_top()), Color32::BLACK);
+        assert_eq!(vertical.color_at(rect, rect.right_bottom()), Color32::WHITE);
+
+        let radial = FillStyle::RadialGradient {
+            center_color: Color32::RED,
+            edge_color: Color32::BLUE,
+        };
+        assert_eq!(radial.color_at(rect, rect.center()), Color32::RED);
+        assert_eq!(radial.color_at(rect, rect.center_top()), Color32::BLUE);
+        assert_eq!(radial.base_color(), Color32::RED);
+    }
+
+    #[test]
+    fn test_gradient_mesh() {
+        let rect = Rect::from_min_max(pos2(0., 0.), pos2(100., 50.));
+        let outline = rounded_rect_outline(rect, 10.);
+        assert!(outline
+            .iter()
+            .all(|point| rect.expand(0.01).contains(*point)));
+
+        let mesh = FillStyle::default().mesh(rect, &outline);
+        assert_eq!(mesh.vertices.len(), outline.len() + 1);
+        assert_eq!(mesh.indices.len(), outline.len() * 3);
+    }
+}
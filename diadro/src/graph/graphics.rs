@@ -1,63 +1,256 @@
 use std::{
     cell::{Ref, RefCell},
+    collections::HashMap,
     rc::Rc,
 };
 
+use chrono::Utc;
 use eframe::{
-    egui::{CursorIcon, Id, InnerResponse, Painter, PointerButton, Sense, Ui},
-    emath::{Align2, Vec2},
-    epaint::{Color32, FontId, Pos2, Stroke},
+    egui::{
+        CursorIcon, Event, Id, InnerResponse, Key, Painter, PointerButton, Sense, TouchPhase, Ui,
+    },
+    emath::Vec2,
+    epaint::{Color32, Pos2, Rect, Shape, Stroke},
 };
+use uuid::Uuid;
 
-use crate::ws::{AddFigure, RequestInfo, WsMessages};
+use crate::{
+    rgraph::{force_directed_layout, shortest_path, ConnectionValidator, MxCell},
+    ws::{AddFigure, FigureShape, RequestInfo, WsMessages},
+};
 
 use super::{
     arrow::{ArrowFigure, ConnectionPoint},
+    commands::{Command, CommandStack},
+    cursors::{cursor_color, draw_remote_cursors, RemoteCursors},
+    export::{id_from_string, id_to_string},
+    guides::{Guide, DEFAULT_SNAP_DISTANCE},
     shapes::{GraphUi, SELECT_MODE_HOVER, SELECT_MODE_SELECTED},
-    utils::PointMath,
-    GraphFigure, RectFigure,
+    touch::{TouchGesture, TouchMode, TouchState},
+    utils::{dashed_path, PointMath, Zoom},
+    CylinderFigure, DiagramMetadata, DiamondFigure, EllipseFigure, FigureKind, GraphFigure,
+    GroupFigure, RectFigure, ViewBookmark,
 };
 
 /// Tolerance for detect cursor in point
 const POINT_OVER_TOLERANCE: f32 = 7.0;
 
+/// Message announcing figure added by this client to other clients. Figures other than basic
+/// shapes are announced as rectangles
+pub(super) fn add_figure_message(figure: &dyn GraphFigure) -> WsMessages {
+    let shape = match figure.to_kind() {
+        Some(FigureKind::Ellipse(_)) => FigureShape::Ellipse,
+        Some(FigureKind::Diamond(_)) => FigureShape::Diamond,
+        Some(FigureKind::Cylinder(_)) => FigureShape::Cylinder,
+        _ => FigureShape::Rect,
+    };
+    WsMessages::AddFigure(AddFigure {
+        rq: RequestInfo::default(),
+        figure_id: id_to_string(figure.id()),
+        shape,
+        rect: figure.rect(),
+        text: figure.label(),
+    })
+}
+
+/// Check connection of the figures by validator. Validators work with cells, so figures
+/// are represented by cells with the same identifiers
+/// ### Arguments
+/// * validator - connection rule, everything is allowed without it
+/// * from - identifier of the edge start figure
+/// * to - identifier of the edge end figure
+fn validate_connection(
+    validator: Option<&dyn ConnectionValidator>,
+    from: Id,
+    to: Id,
+) -> Result<(), String> {
+    match validator {
+        Some(validator) => validator.validate(&MxCell::new(from), &MxCell::new(to)),
+        None => Ok(()),
+    }
+}
+
+/// Speed of the selection border animation, points per second
+const MARCH_SPEED: f32 = 20.;
+
+/// Color used to highlight path between selected figures
+const PATH_HIGHLIGHT_COLOR: Color32 = Color32::from_rgb(0, 255, 255);
+
+/// Fill color of the rubber band selection rectangle
+const RUBBER_BAND_FILL: Color32 = Color32::from_rgba_premultiplied(30, 60, 100, 40);
+
+/// Border color of the rubber band selection rectangle
+const RUBBER_BAND_STROKE_COLOR: Color32 = Color32::from_rgb(100, 150, 255);
+
+/// Color of the grid lines
+const GRID_COLOR: Color32 = Color32::from_rgba_premultiplied(40, 40, 40, 40);
+
+/// Grid lines closer than this distance on screen are not drawn
+const GRID_MIN_SCREEN_STEP: f32 = 4.;
+
+/// Default distance in pixels selected figures are moved by arrow keys
+const DEFAULT_NUDGE_STEP: f32 = 1.;
+
+/// Nudge step is multiplied by this factor when Shift is held
+const NUDGE_SHIFT_FACTOR: f32 = 10.;
+
+/// Opacity of the figure outlines selected by other users
+const REMOTE_SELECTION_OPACITY: f32 = 0.4;
+
+/// Distance between figure and outline of the selection made by another user
+const REMOTE_SELECTION_MARGIN: f32 = 6.;
+
+/// Path between two selected figures
+#[derive(Clone, Debug, Default)]
+pub struct HighlightedPath {
+    /// Edges indexes forming the path
+    edges: Vec<usize>,
+    /// Intermediate figures indexes, excluding start and end figures
+    figures: Vec<usize>,
+}
+
 // #[derive(Clone)]
 pub struct GraphicsData {
-    /// Last used identifier. Used to generate identifiers
-    last_id: usize,
     /// Selected tool - type of added figure or relation between its
     selected_tool: Option<Box<dyn GraphFigure>>,
-    /// Selected figure index - index in figures vector
-    selected_figure_idx: Option<usize>,
+    /// Untouched copy of the selected tool, replaces the tool once its figure is placed
+    tool_template: Option<Box<dyn GraphFigure>>,
+    /// Identifier of the figure under cursor. Identifier stays valid when figures are
+    /// inserted, removed or reordered
+    pub(super) selected_figure_id: Option<Id>,
+    /// Indexes of all selected figures. Filled by rubber band or ctrl-click selection
+    pub(super) selected_figure_indices: Vec<usize>,
+    /// Rubber band selection in progress: start and current cursor positions
+    rubber_band: Option<[Pos2; 2]>,
+    /// Last cursor position while all selected figures are moving together
+    multi_drag_pos: Option<Pos2>,
     /// List of figures in diagram
-    figures: Vec<Rc<RefCell<Box<dyn GraphFigure>>>>,
+    pub(super) figures: Vec<Rc<RefCell<Box<dyn GraphFigure>>>>,
     /// Screen parameters% scroll delta - defines offset [x; y] for whole screen
-    scroll_delta: Vec2,
+    pub(super) scroll_delta: Vec2,
     /// Zoom factor
-    zoom_factor: f32,
+    pub(super) zoom_factor: f32,
     /// Flag defines selection behaviour
     /// - True - elements can selected by mouse pointer and further can receive events
     select_enabled: bool,
     /// Flag used to determine drag state
     is_dragged: bool,
     /// Edges
-    edges: Vec<ArrowFigure>,
+    pub(super) edges: Vec<ArrowFigure>,
     /// Currently dragged arrow (edge)
     dragged_edge: Option<ArrowFigure>,
+    /// Existing edge being dragged as it was at the drag start
+    edge_drag: Option<ArrowFigure>,
     /// Color for drawing connection point
     edge_point_color: Color32,
     /// Color for drawing selected connection point
     selected_edge_point_stroke: Stroke,
+    /// Color for drawing connection point the dragged edge starts from
+    edge_source_point_stroke: Stroke,
+    /// Color for drawing connection point the dragged edge snaps to
+    edge_target_point_stroke: Stroke,
     /// Figure currently selected by dragging edge
     selected_by_edge_figure_idx: Option<usize>,
+    /// Flag defines that screen is scrolling at the moment
+    is_panning: bool,
+    /// Shortest path between two selected figures
+    highlighted_path: Option<HighlightedPath>,
+    /// Offset of the selection border dash pattern, grows over time to animate selection
+    selection_animation_offset: f32,
+    /// Entered groups. Each level holds group identifier and figures of the parent level
+    pub(super) group_stack: Vec<(Id, Vec<Rc<RefCell<Box<dyn GraphFigure>>>>)>,
+    /// Performed commands, the last one is reverted by undo
+    history: Vec<Command>,
+    /// Reverted commands, the last one is performed again by redo
+    redo_stack: Vec<Command>,
+    /// Rectangles of the dragged figures at the drag start, used to record move and resize commands
+    pub(super) drag_rects: Vec<(Id, Rect)>,
+    /// Grid spacing in logical pixels. Positions are snapped to the grid when it is set
+    grid: Option<f32>,
+    /// Messages about diagram changes to be sent to other clients of the board
+    pub(super) outgoing: Vec<WsMessages>,
+    /// Screen area occupied by canvas during the last frame
+    pub(super) viewport: Rect,
+    /// Figure for which context menu is shown, none for the blank canvas
+    pub(super) context_figure: Option<Id>,
+    /// Cursor position where context menu was opened
+    pub(super) context_pos: Pos2,
+    /// Copied figures
+    pub(super) clipboard: Vec<FigureKind>,
+    /// Offset of the duplicated figure from the original one
+    pub(super) paste_offset: Vec2,
+    /// Figure shown in properties window
+    pub(super) properties_figure: Option<Id>,
+    /// Figure which rectangle is edited in properties panel and its rectangle before the edit
+    pub(super) edited_rect: Option<(Id, Rect)>,
+    /// Figures with edited label and their labels before the edit started
+    edited_texts: Vec<(Id, String)>,
+    /// Rule checked before dragged edge is connected to the end figure
+    connection_validator: Option<Box<dyn ConnectionValidator>>,
+    /// Diagram can be viewed but not changed: figures are highlighted under cursor and
+    /// selected by click, dragging, double clicks, context menu and editing shortcuts are disabled
+    pub(super) read_only: bool,
+    /// Named views of the diagram
+    pub(super) bookmarks: Vec<ViewBookmark>,
+    /// Name entered for the next bookmark
+    pub(super) bookmark_name: String,
+    /// Alignment guides of the dragged figure
+    pub(super) guides: Vec<Guide>,
+    /// Distance at which dragged figure is snapped to edges and centers of other figures
+    pub(super) snap_distance: f32,
+    /// Offset applied to the dragged figure by the last alignment snap
+    pub(super) snap_offset: Vec2,
+    /// Distance selected figures are moved by arrow keys
+    nudge_step: f32,
+    /// Fingers touching the canvas, used to pan and zoom by touch gestures
+    touches: TouchState,
+    /// Identifiers of the figures selected by other users of the board, by user identifier
+    pub(super) remote_selections: HashMap<String, Vec<String>>,
+    /// Identifiers of the selected figures last sent to other users of the board
+    sent_selection: Vec<String>,
+    /// Title, author and change tracking of the diagram
+    pub(super) metadata: DiagramMetadata,
+}
+
+/// Cursor state computed once per frame. Variants are listed in priority order,
+/// the first matched state defines the cursor icon
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CursorIconState {
+    /// Edge is dragging from connection point
+    DraggingEdge,
+    /// Cursor is over connection point of the selected figure
+    EdgeControl,
+    /// Cursor is over figure's border, contains resize cursor for the border
+    Resize(CursorIcon),
+    /// Cursor is over figure
+    Hover,
+    /// Screen is scrolling
+    Pan,
+    Default,
+}
+
+impl CursorIconState {
+    /// Return cursor icon for the state
+    pub fn icon(&self) -> CursorIcon {
+        match self {
+            CursorIconState::DraggingEdge | CursorIconState::EdgeControl => CursorIcon::Crosshair,
+            CursorIconState::Resize(icon) => *icon,
+            CursorIconState::Hover => CursorIcon::Move,
+            CursorIconState::Pan => CursorIcon::AllScroll,
+            CursorIconState::Default => CursorIcon::Default,
+        }
+    }
 }
 
 impl Default for GraphicsData {
     fn default() -> Self {
         Self {
-            last_id: Default::default(),
-            selected_tool: Some(Box::new(RectFigure::default())),
-            selected_figure_idx: Default::default(),
+            selected_tool: None,
+            tool_template: None,
+            selected_figure_id: None,
+            selected_figure_indices: vec![],
+            rubber_band: None,
+            multi_drag_pos: None,
             figures: Default::default(),
             scroll_delta: Default::default(),
             zoom_factor: 1.0,
@@ -65,9 +258,41 @@ impl Default for GraphicsData {
             is_dragged: false,
             edges: Default::default(),
             dragged_edge: None,
+            edge_drag: None,
             edge_point_color: Color32::YELLOW,
             selected_edge_point_stroke: Stroke::new(1., Color32::YELLOW),
+            edge_source_point_stroke: Stroke::new(1., Color32::BLUE),
+            edge_target_point_stroke: Stroke::new(1., Color32::GREEN),
             selected_by_edge_figure_idx: None,
+            is_panning: false,
+            highlighted_path: None,
+            selection_animation_offset: 0.,
+            group_stack: vec![],
+            history: vec![],
+            redo_stack: vec![],
+            drag_rects: vec![],
+            grid: None,
+            outgoing: vec![],
+            viewport: Rect::from_min_max(Pos2::ZERO, Pos2::ZERO),
+            context_figure: None,
+            context_pos: Pos2::ZERO,
+            clipboard: vec![],
+            paste_offset: Vec2::new(20., 20.),
+            properties_figure: None,
+            edited_rect: None,
+            edited_texts: vec![],
+            connection_validator: None,
+            read_only: false,
+            bookmarks: vec![],
+            bookmark_name: String::new(),
+            guides: vec![],
+            snap_distance: DEFAULT_SNAP_DISTANCE,
+            snap_offset: Vec2::ZERO,
+            nudge_step: DEFAULT_NUDGE_STEP,
+            touches: TouchState::default(),
+            remote_selections: HashMap::new(),
+            sent_selection: vec![],
+            metadata: DiagramMetadata::default(),
         }
     }
 }
@@ -79,45 +304,96 @@ impl GraphUi for GraphicsData {
         self.figures.push(figure);
     }
 
-    /// Remove figure
+    /// Remove figure together with edges connected to it
     fn remove_figure(&mut self, figure_id: eframe::egui::Id) {
-        self.figures
+        let edges = self.edges_for_figure(figure_id);
+        if let Some(index) = self
+            .figures
             .iter()
             .position(|r| RefCell::borrow(r).id() == figure_id)
-            .iter()
-            .for_each(|found| {
-                self.figures.remove(*found);
+        {
+            let remove_edges = self.remove_edges(&edges);
+            let figure = self.figures.remove(index);
+            let remove_figure = Command::RemoveFigure { index, figure };
+            self.push_command(match remove_edges {
+                // Edges are removed first, so undo restores figure before its edges
+                Some(remove_edges) => Command::Batch(vec![remove_edges, remove_figure]),
+                None => remove_figure,
             });
+            self.outgoing.push(WsMessages::DeleteFigure {
+                rq: RequestInfo::default(),
+                figure_id: id_to_string(figure_id),
+            });
+        }
+
+        // Indexes are not valid anymore
+        self.selected_figure_indices.clear();
     }
 
-    /// Generate new figure id
+    /// Generate new figure id. Identifiers are random, so figures created by different clients
+    /// never share identifier and messages of the clients refer the same figure
     fn generate_id(&mut self) -> eframe::egui::Id {
-        self.last_id += 1;
-        Id::new(self.last_id)
+        Id::new(Uuid::new_v4())
+    }
+}
+
+/// Implies undo/redo for graphics data
+impl CommandStack for GraphicsData {
+    fn push_command(&mut self, command: Command) {
+        self.history.push(command);
+        self.redo_stack.clear();
+        self.mark_modified();
+    }
+
+    fn undo(&mut self) -> bool {
+        match self.history.pop() {
+            Some(command) => {
+                command.undo(&mut self.figures, &mut self.edges);
+                self.redo_stack.push(command);
+                self.clear_selection();
+                self.mark_modified();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn redo(&mut self) -> bool {
+        match self.redo_stack.pop() {
+            Some(command) => {
+                command.redo(&mut self.figures, &mut self.edges);
+                self.history.push(command);
+                self.clear_selection();
+                self.mark_modified();
+                true
+            }
+            None => false,
+        }
     }
 }
 
 impl GraphicsData {
-    /// Function selects element by cursor coordinates
+    /// Function selects element by cursor coordinates. Only hover state is changed,
+    /// figures selected by rubber band or ctrl-click are kept selected
     /// # Arguments
     ///  - point - cursor coordinates
-    pub fn select_by_point(&mut self, point: Pos2) -> Option<CursorIcon> {
-        let mut cursor: Option<CursorIcon> = None;
-
+    pub fn select_by_point(&mut self, point: Pos2) {
         if self.select_enabled {
-            self.selected_figure_idx = None;
-            for (index, r) in self.figures.iter_mut().enumerate() {
+            for r in self.figures.iter_mut() {
                 let s = RefCell::borrow(r).selected();
                 r.borrow_mut().select(s & !SELECT_MODE_HOVER);
-
-                if let Some(ci) = RefCell::borrow(r).contains(point) {
-                    self.selected_figure_idx = Some(index);
-                    cursor = Some(ci);
-                }
             }
 
-            if let Some(idx) = self.selected_figure_idx {
-                let mut figure = RefCell::borrow_mut(&self.figures[idx]);
+            // Figures are drawn in list order, so the top-most figure is the last one
+            self.selected_figure_id = self
+                .figures
+                .iter()
+                .rev()
+                .find(|r| RefCell::borrow(r).contains(point).is_some())
+                .map(|r| RefCell::borrow(r).id());
+
+            if let Some(figure) = self.selected_figure_id.and_then(|id| self.figure_by_id(id)) {
+                let mut figure = RefCell::borrow_mut(figure);
                 let prev = figure.selected();
                 figure.select(prev | SELECT_MODE_HOVER);
             }
@@ -125,6 +401,10 @@ impl GraphicsData {
             // Used whe trying to drag arrow/edge
             self.selected_by_edge_figure_idx = None;
             arrow.disconnect_end();
+            let start_id = arrow
+                .get_start_connection()
+                .as_ref()
+                .map(|con| RefCell::borrow(con.get_figure()).id());
 
             // When drag an arrow then
             for (idx, ref_fig) in self.figures.iter().enumerate() {
@@ -141,6 +421,15 @@ impl GraphicsData {
                     continue;
                 }
 
+                // Figures rejected by validator are not highlighted and can't be connected
+                if let Some(start_id) = start_id {
+                    if validate_connection(self.connection_validator.as_deref(), start_id, fig.id())
+                        .is_err()
+                    {
+                        continue;
+                    }
+                }
+
                 if fig.contains(point).is_some() {
                     self.selected_by_edge_figure_idx = Some(idx);
                 }
@@ -155,63 +444,30 @@ impl GraphicsData {
                 }
             }
         }
-
-        cursor
     }
 
-    /// Drawing scene include all figures, lines, connection points and other
-    fn draw(&mut self, ui: &mut Ui) {
-        for r in self.figures.iter_mut() {
-            RefCell::borrow_mut(r).draw(ui, self.zoom_factor, self.scroll_delta);
-        }
-
-        for a in self.edges.iter_mut() {
-            a.draw(ui, self.zoom_factor, self.scroll_delta);
-        }
-
-        if self.is_dragged {
-            if let Some(fig) = self.selected_tool.as_mut() {
-                fig.draw(ui, self.zoom_factor, self.scroll_delta);
-            }
-        }
-
-        if let Some(edge) = self.dragged_edge.as_mut() {
-            edge.draw(ui, self.zoom_factor, self.scroll_delta);
+    /// Check connection of the edge ends by connection validator
+    /// ### Return
+    /// error message if validator rejects the connection
+    fn validate_edge(&self, edge: &ArrowFigure) -> Result<(), String> {
+        match (edge.get_start_connection(), edge.get_end_connection()) {
+            (Some(start), Some(end)) => validate_connection(
+                self.connection_validator.as_deref(),
+                RefCell::borrow(start.get_figure()).id(),
+                RefCell::borrow(end.get_figure()).id(),
+            ),
+            _ => Ok(()),
         }
     }
 
-    /// Drawing one connection point
-    #[inline]
-    fn draw_edge_point(&self, point: Pos2, painter: &Painter) {
-        painter.circle_filled(point, 3.5, self.edge_point_color);
-    }
-
-    /// Drawing selected connection point
-    #[inline]
-    fn draw_selected_edge_point(&self, point: Pos2, painter: &Painter) {
-        painter.circle_stroke(point, 5., self.selected_edge_point_stroke);
-    }
-}
-
-/// Defines all graphics diagram operations
-#[derive(Default)]
-pub struct Graphics {
-    /// Graphics data
-    graphics_data: GraphicsData,
-}
-
-/// Implies graphics/digram operations
-impl Graphics {
-    /// Determines if point located over connection points
+    /// Determines if point located over connection points of the selected figure
     /// ### Arguments
     /// * point - point for which location will be determined
     /// ### Return
     /// <usize, Pos2> - connection point index and point
     fn point_in_edge_controls(&self, point: Pos2) -> Option<(usize, Pos2)> {
-        if let Some(figure) = self.graphics_data.selected_figure_idx.and_then(|idx| {
-            self.graphics_data
-                .figures
-                .get(idx)
+        if let Some(figure) = self.selected_figure_id.and_then(|id| {
+            self.figure_by_id(id)
                 .map(|ref_fig| ref_fig.as_ref().borrow())
                 .filter(|fig| fig.selected() & SELECT_MODE_SELECTED > 0)
         }) {
@@ -226,217 +482,2483 @@ impl Graphics {
         None
     }
 
-    /// Return currently selected figure
-    #[inline]
-    fn selected_figure(&self) -> Option<&Rc<RefCell<Box<dyn GraphFigure>>>> {
-        self.graphics_data
-            .figures
+    /// Return indexes of all selected figures
+    fn selected_figures_idx(&self) -> Vec<usize> {
+        self.figures
             .iter()
-            .find(|fig| RefCell::borrow(fig).selected() & SELECT_MODE_SELECTED > 0)
+            .enumerate()
+            .filter(|(_, fig)| RefCell::borrow(fig).selected() & SELECT_MODE_SELECTED > 0)
+            .map(|(idx, _)| idx)
+            .collect()
     }
 
-    /// Draw controls to add out edges over selected figure. Each control represents as a circle with plus symbol inside
-    fn draw_edge_controls(&mut self, ui: &mut Ui) {
-        if let Some(fig) = self.selected_figure() {
-            // Draw only for selected figures
-            let fig = RefCell::borrow(fig);
-            // let rect = fig.borrow().rect();
-            let points = fig.connection_points();
-            let painter = ui.painter();
-            for point in points {
-                self.graphics_data.draw_edge_point(*point, painter);
-            }
+    /// Move all selected figures by offset and store the move in history as one user action
+    /// ### Arguments
+    /// * offset - distance the figures are moved by
+    fn nudge_selected(&mut self, offset: Vec2) {
+        let selected = self.selected_figures_idx();
+        if selected.is_empty() {
+            return;
         }
 
-        if let Some(fig) = self
-            .graphics_data
-            .selected_by_edge_figure_idx
-            .and_then(|idx| self.graphics_data.figures.get(idx))
-        {
-            let painter = ui.painter();
-            for point in fig.as_ref().borrow().connection_points() {
-                self.graphics_data.draw_edge_point(*point, painter);
-            }
+        self.remember_drag_rects(&selected);
+        for idx in selected {
+            self.figures[idx]
+                .borrow_mut()
+                .move_to(Pos2::ZERO + offset, Pos2::ZERO);
         }
+        self.record_drag_commands();
     }
 
-    /// Draw whole canvas
-    pub fn ui(
-        &mut self,
-        ui: &mut Ui,
-        incoming: Ref<'_, Vec<WsMessages>>,
-    ) -> InnerResponse<Vec<WsMessages>> {
-        let mut inner = vec![];
-
-        // Compute size
-        let size = ui.available_size_before_wrap();
-        // Allocate the space.
-        let mut response = ui
-            .allocate_response(size, Sense::click_and_drag())
-            .on_hover_cursor(CursorIcon::Default);
-
-        // Zoom factor computing
-        let zd = self.graphics_data.zoom_factor + ui.input().zoom_delta() - 1.;
-        if zd != self.graphics_data.zoom_factor && zd > 0. {
-            self.graphics_data.zoom_factor = zd;
+    /// Find the shortest path between two selected figures and store it to be highlighted.
+    /// Highlight is cleared if there are not exactly two selected figures or figures are not connected
+    pub fn highlight_path_between_selected(&mut self) {
+        let selected = self.selected_figures_idx();
+        if selected.len() != 2 {
+            self.highlighted_path = None;
+            return;
         }
 
-        if response.hovered() {
-            if let Some(hp) = response.hover_pos() {
-                if let Some(cursor) = self.graphics_data.select_by_point(hp) {
-                    response = response.on_hover_cursor(cursor);
+        let from = RefCell::borrow(&self.figures[selected[0]]).id();
+        let to = RefCell::borrow(&self.figures[selected[1]]).id();
+
+        // Only edges connected on both sides take part in the path
+        let (indexes, edges): (Vec<usize>, Vec<(Id, Id)>) = self
+            .edges
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, edge)| {
+                match (edge.get_start_connection(), edge.get_end_connection()) {
+                    (Some(start), Some(end)) => Some((
+                        idx,
+                        (
+                            RefCell::borrow(start.get_figure()).id(),
+                            RefCell::borrow(end.get_figure()).id(),
+                        ),
+                    )),
+                    _ => None,
                 }
+            })
+            .unzip();
 
-                if let Some((_, point)) = self.point_in_edge_controls(hp) {
-                    response = response.on_hover_cursor(CursorIcon::Default);
-                    // Draw cidx point for current figure
-                    self.graphics_data
-                        .draw_selected_edge_point(point, ui.painter());
+        self.highlighted_path = shortest_path(&edges, from, to).map(|path| {
+            // Walk through the path and collect intermediate vertices
+            let mut current = from;
+            let mut figures = vec![];
+            for (start, end) in path.iter().map(|idx| edges[*idx]) {
+                current = if start == current { end } else { start };
+                if current != to {
+                    if let Some(idx) = self
+                        .figures
+                        .iter()
+                        .position(|fig| RefCell::borrow(fig).id() == current)
+                    {
+                        figures.push(idx);
+                    }
                 }
             }
-        }
 
-        // Show incoming messages in current view
-        incoming.iter().for_each(|msg| match msg {
-            WsMessages::MousePosition(mp) => {
-                ui.painter().text(
-                    mp.position,
-                    Align2::RIGHT_TOP,
-                    mp.rq.user.clone(),
-                    FontId::proportional(8.),
-                    Color32::LIGHT_GREEN,
-                );
+            HighlightedPath {
+                edges: path.into_iter().map(|idx| indexes[idx]).collect(),
+                figures,
             }
-            WsMessages::AddFigure(_fig) => tracing::error!("Not yet implemented figures"),
-            WsMessages::AddArrow(_arrow) => tracing::error!("Not yet implemented arrows"),
         });
+    }
 
-        if response.double_clicked() {
-            if let Some(selected_figure) = self
-                .graphics_data
-                .selected_figure_idx
-                .and_then(|idx| self.graphics_data.figures.get_mut(idx))
-            {
-                selected_figure.borrow_mut().double_click();
+    /// Clear selection state for all figures of the current level
+    pub(super) fn clear_selection(&mut self) {
+        for fig in &self.figures {
+            let selected = RefCell::borrow(fig).selected();
+            RefCell::borrow_mut(fig).select(selected & !SELECT_MODE_SELECTED & !SELECT_MODE_HOVER);
+        }
+
+        self.selected_figure_id = None;
+        self.selected_figure_indices.clear();
+        self.highlighted_path = None;
+    }
+
+    /// Synchronize indexes of selected figures with figures selection state
+    pub(super) fn update_selected_indices(&mut self) {
+        self.selected_figure_indices = self.selected_figures_idx();
+    }
+
+    /// Select all figures which bounding rectangles intersect given rectangle.
+    /// Previous selection is cleared
+    /// ### Arguments
+    /// * rect - selection rectangle
+    /// ### Return
+    /// indexes of the selected figures
+    pub fn select_by_rect(&mut self, rect: Rect) -> &[usize] {
+        self.clear_selection();
+        for fig in &self.figures {
+            if RefCell::borrow(fig).bounding_rect().intersects(rect) {
+                let selected = RefCell::borrow(fig).selected();
+                RefCell::borrow_mut(fig).select(selected | SELECT_MODE_SELECTED);
             }
         }
 
-        if response.clicked() {
-            // Clear selection
-            self.graphics_data
-                .figures
-                .iter_mut()
-                .filter(|fig| RefCell::borrow(fig).selected() & SELECT_MODE_SELECTED > 0)
-                .for_each(|fig| {
-                    let selected = RefCell::borrow(fig).selected();
-                    RefCell::borrow_mut(fig).select(selected & !SELECT_MODE_SELECTED)
-                });
+        self.update_selected_indices();
+        self.highlight_path_between_selected();
+        &self.selected_figure_indices
+    }
 
-            if let Some(selected_figure) = self
-                .graphics_data
-                .selected_figure_idx
-                .and_then(|idx| self.graphics_data.figures.get_mut(idx))
-            {
-                let selected = RefCell::borrow(selected_figure).selected();
-                RefCell::borrow_mut(selected_figure).select(selected | SELECT_MODE_SELECTED);
+    /// Find topmost edge whose line goes through the point
+    /// ### Arguments
+    /// * pos - cursor position
+    /// ### Return
+    /// index of the edge or None if there is no edge at the position
+    fn edge_at(&self, pos: Pos2) -> Option<usize> {
+        self.edges
+            .iter()
+            .rposition(|edge| edge.contains(pos).is_some() || edge.waypoint_at(pos).is_some())
+    }
+
+    /// Store change of the edge in history if its route was changed
+    /// ### Arguments
+    /// * from - edge before change
+    /// * idx - index of the changed edge
+    fn push_edge_change(&mut self, from: ArrowFigure, idx: usize) {
+        let to = self.edges[idx].clone();
+        if from.route_points() != to.route_points() {
+            self.push_command(Command::ChangeEdge { from, to });
+        }
+    }
+
+    /// Check whether there is a figure or an edge at the position
+    fn is_occupied(&self, pos: Pos2) -> bool {
+        self.figures
+            .iter()
+            .any(|fig| RefCell::borrow(fig).contains(pos).is_some())
+            || self.edge_at(pos).is_some()
+    }
+
+    /// Start dragging edge under cursor, its waypoint, end or whole line is moved
+    /// ### Arguments
+    /// * pos - cursor position
+    /// ### Return
+    /// true if there was an edge at the position
+    fn start_edge_drag(&mut self, pos: Pos2) -> bool {
+        match self.edge_at(pos) {
+            Some(idx) => {
+                self.edge_drag = Some(self.edges[idx].clone());
+                self.edges[idx].drag_start(pos, PointerButton::Primary, self.zoom_factor);
+                true
             }
+            None => false,
         }
+    }
 
-        // Process drag started event
-        if response.drag_started() {
-            let hover_pos = response.hover_pos().unwrap_or_default();
-            if let Some((cpoint, _)) = self.point_in_edge_controls(hover_pos) {
-                let mut edge =
-                    ArrowFigure::new([hover_pos, hover_pos], self.graphics_data.generate_id());
+    /// Index of the existing edge being dragged
+    fn edge_drag_index(&self) -> Option<usize> {
+        let id = self.edge_drag.as_ref()?.id();
+        self.edges.iter().position(|edge| edge.id() == id)
+    }
 
-                if let Some(fig) = self.selected_figure() {
-                    edge.connect_start(ConnectionPoint::new(Rc::clone(fig), cpoint));
-                }
+    /// Finish dragging edge and store its change in history
+    /// ### Arguments
+    /// * pos - cursor position
+    fn release_edge_drag(&mut self, pos: Pos2) {
+        if let Some(idx) = self.edge_drag_index() {
+            self.edges[idx].drag_released(pos, PointerButton::Primary);
+            if let Some(from) = self.edge_drag.take() {
+                self.push_edge_change(from, idx);
+            }
+        }
+        self.edge_drag = None;
+    }
 
-                self.graphics_data.dragged_edge = Some(edge);
-            } else if let Some(selected_figure) = self
-                .graphics_data
-                .selected_figure_idx
-                .and_then(|idx| self.graphics_data.figures.get_mut(idx))
-            {
-                selected_figure.borrow_mut().drag_start(
-                    hover_pos,
-                    PointerButton::Primary,
-                    self.graphics_data.zoom_factor,
-                );
-            } else if let Some(fig) = self.graphics_data.selected_tool.as_mut() {
-                fig.drag_start(
-                    hover_pos,
-                    PointerButton::Primary,
-                    self.graphics_data.zoom_factor,
-                );
-                self.graphics_data.is_dragged = true;
+    /// Double click on the edge: waypoint is removed if clicked on it, otherwise new
+    /// waypoint is inserted into the clicked segment
+    /// ### Arguments
+    /// * pos - cursor position
+    /// ### Return
+    /// true if there was an edge at the position
+    fn double_click_edge(&mut self, pos: Pos2) -> bool {
+        match self.edge_at(pos) {
+            Some(idx) => {
+                let from = self.edges[idx].clone();
+                self.edges[idx].double_click_at(pos);
+                self.push_edge_change(from, idx);
+                true
             }
-            self.graphics_data.select_enabled = false;
+            None => false,
         }
+    }
 
-        if response.dragged_by(PointerButton::Primary) {
-            let hover_pos = response.hover_pos().unwrap_or_default();
-            if let Some(edge) = self.graphics_data.dragged_edge.as_mut() {
-                edge.set_end_pos(hover_pos);
-            } else if let Some(selected_figure) = self
-                .graphics_data
-                .selected_figure_idx
-                .and_then(|idx| self.graphics_data.figures.get_mut(idx))
-            {
-                selected_figure
-                    .borrow_mut()
-                    .dragged_by(hover_pos, PointerButton::Primary)
-            } else if let Some(fig) = self.graphics_data.selected_tool.as_mut() {
-                fig.dragged_by(hover_pos, PointerButton::Primary);
+    /// Start drag on blank canvas. Figure of the selected tool is placed, without tool or
+    /// with shift held figures are selected by rubber band
+    /// ### Arguments
+    /// * pos - cursor position
+    /// * shift - shift key is held
+    fn start_canvas_drag(&mut self, pos: Pos2, shift: bool) {
+        let snapped = self.snap_to_grid(pos);
+        let zoom_factor = self.zoom_factor;
+        match self.selected_tool.as_mut() {
+            Some(fig) if !shift => {
+                self.tool_template = Some(fig.clone_box());
+                fig.drag_start(snapped, PointerButton::Primary, zoom_factor);
+                self.is_dragged = true;
             }
+            _ => self.rubber_band = Some([pos, pos]),
         }
+    }
 
-        if response.drag_released() {
-            let hover_pos = response.hover_pos().unwrap_or_default();
-            if let Some(mut edge) = self.graphics_data.dragged_edge.take() {
-                edge.set_end_pos(hover_pos);
-                self.graphics_data.edges.push(edge.clone());
-            } else if let Some(selected_figure) = self
-                .graphics_data
-                .selected_figure_idx
-                .and_then(|idx| self.graphics_data.figures.get_mut(idx))
-            {
-                selected_figure
-                    .borrow_mut()
-                    .drag_released(hover_pos, PointerButton::Primary)
-            } else if self.graphics_data.selected_tool.is_some() {
-                let fig = self.graphics_data.selected_tool.take();
-                let mut f = fig.unwrap();
-                f.set_id(self.graphics_data.generate_id());
-                f.drag_released(hover_pos, PointerButton::Primary);
-                let rect = f.rect();
-                // Send message to WebSocket
-                inner.push(WsMessages::AddFigure(AddFigure {
-                    rq: RequestInfo {
-                        board: "".to_owned(),
-                        user: "".to_owned(),
-                    },
-                    rect,
-                    text: "".to_owned(),
-                }));
-
-                self.graphics_data.add_figure(Rc::new(RefCell::new(f)));
-                self.graphics_data.selected_tool = Some(Box::new(RectFigure::default()));
+    /// Place figure of the selected tool dragged on blank canvas. Tool stays selected for the
+    /// next figure
+    /// ### Arguments
+    /// * pos - cursor position
+    /// ### Return
+    /// Message announcing placed figure to other clients, None without tool
+    fn place_tool_figure(&mut self, pos: Pos2) -> Option<WsMessages> {
+        let mut figure = self.selected_tool.take()?;
+        self.selected_tool = self.tool_template.take();
+        figure.set_id(self.generate_id());
+        figure.drag_released(pos, PointerButton::Primary);
+        let message = add_figure_message(&*figure);
+
+        let figure = Rc::new(RefCell::new(figure));
+        self.add_figure(Rc::clone(&figure));
+        self.push_command(Command::AddFigure(figure));
+        Some(message)
+    }
+
+    /// Rectangle of the rubber band selection in progress
+    fn rubber_band_rect(&self) -> Option<Rect> {
+        self.rubber_band
+            .map(|[start, current]| Rect::from_two_pos(start, current))
+    }
+
+    /// Check if drag started at the given point must move all selected figures together.
+    /// It happens when more than one figure is selected and the drag starts inside one of them
+    /// ### Arguments
+    /// * idx - index of the figure under cursor
+    /// * point - drag start position
+    fn is_multi_move(&self, idx: usize, point: Pos2) -> bool {
+        self.selected_figure_indices.len() > 1
+            && self.selected_figure_indices.contains(&idx)
+            && self
+                .figures
+                .get(idx)
+                .and_then(|fig| RefCell::borrow(fig).contains(point))
+                == Some(CursorIcon::Default)
+    }
+
+    /// Remember rectangles of the figures before dragging
+    /// ### Arguments
+    /// * indexes - indexes of the dragged figures
+    fn remember_drag_rects(&mut self, indexes: &[usize]) {
+        self.drag_rects = indexes
+            .iter()
+            .filter_map(|idx| self.figures.get(*idx))
+            .map(|fig| {
+                let fig = RefCell::borrow(fig);
+                (fig.id(), fig.rect())
+            })
+            .collect();
+    }
+
+    /// Compare rectangles of the dragged figures with the remembered ones and store
+    /// move or resize commands in history. Moves are also sent to other clients
+    fn record_drag_commands(&mut self) {
+        let commands: Vec<Command> = std::mem::take(&mut self.drag_rects)
+            .into_iter()
+            .filter_map(|(id, from_rect)| {
+                self.figures
+                    .iter()
+                    .find(|fig| RefCell::borrow(fig).id() == id)
+                    .and_then(|fig| {
+                        Command::from_rect_change(id, from_rect, RefCell::borrow(fig).rect())
+                    })
+            })
+            .collect();
+        self.push_user_commands(commands);
+    }
+
+    /// Store commands performed as one user action in history. Moves are also sent to other clients
+    pub(super) fn push_user_commands(&mut self, mut commands: Vec<Command>) {
+        for command in &commands {
+            if let Command::MoveFigure { id, from, to } = command {
+                let offset = *to - *from;
+                self.outgoing.push(WsMessages::MoveFigure {
+                    rq: RequestInfo::default(),
+                    figure_id: id_to_string(*id),
+                    dx: offset.x,
+                    dy: offset.y,
+                });
             }
-            self.graphics_data.select_enabled = true;
-            self.graphics_data.is_dragged = false;
-            self.graphics_data.selected_by_edge_figure_idx = None;
         }
 
-        let scroll_delta = ui.input().scroll_delta;
-        if scroll_delta != Vec2::ZERO {
-            self.graphics_data.scroll_delta = scroll_delta;
+        match commands.len() {
+            0 => {}
+            1 => self.push_command(commands.remove(0)),
+            _ => self.push_command(Command::Batch(commands)),
         }
+    }
 
-        self.graphics_data.draw(ui);
-        self.draw_edge_controls(ui);
+    /// Arrange figures using force-directed layout, edges connected on both sides act as springs.
+    /// Moves are stored in history as one command
+    /// ### Arguments
+    /// * iterations - number of simulation steps
+    /// * repulsion - repulsion factor between all figures
+    /// * attraction - attraction factor of the edges
+    pub fn force_directed_layout(&mut self, iterations: u32, repulsion: f32, attraction: f32) {
+        let rects: Vec<(Id, Rect)> = self
+            .figures
+            .iter()
+            .map(|fig| {
+                let fig = RefCell::borrow(fig);
+                (fig.id(), fig.rect())
+            })
+            .collect();
+        let index = |cp: &Option<ConnectionPoint>| {
+            let id = RefCell::borrow(cp.as_ref()?.get_figure()).id();
+            rects.iter().position(|(fig_id, _)| *fig_id == id)
+        };
+        let edges: Vec<(usize, usize)> = self
+            .edges
+            .iter()
+            .filter_map(|edge| {
+                Some((
+                    index(edge.get_start_connection())?,
+                    index(edge.get_end_connection())?,
+                ))
+            })
+            .collect();
 
-        InnerResponse { inner, response }
+        let positions: Vec<Pos2> = rects.iter().map(|(_, rect)| rect.center()).collect();
+        let layout = force_directed_layout(&positions, &edges, iterations, repulsion, attraction);
+        let commands = self
+            .figures
+            .iter()
+            .zip(rects)
+            .zip(positions.into_iter().zip(layout))
+            .filter(|(_, (from, to))| from != to)
+            .map(|((figure, (id, rect)), (from, to))| {
+                RefCell::borrow_mut(figure).move_to(to, from);
+                Command::MoveFigure {
+                    id,
+                    from: rect.min,
+                    to: rect.min + (to - from),
+                }
+            })
+            .collect();
+        self.push_user_commands(commands);
+    }
+
+    /// Move figure to another position of drawing order. Command is stored in history
+    /// ### Arguments
+    /// * id - figure identifier
+    /// * target - function computing new position by current position and number of figures
+    fn reorder(&mut self, id: Id, target: impl FnOnce(usize, usize) -> usize) {
+        let from = match self
+            .figures
+            .iter()
+            .position(|fig| RefCell::borrow(fig).id() == id)
+        {
+            Some(from) => from,
+            None => return,
+        };
+
+        let to = target(from, self.figures.len()).min(self.figures.len() - 1);
+        if from == to {
+            return;
+        }
+
+        let command = Command::Reorder { from, to };
+        command.redo(&mut self.figures, &mut self.edges);
+        self.push_command(command);
+
+        // Indexes of selected figures are changed by reordering
+        self.update_selected_indices();
+        self.highlighted_path = None;
+    }
+
+    /// Draw figure above all other figures
+    pub fn bring_to_front(&mut self, id: Id) {
+        self.reorder(id, |_, len| len - 1);
+    }
+
+    /// Draw figure below all other figures
+    pub fn send_to_back(&mut self, id: Id) {
+        self.reorder(id, |_, _| 0);
+    }
+
+    /// Draw figure one level higher
+    pub fn bring_forward(&mut self, id: Id) {
+        self.reorder(id, |idx, _| idx + 1);
+    }
+
+    /// Draw figure one level lower
+    pub fn send_backward(&mut self, id: Id) {
+        self.reorder(id, |idx, _| idx.saturating_sub(1));
+    }
+
+    /// Find figure by identifier
+    pub(super) fn figure_by_id(&self, id: Id) -> Option<&Rc<RefCell<Box<dyn GraphFigure>>>> {
+        self.figures
+            .iter()
+            .find(|fig| RefCell::borrow(fig).id() == id)
+    }
+
+    /// Index of the figure under cursor in the figures list
+    fn selected_figure_index(&self) -> Option<usize> {
+        self.selected_figure_id.and_then(|id| {
+            self.figures
+                .iter()
+                .position(|fig| RefCell::borrow(fig).id() == id)
+        })
+    }
+
+    /// Change zoom keeping the diagram point under pointer in place. Figures are zoomed
+    /// relative to the screen origin, so the view is scrolled by the difference of the pointer
+    /// positions in diagram coordinates before and after zoom
+    /// ### Arguments
+    /// * pointer - pointer position on screen
+    /// * zoom_factor - new zoom factor
+    fn zoom_at(&mut self, pointer: Pos2, zoom_factor: f32) {
+        let pointer_before = pointer.unzoom(self.zoom_factor);
+        let pointer_after = pointer.unzoom(zoom_factor);
+        self.scroll_delta = (pointer_after - pointer_before).zoom(zoom_factor);
+        self.zoom_factor = zoom_factor;
+    }
+
+    /// Round position to the nearest grid node. Grid is aligned to the scroll offset and scaled by zoom
+    /// ### Arguments
+    /// * pos - position on screen
+    /// ### Return
+    /// snapped position or the same position if grid is off
+    fn snap_to_grid(&self, pos: Pos2) -> Pos2 {
+        match self.grid {
+            Some(spacing) if spacing > 0. => {
+                let step = spacing * self.zoom_factor;
+                let origin = self.scroll_delta.to_pos2();
+                origin + ((pos - origin) / step).round() * step
+            }
+            _ => pos,
+        }
+    }
+
+    /// Snap dragged figures to the grid. Figures are moved by the same offset which puts
+    /// left top corner of the first figure to the grid, so sizes and relative positions
+    /// are kept
+    fn snap_dragged_figures(&mut self) {
+        if self.grid.is_none() {
+            return;
+        }
+
+        let figures: Vec<_> = self
+            .drag_rects
+            .iter()
+            .filter_map(|(id, _)| self.figure_by_id(*id).cloned())
+            .collect();
+        let offset = match figures.first() {
+            Some(fig) => {
+                let min = RefCell::borrow(fig).rect().min;
+                self.snap_to_grid(min) - min
+            }
+            None => return,
+        };
+
+        for fig in figures {
+            let rect = RefCell::borrow(&fig).rect();
+            RefCell::borrow_mut(&fig).set_rect(rect.translate(offset));
+        }
+    }
+
+    /// Draw grid lines over the visible area
+    fn draw_grid(&self, ui: &mut Ui) {
+        let step = match self.grid {
+            Some(spacing) if spacing * self.zoom_factor >= GRID_MIN_SCREEN_STEP => {
+                spacing * self.zoom_factor
+            }
+            _ => return,
+        };
+
+        let clip = ui.clip_rect();
+        let first = self.snap_to_grid(clip.min);
+        let painter = ui.painter();
+        let stroke = Stroke::new(1., GRID_COLOR);
+
+        let mut x = first.x;
+        while x <= clip.max.x {
+            painter.line_segment([Pos2::new(x, clip.min.y), Pos2::new(x, clip.max.y)], stroke);
+            x += step;
+        }
+
+        let mut y = first.y;
+        while y <= clip.max.y {
+            painter.line_segment([Pos2::new(clip.min.x, y), Pos2::new(clip.max.x, y)], stroke);
+            y += step;
+        }
+    }
+
+    /// Translate all selected figures by cursor offset since the last call
+    /// ### Arguments
+    /// * pos - current cursor position
+    fn move_selected(&mut self, pos: Pos2) {
+        if let Some(drag_pos) = self.multi_drag_pos {
+            for fig in self
+                .selected_figure_indices
+                .iter()
+                .filter_map(|idx| self.figures.get(*idx))
+            {
+                RefCell::borrow_mut(fig).move_to(pos, drag_pos);
+            }
+            self.multi_drag_pos = Some(pos);
+        }
+    }
+
+    /// Wrap all selected figures into a new group
+    /// ### Return
+    /// identifier of the new group or None if less than two figures are selected
+    pub fn group_selected(&mut self) -> Option<Id> {
+        let selected = self.selected_figures_idx();
+        if selected.len() < 2 {
+            return None;
+        }
+
+        self.clear_selection();
+
+        // Remove from the end to keep indexes valid
+        let mut members: Vec<_> = selected
+            .iter()
+            .rev()
+            .map(|idx| (*idx, self.figures.remove(*idx)))
+            .collect();
+        members.reverse();
+
+        let id = self.generate_id();
+        let mut group =
+            GroupFigure::new(id, members.iter().map(|(_, fig)| Rc::clone(fig)).collect());
+        group.select(SELECT_MODE_SELECTED);
+        let group: Rc<RefCell<Box<dyn GraphFigure>>> = Rc::new(RefCell::new(Box::new(group)));
+        self.add_figure(Rc::clone(&group));
+        self.push_command(Command::Group { group, members });
+
+        Some(id)
+    }
+
+    /// Dissolve group back into its members. Members keep their current positions
+    /// ### Arguments
+    /// * group_id - identifier of the group
+    /// ### Return
+    /// true if group was found and dissolved
+    pub fn ungroup(&mut self, group_id: Id) -> bool {
+        let found = self.figures.iter().enumerate().find_map(|(idx, fig)| {
+            let fig = RefCell::borrow(fig);
+            fig.as_group()
+                .filter(|group| group.id() == group_id)
+                .map(|group| (idx, group.members().clone()))
+        });
+
+        match found {
+            Some((idx, members)) => {
+                self.clear_selection();
+                let group = self.figures.splice(idx..=idx, members).next();
+                if let Some(group) = group {
+                    self.push_command(Command::Ungroup { index: idx, group });
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Dissolve selected group back into its members
+    /// ### Return
+    /// true if selected figure was a group and it was dissolved
+    pub fn ungroup_selected(&mut self) -> bool {
+        let group_id = self.selected_figures_idx().into_iter().find_map(|idx| {
+            let fig = RefCell::borrow(&self.figures[idx]);
+            fig.as_group().map(|group| group.id())
+        });
+
+        match group_id {
+            Some(id) => self.ungroup(id),
+            None => false,
+        }
+    }
+
+    /// Find edges connected to the figure
+    /// ### Arguments
+    /// * id - figure identifier
+    /// ### Return
+    /// positions of the edges starting or ending at the figure in ascending order
+    fn edges_for_figure(&self, id: Id) -> Vec<usize> {
+        let figure = match self.figure_by_id(id) {
+            Some(figure) => figure,
+            None => return vec![],
+        };
+        let is_connected = |connection: &Option<ConnectionPoint>| {
+            connection
+                .as_ref()
+                .map(|con| Rc::ptr_eq(figure, con.get_figure()))
+                .unwrap_or_default()
+        };
+
+        self.edges
+            .iter()
+            .enumerate()
+            .filter(|(_, edge)| {
+                is_connected(edge.get_start_connection()) || is_connected(edge.get_end_connection())
+            })
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    /// Remove edges at the given positions
+    /// ### Arguments
+    /// * indexes - positions of the edges in ascending order
+    /// ### Return
+    /// command restoring removed edges or None if there is nothing to remove
+    fn remove_edges(&mut self, indexes: &[usize]) -> Option<Command> {
+        if indexes.is_empty() {
+            return None;
+        }
+
+        // Remove from the end to keep indexes valid
+        let mut removed: Vec<(usize, ArrowFigure)> = indexes
+            .iter()
+            .rev()
+            .map(|idx| (*idx, self.edges.remove(*idx)))
+            .collect();
+        removed.reverse();
+        Some(Command::RemoveEdges(removed))
+    }
+
+    /// Remove selected figures together with edges connected to them. Removal is stored
+    /// in history as one command
+    /// ### Return
+    /// true if any figure was removed
+    pub fn delete_selected(&mut self) -> bool {
+        let selected = self.selected_figures_idx();
+        if selected.is_empty() {
+            return false;
+        }
+
+        let mut edges: Vec<usize> = selected
+            .iter()
+            .flat_map(|idx| self.edges_for_figure(RefCell::borrow(&self.figures[*idx]).id()))
+            .collect();
+        edges.sort_unstable();
+        edges.dedup();
+
+        // Edges are removed first, so undo restores figures before their edges
+        let mut commands: Vec<Command> = self.remove_edges(&edges).into_iter().collect();
+
+        // Remove from the end to keep indexes valid
+        for index in selected.into_iter().rev() {
+            let figure = self.figures.remove(index);
+            self.outgoing.push(WsMessages::DeleteFigure {
+                rq: RequestInfo::default(),
+                figure_id: id_to_string(RefCell::borrow(&figure).id()),
+            });
+            commands.push(Command::RemoveFigure { index, figure });
+        }
+
+        self.push_command(Command::Batch(commands));
+        self.selected_figure_indices.clear();
+        self.selected_figure_id = None;
+        self.highlighted_path = None;
+        true
+    }
+
+    /// Enter the group. After entering only group members can be selected and edited
+    /// ### Arguments
+    /// * idx - group index in figures vector
+    fn enter_group(&mut self, idx: usize) {
+        let group = self.figures.get(idx).and_then(|fig| {
+            let fig = RefCell::borrow(fig);
+            fig.as_group()
+                .map(|group| (group.id(), group.members().clone()))
+        });
+
+        if let Some((group_id, members)) = group {
+            self.clear_selection();
+            let parent = std::mem::replace(&mut self.figures, members);
+            self.group_stack.push((group_id, parent));
+        }
+    }
+
+    /// Exit currently entered group back to the parent level
+    pub fn exit_group(&mut self) {
+        if let Some((group_id, parent)) = self.group_stack.pop() {
+            self.clear_selection();
+            let members = std::mem::replace(&mut self.figures, parent);
+            if let Some(group) = self
+                .figures
+                .iter()
+                .find(|fig| RefCell::borrow(fig).id() == group_id)
+            {
+                if let Some(group) = RefCell::borrow_mut(group).as_group_mut() {
+                    group.set_members(members);
+                }
+            }
+        }
+    }
+
+    /// Return number of edges in the highlighted path
+    pub fn highlighted_path_len(&self) -> Option<usize> {
+        self.highlighted_path.as_ref().map(|path| path.edges.len())
+    }
+
+    /// Draw highlighted path over the diagram
+    fn draw_highlighted_path(&self, painter: &Painter) {
+        if let Some(path) = &self.highlighted_path {
+            let stroke = Stroke::new(3., PATH_HIGHLIGHT_COLOR);
+            for edge in path.edges.iter().filter_map(|idx| self.edges.get(*idx)) {
+                painter.line_segment(
+                    [edge.compute_start_point(), edge.compute_end_point()],
+                    stroke,
+                );
+            }
+
+            for fig in path.figures.iter().filter_map(|idx| self.figures.get(*idx)) {
+                let rect = RefCell::borrow(fig).rect();
+                painter.circle_stroke(
+                    rect.center(),
+                    rect.size().max_elem() / 2. + POINT_OVER_TOLERANCE,
+                    Stroke::new(2., PATH_HIGHLIGHT_COLOR),
+                );
+            }
+        }
+    }
+
+    /// Compute cursor state for the current frame
+    /// ### Arguments
+    /// * hover_pos - cursor position, None if cursor is outside of the canvas
+    fn cursor_icon_state(&self, hover_pos: Option<Pos2>) -> CursorIconState {
+        if self.dragged_edge.is_some() {
+            return CursorIconState::DraggingEdge;
+        }
+
+        if let Some(pos) = hover_pos {
+            if self.point_in_edge_controls(pos).is_some() {
+                return CursorIconState::EdgeControl;
+            }
+
+            let hovered = self
+                .selected_figure_id
+                .and_then(|id| self.figure_by_id(id))
+                .and_then(|fig| RefCell::borrow(fig).contains(pos));
+
+            match hovered {
+                Some(CursorIcon::Default) => return CursorIconState::Hover,
+                Some(icon) => return CursorIconState::Resize(icon),
+                None => {}
+            }
+        }
+
+        if self.is_panning {
+            CursorIconState::Pan
+        } else {
+            CursorIconState::Default
+        }
+    }
+
+    /// Return the single cursor icon for the current state. Must be called once per frame
+    /// ### Arguments
+    /// * hover_pos - cursor position, None if cursor is outside of the canvas
+    pub fn compute_cursor_icon(&self, hover_pos: Option<Pos2>) -> CursorIcon {
+        self.cursor_icon_state(hover_pos).icon()
+    }
+
+    /// Track figures whose label is edited. When editing is finished and label was changed
+    /// new label is sent to other clients
+    fn track_text_edits(&mut self) {
+        let figures = &self.figures;
+        let outgoing = &mut self.outgoing;
+        self.edited_texts.retain(|(id, text)| {
+            let figure = figures.iter().find(|fig| RefCell::borrow(fig).id() == *id);
+            match figure.map(|fig| RefCell::borrow(fig)) {
+                Some(figure) if figure.text_edited() => true,
+                Some(figure) => {
+                    let label = figure.label();
+                    if label != *text {
+                        outgoing.push(WsMessages::UpdateFigureText {
+                            rq: RequestInfo::default(),
+                            figure_id: id_to_string(*id),
+                            text: label,
+                        });
+                    }
+                    false
+                }
+                None => false,
+            }
+        });
+
+        for figure in &self.figures {
+            let figure = RefCell::borrow(figure);
+            let id = figure.id();
+            if figure.text_edited() && self.edited_texts.iter().all(|(edited, _)| *edited != id) {
+                self.edited_texts.push((id, figure.label()));
+            }
+        }
+    }
+
+    /// Find figure by identifier received from other client
+    fn figure_by_remote_id(&self, figure_id: &str) -> Option<&Rc<RefCell<Box<dyn GraphFigure>>>> {
+        id_from_string(figure_id).and_then(|id| self.figure_by_id(id))
+    }
+
+    /// Add figure created by other client. Figure keeps identifier given by its creator,
+    /// figures already known are skipped
+    fn add_remote_figure(&mut self, figure: &AddFigure) {
+        let id = match id_from_string(&figure.figure_id) {
+            Some(id) => id,
+            None => {
+                tracing::warn!("Invalid identifier of added figure {}", figure.figure_id);
+                return;
+            }
+        };
+        if self.figure_by_id(id).is_none() {
+            let (rect, text) = (figure.rect, figure.text.clone());
+            let added: Box<dyn GraphFigure> = match figure.shape {
+                FigureShape::Rect => Box::new(RectFigure::new(id, rect, text)),
+                FigureShape::Ellipse => Box::new(EllipseFigure::new(id, rect, text)),
+                FigureShape::Diamond => Box::new(DiamondFigure::new(id, rect, text)),
+                FigureShape::Cylinder => Box::new(CylinderFigure::new(id, rect, text)),
+            };
+            self.add_figure(Rc::new(RefCell::new(added)));
+        }
+    }
+
+    /// Move figure moved by other client
+    fn move_remote_figure(&mut self, figure_id: &str, dx: f32, dy: f32) {
+        match self.figure_by_remote_id(figure_id) {
+            Some(figure) => RefCell::borrow_mut(figure).move_to(Pos2::new(dx, dy), Pos2::ZERO),
+            None => tracing::warn!("Figure {} not found to move", figure_id),
+        }
+    }
+
+    /// Remove figure deleted by other client together with edges connected to it. Removal
+    /// is neither sent back nor stored in history
+    fn delete_remote_figure(&mut self, figure_id: &str) {
+        let id = match self.figure_by_remote_id(figure_id) {
+            Some(figure) => RefCell::borrow(figure).id(),
+            None => {
+                tracing::warn!("Figure {} not found to delete", figure_id);
+                return;
+            }
+        };
+        let edges = self.edges_for_figure(id);
+        self.remove_edges(&edges);
+        self.figures.retain(|fig| RefCell::borrow(fig).id() != id);
+
+        // Indexes are not valid anymore
+        self.selected_figure_indices.clear();
+        if self.selected_figure_id == Some(id) {
+            self.selected_figure_id = None;
+        }
+    }
+
+    /// Replace label of the figure edited by other client
+    fn update_figure_text(&mut self, figure_id: &str, text: &str) {
+        match self.figure_by_remote_id(figure_id) {
+            Some(figure) => RefCell::borrow_mut(figure).set_text(text.to_owned()),
+            None => tracing::warn!("Figure {} not found to update text", figure_id),
+        }
+    }
+
+    /// Drawing scene include all figures, lines, connection points and other
+    fn draw(&mut self, ui: &mut Ui) {
+        self.draw_grid(ui);
+
+        // Draw parent levels of entered groups, the entered group itself is drawn as current level
+        for (group_id, parent) in &self.group_stack {
+            for r in parent
+                .iter()
+                .filter(|r| RefCell::borrow(r).id() != *group_id)
+            {
+                RefCell::borrow_mut(r).draw(ui, self.zoom_factor, self.scroll_delta);
+            }
+        }
+
+        self.selection_animation_offset += ui.input().unstable_dt * MARCH_SPEED;
+        for r in self.figures.iter_mut() {
+            let mut fig = RefCell::borrow_mut(r);
+            if fig.selected() & SELECT_MODE_SELECTED > 0 {
+                fig.set_selection_offset(self.selection_animation_offset);
+            }
+            fig.draw(ui, self.zoom_factor, self.scroll_delta);
+        }
+
+        for a in self.edges.iter_mut() {
+            a.draw(ui, self.zoom_factor, self.scroll_delta);
+        }
+
+        if self.is_dragged {
+            if let Some(fig) = self.selected_tool.as_mut() {
+                fig.draw(ui, self.zoom_factor, self.scroll_delta);
+            }
+        }
+
+        if let Some(edge) = self.dragged_edge.as_mut() {
+            edge.draw(ui, self.zoom_factor, self.scroll_delta);
+        }
+
+        self.draw_highlighted_path(ui.painter());
+        self.draw_remote_selections(ui.painter());
+        self.draw_guides(ui.painter());
+
+        if let Some(rect) = self.rubber_band_rect() {
+            ui.painter().rect(
+                rect,
+                0.,
+                RUBBER_BAND_FILL,
+                Stroke::new(1., RUBBER_BAND_STROKE_COLOR),
+            );
+        }
+    }
+
+    /// Remember figures selected by another user, empty selection is forgotten
+    /// ### Arguments
+    /// * user - user identifier
+    /// * selected_ids - identifiers of the selected figures
+    fn update_remote_selection(&mut self, user: &str, selected_ids: &[String]) {
+        if selected_ids.is_empty() {
+            self.remote_selections.remove(user);
+        } else {
+            self.remote_selections
+                .insert(user.to_owned(), selected_ids.to_vec());
+        }
+    }
+
+    /// Send identifiers of the selected figures to other users of the board when selection is
+    /// changed, so selection by click, rubber band, menu or deletion is shared the same way
+    pub(super) fn track_selection(&mut self) {
+        let selected_ids: Vec<String> = self
+            .selected_figure_indices
+            .iter()
+            .filter_map(|idx| self.figures.get(*idx))
+            .map(|fig| id_to_string(RefCell::borrow(fig).id()))
+            .collect();
+        if selected_ids != self.sent_selection {
+            self.sent_selection = selected_ids.clone();
+            self.outgoing.push(WsMessages::SelectionChange {
+                rq: RequestInfo::default(),
+                selected_ids,
+            });
+        }
+    }
+
+    /// Draw faint dashed outlines around figures selected by other users, colored as their cursors
+    fn draw_remote_selections(&self, painter: &Painter) {
+        for (user, ids) in &self.remote_selections {
+            let stroke = Stroke::new(
+                1.,
+                cursor_color(user).linear_multiply(REMOTE_SELECTION_OPACITY),
+            );
+            for fig in self.figures.iter().map(|fig| RefCell::borrow(fig)) {
+                if !ids.contains(&id_to_string(fig.id())) {
+                    continue;
+                }
+
+                let rect = fig.rect().expand(REMOTE_SELECTION_MARGIN);
+                let border = [
+                    rect.left_top(),
+                    rect.right_top(),
+                    rect.right_bottom(),
+                    rect.left_bottom(),
+                    rect.left_top(),
+                ];
+                for dash in dashed_path(&border, 4., 4., 0.) {
+                    painter.add(Shape::line(dash, stroke));
+                }
+            }
+        }
+    }
+
+    /// Drawing one connection point
+    #[inline]
+    fn draw_edge_point(&self, point: Pos2, painter: &Painter) {
+        painter.circle_filled(point, 3.5, self.edge_point_color);
+    }
+
+    /// Drawing selected connection point
+    #[inline]
+    fn draw_selected_edge_point(&self, point: Pos2, painter: &Painter, stroke: Stroke) {
+        painter.circle_stroke(point, 5., stroke);
+    }
+
+    /// Return positions of the connection points the dragged edge starts from and snaps to
+    fn dragged_edge_points(&self) -> (Option<Pos2>, Option<Pos2>) {
+        let position = |connection: &Option<ConnectionPoint>| {
+            connection
+                .as_ref()
+                .and_then(|connection| connection.get_connection_pos())
+        };
+
+        match self.dragged_edge.as_ref() {
+            Some(edge) => (
+                position(edge.get_start_connection()),
+                position(edge.get_end_connection()),
+            ),
+            None => (None, None),
+        }
+    }
+}
+
+/// Defines all graphics diagram operations
+pub struct Graphics {
+    /// Graphics data
+    pub(super) graphics_data: GraphicsData,
+}
+
+impl Default for Graphics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Implies graphics/digram operations
+impl Graphics {
+    /// Create empty diagram
+    pub fn new() -> Self {
+        Self {
+            graphics_data: GraphicsData::default(),
+        }
+    }
+
+    /// Return currently selected figure
+    #[inline]
+    fn selected_figure(&self) -> Option<&Rc<RefCell<Box<dyn GraphFigure>>>> {
+        self.graphics_data
+            .figures
+            .iter()
+            .find(|fig| RefCell::borrow(fig).selected() & SELECT_MODE_SELECTED > 0)
+    }
+
+    /// Draw controls to add out edges over selected figure. Each control represents as a circle with plus symbol inside
+    fn draw_edge_controls(&mut self, ui: &mut Ui) {
+        if let Some(fig) = self.selected_figure() {
+            // Draw only for selected figures
+            let fig = RefCell::borrow(fig);
+            // let rect = fig.borrow().rect();
+            let points = fig.connection_points();
+            let painter = ui.painter();
+            for point in points {
+                self.graphics_data.draw_edge_point(*point, painter);
+            }
+        }
+
+        if let Some(fig) = self
+            .graphics_data
+            .selected_by_edge_figure_idx
+            .and_then(|idx| self.graphics_data.figures.get(idx))
+        {
+            let painter = ui.painter();
+            for point in fig.as_ref().borrow().connection_points() {
+                self.graphics_data.draw_edge_point(*point, painter);
+            }
+        }
+
+        // Highlight where the dragged edge starts and where it would be connected
+        let (source, target) = self.graphics_data.dragged_edge_points();
+        let painter = ui.painter();
+        if let Some(point) = source {
+            self.graphics_data.draw_selected_edge_point(
+                point,
+                painter,
+                self.graphics_data.edge_source_point_stroke,
+            );
+        }
+
+        if let Some(point) = target {
+            self.graphics_data.draw_selected_edge_point(
+                point,
+                painter,
+                self.graphics_data.edge_target_point_stroke,
+            );
+        }
+    }
+
+    /// Return figures of the diagram
+    pub fn figures(&self) -> &[Rc<RefCell<Box<dyn GraphFigure>>>] {
+        &self.graphics_data.figures
+    }
+
+    /// Return edges of the diagram
+    pub fn edges(&self) -> &[ArrowFigure] {
+        &self.graphics_data.edges
+    }
+
+    /// Generate new figure identifier
+    pub fn generate_id(&mut self) -> Id {
+        self.graphics_data.generate_id()
+    }
+
+    /// Add figure into diagram
+    pub fn add_figure(&mut self, figure: Rc<RefCell<Box<dyn GraphFigure>>>) {
+        self.graphics_data.add_figure(figure);
+    }
+
+    /// Set distance in pixels selected figures are moved by arrow keys, Shift multiplies it by 10
+    pub fn set_nudge_step(&mut self, step: f32) {
+        self.graphics_data.nudge_step = step;
+    }
+
+    /// Add edge into diagram
+    pub fn add_edge(&mut self, edge: ArrowFigure) {
+        self.graphics_data.edges.push(edge);
+    }
+
+    /// Set grid spacing in logical pixels. None turns grid and snapping off
+    pub fn set_grid(&mut self, spacing: Option<f32>) {
+        self.graphics_data.grid = spacing;
+    }
+
+    /// Return grid spacing, None if grid is off
+    pub fn grid(&self) -> Option<f32> {
+        self.graphics_data.grid
+    }
+
+    /// Next grid setting for the toggle button: 20 px, no grid, 10 px
+    fn next_grid(grid: Option<f32>) -> Option<f32> {
+        match grid {
+            Some(spacing) if spacing >= 20. => None,
+            Some(_) => Some(20.),
+            None => Some(10.),
+        }
+    }
+
+    /// Number of edges in the path between two selected figures
+    pub fn highlighted_path_len(&self) -> Option<usize> {
+        self.graphics_data.highlighted_path_len()
+    }
+
+    /// Scroll canvas by the offset. Figures are moved when they are drawn next time
+    pub fn set_scroll_delta(&mut self, scroll_delta: Vec2) {
+        self.graphics_data.scroll_delta = scroll_delta;
+    }
+
+    /// Arrange figures using force-directed layout
+    /// ### Arguments
+    /// * iterations - number of simulation steps
+    /// * repulsion - repulsion factor between all figures
+    /// * attraction - attraction factor of the edges
+    pub fn force_directed_layout(&mut self, iterations: u32, repulsion: f32, attraction: f32) {
+        self.graphics_data
+            .force_directed_layout(iterations, repulsion, attraction);
+    }
+
+    /// Draw figure above all other figures
+    pub fn bring_to_front(&mut self, id: Id) {
+        self.graphics_data.bring_to_front(id);
+    }
+
+    /// Draw figure below all other figures
+    pub fn send_to_back(&mut self, id: Id) {
+        self.graphics_data.send_to_back(id);
+    }
+
+    /// Draw figure one level higher
+    pub fn bring_forward(&mut self, id: Id) {
+        self.graphics_data.bring_forward(id);
+    }
+
+    /// Draw figure one level lower
+    pub fn send_backward(&mut self, id: Id) {
+        self.graphics_data.send_backward(id);
+    }
+
+    /// Draw properties of the selected figure
+    pub fn show_properties_panel(&mut self, ui: &mut Ui) {
+        self.graphics_data.properties_panel(ui);
+    }
+
+    /// Forbid or allow diagram changes by user. Diagram still can be viewed and
+    /// figures selected in read-only mode
+    pub fn set_read_only(&mut self, read_only: bool) {
+        let data = &mut self.graphics_data;
+        data.read_only = read_only;
+        if read_only {
+            // Drop actions started before the mode was switched
+            data.dragged_edge = None;
+            data.rubber_band = None;
+            data.multi_drag_pos = None;
+            data.is_dragged = false;
+            data.select_enabled = true;
+        }
+    }
+
+    /// Select figure placed by drag on blank canvas, None selects figures by rubber band
+    pub fn select_tool(&mut self, tool: Option<Box<dyn GraphFigure>>) {
+        self.graphics_data.selected_tool = tool;
+        self.graphics_data.tool_template = None;
+        self.graphics_data.is_dragged = false;
+    }
+
+    /// Return true if figure is placed by drag on blank canvas
+    pub fn has_tool(&self) -> bool {
+        self.graphics_data.selected_tool.is_some()
+    }
+
+    /// Return true if diagram can't be changed by user
+    pub fn read_only(&self) -> bool {
+        self.graphics_data.read_only
+    }
+
+    /// Set rule checked before dragged edge is connected to the end figure
+    pub fn set_connection_validator(&mut self, validator: Option<Box<dyn ConnectionValidator>>) {
+        self.graphics_data.connection_validator = validator;
+    }
+
+    /// Return number of figures and edges in the diagram
+    pub fn statistics(&self) -> (usize, usize) {
+        (
+            self.graphics_data.figures.len(),
+            self.graphics_data.edges.len(),
+        )
+    }
+
+    /// Apply messages of other users of the board to the diagram
+    pub fn apply_messages(&mut self, messages: &[WsMessages]) {
+        for msg in messages {
+            match msg {
+                // Remote cursors are accumulated by application and drawn after figures
+                WsMessages::MousePosition(_) => {}
+                WsMessages::AddFigure(figure) => self.graphics_data.add_remote_figure(figure),
+                WsMessages::AddArrow(_arrow) => tracing::error!("Not yet implemented arrows"),
+                WsMessages::DeleteFigure { figure_id, .. } => {
+                    self.graphics_data.delete_remote_figure(figure_id)
+                }
+                WsMessages::MoveFigure {
+                    figure_id, dx, dy, ..
+                } => self.graphics_data.move_remote_figure(figure_id, *dx, *dy),
+                WsMessages::UpdateFigureText {
+                    figure_id, text, ..
+                } => self.graphics_data.update_figure_text(figure_id, text),
+                // Protocol messages are processed by application
+                WsMessages::Handshake { .. }
+                | WsMessages::HandshakeAck { .. }
+                | WsMessages::Hello { .. }
+                | WsMessages::HelloAck { .. } => {}
+                // Boards and pages are managed by application, not by canvas
+                WsMessages::CreateBoard { .. }
+                | WsMessages::BoardCreated { .. }
+                | WsMessages::ListBoards { .. }
+                | WsMessages::BoardList { .. }
+                | WsMessages::GetBoardMembers { .. }
+                | WsMessages::BoardMembers { .. }
+                | WsMessages::JoinBoard { .. }
+                | WsMessages::BoardAcl { .. }
+                | WsMessages::AddPage { .. } => {}
+                WsMessages::SelectionChange { rq, selected_ids } => self
+                    .graphics_data
+                    .update_remote_selection(&rq.user, selected_ids),
+                WsMessages::UpdateMetadata {
+                    title,
+                    author,
+                    description,
+                    ..
+                } => self
+                    .graphics_data
+                    .update_metadata(title, author, description),
+                WsMessages::VersionMismatch {
+                    server_version,
+                    client_version,
+                } => tracing::error!(
+                    "Protocol version {} is not supported by server, server version: {}",
+                    client_version,
+                    server_version
+                ),
+            }
+        }
+    }
+
+    /// Draw whole canvas
+    pub fn ui(
+        &mut self,
+        ui: &mut Ui,
+        incoming: Ref<'_, Vec<WsMessages>>,
+        remote_cursors: &RemoteCursors,
+    ) -> InnerResponse<Vec<WsMessages>> {
+        let mut inner = vec![];
+
+        let grid_label = match self.graphics_data.grid {
+            Some(spacing) => format!("Grid: {}", spacing),
+            None => "Grid: off".to_owned(),
+        };
+        if ui.small_button(grid_label).clicked() {
+            self.set_grid(Self::next_grid(self.graphics_data.grid));
+        }
+
+        // Compute size
+        let size = ui.available_size_before_wrap();
+        // Allocate the space.
+        let response = ui.allocate_response(size, Sense::click_and_drag());
+        self.graphics_data.viewport = response.rect;
+
+        // Two fingers zoom canvas. One finger touched blank canvas scrolls it, finger touched
+        // figure drags it by the pointer emulated from the touch. Release of the panning finger
+        // is ignored as well, so pan mode is taken before events are processed
+        let was_panning = self.graphics_data.touches.mode() == Some(TouchMode::Pan);
+        let touch_events: Vec<_> = ui
+            .input()
+            .events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Touch { id, phase, pos, .. } => Some((*id, *phase, *pos)),
+                _ => None,
+            })
+            .collect();
+        let mut touch_pan = Vec2::ZERO;
+        for (id, phase, pos) in touch_events {
+            if phase == TouchPhase::Start && !self.graphics_data.touches.is_active() {
+                let mode = if self.graphics_data.is_occupied(pos) {
+                    TouchMode::Drag
+                } else {
+                    TouchMode::Pan
+                };
+                self.graphics_data.touches.set_mode(mode);
+            }
+
+            match self.graphics_data.touches.process(id, phase, pos) {
+                Some(TouchGesture::Pinch { ratio, center }) => {
+                    let zd = self.graphics_data.zoom_factor * ratio;
+                    if zd > 0. {
+                        self.graphics_data.zoom_at(center, zd);
+                    }
+                }
+                Some(TouchGesture::Pan(delta)) => touch_pan += delta,
+                None => {}
+            }
+        }
+        let touch_active = self.graphics_data.touches.is_active();
+        let touch_panning =
+            was_panning || self.graphics_data.touches.mode() == Some(TouchMode::Pan);
+
+        // Zoom factor computing, point under pointer stays in place. Pinch is already
+        // applied from touch events
+        let zd = self.graphics_data.zoom_factor + ui.input().zoom_delta() - 1.;
+        if zd != self.graphics_data.zoom_factor && zd > 0. && !touch_active {
+            match response.hover_pos() {
+                Some(hp) => self.graphics_data.zoom_at(hp, zd),
+                None => self.graphics_data.zoom_factor = zd,
+            }
+        }
+
+        let scroll_delta = ui.input().scroll_delta + touch_pan;
+        self.graphics_data.is_panning = scroll_delta != Vec2::ZERO;
+
+        let editable = !self.graphics_data.read_only;
+        if response.hovered() {
+            if let Some(hp) = response.hover_pos() {
+                self.graphics_data.select_by_point(hp);
+
+                if let Some((_, point)) = self
+                    .graphics_data
+                    .point_in_edge_controls(hp)
+                    .filter(|_| editable)
+                {
+                    // Draw cidx point for current figure
+                    self.graphics_data.draw_selected_edge_point(
+                        point,
+                        ui.painter(),
+                        self.graphics_data.selected_edge_point_stroke,
+                    );
+                }
+            }
+        }
+
+        // Cursor computed once per frame using current state
+        let cursor = self.graphics_data.compute_cursor_icon(response.hover_pos());
+        let response = response.on_hover_cursor(cursor);
+
+        // Tooltip of the figure or edge under cursor
+        let tooltip = response.hover_pos().and_then(|hp| {
+            match self
+                .graphics_data
+                .selected_figure_id
+                .and_then(|id| self.graphics_data.figure_by_id(id))
+            {
+                Some(figure) => RefCell::borrow(figure).tooltip().map(str::to_owned),
+                None => self
+                    .graphics_data
+                    .edges
+                    .iter()
+                    .rev()
+                    .find(|edge| edge.contains(hp).is_some())
+                    .and_then(|edge| edge.tooltip().map(str::to_owned)),
+            }
+        });
+        let response = match tooltip {
+            Some(tooltip) => response.on_hover_text(tooltip),
+            None => response,
+        };
+
+        // Show incoming messages in current view
+        self.apply_messages(&incoming);
+
+        let response = if editable {
+            self.graphics_data.context_menu(response)
+        } else {
+            response
+        };
+
+        // Images dropped onto canvas are placed at the cursor
+        let dropped_files = ui.input().raw.dropped_files.clone();
+        if editable && !dropped_files.is_empty() {
+            let pos = ui
+                .input()
+                .pointer
+                .hover_pos()
+                .unwrap_or_else(|| self.graphics_data.viewport.center());
+            self.graphics_data.drop_images(&dropped_files, pos);
+        }
+
+        if editable && response.double_clicked() {
+            // Edges are drawn over figures, so they are checked first
+            let edge_clicked = response
+                .hover_pos()
+                .map(|pos| self.graphics_data.double_click_edge(pos))
+                .unwrap_or_default();
+            if let Some(idx) = self
+                .graphics_data
+                .selected_figure_index()
+                .filter(|_| !edge_clicked)
+            {
+                let is_group = self
+                    .graphics_data
+                    .figures
+                    .get(idx)
+                    .map(|fig| RefCell::borrow(fig).as_group().is_some())
+                    .unwrap_or_default();
+
+                if is_group {
+                    self.graphics_data.enter_group(idx);
+                } else if let Some(selected_figure) = self.graphics_data.figures.get_mut(idx) {
+                    match response.hover_pos() {
+                        Some(pos) => selected_figure.borrow_mut().double_click_at(pos),
+                        None => selected_figure.borrow_mut().double_click(),
+                    }
+                }
+            }
+        }
+
+        // Group/ungroup, duplicate, delete and undo/redo shortcuts, only leaving group is
+        // allowed in read-only mode
+        let (group, ungroup, escape, undo, redo, duplicate, delete, nudge) = {
+            let input = ui.input();
+            let command = input.modifiers.command && editable;
+            let step = match input.modifiers.shift {
+                true => self.graphics_data.nudge_step * NUDGE_SHIFT_FACTOR,
+                false => self.graphics_data.nudge_step,
+            };
+            let nudge = [
+                (Key::ArrowLeft, Vec2::new(-step, 0.)),
+                (Key::ArrowRight, Vec2::new(step, 0.)),
+                (Key::ArrowUp, Vec2::new(0., -step)),
+                (Key::ArrowDown, Vec2::new(0., step)),
+            ]
+            .iter()
+            .filter(|(key, _)| input.key_pressed(*key))
+            .fold(Vec2::ZERO, |acc, (_, offset)| acc + *offset);
+            (
+                command && !input.modifiers.shift && input.key_pressed(Key::G),
+                command && input.modifiers.shift && input.key_pressed(Key::G),
+                input.key_pressed(Key::Escape),
+                command && input.key_pressed(Key::Z),
+                command && input.key_pressed(Key::Y),
+                command && input.key_pressed(Key::D),
+                editable && (input.key_pressed(Key::Delete) || input.key_pressed(Key::Backspace)),
+                match editable && !input.modifiers.command && !input.modifiers.alt {
+                    true => nudge,
+                    false => Vec2::ZERO,
+                },
+            )
+        };
+
+        // Keys are used by text editor while label is edited
+        let text_edited = self
+            .graphics_data
+            .figures
+            .iter()
+            .any(|fig| RefCell::borrow(fig).text_edited());
+
+        // Text editor has its own history
+        if undo && !text_edited {
+            self.graphics_data.undo();
+        } else if redo && !text_edited {
+            self.graphics_data.redo();
+        }
+
+        if group {
+            self.graphics_data.group_selected();
+        } else if ungroup {
+            self.graphics_data.ungroup_selected();
+        }
+
+        if escape {
+            self.graphics_data.exit_group();
+        }
+
+        if duplicate {
+            self.graphics_data.duplicate_selected();
+        }
+
+        if delete && self.graphics_data.select_enabled && !text_edited {
+            self.graphics_data.delete_selected();
+        }
+
+        if nudge != Vec2::ZERO && !text_edited {
+            self.graphics_data.nudge_selected(nudge);
+        }
+
+        if response.clicked() {
+            // Ctrl-click adds figure to selection or removes it from selection
+            let multi_select = ui.input().modifiers.command;
+
+            if !multi_select {
+                // Clear selection
+                self.graphics_data
+                    .figures
+                    .iter_mut()
+                    .filter(|fig| RefCell::borrow(fig).selected() & SELECT_MODE_SELECTED > 0)
+                    .for_each(|fig| {
+                        let selected = RefCell::borrow(fig).selected();
+                        RefCell::borrow_mut(fig).select(selected & !SELECT_MODE_SELECTED)
+                    });
+            }
+
+            if let Some(selected_figure) = self
+                .graphics_data
+                .selected_figure_id
+                .and_then(|id| self.graphics_data.figure_by_id(id))
+            {
+                let selected = RefCell::borrow(selected_figure).selected();
+                let selected = if multi_select {
+                    selected ^ SELECT_MODE_SELECTED
+                } else {
+                    selected | SELECT_MODE_SELECTED
+                };
+                RefCell::borrow_mut(selected_figure).select(selected);
+            }
+
+            self.graphics_data.update_selected_indices();
+            self.graphics_data.highlight_path_between_selected();
+        }
+
+        // Process drag started event, pointer emulated from the panning finger drags nothing
+        if editable && !touch_panning && response.drag_started() {
+            let hover_pos = response.hover_pos().unwrap_or_default();
+            if let Some((cpoint, _)) = self.graphics_data.point_in_edge_controls(hover_pos) {
+                let mut edge =
+                    ArrowFigure::new([hover_pos, hover_pos], self.graphics_data.generate_id());
+
+                if let Some(fig) = self.selected_figure() {
+                    edge.connect_start(ConnectionPoint::new(Rc::clone(fig), cpoint));
+                }
+
+                self.graphics_data.dragged_edge = Some(edge);
+            } else if self.graphics_data.start_edge_drag(hover_pos) {
+                // Edges are drawn over figures, so they are dragged first
+            } else if self
+                .graphics_data
+                .selected_figure_index()
+                .filter(|idx| self.graphics_data.is_multi_move(*idx, hover_pos))
+                .is_some()
+            {
+                self.graphics_data.multi_drag_pos = Some(hover_pos);
+                let selected = self.graphics_data.selected_figure_indices.clone();
+                self.graphics_data.remember_drag_rects(&selected);
+            } else if let Some((idx, selected_figure)) = self
+                .graphics_data
+                .selected_figure_index()
+                .map(|idx| (idx, Rc::clone(&self.graphics_data.figures[idx])))
+            {
+                self.graphics_data.remember_drag_rects(&[idx]);
+                selected_figure.borrow_mut().drag_start(
+                    hover_pos,
+                    PointerButton::Primary,
+                    self.graphics_data.zoom_factor,
+                );
+            } else {
+                let shift = ui.input().modifiers.shift;
+                self.graphics_data.start_canvas_drag(hover_pos, shift);
+            }
+            self.graphics_data.select_enabled = false;
+        }
+
+        if editable && !touch_panning && response.dragged_by(PointerButton::Primary) {
+            let hover_pos = response.hover_pos().unwrap_or_default();
+            if let Some(edge) = self.graphics_data.dragged_edge.as_mut() {
+                edge.set_end_pos(hover_pos);
+            } else if let Some(idx) = self.graphics_data.edge_drag_index() {
+                self.graphics_data.edges[idx].dragged_by(hover_pos, PointerButton::Primary);
+            } else if self.graphics_data.multi_drag_pos.is_some() {
+                self.graphics_data.move_selected(hover_pos);
+            } else if let Some(selected_figure) = self
+                .graphics_data
+                .selected_figure_id
+                .and_then(|id| self.graphics_data.figure_by_id(id))
+                .cloned()
+            {
+                selected_figure
+                    .borrow_mut()
+                    .dragged_by(hover_pos, PointerButton::Primary);
+                let id = RefCell::borrow(&selected_figure).id();
+                self.graphics_data.snap_to_guides(id);
+            } else if let Some([_, current]) = self.graphics_data.rubber_band.as_mut() {
+                *current = hover_pos;
+            } else if self.graphics_data.selected_tool.is_some() {
+                let hover_pos = self.graphics_data.snap_to_grid(hover_pos);
+                if let Some(fig) = self.graphics_data.selected_tool.as_mut() {
+                    fig.dragged_by(hover_pos, PointerButton::Primary);
+                }
+            }
+        }
+
+        if editable && !touch_panning && response.drag_released() {
+            let hover_pos = response.hover_pos().unwrap_or_default();
+            let snapped_pos = self.graphics_data.snap_to_grid(hover_pos);
+            self.graphics_data.clear_guides();
+            if let Some(mut edge) = self.graphics_data.dragged_edge.take() {
+                edge.set_end_pos(snapped_pos);
+                if let Err(err) = self.graphics_data.validate_edge(&edge) {
+                    tracing::warn!("Connection rejected: {}", err);
+                    edge.disconnect_end();
+                }
+                self.graphics_data.edges.push(edge.clone());
+                self.graphics_data.push_command(Command::AddEdge(edge));
+            } else if self.graphics_data.edge_drag.is_some() {
+                self.graphics_data.release_edge_drag(hover_pos);
+            } else if self.graphics_data.multi_drag_pos.is_some() {
+                self.graphics_data.move_selected(hover_pos);
+                self.graphics_data.multi_drag_pos = None;
+                self.graphics_data.snap_dragged_figures();
+                self.graphics_data.record_drag_commands();
+            } else if let Some(selected_figure) = self
+                .graphics_data
+                .selected_figure_id
+                .and_then(|id| self.graphics_data.figure_by_id(id))
+            {
+                selected_figure
+                    .borrow_mut()
+                    .drag_released(hover_pos, PointerButton::Primary);
+                self.graphics_data.snap_dragged_figures();
+                self.graphics_data.record_drag_commands();
+            } else if let Some([start, _]) = self.graphics_data.rubber_band.take() {
+                self.graphics_data
+                    .select_by_rect(Rect::from_two_pos(start, hover_pos));
+            } else if let Some(message) = self.graphics_data.place_tool_figure(snapped_pos) {
+                // Send message to WebSocket
+                inner.push(message);
+            }
+            self.graphics_data.select_enabled = true;
+            self.graphics_data.is_dragged = false;
+            self.graphics_data.selected_by_edge_figure_idx = None;
+        }
+
+        if scroll_delta != Vec2::ZERO {
+            self.graphics_data.scroll_delta = scroll_delta;
+        }
+
+        self.graphics_data.track_text_edits();
+        self.graphics_data.track_selection();
+        self.graphics_data.draw(ui);
+        self.draw_edge_controls(ui);
+        self.graphics_data.draw_properties(ui.ctx());
+        draw_remote_cursors(ui.painter(), remote_cursors, Utc::now());
+
+        // Keep repainting to animate selection border and fade out remote cursors
+        if self.selected_figure().is_some() || !remote_cursors.is_empty() {
+            ui.ctx().request_repaint();
+        }
+
+        inner.append(&mut self.graphics_data.outgoing);
+        InnerResponse { inner, response }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, f32::consts::FRAC_PI_2, rc::Rc};
+
+    use eframe::{
+        egui::{
+            CentralPanel, Context, Event, Id, Key, Modifiers, PointerButton, RawInput,
+            TouchDeviceId, TouchId, TouchPhase,
+        },
+        emath::{pos2, vec2, Pos2, Rect},
+    };
+
+    use super::{add_figure_message, Graphics, GraphicsData};
+    use crate::graph::{
+        cursors::RemoteCursors,
+        export::{id_from_string, id_to_string},
+        shapes::{GraphUi, SELECT_MODE_SELECTED},
+        touch::TouchMode,
+        ArrowFigure, CommandStack, ConnectionPoint, FigureKind, GraphFigure, RectFigure, Zoom,
+    };
+    use crate::rgraph::TypedConnectionValidator;
+    use crate::ws::{AddFigure, FigureShape, RequestInfo, WsMessages};
+
+    fn graphics_data() -> GraphicsData {
+        let mut data = GraphicsData::default();
+        for rect in [
+            Rect::from_min_max(pos2(0., 0.), pos2(100., 50.)),
+            Rect::from_min_max(pos2(200., 0.), pos2(300., 50.)),
+            Rect::from_min_max(pos2(0., 200.), pos2(100., 250.)),
+        ] {
+            let id = data.generate_id();
+            let figure = RectFigure::new(id, rect, "".to_owned());
+            data.add_figure(Rc::new(RefCell::new(Box::new(figure))));
+        }
+        data
+    }
+
+    /// Run one frame of the canvas with the input
+    fn run_frame(graphics: &mut Graphics, input: RawInput) {
+        run_frame_with_messages(graphics, input, vec![]);
+    }
+
+    /// Run one frame of the canvas with the input and messages received from other users
+    fn run_frame_with_messages(
+        graphics: &mut Graphics,
+        input: RawInput,
+        messages: Vec<WsMessages>,
+    ) {
+        let incoming = RefCell::new(messages);
+        let _ = Context::default().run(input, |ctx| {
+            CentralPanel::default().show(ctx, |ui| {
+                graphics.ui(ui, incoming.borrow(), &RemoteCursors::default());
+            });
+        });
+    }
+
+    /// Input with pressed keys, modifiers of the last key are held
+    fn key_input(keys: &[(Key, Modifiers)]) -> RawInput {
+        let mut input = RawInput::default();
+        input.events = keys
+            .iter()
+            .map(|(key, modifiers)| Event::Key {
+                key: *key,
+                pressed: true,
+                modifiers: *modifiers,
+            })
+            .collect();
+        input.modifiers = keys
+            .last()
+            .map(|(_, modifiers)| *modifiers)
+            .unwrap_or_default();
+        input
+    }
+
+    /// Input with touch events of the fingers
+    fn touch_input(touches: &[(u64, TouchPhase, Pos2)]) -> RawInput {
+        let mut input = RawInput::default();
+        input.events = touches
+            .iter()
+            .map(|(id, phase, pos)| Event::Touch {
+                device_id: TouchDeviceId(0),
+                id: TouchId(*id),
+                phase: *phase,
+                pos: *pos,
+                force: 0.,
+            })
+            .collect();
+        input
+    }
+
+    #[test]
+    fn test_select_by_rect() {
+        let mut data = graphics_data();
+        let selected = data.select_by_rect(Rect::from_two_pos(pos2(250., 25.), pos2(50., 100.)));
+        assert_eq!(selected, &[0, 1]);
+
+        // Blank area clears selection
+        let selected = data.select_by_rect(Rect::from_two_pos(pos2(400., 400.), pos2(500., 500.)));
+        assert!(selected.is_empty());
+
+        // Rotated figure is selected by its drawn area
+        let id = data.generate_id();
+        let rect = Rect::from_min_max(pos2(0., 400.), pos2(100., 450.));
+        let figure = RectFigure::new(id, rect, "".to_owned()).with_rotation(FRAC_PI_2);
+        data.add_figure(Rc::new(RefCell::new(Box::new(figure))));
+        let selected = data.select_by_rect(Rect::from_two_pos(pos2(60., 460.), pos2(70., 470.)));
+        assert_eq!(selected, &[3]);
+    }
+
+    #[test]
+    fn test_force_directed_layout() {
+        let mut data = graphics_data();
+        let rects = |data: &GraphicsData| -> Vec<Rect> {
+            data.figures
+                .iter()
+                .map(|fig| RefCell::borrow(fig).rect())
+                .collect()
+        };
+        let before = rects(&data);
+
+        data.force_directed_layout(50, 1e6, 1.);
+        let after = rects(&data);
+        assert_ne!(before, after);
+        // Figures are moved without resizing
+        for (before, after) in before.iter().zip(&after) {
+            assert_eq!(before.size(), after.size());
+        }
+        assert_eq!(data.outgoing.len(), 3);
+
+        assert!(data.undo());
+        assert_eq!(rects(&data), before);
+    }
+
+    #[test]
+    fn test_z_order() {
+        let mut data = graphics_data();
+        let ids = |data: &GraphicsData| -> Vec<Id> {
+            data.figures
+                .iter()
+                .map(|fig| RefCell::borrow(fig).id())
+                .collect()
+        };
+        let [a, b, c] = match ids(&data)[..] {
+            [a, b, c] => [a, b, c],
+            _ => unreachable!(),
+        };
+
+        data.bring_to_front(a);
+        assert_eq!(ids(&data), vec![b, c, a]);
+        data.send_backward(a);
+        assert_eq!(ids(&data), vec![b, a, c]);
+        data.send_to_back(c);
+        assert_eq!(ids(&data), vec![c, b, a]);
+        data.bring_forward(c);
+        assert_eq!(ids(&data), vec![b, c, a]);
+        // Top-most figure can not be moved higher
+        data.bring_forward(a);
+        assert_eq!(ids(&data), vec![b, c, a]);
+
+        assert!(data.undo());
+        assert_eq!(ids(&data), vec![c, b, a]);
+        assert!(data.redo());
+        assert_eq!(ids(&data), vec![b, c, a]);
+    }
+
+    #[test]
+    fn test_select_top_most() {
+        let mut data = graphics_data();
+        let id = data.generate_id();
+        let rect = Rect::from_min_max(pos2(50., 25.), pos2(150., 75.));
+        data.add_figure(Rc::new(RefCell::new(Box::new(RectFigure::new(
+            id,
+            rect,
+            "".to_owned(),
+        )))));
+
+        // Point is covered by the first and the last figures
+        data.select_enabled = true;
+        data.select_by_point(pos2(75., 40.));
+        assert_eq!(data.selected_figure_id, Some(id));
+
+        data.send_to_back(id);
+        data.select_by_point(pos2(75., 40.));
+        let first_id = RefCell::borrow(&data.figures[1]).id();
+        assert_eq!(data.selected_figure_id, Some(first_id));
+        assert_eq!(data.selected_figure_index(), Some(1));
+
+        // Figure under cursor is kept when figures are inserted before it
+        let id = data.generate_id();
+        data.figures.insert(
+            0,
+            Rc::new(RefCell::new(Box::new(RectFigure::new(
+                id,
+                rect,
+                "".to_owned(),
+            )))),
+        );
+        assert_eq!(data.selected_figure_index(), Some(2));
+        assert!(Rc::ptr_eq(
+            data.figure_by_id(first_id).unwrap(),
+            &data.figures[2]
+        ));
+    }
+
+    #[test]
+    fn test_move_selected() {
+        let mut data = graphics_data();
+        data.select_by_rect(Rect::from_two_pos(pos2(0., 0.), pos2(50., 300.)));
+        assert!(data.is_multi_move(0, pos2(50., 25.)));
+        assert!(!data.is_multi_move(1, pos2(250., 25.)));
+
+        data.multi_drag_pos = Some(pos2(50., 25.));
+        data.move_selected(pos2(55., 30.));
+        data.move_selected(pos2(60., 35.));
+
+        let rects: Vec<_> = data
+            .figures
+            .iter()
+            .map(|fig| RefCell::borrow(fig).rect())
+            .collect();
+        assert_eq!(
+            rects,
+            vec![
+                Rect::from_min_max(pos2(10., 10.), pos2(110., 60.)),
+                Rect::from_min_max(pos2(200., 0.), pos2(300., 50.)),
+                Rect::from_min_max(pos2(10., 210.), pos2(110., 260.)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_undo_redo_remove_figure() {
+        let mut data = graphics_data();
+        let id = RefCell::borrow(&data.figures[1]).id();
+
+        data.remove_figure(id);
+        assert_eq!(data.figures.len(), 2);
+        assert!(data.undo());
+        assert_eq!(RefCell::borrow(&data.figures[1]).id(), id);
+        assert!(!data.undo());
+
+        assert!(data.redo());
+        assert_eq!(data.figures.len(), 2);
+        assert!(!data.redo());
+
+        assert!(matches!(
+            data.outgoing.as_slice(),
+            [WsMessages::DeleteFigure { figure_id, .. }] if *figure_id == format!("{:?}", id)
+        ));
+    }
+
+    #[test]
+    fn test_move_message() {
+        let mut data = graphics_data();
+        data.select_by_rect(Rect::from_two_pos(pos2(0., 0.), pos2(50., 300.)));
+        let selected = data.selected_figure_indices.clone();
+        data.remember_drag_rects(&selected);
+        data.multi_drag_pos = Some(pos2(50., 25.));
+        data.move_selected(pos2(60., 45.));
+        data.record_drag_commands();
+
+        assert_eq!(data.outgoing.len(), 2);
+        assert!(data.outgoing.iter().all(|msg| matches!(
+            msg,
+            WsMessages::MoveFigure { dx, dy, .. } if *dx == 10. && *dy == 20.
+        )));
+    }
+
+    #[test]
+    fn test_text_update_message() {
+        let mut data = graphics_data();
+        let (id, rect, label) = {
+            let figure = RefCell::borrow(&data.figures[0]);
+            (figure.id(), figure.rect(), figure.label())
+        };
+
+        RefCell::borrow_mut(&data.figures[0]).double_click();
+        data.track_text_edits();
+        assert_eq!(data.edited_texts, vec![(id, label)]);
+
+        // Figure is not edited anymore and has new label
+        data.figures[0] = Rc::new(RefCell::new(Box::new(RectFigure::new(
+            id,
+            rect,
+            "New".to_owned(),
+        ))));
+        data.track_text_edits();
+        assert!(data.edited_texts.is_empty());
+        assert!(matches!(
+            data.outgoing.as_slice(),
+            [WsMessages::UpdateFigureText { figure_id, text, .. }]
+                if *figure_id == format!("{:?}", id) && text == "New"
+        ));
+
+        data.update_figure_text(&format!("{:?}", id), "Remote");
+        assert_eq!(RefCell::borrow(&data.figures[0]).label(), "Remote");
+    }
+
+    #[test]
+    fn test_remote_figure_changes() {
+        let mut data = graphics_data();
+        let figures = data.figures.len();
+
+        // Figure added by other client keeps its identifier, so following changes find it
+        let id = GraphicsData::default().generate_id();
+        assert!(data.figure_by_id(id).is_none());
+        let figure_id = id_to_string(id);
+        let added = AddFigure {
+            rq: RequestInfo::default(),
+            figure_id: figure_id.clone(),
+            shape: FigureShape::Ellipse,
+            rect: Rect::from_min_max(pos2(10., 20.), pos2(110., 70.)),
+            text: "Remote".to_owned(),
+        };
+        data.add_remote_figure(&added);
+        data.add_remote_figure(&added);
+        assert_eq!(data.figures.len(), figures + 1);
+        assert_eq!(id_from_string(&figure_id), Some(id));
+
+        data.move_remote_figure(&figure_id, 10., -5.);
+        data.update_figure_text(&figure_id, "Moved");
+        {
+            let figure = RefCell::borrow(data.figure_by_id(id).unwrap());
+            assert_eq!(
+                figure.rect(),
+                Rect::from_min_max(pos2(20., 15.), pos2(120., 65.))
+            );
+            assert_eq!(figure.label(), "Moved");
+            assert!(matches!(figure.to_kind(), Some(FigureKind::Ellipse(_))));
+
+            // Figure is announced with its shape and label
+            match add_figure_message(&**figure) {
+                WsMessages::AddFigure(message) => {
+                    assert_eq!(message.figure_id, figure_id);
+                    assert_eq!(message.shape, FigureShape::Ellipse);
+                    assert_eq!(message.text, "Moved");
+                }
+                message => panic!("Unexpected message {:?}", message),
+            }
+        }
+
+        // Remote changes are not sent back to other clients
+        data.delete_remote_figure(&figure_id);
+        assert!(data.figure_by_id(id).is_none());
+        assert_eq!(data.figures.len(), figures);
+        assert!(data.outgoing.is_empty());
+        assert!(id_from_string("not an id").is_none());
+    }
+
+    #[test]
+    fn test_zoom_at_pointer() {
+        let mut data = graphics_data();
+        let pointer = pos2(300., 200.);
+
+        // Point under pointer is transformed the same way as figures when they are drawn
+        let mut point = pointer;
+        for zoom_factor in [1.5, 0.8] {
+            let ratio = zoom_factor / data.zoom_factor;
+            data.zoom_at(pointer, zoom_factor);
+            point = point.zoom(ratio) + data.scroll_delta;
+            assert!((point - pointer).length() < 1e-3);
+        }
+        assert_eq!(data.zoom_factor, 0.8);
+    }
+
+    #[test]
+    fn test_snap_to_grid() {
+        let mut data = graphics_data();
+        assert_eq!(data.snap_to_grid(pos2(13., 27.)), pos2(13., 27.));
+
+        data.grid = Some(20.);
+        assert_eq!(data.snap_to_grid(pos2(13., 27.)), pos2(20., 20.));
+
+        // Grid follows zoom
+        data.zoom_factor = 0.5;
+        assert_eq!(data.snap_to_grid(pos2(13., 27.)), pos2(10., 30.));
+        data.zoom_factor = 1.;
+
+        // Dragged figures are moved by the same offset keeping their sizes
+        data.remember_drag_rects(&[0, 1]);
+        RefCell::borrow_mut(&data.figures[0])
+            .set_rect(Rect::from_min_max(pos2(7., 12.), pos2(104., 49.)));
+        RefCell::borrow_mut(&data.figures[1])
+            .set_rect(Rect::from_min_max(pos2(207., 12.), pos2(307., 62.)));
+        data.snap_dragged_figures();
+        assert_eq!(
+            RefCell::borrow(&data.figures[0]).rect(),
+            Rect::from_min_max(pos2(0., 20.), pos2(97., 57.))
+        );
+        assert_eq!(
+            RefCell::borrow(&data.figures[1]).rect(),
+            Rect::from_min_max(pos2(200., 20.), pos2(300., 70.))
+        );
+    }
+
+    #[test]
+    fn test_canvas_drag_start() {
+        // No tool selected, plain drag starts rubber band
+        let mut data = graphics_data();
+        data.start_canvas_drag(pos2(10., 10.), false);
+        assert_eq!(data.rubber_band, Some([pos2(10., 10.), pos2(10., 10.)]));
+        assert!(!data.is_dragged);
+
+        // Tool places figure, shift selects anyway
+        let mut data = graphics_data();
+        data.selected_tool = Some(Box::new(RectFigure::default()));
+        data.start_canvas_drag(pos2(10., 10.), true);
+        assert!(data.rubber_band.is_some());
+        assert!(!data.is_dragged);
+
+        data.rubber_band = None;
+        data.start_canvas_drag(pos2(10., 10.), false);
+        assert!(data.rubber_band.is_none());
+        assert!(data.is_dragged);
+
+        // Tool stays selected after placing figure, so next drag places figure again
+        let figures = data.figures.len();
+        for _ in 0..2 {
+            data.start_canvas_drag(pos2(10., 10.), false);
+            assert!(matches!(
+                data.place_tool_figure(pos2(60., 40.)),
+                Some(WsMessages::AddFigure(_))
+            ));
+            assert!(data.selected_tool.is_some());
+        }
+        assert_eq!(data.figures.len(), figures + 2);
+    }
+
+    #[test]
+    fn test_next_grid() {
+        assert_eq!(Graphics::next_grid(Some(20.)), None);
+        assert_eq!(Graphics::next_grid(None), Some(10.));
+        assert_eq!(Graphics::next_grid(Some(10.)), Some(20.));
+    }
+
+    #[test]
+    fn test_group_undo_redo() {
+        let mut data = graphics_data();
+        let first = Rc::clone(&data.figures[0]);
+        for idx in [0, 2] {
+            RefCell::borrow_mut(&data.figures[idx]).select(SELECT_MODE_SELECTED);
+        }
+
+        let id = data.group_selected().unwrap();
+        assert_eq!(data.figures.len(), 2);
+        assert_eq!(
+            RefCell::borrow(&data.figures[1]).rect(),
+            Rect::from_min_max(pos2(0., 0.), pos2(100., 250.))
+        );
+
+        assert!(data.undo());
+        assert_eq!(data.figures.len(), 3);
+        assert!(Rc::ptr_eq(&data.figures[0], &first));
+
+        assert!(data.redo());
+        assert_eq!(RefCell::borrow(&data.figures[1]).id(), id);
+
+        RefCell::borrow_mut(&data.figures[1]).select(SELECT_MODE_SELECTED);
+        assert!(data.ungroup_selected());
+        assert_eq!(data.figures.len(), 3);
+        assert!(Rc::ptr_eq(&data.figures[1], &first));
+
+        assert!(data.undo());
+        assert_eq!(data.figures.len(), 2);
+        assert_eq!(RefCell::borrow(&data.figures[1]).id(), id);
+    }
+
+    #[test]
+    fn test_delete_key() {
+        let mut graphics = Graphics::default();
+        let mut data = graphics_data();
+        let mut edge = ArrowFigure::new([pos2(0., 0.), pos2(200., 0.)], data.generate_id());
+        edge.connect_start(ConnectionPoint::new(Rc::clone(&data.figures[0]), 0));
+        edge.connect_end(ConnectionPoint::new(Rc::clone(&data.figures[1]), 0));
+        data.edges.push(edge);
+        data.select_by_rect(Rect::from_two_pos(pos2(0., 0.), pos2(50., 300.)));
+        data.select_enabled = true;
+        graphics.graphics_data = data;
+
+        run_frame(
+            &mut graphics,
+            key_input(&[(Key::Delete, Modifiers::default())]),
+        );
+
+        let data = &mut graphics.graphics_data;
+        assert_eq!(data.figures.len(), 1);
+        assert!(data.edges.is_empty());
+        assert!(data.selected_figure_indices.is_empty());
+
+        assert!(data.undo());
+        assert_eq!(data.figures.len(), 3);
+        assert_eq!(data.edges.len(), 1);
+        assert!(!data.undo());
+    }
+
+    #[test]
+    fn test_remove_figure_with_edges() {
+        let mut data = graphics_data();
+        for (start, end) in [(0, 1), (1, 2), (2, 0)] {
+            let mut edge = ArrowFigure::new([pos2(0., 0.), pos2(200., 0.)], data.generate_id());
+            edge.connect_start(ConnectionPoint::new(Rc::clone(&data.figures[start]), 0));
+            edge.connect_end(ConnectionPoint::new(Rc::clone(&data.figures[end]), 0));
+            data.edges.push(edge);
+        }
+
+        let id = RefCell::borrow(&data.figures[1]).id();
+        assert_eq!(data.edges_for_figure(id), vec![0, 1]);
+        assert!(data.edges_for_figure(Id::new(100)).is_empty());
+
+        data.remove_figure(id);
+        assert_eq!(data.figures.len(), 2);
+        assert_eq!(data.edges.len(), 1);
+
+        // Figure and its edges are restored by one undo
+        let edge_ids: Vec<Id> = data.edges.iter().map(|edge| edge.id()).collect();
+        assert!(data.undo());
+        assert_eq!(data.figures.len(), 3);
+        assert_eq!(data.edges.len(), 3);
+        assert_eq!(data.edges[2].id(), edge_ids[0]);
+        assert_eq!(data.edges_for_figure(id), vec![0, 1]);
+
+        assert!(data.redo());
+        assert_eq!(data.edges.len(), 1);
+    }
+
+    #[test]
+    fn test_arrow_key_nudge() {
+        let mut graphics = Graphics::default();
+        let mut data = graphics_data();
+        data.select_by_rect(Rect::from_two_pos(pos2(0., 0.), pos2(50., 300.)));
+        let [first, third] = [0, 2].map(|idx| RefCell::borrow(&data.figures[idx]).rect().min);
+        graphics.graphics_data = data;
+
+        run_frame(
+            &mut graphics,
+            key_input(&[(Key::ArrowRight, Modifiers::default())]),
+        );
+        run_frame(
+            &mut graphics,
+            key_input(&[(Key::ArrowDown, Modifiers::SHIFT)]),
+        );
+
+        // All selected figures are moved, each key press is a separate undo step
+        let data = &mut graphics.graphics_data;
+        let min = |data: &GraphicsData, idx: usize| RefCell::borrow(&data.figures[idx]).rect().min;
+        assert_eq!(min(data, 0), first + vec2(1., 10.));
+        assert_eq!(min(data, 2), third + vec2(1., 10.));
+        assert_eq!(data.outgoing.len(), 4);
+
+        assert!(data.undo());
+        assert_eq!(min(data, 0), first + vec2(1., 0.));
+        assert!(data.undo());
+        assert_eq!(min(data, 2), third);
+    }
+
+    #[test]
+    fn test_touch_gestures() {
+        let mut graphics = Graphics::default();
+
+        // Fingers moved apart zoom in
+        run_frame(
+            &mut graphics,
+            touch_input(&[
+                (1, TouchPhase::Start, pos2(100., 100.)),
+                (2, TouchPhase::Start, pos2(200., 100.)),
+            ]),
+        );
+        run_frame(
+            &mut graphics,
+            touch_input(&[(2, TouchPhase::Move, pos2(300., 100.))]),
+        );
+        assert_eq!(graphics.graphics_data.zoom_factor, 2.);
+
+        // One finger touched blank canvas scrolls
+        run_frame(
+            &mut graphics,
+            touch_input(&[(2, TouchPhase::End, pos2(300., 100.))]),
+        );
+        run_frame(
+            &mut graphics,
+            touch_input(&[(1, TouchPhase::Move, pos2(110., 95.))]),
+        );
+        assert_eq!(graphics.graphics_data.zoom_factor, 2.);
+        assert_eq!(graphics.graphics_data.scroll_delta, vec2(10., -5.));
+        assert!(graphics.graphics_data.touches.is_active());
+        assert_eq!(graphics.graphics_data.touches.mode(), Some(TouchMode::Pan));
+
+        // Finger touched figure drags it instead of scrolling
+        let mut graphics = Graphics::default();
+        graphics.graphics_data = graphics_data();
+        run_frame(
+            &mut graphics,
+            touch_input(&[(1, TouchPhase::Start, pos2(50., 25.))]),
+        );
+        assert_eq!(graphics.graphics_data.touches.mode(), Some(TouchMode::Drag));
+        run_frame(
+            &mut graphics,
+            touch_input(&[(1, TouchPhase::Move, pos2(150., 100.))]),
+        );
+        assert_eq!(graphics.graphics_data.scroll_delta, vec2(0., 0.));
+        run_frame(
+            &mut graphics,
+            touch_input(&[(1, TouchPhase::End, pos2(150., 100.))]),
+        );
+        assert_eq!(graphics.graphics_data.touches.mode(), None);
+    }
+
+    #[test]
+    fn test_read_only() {
+        let mut graphics = Graphics::default();
+        let mut data = graphics_data();
+        data.select_by_rect(Rect::from_two_pos(pos2(0., 0.), pos2(50., 300.)));
+        graphics.graphics_data = data;
+        graphics.set_read_only(true);
+        assert!(graphics.read_only());
+
+        run_frame(
+            &mut graphics,
+            key_input(&[
+                (Key::Delete, Modifiers::default()),
+                (Key::D, Modifiers::COMMAND),
+            ]),
+        );
+
+        // Neither deleted nor duplicated
+        let data = &graphics.graphics_data;
+        assert_eq!(data.figures.len(), 3);
+        assert_eq!(data.selected_figure_indices, [0, 2]);
+        assert!(data.outgoing.is_empty());
+    }
+
+    #[test]
+    fn test_connection_validator() {
+        let mut data = graphics_data();
+        let [first, second, third] = [0, 1, 2].map(|idx| RefCell::borrow(&data.figures[idx]).id());
+        let mut validator = TypedConnectionValidator::new([("service", "database")]);
+        validator.set_type(first, "service");
+        validator.set_type(second, "database");
+        validator.set_type(third, "service");
+        data.connection_validator = Some(Box::new(validator));
+
+        let drag_to = |data: &mut GraphicsData, target: usize| {
+            let mut edge = ArrowFigure::new([pos2(50., 50.), pos2(50., 50.)], data.generate_id());
+            edge.connect_start(ConnectionPoint::new(Rc::clone(&data.figures[0]), 0));
+            data.dragged_edge = Some(edge);
+            data.select_enabled = false;
+            let point = RefCell::borrow(&data.figures[target]).connection_points()[0];
+            data.select_by_point(point);
+            let edge = data.dragged_edge.take().unwrap();
+            (data.selected_by_edge_figure_idx, edge)
+        };
+
+        // Service may be connected to database
+        let (highlighted, edge) = drag_to(&mut data, 1);
+        assert_eq!(highlighted, Some(1));
+        assert!(edge.get_end_connection().is_some());
+        assert!(data.validate_edge(&edge).is_ok());
+
+        // Service can't be connected to another service
+        let (highlighted, edge) = drag_to(&mut data, 2);
+        assert_eq!(highlighted, None);
+        assert!(edge.get_end_connection().is_none());
+
+        let mut edge = edge;
+        edge.connect_end(ConnectionPoint::new(Rc::clone(&data.figures[2]), 0));
+        assert!(data.validate_edge(&edge).is_err());
+    }
+
+    #[test]
+    fn test_double_click_edge() {
+        let mut data = graphics_data();
+        let id = data.generate_id();
+        data.edges
+            .push(ArrowFigure::new([pos2(0., 400.), pos2(200., 400.)], id));
+
+        assert!(!data.double_click_edge(pos2(100., 300.)));
+        assert!(data.double_click_edge(pos2(100., 401.)));
+        assert_eq!(data.edges[0].waypoints(), &[pos2(100., 401.)]);
+
+        // Double click on waypoint removes it
+        assert!(data.double_click_edge(pos2(101., 401.)));
+        assert!(data.edges[0].waypoints().is_empty());
+
+        // Both changes are undone
+        assert!(data.undo());
+        assert_eq!(data.edges[0].waypoints(), &[pos2(100., 401.)]);
+        assert!(data.undo());
+        assert!(data.edges[0].waypoints().is_empty());
+    }
+
+    #[test]
+    fn test_edge_waypoint_drag() {
+        let mut data = graphics_data();
+        let id = data.generate_id();
+        data.edges
+            .push(ArrowFigure::new([pos2(0., 400.), pos2(200., 400.)], id));
+        data.double_click_edge(pos2(100., 400.));
+
+        // Newly added waypoint is dragged
+        assert!(data.start_edge_drag(pos2(101., 400.)));
+        let idx = data.edge_drag_index().unwrap();
+        data.edges[idx].dragged_by(pos2(100., 350.), PointerButton::Primary);
+        data.release_edge_drag(pos2(100., 300.));
+        assert!(data.edge_drag.is_none());
+        assert_eq!(data.edges[0].waypoints(), &[pos2(100., 300.)]);
+
+        assert!(data.undo());
+        assert_eq!(data.edges[0].waypoints(), &[pos2(100., 400.)]);
+        assert!(!data.start_edge_drag(pos2(100., 300.)));
+    }
+
+    #[test]
+    fn test_dragged_edge_points() {
+        let mut data = graphics_data();
+        assert_eq!(data.dragged_edge_points(), (None, None));
+
+        let mut edge = ArrowFigure::new([pos2(50., 50.), pos2(50., 50.)], data.generate_id());
+        edge.connect_start(ConnectionPoint::new(Rc::clone(&data.figures[0]), 0));
+        data.dragged_edge = Some(edge);
+        data.select_enabled = false;
+        let source = RefCell::borrow(&data.figures[0]).connection_points()[0];
+        assert_eq!(data.dragged_edge_points(), (Some(source), None));
+
+        let target = RefCell::borrow(&data.figures[1]).connection_points()[0];
+        data.select_by_point(target);
+        assert_eq!(data.dragged_edge_points(), (Some(source), Some(target)));
+    }
+
+    #[test]
+    fn test_remote_selection() {
+        let mut graphics = Graphics::default();
+        let mut data = graphics_data();
+        data.select_by_rect(Rect::from_two_pos(pos2(0., 0.), pos2(50., 300.)));
+        let ids: Vec<String> = [0, 2]
+            .iter()
+            .map(|idx| id_to_string(RefCell::borrow(&data.figures[*idx]).id()))
+            .collect();
+        data.track_selection();
+        assert!(matches!(
+            data.outgoing.as_slice(),
+            [WsMessages::SelectionChange { selected_ids, .. }] if *selected_ids == ids
+        ));
+
+        // Selection is sent only when it is changed
+        data.outgoing.clear();
+        data.track_selection();
+        assert!(data.outgoing.is_empty());
+        graphics.graphics_data = data;
+
+        run_frame_with_messages(
+            &mut graphics,
+            RawInput::default(),
+            vec![WsMessages::SelectionChange {
+                rq: RequestInfo {
+                    board: "Main".to_owned(),
+                    user: "other".to_owned(),
+                    message_id: None,
+                    page_id: None,
+                },
+                selected_ids: ids.clone(),
+            }],
+        );
+
+        // Own selection is not changed by selection of another user
+        let data = &mut graphics.graphics_data;
+        assert_eq!(data.remote_selections.get("other"), Some(&ids));
+        assert_eq!(data.selected_figure_indices, [0, 2]);
+
+        data.update_remote_selection("other", &[]);
+        assert!(data.remote_selections.is_empty());
+    }
+
+    #[test]
+    fn test_default_is_new() {
+        let default = Graphics::default();
+        let new = Graphics::new();
+        assert_eq!(default.statistics(), new.statistics());
+        assert_eq!(default.grid(), new.grid());
+        assert_eq!(default.read_only(), new.read_only());
+        assert_eq!(default.snap_distance(), new.snap_distance());
+        assert_eq!(
+            default.graphics_data.zoom_factor,
+            new.graphics_data.zoom_factor
+        );
+        assert_eq!(
+            default.graphics_data.scroll_delta,
+            new.graphics_data.scroll_delta
+        );
     }
 }
@@ -0,0 +1,234 @@
+use std::{cell::RefCell, rc::Rc};
+
+use eframe::{
+    egui::{CursorIcon, Id, PointerButton, Ui},
+    emath::{Pos2, Rect, Vec2},
+    epaint::{Color32, Rounding, Stroke},
+};
+
+use crate::rgraph::CanvasCommand;
+
+use super::{
+    persist::FigureKind,
+    shapes::{SelectMode, SELECT_MODE_NONE, SELECT_MODE_SELECTED},
+    GraphFigure,
+};
+
+/// Group of figures. All group members are moved and drawn together
+pub struct GroupFigure {
+    id: Id,
+    members: Vec<Rc<RefCell<Box<dyn GraphFigure>>>>,
+    selected: SelectMode,
+    /// Last drag position
+    drag_pos: Option<Pos2>,
+    /// Group has no own connection points, edges are connected to members
+    connection_points: Vec<Pos2>,
+    stroke: Stroke,
+}
+
+impl std::fmt::Debug for GroupFigure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GroupFigure")
+            .field("id", &self.id)
+            .field("members", &self.members.len())
+            .field("selected", &self.selected)
+            .finish()
+    }
+}
+
+impl GroupFigure {
+    /// Construct new group from figures
+    pub fn new(id: Id, members: Vec<Rc<RefCell<Box<dyn GraphFigure>>>>) -> Self {
+        Self {
+            id,
+            members,
+            selected: SELECT_MODE_NONE,
+            drag_pos: None,
+            connection_points: vec![],
+            stroke: Stroke::new(1., Color32::LIGHT_GRAY),
+        }
+    }
+
+    /// Return group members
+    pub fn members(&self) -> &Vec<Rc<RefCell<Box<dyn GraphFigure>>>> {
+        &self.members
+    }
+
+    /// Replace group members
+    pub fn set_members(&mut self, members: Vec<Rc<RefCell<Box<dyn GraphFigure>>>>) {
+        self.members = members;
+    }
+}
+
+impl GraphFigure for GroupFigure {
+    /// Members are cloned too, so the copy does not share figures with the original group
+    fn clone_box(&self) -> Box<dyn GraphFigure> {
+        Box::new(Self {
+            id: self.id,
+            members: self
+                .members
+                .iter()
+                .map(|member| Rc::new(RefCell::new(member.borrow().clone_box())))
+                .collect(),
+            selected: self.selected,
+            drag_pos: None,
+            connection_points: self.connection_points.clone(),
+            stroke: self.stroke,
+        })
+    }
+
+    fn set_id(&mut self, id: Id) {
+        self.id = id;
+    }
+
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn draw(&mut self, ui: &mut Ui, zoom_factor: f32, scroll_delta: Vec2) {
+        for member in &self.members {
+            member.borrow_mut().draw(ui, zoom_factor, scroll_delta);
+        }
+
+        if self.selected & SELECT_MODE_SELECTED > 0 {
+            ui.painter()
+                .rect_stroke(self.rect().expand(4.), Rounding::none(), self.stroke);
+        }
+    }
+
+    fn select(&mut self, selected: SelectMode) {
+        self.selected = selected;
+    }
+
+    fn contains(&self, point: Pos2) -> Option<CursorIcon> {
+        if self
+            .members
+            .iter()
+            .any(|member| member.borrow().rect().contains(point))
+        {
+            Some(CursorIcon::Default)
+        } else {
+            None
+        }
+    }
+
+    fn selected(&self) -> SelectMode {
+        self.selected
+    }
+
+    fn move_to(&mut self, pos: Pos2, drag_started: Pos2) {
+        for member in &self.members {
+            member.borrow_mut().move_to(pos, drag_started);
+        }
+    }
+
+    fn drag_start(&mut self, hover_pos: Pos2, _button: PointerButton, _zoom_factor: f32) {
+        self.drag_pos = Some(hover_pos);
+    }
+
+    fn dragged_by(&mut self, hover_pos: Pos2, _button: PointerButton) {
+        if let Some(drag_pos) = self.drag_pos {
+            self.move_to(hover_pos, drag_pos);
+            self.drag_pos = Some(hover_pos);
+        }
+    }
+
+    fn drag_released(&mut self, hover_pos: Pos2, button: PointerButton) {
+        self.dragged_by(hover_pos, button);
+        self.drag_pos = None;
+    }
+
+    fn double_click(&mut self) {}
+
+    fn rect(&self) -> Rect {
+        self.members
+            .iter()
+            .map(|member| member.borrow().rect())
+            .reduce(|acc, rect| acc.union(rect))
+            .unwrap_or(Rect::NOTHING)
+    }
+
+    fn bounding_rect(&self) -> Rect {
+        self.members
+            .iter()
+            .map(|member| member.borrow().bounding_rect())
+            .reduce(|acc, rect| acc.union(rect))
+            .unwrap_or(Rect::NOTHING)
+    }
+
+    fn connection_points(&self) -> &Vec<Pos2> {
+        &self.connection_points
+    }
+
+    fn type_name(&self) -> &'static str {
+        "group"
+    }
+
+    fn to_canvas_commands(&self) -> Vec<CanvasCommand> {
+        self.members
+            .iter()
+            .flat_map(|member| member.borrow().to_canvas_commands())
+            .collect()
+    }
+
+    fn as_group(&self) -> Option<&GroupFigure> {
+        Some(self)
+    }
+
+    fn as_group_mut(&mut self) -> Option<&mut GroupFigure> {
+        Some(self)
+    }
+
+    fn to_kind(&self) -> Option<FigureKind> {
+        Some(FigureKind::Group {
+            id: self.id,
+            members: self
+                .members
+                .iter()
+                .filter_map(|member| member.borrow().to_kind())
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use eframe::{
+        egui::{Id, PointerButton},
+        emath::{pos2, Pos2},
+    };
+
+    use super::GroupFigure;
+    use crate::graph::{GraphFigure, RectFigure};
+
+    fn rect_figure(start: Pos2, end: Pos2) -> Rc<RefCell<Box<dyn GraphFigure>>> {
+        let mut figure = RectFigure::default();
+        figure.drag_start(start, PointerButton::Primary, 1.);
+        figure.drag_released(end, PointerButton::Primary);
+        Rc::new(RefCell::new(Box::new(figure)))
+    }
+
+    #[test]
+    fn test_group_rect_and_move() {
+        let mut group = GroupFigure::new(
+            Id::new(1),
+            vec![
+                rect_figure(pos2(0., 0.), pos2(10., 10.)),
+                rect_figure(pos2(20., 20.), pos2(30., 40.)),
+            ],
+        );
+
+        assert_eq!(group.rect().min, pos2(0., 0.));
+        assert_eq!(group.rect().max, pos2(30., 40.));
+        assert!(group.contains(pos2(5., 5.)).is_some());
+        assert!(group.contains(pos2(15., 15.)).is_none());
+
+        group.drag_start(pos2(5., 5.), PointerButton::Primary, 1.);
+        group.dragged_by(pos2(10., 5.), PointerButton::Primary);
+        group.drag_released(pos2(15., 5.), PointerButton::Primary);
+        assert_eq!(group.rect().min, pos2(10., 0.));
+        assert_eq!(group.rect().max, pos2(40., 40.));
+    }
+}
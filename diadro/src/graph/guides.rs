@@ -0,0 +1,220 @@
+use std::cell::RefCell;
+
+use eframe::{
+    egui::{Id, Painter},
+    emath::{pos2, Rect, Vec2},
+    epaint::{Color32, Stroke},
+};
+
+use super::{graphics::GraphicsData, Graphics};
+
+/// Default distance at which dragged figure is snapped to other figures
+pub const DEFAULT_SNAP_DISTANCE: f32 = 5.;
+
+/// Color of the alignment guides
+const GUIDE_COLOR: Color32 = Color32::from_rgb(255, 0, 255);
+
+/// Alignment line shown while figure is dragged
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Guide {
+    /// Vertical line at x coordinate
+    Vertical(f32),
+    /// Horizontal line at y coordinate
+    Horizontal(f32),
+}
+
+/// Find the nearest alignment of the given coordinates with other coordinates
+/// ### Arguments
+/// * coords - edges and center of the dragged figure along one axis
+/// * others - edges and centers of other figures along the same axis
+/// * snap_distance - maximum distance of aligned coordinates
+/// ### Return
+/// offset of the dragged figure and aligned coordinate
+fn nearest_alignment(coords: [f32; 3], others: &[f32], snap_distance: f32) -> Option<(f32, f32)> {
+    coords
+        .iter()
+        .flat_map(|coord| others.iter().map(move |other| (other - coord, *other)))
+        .filter(|(offset, _)| offset.abs() <= snap_distance)
+        .min_by(|(a, _), (b, _)| {
+            a.abs()
+                .partial_cmp(&b.abs())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+}
+
+/// Compute offset aligning the rectangle edges or center with edges or centers of other rectangles
+/// ### Arguments
+/// * rect - rectangle of the dragged figure
+/// * others - rectangles of the other figures
+/// * snap_distance - maximum distance of aligned coordinates
+/// ### Return
+/// offset of the dragged figure and guides of the found alignments
+pub fn alignment_guides(rect: Rect, others: &[Rect], snap_distance: f32) -> (Vec2, Vec<Guide>) {
+    let xs: Vec<f32> = others
+        .iter()
+        .flat_map(|other| [other.left(), other.center().x, other.right()])
+        .collect();
+    let ys: Vec<f32> = others
+        .iter()
+        .flat_map(|other| [other.top(), other.center().y, other.bottom()])
+        .collect();
+
+    let mut offset = Vec2::ZERO;
+    let mut guides = vec![];
+    if let Some((dx, x)) = nearest_alignment(
+        [rect.left(), rect.center().x, rect.right()],
+        &xs,
+        snap_distance,
+    ) {
+        offset.x = dx;
+        guides.push(Guide::Vertical(x));
+    }
+
+    if let Some((dy, y)) = nearest_alignment(
+        [rect.top(), rect.center().y, rect.bottom()],
+        &ys,
+        snap_distance,
+    ) {
+        offset.y = dy;
+        guides.push(Guide::Horizontal(y));
+    }
+
+    (offset, guides)
+}
+
+/// Implies alignment guides of the dragged figure
+impl GraphicsData {
+    /// Snap figure moved by dragging to edges and centers of other figures. Offset of the
+    /// previous snap is removed first, so figure follows the cursor when it leaves snap distance.
+    /// Resized and rotated figures are not snapped
+    /// ### Arguments
+    /// * id - identifier of the dragged figure
+    pub(super) fn snap_to_guides(&mut self, id: Id) {
+        let figure = match self.figure_by_id(id) {
+            Some(figure) => figure.clone(),
+            None => return,
+        };
+
+        let rect = RefCell::borrow(&figure).rect().translate(-self.snap_offset);
+        let not_moved = self.drag_rects.iter().any(|(drag_id, start)| {
+            *drag_id == id && (start.size() != rect.size() || *start == rect)
+        });
+        if not_moved {
+            self.clear_guides();
+            return;
+        }
+
+        let others: Vec<Rect> = self
+            .figures
+            .iter()
+            .map(|fig| RefCell::borrow(fig))
+            .filter(|fig| fig.id() != id)
+            .map(|fig| fig.rect())
+            .collect();
+
+        let (offset, guides) = alignment_guides(rect, &others, self.snap_distance);
+        RefCell::borrow_mut(&figure).set_rect(rect.translate(offset));
+        self.snap_offset = offset;
+        self.guides = guides;
+    }
+
+    /// Remove guides when dragging is finished
+    pub(super) fn clear_guides(&mut self) {
+        self.guides.clear();
+        self.snap_offset = Vec2::ZERO;
+    }
+
+    /// Draw guides across the whole canvas
+    pub(super) fn draw_guides(&self, painter: &Painter) {
+        let viewport = self.viewport;
+        let stroke = Stroke::new(1., GUIDE_COLOR);
+        for guide in &self.guides {
+            let points = match *guide {
+                Guide::Vertical(x) => [pos2(x, viewport.top()), pos2(x, viewport.bottom())],
+                Guide::Horizontal(y) => [pos2(viewport.left(), y), pos2(viewport.right(), y)],
+            };
+            painter.line_segment(points, stroke);
+        }
+    }
+}
+
+/// Implies alignment guides settings
+impl Graphics {
+    /// Set distance at which dragged figure is snapped to other figures, 0 disables snapping
+    pub fn set_snap_distance(&mut self, snap_distance: f32) {
+        self.graphics_data.snap_distance = snap_distance.max(0.);
+    }
+
+    /// Return distance at which dragged figure is snapped to other figures
+    pub fn snap_distance(&self) -> f32 {
+        self.graphics_data.snap_distance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use eframe::{
+        egui::Id,
+        emath::{pos2, vec2, Rect},
+    };
+
+    use super::{alignment_guides, Guide, DEFAULT_SNAP_DISTANCE};
+    use crate::graph::{graphics::GraphicsData, shapes::GraphUi, GraphFigure, RectFigure};
+
+    #[test]
+    fn test_alignment_guides() {
+        let others = [Rect::from_min_max(pos2(0., 0.), pos2(100., 50.))];
+
+        // Left edge is near the right edge
+        let rect = Rect::from_min_max(pos2(103., 60.), pos2(153., 90.));
+        let (offset, guides) = alignment_guides(rect, &others, DEFAULT_SNAP_DISTANCE);
+        assert_eq!(offset, vec2(-3., 0.));
+        assert_eq!(guides, vec![Guide::Vertical(100.)]);
+
+        // Center is near the center
+        let rect = Rect::from_min_max(pos2(200., 12.), pos2(250., 42.));
+        let (offset, guides) = alignment_guides(rect, &others, DEFAULT_SNAP_DISTANCE);
+        assert_eq!(offset, vec2(0., -2.));
+        assert_eq!(guides, vec![Guide::Horizontal(25.)]);
+
+        let rect = Rect::from_min_max(pos2(200., 100.), pos2(250., 130.));
+        assert_eq!(
+            alignment_guides(rect, &others, DEFAULT_SNAP_DISTANCE),
+            (vec2(0., 0.), vec![])
+        );
+    }
+
+    #[test]
+    fn test_snap_to_guides() {
+        let mut data = GraphicsData::default();
+        for (id, rect) in [
+            (1, Rect::from_min_max(pos2(0., 0.), pos2(100., 50.))),
+            (2, Rect::from_min_max(pos2(200., 100.), pos2(300., 150.))),
+        ] {
+            data.add_figure(Rc::new(RefCell::new(Box::new(RectFigure::new(
+                Id::new(id),
+                rect,
+                "".to_owned(),
+            )))));
+        }
+        data.drag_rects = vec![(Id::new(2), data.figures[1].borrow().rect())];
+
+        // Top edge is snapped to the bottom edge of the first figure
+        let figure = Rc::clone(&data.figures[1]);
+        figure.borrow_mut().move_to(pos2(0., -47.), pos2(0., 0.));
+        data.snap_to_guides(Id::new(2));
+        assert_eq!(figure.borrow().rect().top(), 50.);
+        assert_eq!(data.guides, vec![Guide::Horizontal(50.)]);
+
+        // Figure leaves snap distance, previous snap offset is not kept
+        figure.borrow_mut().move_to(pos2(0., 20.), pos2(0., 0.));
+        data.snap_to_guides(Id::new(2));
+        assert_eq!(figure.borrow().rect().top(), 73.);
+        assert!(data.guides.is_empty());
+
+        data.clear_guides();
+        assert_eq!(data.snap_offset, vec2(0., 0.));
+    }
+}
@@ -0,0 +1,413 @@
+use std::{cell::RefCell, rc::Rc};
+
+use eframe::{
+    egui::{ColorImage, Context, CursorIcon, DroppedFile, Id, PointerButton, TextureHandle, Ui},
+    emath::pos2,
+    epaint::{Color32, Pos2, Rect, Rounding, Vec2},
+};
+use serde::{Deserialize, Serialize};
+
+use super::graphics::GraphicsData;
+use super::persist::FigureKind;
+use super::shapes::{
+    draw_selection_border, resize_direction, zoom_rect, FigureBasics, GraphUi, SelectMode,
+    SELECT_MODE_NONE, SELECT_MODE_SELECTED,
+};
+use super::{DragMode, GraphFigure};
+
+/// Tolerance to detect cursor over the border
+const MARGIN: f32 = 10.;
+
+/// Raster image placed on the canvas. Image is stored in its original encoding
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageFigure {
+    id: Id,
+    rect: Rect,
+    fb: FigureBasics,
+    /// Encoded image, serialized as base64 string
+    #[serde(with = "base64_bytes")]
+    bytes: Vec<u8>,
+    /// Image uploaded to egui, uploaded on first drawing
+    #[serde(skip)]
+    image: Option<TextureHandle>,
+    /// Decoded image waiting to be uploaded
+    #[serde(skip)]
+    decoded: Option<ColorImage>,
+    /// Image can not be decoded, used to report error once
+    #[serde(skip)]
+    broken: bool,
+    #[serde(skip)]
+    selected: SelectMode,
+    zoom_factor: f32,
+    scroll_delta: Vec2,
+    #[serde(skip)]
+    drag_mode: DragMode,
+    #[serde(skip)]
+    connection_points: Vec<Pos2>,
+    /// Offset of the selection border dash pattern
+    #[serde(skip)]
+    selection_offset: f32,
+}
+
+impl Default for ImageFigure {
+    fn default() -> Self {
+        Self {
+            id: Id::new(1),
+            rect: Rect {
+                min: Pos2::ZERO,
+                max: Pos2::ZERO,
+            },
+            fb: Default::default(),
+            bytes: vec![],
+            image: None,
+            decoded: None,
+            broken: false,
+            selected: SELECT_MODE_NONE,
+            zoom_factor: 1.0,
+            scroll_delta: Vec2::ZERO,
+            drag_mode: DragMode::Move(Pos2::ZERO),
+            connection_points: Default::default(),
+            selection_offset: 0.,
+        }
+    }
+}
+
+/// Serialize bytes as base64 string
+mod base64_bytes {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        base64::decode(text).map_err(D::Error::custom)
+    }
+}
+
+/// Decode PNG or JPEG image
+fn decode(bytes: &[u8]) -> image::ImageResult<ColorImage> {
+    let image = image::load_from_memory(bytes)?.to_rgba8();
+    let size = [image.width() as usize, image.height() as usize];
+    Ok(ColorImage::from_rgba_unmultiplied(size, image.as_raw()))
+}
+
+impl GraphFigure for ImageFigure {
+    fn clone_box(&self) -> Box<dyn GraphFigure> {
+        Box::new(self.clone())
+    }
+
+    fn set_id(&mut self, id: Id) {
+        self.id = id;
+    }
+
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn draw(&mut self, ui: &mut Ui, zoom_factor: f32, scroll_delta: Vec2) {
+        self.zoom(zoom_factor, scroll_delta);
+
+        match self.texture(ui.ctx()).map(|texture| texture.id()) {
+            Some(texture_id) => {
+                let uv = Rect::from_min_max(Pos2::ZERO, pos2(1., 1.));
+                ui.painter()
+                    .image(texture_id, self.rect, uv, Color32::WHITE);
+            }
+            None => {
+                ui.painter()
+                    .rect_stroke(self.rect, Rounding::none(), self.fb.stroke);
+            }
+        }
+
+        if self.selected & SELECT_MODE_SELECTED > 0 {
+            self.draw_selection_border(ui);
+        }
+    }
+
+    fn select(&mut self, selected: SelectMode) {
+        self.selected = selected;
+    }
+
+    fn contains(&self, point: Pos2) -> Option<CursorIcon> {
+        if let Some((icon, _)) = resize_direction(self.rect, point, MARGIN) {
+            return Some(icon);
+        }
+
+        if self.rect.contains(point) {
+            Some(CursorIcon::Default)
+        } else {
+            None
+        }
+    }
+
+    fn selected(&self) -> SelectMode {
+        self.selected
+    }
+
+    fn move_to(&mut self, pos: Pos2, drag_started: Pos2) {
+        let offset = pos - drag_started;
+        self.rect = self.rect.translate(offset);
+        for point in &mut self.connection_points {
+            *point += offset;
+        }
+    }
+
+    fn drag_start(&mut self, hover_pos: Pos2, _button: PointerButton, zoom_factor: f32) {
+        self.drag_mode = match resize_direction(self.rect, hover_pos, MARGIN) {
+            Some((_, drag_mode)) => drag_mode,
+            None => DragMode::Move(hover_pos),
+        };
+        self.zoom_factor = zoom_factor;
+    }
+
+    fn dragged_by(&mut self, hover_pos: Pos2, _button: PointerButton) {
+        match self.drag_mode {
+            DragMode::Move(drag_started) => {
+                self.move_to(hover_pos, drag_started);
+                self.drag_mode = DragMode::Move(hover_pos);
+            }
+            drag_mode => {
+                drag_mode.resize(&mut self.rect, hover_pos);
+                self.compute_connection_points();
+            }
+        }
+    }
+
+    fn drag_released(&mut self, hover_pos: Pos2, button: PointerButton) {
+        self.dragged_by(hover_pos, button);
+    }
+
+    fn double_click(&mut self) {}
+
+    fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    fn set_rect(&mut self, rect: Rect) {
+        self.rect = rect;
+        self.compute_connection_points();
+    }
+
+    fn connection_points(&self) -> &Vec<Pos2> {
+        &self.connection_points
+    }
+
+    fn type_name(&self) -> &'static str {
+        "image"
+    }
+
+    fn set_selection_offset(&mut self, offset: f32) {
+        self.selection_offset = offset;
+    }
+
+    fn to_kind(&self) -> Option<FigureKind> {
+        Some(FigureKind::Image(self.clone()))
+    }
+}
+
+impl ImageFigure {
+    /// Construct figure of the image size
+    /// ### Arguments
+    /// * id - figure identifier
+    /// * pos - left top corner of the figure
+    /// * bytes - PNG or JPEG encoded image
+    /// ### Return
+    /// figure or error if image can not be decoded
+    pub fn from_bytes(id: Id, pos: Pos2, bytes: Vec<u8>) -> image::ImageResult<Self> {
+        let decoded = decode(&bytes)?;
+        let size = Vec2::new(decoded.size[0] as f32, decoded.size[1] as f32);
+        let mut figure = Self {
+            id,
+            rect: Rect::from_min_size(pos, size),
+            bytes,
+            decoded: Some(decoded),
+            ..Default::default()
+        };
+        figure.compute_connection_points();
+        Ok(figure)
+    }
+
+    /// Return image uploaded to egui. Image is decoded and uploaded on the first call
+    fn texture(&mut self, ctx: &Context) -> Option<&TextureHandle> {
+        if self.image.is_none() && !self.broken {
+            let decoded = match self.decoded.take() {
+                Some(decoded) => Ok(decoded),
+                None => decode(&self.bytes),
+            };
+
+            match decoded {
+                Ok(decoded) => {
+                    self.image = Some(ctx.load_texture(format!("image-{:?}", self.id), decoded))
+                }
+                Err(err) => {
+                    tracing::error!("Error decoding image: {}", err);
+                    self.broken = true;
+                }
+            }
+        }
+
+        self.image.as_ref()
+    }
+
+    fn zoom(&mut self, zoom_factor: f32, scroll_delta: Vec2) {
+        self.rect = zoom_rect(
+            self.rect,
+            (self.zoom_factor, self.scroll_delta),
+            (zoom_factor, scroll_delta),
+        );
+        self.zoom_factor = zoom_factor;
+        self.scroll_delta = scroll_delta;
+        self.compute_connection_points();
+    }
+
+    /// Connection points are the middles of the sides: top, right, bottom, left
+    fn compute_connection_points(&mut self) {
+        if self.rect.size() != Vec2::ZERO {
+            self.connection_points = vec![
+                self.rect.center_top(),
+                self.rect.right_center(),
+                self.rect.center_bottom(),
+                self.rect.left_center(),
+            ];
+        }
+    }
+
+    /// Draw animated dashed border around selected figure
+    fn draw_selection_border(&self, ui: &mut Ui) {
+        let rect = self.rect.expand(3.);
+        let border = [
+            rect.left_top(),
+            rect.right_top(),
+            rect.right_bottom(),
+            rect.left_bottom(),
+            rect.left_top(),
+        ];
+
+        draw_selection_border(ui, &border, self.selection_offset, self.fb.selected_stroke);
+    }
+}
+
+/// Return content of the dropped file. Web browsers provide the content,
+/// desktop application reads the file
+fn dropped_bytes(file: &DroppedFile) -> Option<Vec<u8>> {
+    file.bytes.as_ref().map(|bytes| bytes.to_vec()).or_else(|| {
+        file.path.as_ref().and_then(|path| {
+            std::fs::read(path)
+                .map_err(|err| tracing::error!("Error reading {}: {}", path.display(), err))
+                .ok()
+        })
+    })
+}
+
+/// Implies adding images dropped onto canvas
+impl GraphicsData {
+    /// Add dropped images, files which are not images are skipped
+    /// ### Arguments
+    /// * files - dropped files
+    /// * pos - drop position, left top corner of the images
+    pub(super) fn drop_images(&mut self, files: &[DroppedFile], pos: Pos2) {
+        let mut figures = vec![];
+        for file in files {
+            let bytes = match dropped_bytes(file) {
+                Some(bytes) => bytes,
+                None => continue,
+            };
+
+            let id = self.generate_id();
+            match ImageFigure::from_bytes(id, pos, bytes) {
+                Ok(figure) => {
+                    let figure: Box<dyn GraphFigure> = Box::new(figure);
+                    figures.push(Rc::new(RefCell::new(figure)));
+                }
+                Err(err) => tracing::warn!("Dropped file {} is not an image: {}", file.name, err),
+            }
+        }
+
+        self.add_new_figures(figures);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, sync::Arc};
+
+    use eframe::{
+        egui::{CursorIcon, DroppedFile, Id, PointerButton},
+        emath::{pos2, vec2, Rect},
+    };
+
+    use super::ImageFigure;
+    use crate::graph::{graphics::GraphicsData, GraphFigure};
+
+    /// Transparent 1x1 PNG image
+    const PIXEL_PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+
+    #[test]
+    fn test_image_serialization() {
+        let bytes = base64::decode(PIXEL_PNG).unwrap();
+        let figure = ImageFigure::from_bytes(Id::new(1), pos2(10., 20.), bytes.clone()).unwrap();
+        assert_eq!(
+            figure.rect(),
+            Rect::from_min_max(pos2(10., 20.), pos2(11., 21.))
+        );
+        assert!(figure.contains(pos2(10.5, 20.5)).is_some());
+
+        let json = serde_json::to_value(&figure).unwrap();
+        assert_eq!(json["bytes"], PIXEL_PNG);
+        let restored: ImageFigure = serde_json::from_value(json).unwrap();
+        assert_eq!(restored.bytes, bytes);
+        assert!(ImageFigure::from_bytes(Id::new(2), pos2(0., 0.), vec![1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn test_image_resize() {
+        let bytes = base64::decode(PIXEL_PNG).unwrap();
+        let mut figure = ImageFigure::from_bytes(Id::new(1), pos2(0., 0.), bytes).unwrap();
+        figure.set_rect(Rect::from_min_size(pos2(0., 0.), vec2(100., 50.)));
+        assert_eq!(
+            figure.contains(pos2(100., 50.)),
+            Some(CursorIcon::ResizeNwSe)
+        );
+        assert_eq!(figure.contains(pos2(50., 25.)), Some(CursorIcon::Default));
+
+        figure.drag_start(pos2(100., 50.), PointerButton::Primary, 1.);
+        figure.drag_released(pos2(150., 80.), PointerButton::Primary);
+        assert_eq!(
+            figure.rect(),
+            Rect::from_min_max(pos2(0., 0.), pos2(150., 80.))
+        );
+        assert_eq!(figure.connection_points()[1], pos2(150., 40.));
+
+        figure.drag_start(pos2(50., 25.), PointerButton::Primary, 1.);
+        figure.drag_released(pos2(60., 35.), PointerButton::Primary);
+        assert_eq!(
+            figure.rect(),
+            Rect::from_min_max(pos2(10., 10.), pos2(160., 90.))
+        );
+    }
+
+    #[test]
+    fn test_drop_images() {
+        let mut data = GraphicsData::default();
+        let files = [
+            DroppedFile {
+                name: "pixel.png".to_owned(),
+                bytes: Some(Arc::from(base64::decode(PIXEL_PNG).unwrap())),
+                ..Default::default()
+            },
+            DroppedFile {
+                name: "notes.txt".to_owned(),
+                bytes: Some(Arc::from(b"notes".to_vec())),
+                ..Default::default()
+            },
+        ];
+
+        data.drop_images(&files, pos2(100., 100.));
+        assert_eq!(data.figures.len(), 1);
+        assert_eq!(RefCell::borrow(&data.figures[0]).type_name(), "image");
+        assert_eq!(data.outgoing.len(), 1);
+    }
+}
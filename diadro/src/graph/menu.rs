@@ -0,0 +1,553 @@
+use std::{cell::RefCell, rc::Rc};
+
+use eframe::{
+    egui::{
+        Button, ComboBox, Context, DragValue, FontFamily, Grid, Id, Response, TextEdit, Ui, Window,
+    },
+    emath::{Pos2, Rect, Vec2},
+};
+
+use super::{
+    commands::{Command, CommandStack},
+    graphics::{add_figure_message, GraphicsData},
+    shapes::{GraphUi, SELECT_MODE_SELECTED},
+    CylinderFigure, EllipseFigure, GraphFigure, RectFigure, SizeMode,
+};
+
+/// Size of the figure added from context menu
+const NEW_FIGURE_SIZE: Vec2 = Vec2::new(100., 50.);
+
+type FigureRef = Rc<RefCell<Box<dyn GraphFigure>>>;
+
+/// Name of the font family shown in properties panel
+fn family_name(family: &FontFamily) -> String {
+    match family {
+        FontFamily::Proportional => "Proportional".to_owned(),
+        FontFamily::Monospace => "Monospace".to_owned(),
+        FontFamily::Name(name) => name.to_string(),
+    }
+}
+
+/// Implies context menu actions
+impl GraphicsData {
+    /// Add figures created by user action. Command is stored in history and figures are sent
+    /// to other clients
+    pub(super) fn add_new_figures(&mut self, figures: Vec<FigureRef>) {
+        let mut commands = vec![];
+        for figure in figures {
+            self.outgoing
+                .push(add_figure_message(&**RefCell::borrow(&figure)));
+            self.add_figure(Rc::clone(&figure));
+            commands.push(Command::AddFigure(figure));
+        }
+
+        match commands.len() {
+            0 => {}
+            1 => self.push_command(commands.remove(0)),
+            _ => self.push_command(Command::Batch(commands)),
+        }
+    }
+
+    /// Remove figure, command is stored in history
+    pub fn delete_figure(&mut self, id: Id) {
+        self.remove_figure(id);
+        self.selected_figure_id = None;
+        if self.properties_figure == Some(id) {
+            self.properties_figure = None;
+        }
+    }
+
+    /// Give new identifiers to the figure and members of the group
+    fn renew_ids(&mut self, figure: &mut dyn GraphFigure) {
+        figure.set_id(self.generate_id());
+        if let Some(group) = figure.as_group_mut() {
+            for member in group.members() {
+                self.renew_ids(RefCell::borrow_mut(member).as_mut());
+            }
+        }
+    }
+
+    /// Add copy of the figure shifted by `paste_offset`. The copy becomes the only selected figure
+    /// ### Return
+    /// identifier of the copy or None if figure is not found
+    pub fn duplicate_figure(&mut self, id: Id) -> Option<Id> {
+        let mut figure = RefCell::borrow(self.figure_by_id(id)?).clone_box();
+        self.renew_ids(figure.as_mut());
+        let min = figure.rect().min;
+        figure.move_to(min + self.paste_offset, min);
+
+        let copy_id = figure.id();
+        let figure = Rc::new(RefCell::new(figure));
+        self.add_new_figures(vec![Rc::clone(&figure)]);
+
+        self.clear_selection();
+        let selected = RefCell::borrow(&figure).selected();
+        RefCell::borrow_mut(&figure).select(selected | SELECT_MODE_SELECTED);
+        self.update_selected_indices();
+        Some(copy_id)
+    }
+
+    /// Duplicate figure if exactly one figure is selected
+    pub(super) fn duplicate_selected(&mut self) {
+        if let [idx] = self.selected_figure_indices[..] {
+            let id = RefCell::borrow(&self.figures[idx]).id();
+            self.duplicate_figure(id);
+        }
+    }
+
+    /// Put figure into the clipboard
+    pub fn copy_figure(&mut self, id: Id) {
+        if let Some(kind) = self
+            .figure_by_id(id)
+            .and_then(|fig| RefCell::borrow(fig).to_kind())
+        {
+            self.clipboard = vec![kind];
+        }
+    }
+
+    /// Add copies of the clipboard figures. Figures keep their relative positions,
+    /// left top corner of their bounding rectangle is placed at the given position
+    pub fn paste(&mut self, pos: Pos2) {
+        let clipboard = self.clipboard.clone();
+        let figures: Vec<FigureRef> = clipboard
+            .iter()
+            .map(|kind| kind.instantiate(self))
+            .collect();
+        let bounds = figures.iter().fold(Rect::NOTHING, |bounds, fig| {
+            bounds.union(RefCell::borrow(fig).rect())
+        });
+        for figure in &figures {
+            RefCell::borrow_mut(figure).move_to(pos, bounds.min);
+        }
+
+        self.add_new_figures(figures);
+    }
+
+    /// Select all figures of the current level
+    pub fn select_all(&mut self) {
+        for fig in &self.figures {
+            let selected = RefCell::borrow(fig).selected();
+            RefCell::borrow_mut(fig).select(selected | SELECT_MODE_SELECTED);
+        }
+        self.update_selected_indices();
+    }
+
+    /// Start editing text of the figure
+    pub fn edit_text(&mut self, id: Id) {
+        if let Some(figure) = self.figure_by_id(id) {
+            RefCell::borrow_mut(figure).double_click();
+        }
+    }
+
+    /// Show properties window of the figure
+    pub fn show_properties(&mut self, id: Id) {
+        self.properties_figure = Some(id);
+    }
+
+    /// Add rectangle with left top corner at the given position
+    pub fn add_rect_at(&mut self, pos: Pos2) {
+        let id = self.generate_id();
+        let figure: Box<dyn GraphFigure> = Box::new(RectFigure::new(
+            id,
+            Rect::from_min_size(pos, NEW_FIGURE_SIZE),
+            "".to_owned(),
+        ));
+        self.add_new_figures(vec![Rc::new(RefCell::new(figure))]);
+    }
+
+    /// Add ellipse with left top corner of the bounding rectangle at the given position
+    pub fn add_ellipse_at(&mut self, pos: Pos2) {
+        let id = self.generate_id();
+        let figure: Box<dyn GraphFigure> = Box::new(EllipseFigure::new(
+            id,
+            Rect::from_min_size(pos, NEW_FIGURE_SIZE),
+            "".to_owned(),
+        ));
+        self.add_new_figures(vec![Rc::new(RefCell::new(figure))]);
+    }
+
+    /// Add cylinder with left top corner of the bounding rectangle at the given position
+    pub fn add_cylinder_at(&mut self, pos: Pos2) {
+        let id = self.generate_id();
+        let figure: Box<dyn GraphFigure> = Box::new(CylinderFigure::new(
+            id,
+            Rect::from_min_size(pos, NEW_FIGURE_SIZE),
+            "".to_owned(),
+        ));
+        self.add_new_figures(vec![Rc::new(RefCell::new(figure))]);
+    }
+
+    /// Show context menu of the figure under cursor or of the blank canvas.
+    /// Menu is closed by click outside of it
+    pub(super) fn context_menu(&mut self, response: Response) -> Response {
+        if response.secondary_clicked() {
+            self.context_figure = self.selected_figure_id;
+            if let Some(pos) = response.interact_pointer_pos() {
+                self.context_pos = pos;
+            }
+        }
+
+        response.context_menu(|ui| match self.context_figure {
+            Some(id) => self.figure_menu(ui, id),
+            None => self.canvas_menu(ui),
+        })
+    }
+
+    /// Actions over the figure
+    fn figure_menu(&mut self, ui: &mut Ui, id: Id) {
+        for (label, action) in [
+            ("Delete", Self::delete_figure as fn(&mut Self, Id)),
+            ("Duplicate", |data: &mut Self, id: Id| {
+                data.duplicate_figure(id);
+            }),
+            ("Copy", Self::copy_figure),
+            ("Bring to Front", Self::bring_to_front),
+            ("Bring Forward", Self::bring_forward),
+            ("Send Backward", Self::send_backward),
+            ("Send to Back", Self::send_to_back),
+            ("Edit Text", Self::edit_text),
+            ("Properties", Self::show_properties),
+        ] {
+            if ui.button(label).clicked() {
+                action(self, id);
+                ui.close_menu();
+            }
+        }
+    }
+
+    /// Actions over the blank canvas
+    fn canvas_menu(&mut self, ui: &mut Ui) {
+        let pos = self.context_pos;
+        if ui
+            .add_enabled(!self.clipboard.is_empty(), Button::new("Paste"))
+            .clicked()
+        {
+            self.paste(pos);
+            ui.close_menu();
+        }
+
+        for (label, action) in [
+            (
+                "Select All",
+                (|data: &mut Self, _: Pos2| data.select_all()) as fn(&mut Self, Pos2),
+            ),
+            ("Add Rect", Self::add_rect_at),
+            ("Add Ellipse", Self::add_ellipse_at),
+            ("Add Cylinder", Self::add_cylinder_at),
+        ] {
+            if ui.button(label).clicked() {
+                action(self, pos);
+                ui.close_menu();
+            }
+        }
+    }
+
+    /// Change rectangle of the figure from properties panel. Rectangle before the first
+    /// change is remembered until the edit is finished
+    pub(super) fn edit_figure_rect(&mut self, figure: &FigureRef, rect: Rect) {
+        let id = RefCell::borrow(figure).id();
+        if !matches!(self.edited_rect, Some((edited, _)) if edited == id) {
+            self.finish_rect_edit();
+            self.edited_rect = Some((id, RefCell::borrow(figure).rect()));
+        }
+        RefCell::borrow_mut(figure).set_rect(rect);
+    }
+
+    /// Store rectangle change made in properties panel in history
+    pub(super) fn finish_rect_edit(&mut self) {
+        if let Some((id, from_rect)) = self.edited_rect.take() {
+            let to_rect = match self.figure_by_id(id) {
+                Some(figure) => RefCell::borrow(figure).rect(),
+                None => return,
+            };
+
+            if from_rect != to_rect {
+                self.push_user_commands(vec![Command::ResizeFigure {
+                    id,
+                    from_rect,
+                    to_rect,
+                }]);
+            }
+        }
+    }
+
+    /// Draw position and size of the figure. Values are applied while they are changed,
+    /// command is stored when dragging or typing is finished
+    fn geometry_properties(&mut self, ui: &mut Ui, figure: &FigureRef) {
+        let rect = RefCell::borrow(figure).rect();
+        let (mut pos, mut size) = (rect.min, rect.size());
+        let read_only = self.read_only;
+        let responses = Grid::new("figure_geometry")
+            .show(ui, |ui| {
+                let mut responses = vec![];
+                for (name, value) in [
+                    ("x", &mut pos.x),
+                    ("y", &mut pos.y),
+                    ("width", &mut size.x),
+                    ("height", &mut size.y),
+                ] {
+                    ui.label(name);
+                    responses.push(ui.add_enabled(!read_only, DragValue::new(value)));
+                    ui.end_row();
+                }
+                responses
+            })
+            .inner;
+
+        let size = size.max(Vec2::splat(1.));
+        if responses.iter().any(Response::changed) {
+            self.edit_figure_rect(figure, Rect::from_min_size(pos, size));
+        }
+
+        if !responses
+            .iter()
+            .any(|resp| resp.dragged() || resp.has_focus())
+        {
+            self.finish_rect_edit();
+        }
+    }
+
+    /// Draw properties of the only selected figure: position and size, font family chosen
+    /// among built-in families and families registered in font definitions. Rectangles may be
+    /// switched to fit their text and have shadow
+    pub(super) fn properties_panel(&mut self, ui: &mut Ui) {
+        let figure = match self.selected_figure_indices[..] {
+            [idx] => Rc::clone(&self.figures[idx]),
+            _ => {
+                self.finish_rect_edit();
+                ui.label("Select one figure");
+                return;
+            }
+        };
+
+        self.geometry_properties(ui, &figure);
+
+        let mut tooltip = RefCell::borrow(&figure)
+            .tooltip()
+            .unwrap_or_default()
+            .to_owned();
+        let changed = ui
+            .horizontal(|ui| {
+                ui.label("Tooltip");
+                ui.add_enabled(!self.read_only, TextEdit::singleline(&mut tooltip))
+                    .changed()
+            })
+            .inner;
+        if changed {
+            RefCell::borrow_mut(&figure).set_tooltip(Some(tooltip).filter(|t| !t.is_empty()));
+        }
+        ui.separator();
+
+        let current = match RefCell::borrow(&figure).font_family() {
+            Some(family) => family,
+            None => {
+                ui.label("Figure has no text");
+                return;
+            }
+        };
+
+        let mut families = vec![FontFamily::Proportional, FontFamily::Monospace];
+        for family in ui.fonts().families() {
+            if !families.contains(&family) {
+                families.push(family);
+            }
+        }
+
+        let mut selected = current.clone();
+        ComboBox::from_label("Font")
+            .selected_text(family_name(&selected))
+            .show_ui(ui, |ui| {
+                for family in families {
+                    let name = family_name(&family);
+                    ui.selectable_value(&mut selected, family, name);
+                }
+            });
+
+        if selected != current {
+            RefCell::borrow_mut(&figure).set_font_family(selected);
+        }
+
+        let size_mode = RefCell::borrow(&figure).size_mode();
+        if let Some(size_mode) = size_mode {
+            let mut auto_fit = size_mode == SizeMode::AutoFitText;
+            if ui.checkbox(&mut auto_fit, "Fit to text").changed() {
+                RefCell::borrow_mut(&figure).set_size_mode(if auto_fit {
+                    SizeMode::AutoFitText
+                } else {
+                    SizeMode::Manual
+                });
+            }
+        }
+
+        let shadow = RefCell::borrow(&figure).shadow();
+        if let Some((mut offset, mut color)) = shadow {
+            let changed = ui
+                .horizontal(|ui| {
+                    ui.label("Shadow");
+                    ui.color_edit_button_srgba(&mut color).changed()
+                        | ui.add(DragValue::new(&mut offset.x).prefix("x: "))
+                            .changed()
+                        | ui.add(DragValue::new(&mut offset.y).prefix("y: "))
+                            .changed()
+                })
+                .inner;
+
+            if changed {
+                RefCell::borrow_mut(&figure).set_shadow(offset, color);
+            }
+        }
+    }
+
+    /// Draw properties window of the figure chosen in context menu
+    pub(super) fn draw_properties(&mut self, ctx: &Context) {
+        let figure = match self.properties_figure.and_then(|id| self.figure_by_id(id)) {
+            Some(figure) => Rc::clone(figure),
+            None => return,
+        };
+
+        let mut open = true;
+        Window::new("Properties")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let figure = RefCell::borrow(&figure);
+                let rect = figure.rect();
+                Grid::new("figure_properties").show(ui, |ui| {
+                    for (name, value) in [
+                        ("Type", figure.type_name().to_owned()),
+                        ("Label", figure.label()),
+                        ("Position", format!("{:.0}, {:.0}", rect.min.x, rect.min.y)),
+                        (
+                            "Size",
+                            format!("{:.0} x {:.0}", rect.width(), rect.height()),
+                        ),
+                        ("Fill", format!("{:?}", figure.fill_color())),
+                    ] {
+                        ui.label(name);
+                        ui.label(value);
+                        ui.end_row();
+                    }
+                });
+            });
+
+        if !open {
+            self.properties_figure = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use eframe::{
+        egui::Id,
+        emath::{pos2, vec2, Rect},
+    };
+
+    use crate::graph::{
+        commands::CommandStack, graphics::GraphicsData, shapes::GraphUi, GraphFigure, RectFigure,
+    };
+
+    fn graphics_data() -> (GraphicsData, Id) {
+        let mut data = GraphicsData::default();
+        let id = data.generate_id();
+        let rect = Rect::from_min_max(pos2(0., 0.), pos2(100., 50.));
+        let figure = RectFigure::new(id, rect, "Label".to_owned());
+        data.add_figure(Rc::new(RefCell::new(Box::new(figure))));
+        (data, id)
+    }
+
+    #[test]
+    fn test_duplicate_and_delete() {
+        let (mut data, id) = graphics_data();
+        let copy_id = data.duplicate_figure(id).unwrap();
+        assert_ne!(copy_id, id);
+        assert_eq!(data.figures.len(), 2);
+
+        let copy = RefCell::borrow(&data.figures[1]);
+        assert_eq!(copy.id(), copy_id);
+        assert_eq!(copy.label(), "Label");
+        assert_eq!(copy.rect().min, pos2(20., 20.));
+        drop(copy);
+
+        // Copy is the only selected figure
+        assert_eq!(data.selected_figure_indices, [1]);
+        data.duplicate_selected();
+        assert_eq!(data.figures.len(), 3);
+        assert_eq!(RefCell::borrow(&data.figures[2]).rect().min, pos2(40., 40.));
+        assert!(data.undo());
+
+        data.delete_figure(id);
+        assert_eq!(data.figures.len(), 1);
+        assert!(data.undo());
+        assert!(data.undo());
+        assert_eq!(data.figures.len(), 1);
+        assert_eq!(RefCell::borrow(&data.figures[0]).id(), id);
+    }
+
+    #[test]
+    fn test_edit_figure_rect() {
+        let (mut data, id) = graphics_data();
+        let figure = Rc::clone(&data.figures[0]);
+        let rect = Rect::from_min_max(pos2(0., 0.), pos2(100., 50.));
+
+        // Several changes of one edit are stored as one command
+        data.edit_figure_rect(&figure, rect.translate(vec2(10., 0.)));
+        data.edit_figure_rect(&figure, Rect::from_min_size(pos2(10., 0.), vec2(200., 50.)));
+        assert_eq!(data.edited_rect, Some((id, rect)));
+        data.finish_rect_edit();
+        assert_eq!(data.edited_rect, None);
+        assert_eq!(
+            RefCell::borrow(&figure).rect(),
+            Rect::from_min_size(pos2(10., 0.), vec2(200., 50.))
+        );
+
+        assert!(data.undo());
+        assert_eq!(RefCell::borrow(&figure).rect(), rect);
+        assert!(!data.undo());
+
+        // Unchanged rectangle is not stored
+        data.edit_figure_rect(&figure, rect);
+        data.finish_rect_edit();
+        assert!(!data.undo());
+    }
+
+    #[test]
+    fn test_copy_paste() {
+        let (mut data, id) = graphics_data();
+        data.paste(pos2(10., 10.));
+        assert_eq!(data.figures.len(), 1);
+
+        data.copy_figure(id);
+        data.paste(pos2(200., 100.));
+        data.paste(pos2(300., 100.));
+        assert_eq!(data.figures.len(), 3);
+        assert_eq!(
+            RefCell::borrow(&data.figures[2]).rect(),
+            Rect::from_min_size(pos2(300., 100.), vec2(100., 50.))
+        );
+        assert_eq!(data.outgoing.len(), 2);
+    }
+
+    #[test]
+    fn test_canvas_actions() {
+        let (mut data, _) = graphics_data();
+        data.add_rect_at(pos2(200., 0.));
+        data.add_ellipse_at(pos2(400., 0.));
+        data.add_cylinder_at(pos2(600., 0.));
+        let types: Vec<&str> = data
+            .figures
+            .iter()
+            .map(|fig| RefCell::borrow(fig).type_name())
+            .collect();
+        assert_eq!(types, ["rect", "rect", "ellipse", "cylinder"]);
+        assert_eq!(
+            RefCell::borrow(&data.figures[2]).rect(),
+            Rect::from_min_size(pos2(400., 0.), vec2(100., 50.))
+        );
+
+        data.select_all();
+        assert_eq!(data.selected_figure_indices, [0, 1, 2, 3]);
+    }
+}
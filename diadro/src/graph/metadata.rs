@@ -0,0 +1,177 @@
+use chrono::{DateTime, Utc};
+use eframe::egui::{Grid, Ui};
+use serde::{Deserialize, Serialize};
+
+use super::{graphics::GraphicsData, Graphics};
+use crate::ws::{RequestInfo, WsMessages};
+
+/// Descriptive information about the diagram
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DiagramMetadata {
+    pub title: String,
+    pub author: String,
+    pub created_at: DateTime<Utc>,
+    /// Time of the last change
+    pub modified_at: DateTime<Utc>,
+    /// Number of changes since creation
+    pub version: u32,
+    pub description: String,
+}
+
+impl Default for DiagramMetadata {
+    fn default() -> Self {
+        let now = Utc::now();
+        Self {
+            title: String::new(),
+            author: String::new(),
+            created_at: now,
+            modified_at: now,
+            version: 0,
+            description: String::new(),
+        }
+    }
+}
+
+/// Implies tracking of the diagram changes
+impl GraphicsData {
+    /// Update modification time and increment version, called on every diagram change
+    pub(super) fn mark_modified(&mut self) {
+        self.metadata.modified_at = Utc::now();
+        self.metadata.version += 1;
+    }
+
+    /// Apply title, author and description edited by another user of the board
+    pub(super) fn update_metadata(&mut self, title: &str, author: &str, description: &str) {
+        self.metadata.title = title.to_owned();
+        self.metadata.author = author.to_owned();
+        self.metadata.description = description.to_owned();
+        self.mark_modified();
+    }
+
+    /// Message informing other users of the board about edited title, author or description
+    fn metadata_message(&self) -> WsMessages {
+        WsMessages::UpdateMetadata {
+            rq: RequestInfo::default(),
+            title: self.metadata.title.clone(),
+            author: self.metadata.author.clone(),
+            description: self.metadata.description.clone(),
+        }
+    }
+}
+
+/// Implies diagram metadata access
+impl Graphics {
+    /// Return diagram metadata
+    pub fn metadata(&self) -> &DiagramMetadata {
+        &self.graphics_data.metadata
+    }
+
+    /// Draw "About this diagram" panel. Title, author and description are editable
+    /// unless diagram is read-only, edits are sent to other users of the board
+    pub fn about_ui(&mut self, ui: &mut Ui) {
+        let editable = !self.graphics_data.read_only;
+        let data = &mut self.graphics_data;
+        let mut changed = false;
+        Grid::new("diagram_metadata").num_columns(2).show(ui, |ui| {
+            ui.label("Title");
+            ui.add_enabled_ui(editable, |ui| {
+                changed |= ui.text_edit_singleline(&mut data.metadata.title).changed();
+            });
+            ui.end_row();
+
+            ui.label("Author");
+            ui.add_enabled_ui(editable, |ui| {
+                changed |= ui.text_edit_singleline(&mut data.metadata.author).changed();
+            });
+            ui.end_row();
+
+            ui.label("Description");
+            ui.add_enabled_ui(editable, |ui| {
+                changed |= ui
+                    .text_edit_multiline(&mut data.metadata.description)
+                    .changed();
+            });
+            ui.end_row();
+
+            for (name, value) in [
+                (
+                    "Created",
+                    data.metadata
+                        .created_at
+                        .format("%Y-%m-%d %H:%M")
+                        .to_string(),
+                ),
+                (
+                    "Modified",
+                    data.metadata
+                        .modified_at
+                        .format("%Y-%m-%d %H:%M")
+                        .to_string(),
+                ),
+                ("Version", data.metadata.version.to_string()),
+            ] {
+                ui.label(name);
+                ui.label(value);
+                ui.end_row();
+            }
+        });
+
+        if changed {
+            data.mark_modified();
+            let message = data.metadata_message();
+            data.outgoing.push(message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use eframe::{
+        egui::Id,
+        emath::{pos2, Rect},
+    };
+
+    use crate::graph::{
+        graphics::GraphicsData, shapes::GraphUi, Command, CommandStack, GraphFigure, Graphics,
+        RectFigure,
+    };
+
+    #[test]
+    fn test_mark_modified() {
+        let mut data = GraphicsData::default();
+        let created_at = data.metadata.created_at;
+        assert_eq!(data.metadata.version, 0);
+
+        let figure: Rc<RefCell<Box<dyn GraphFigure>>> =
+            Rc::new(RefCell::new(Box::new(RectFigure::new(
+                Id::new(1),
+                Rect::from_min_max(pos2(0., 0.), pos2(100., 50.)),
+                "".to_owned(),
+            ))));
+        data.add_figure(Rc::clone(&figure));
+        data.push_command(Command::AddFigure(figure));
+        assert_eq!(data.metadata.version, 1);
+        assert!(data.undo());
+        assert!(data.redo());
+        assert_eq!(data.metadata.version, 3);
+        assert_eq!(data.metadata.created_at, created_at);
+        assert!(data.metadata.modified_at >= created_at);
+
+        data.update_metadata("Deployment", "admin", "Services and databases");
+        assert_eq!(data.metadata.title, "Deployment");
+        assert_eq!(data.metadata.version, 4);
+
+        // Metadata is saved with the diagram
+        let json = serde_json::to_string(&data).unwrap();
+        let restored = Graphics::from_json(&json).unwrap();
+        assert_eq!(restored.metadata(), &data.metadata);
+
+        // Diagrams saved before metadata was added get default one
+        let mut json = serde_json::to_value(&data).unwrap();
+        json.as_object_mut().unwrap().remove("metadata");
+        let restored = Graphics::from_json(&json.to_string()).unwrap();
+        assert_eq!(restored.metadata().version, 0);
+    }
+}
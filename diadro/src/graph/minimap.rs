@@ -0,0 +1,132 @@
+use std::cell::RefCell;
+
+use eframe::{
+    egui::Painter,
+    emath::{Pos2, Rect, Vec2},
+    epaint::{Color32, Rounding, Stroke},
+};
+
+use super::Graphics;
+
+/// Color of the figures outlines and edges on minimap
+const MINIMAP_FIGURE_COLOR: Color32 = Color32::GRAY;
+/// Fill of the minimap background
+const MINIMAP_BACKGROUND: Color32 = Color32::from_rgba_premultiplied(20, 20, 20, 200);
+/// Fill of the current viewport overlay
+const MINIMAP_VIEWPORT_FILL: Color32 = Color32::from_rgba_premultiplied(40, 40, 80, 40);
+
+/// Maps canvas coordinates into minimap rectangle and back
+struct MinimapTransform {
+    /// Canvas area shown on minimap
+    canvas: Rect,
+    /// Minimap rectangle on screen
+    dest: Rect,
+}
+
+impl MinimapTransform {
+    /// Scale of the minimap by both axes, zero sized canvas is not scaled
+    fn scale(&self) -> Vec2 {
+        let scale = |dest: f32, canvas: f32| if canvas > 0. { dest / canvas } else { 1. };
+        Vec2::new(
+            scale(self.dest.width(), self.canvas.width()),
+            scale(self.dest.height(), self.canvas.height()),
+        )
+    }
+
+    fn to_minimap(&self, pos: Pos2) -> Pos2 {
+        let scale = self.scale();
+        let offset = pos - self.canvas.min;
+        self.dest.min + Vec2::new(offset.x * scale.x, offset.y * scale.y)
+    }
+
+    fn rect_to_minimap(&self, rect: Rect) -> Rect {
+        Rect::from_two_pos(self.to_minimap(rect.min), self.to_minimap(rect.max))
+    }
+
+    fn to_canvas(&self, pos: Pos2) -> Pos2 {
+        let scale = self.scale();
+        let offset = pos - self.dest.min;
+        self.canvas.min + Vec2::new(offset.x / scale.x, offset.y / scale.y)
+    }
+}
+
+/// Implies drawing of the diagram overview
+impl Graphics {
+    /// Area shown on minimap: all figures and current viewport
+    fn minimap_transform(&self, dest_rect: Rect) -> MinimapTransform {
+        let viewport = self.graphics_data.viewport;
+        let canvas = if self.graphics_data.figures.is_empty() && self.graphics_data.edges.is_empty()
+        {
+            viewport
+        } else {
+            self.bounding_rect().union(viewport)
+        };
+
+        MinimapTransform {
+            canvas,
+            dest: dest_rect,
+        }
+    }
+
+    /// Draw simplified diagram scaled to fit into destination rectangle. Figures are drawn as
+    /// rectangle outlines, edges as straight lines, current viewport is shown by overlay
+    /// ### Arguments
+    /// * painter - painter to draw with
+    /// * dest_rect - rectangle to draw diagram in
+    pub fn render_to_painter(&self, painter: &Painter, dest_rect: Rect) {
+        let transform = self.minimap_transform(dest_rect);
+        let stroke = Stroke::new(1., MINIMAP_FIGURE_COLOR);
+        painter.rect_filled(dest_rect, Rounding::none(), MINIMAP_BACKGROUND);
+
+        for figure in &self.graphics_data.figures {
+            let rect = transform.rect_to_minimap(RefCell::borrow(figure).rect());
+            painter.rect_stroke(rect, Rounding::none(), stroke);
+        }
+
+        for edge in &self.graphics_data.edges {
+            painter.line_segment(
+                [
+                    transform.to_minimap(edge.compute_start_point()),
+                    transform.to_minimap(edge.compute_end_point()),
+                ],
+                stroke,
+            );
+        }
+
+        painter.rect(
+            transform.rect_to_minimap(self.graphics_data.viewport),
+            Rounding::none(),
+            MINIMAP_VIEWPORT_FILL,
+            Stroke::new(1., Color32::WHITE),
+        );
+    }
+
+    /// Pan canvas to show the point clicked on minimap in the center of the viewport
+    /// ### Arguments
+    /// * dest_rect - rectangle the minimap is drawn in
+    /// * pos - clicked point
+    pub fn minimap_clicked(&mut self, dest_rect: Rect, pos: Pos2) {
+        let target = self.minimap_transform(dest_rect).to_canvas(pos);
+        self.set_scroll_delta(self.graphics_data.viewport.center() - target);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eframe::emath::{pos2, Rect};
+
+    use super::MinimapTransform;
+
+    #[test]
+    fn test_minimap_transform() {
+        let transform = MinimapTransform {
+            canvas: Rect::from_min_max(pos2(-100., 0.), pos2(300., 200.)),
+            dest: Rect::from_min_max(pos2(10., 10.), pos2(210., 60.)),
+        };
+
+        assert_eq!(transform.to_minimap(pos2(-100., 0.)), pos2(10., 10.));
+        assert_eq!(transform.to_minimap(pos2(300., 200.)), pos2(210., 60.));
+        assert_eq!(transform.to_minimap(pos2(100., 100.)), pos2(110., 35.));
+        assert_eq!(transform.to_canvas(pos2(110., 35.)), pos2(100., 100.));
+    }
+}
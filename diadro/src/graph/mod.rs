@@ -1,13 +1,54 @@
+mod align;
 mod arrow;
+mod bookmarks;
+mod commands;
+mod cursors;
+mod cylinder;
+mod dash;
+mod diamond;
+mod ellipse;
+mod export;
+mod fill;
 mod graphics;
+mod group;
+mod guides;
+mod image;
+mod menu;
+mod metadata;
+mod minimap;
+mod persist;
 mod rect;
+mod routing;
 mod shapes;
 mod text;
+mod touch;
 mod utils;
 
+pub use self::image::ImageFigure;
+pub use align::Alignment;
+pub use arrow::{
+    draw_head, outward_normal, ArrowFigure, ArrowHeadStyle, ConnectionPoint, LOOP_RADIUS,
+};
+pub use bookmarks::ViewBookmark;
+pub use commands::{Command, CommandStack};
+pub use cursors::{
+    cursor_color, remove_stale_cursors, update_remote_cursor, RemoteCursor, RemoteCursors,
+};
+pub use cylinder::CylinderFigure;
+pub use dash::{apply_dash, DashPattern, DEFAULT_DASH_LENGTH};
+pub use diamond::DiamondFigure;
+pub use ellipse::EllipseFigure;
+pub(crate) use export::id_to_string;
+pub use fill::FillStyle;
 pub use graphics::Graphics;
-pub use rect::RectFigure;
+pub use group::GroupFigure;
+pub use guides::{Guide, DEFAULT_SNAP_DISTANCE};
+pub use metadata::DiagramMetadata;
+pub use persist::FigureKind;
+pub use rect::{RectFigure, SizeMode};
+pub use routing::{orthogonal_route, EdgeRouting};
 pub use shapes::{DragMode, GraphFigure};
+pub use text::WordWrap;
 pub use utils::pos_by_angle;
 pub use utils::TwoPosLine;
 pub use utils::Zoom;
@@ -0,0 +1,356 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use eframe::{egui::Id, emath::Vec2};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{
+    arrow::ArrowData, graphics::GraphicsData, shapes::GraphUi, CylinderFigure, DiagramMetadata,
+    DiamondFigure, EllipseFigure, GraphFigure, Graphics, GroupFigure, ImageFigure, RectFigure,
+    ViewBookmark,
+};
+
+type FigureRef = Rc<RefCell<Box<dyn GraphFigure>>>;
+
+/// Concrete figure type. Used to serialize figures stored as trait objects
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum FigureKind {
+    Rect(RectFigure),
+    Ellipse(EllipseFigure),
+    Diamond(DiamondFigure),
+    Cylinder(CylinderFigure),
+    Image(ImageFigure),
+    Group { id: Id, members: Vec<FigureKind> },
+}
+
+impl FigureKind {
+    /// Restore figure from its serializable form
+    /// ### Arguments
+    /// * figures - map to register restored figures in, group members are registered as well
+    fn into_figure(self, figures: &mut HashMap<Id, FigureRef>) -> FigureRef {
+        let mut figure: Box<dyn GraphFigure> = match self {
+            FigureKind::Rect(figure) => Box::new(figure),
+            FigureKind::Ellipse(figure) => Box::new(figure),
+            FigureKind::Diamond(figure) => Box::new(figure),
+            FigureKind::Cylinder(figure) => Box::new(figure),
+            FigureKind::Image(figure) => Box::new(figure),
+            FigureKind::Group { id, members } => Box::new(GroupFigure::new(
+                id,
+                members
+                    .into_iter()
+                    .map(|member| member.into_figure(figures))
+                    .collect(),
+            )),
+        };
+
+        // Connection points are not serialized and must be computed again
+        let rect = figure.rect();
+        figure.set_rect(rect);
+
+        let id = figure.id();
+        let figure = Rc::new(RefCell::new(figure));
+        figures.insert(id, Rc::clone(&figure));
+        figure
+    }
+
+    /// Give new identifiers to the figure and members of the group
+    fn renew_ids(&mut self, data: &mut GraphicsData) {
+        match self {
+            FigureKind::Rect(figure) => figure.set_id(data.generate_id()),
+            FigureKind::Ellipse(figure) => figure.set_id(data.generate_id()),
+            FigureKind::Diamond(figure) => figure.set_id(data.generate_id()),
+            FigureKind::Cylinder(figure) => figure.set_id(data.generate_id()),
+            FigureKind::Image(figure) => figure.set_id(data.generate_id()),
+            FigureKind::Group { id, members } => {
+                *id = data.generate_id();
+                for member in members {
+                    member.renew_ids(data);
+                }
+            }
+        }
+    }
+
+    /// Create independent copy of the figure with new identifiers
+    /// ### Arguments
+    /// * data - diagram generating identifiers
+    pub(super) fn instantiate(&self, data: &mut GraphicsData) -> FigureRef {
+        let mut kind = self.clone();
+        kind.renew_ids(data);
+        kind.into_figure(&mut HashMap::new())
+    }
+}
+
+/// Serializable form of the diagram
+#[derive(Serialize, Deserialize)]
+struct DiagramData {
+    zoom_factor: f32,
+    scroll_delta: Vec2,
+    figures: Vec<FigureKind>,
+    edges: Vec<ArrowData>,
+    #[serde(default)]
+    bookmarks: Vec<ViewBookmark>,
+    #[serde(default)]
+    metadata: DiagramMetadata,
+}
+
+impl GraphicsData {
+    /// Convert top level figures into serializable form. If some groups are entered at the moment
+    /// then their members are taken from the current level instead of the stale group content
+    fn top_level_figures(&self) -> Vec<FigureKind> {
+        let mut figures: Vec<_> = self
+            .figures
+            .iter()
+            .filter_map(|fig| RefCell::borrow(fig).to_kind())
+            .collect();
+
+        for (group_id, parent) in self.group_stack.iter().rev() {
+            let mut members = Some(std::mem::take(&mut figures));
+            figures = parent
+                .iter()
+                .filter_map(|fig| {
+                    let fig = RefCell::borrow(fig);
+                    match members.take() {
+                        Some(members) if fig.id() == *group_id => Some(FigureKind::Group {
+                            id: *group_id,
+                            members,
+                        }),
+                        other => {
+                            members = other;
+                            fig.to_kind()
+                        }
+                    }
+                })
+                .collect();
+        }
+
+        figures
+    }
+}
+
+impl Serialize for GraphicsData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        DiagramData {
+            zoom_factor: self.zoom_factor,
+            scroll_delta: self.scroll_delta,
+            figures: self.top_level_figures(),
+            edges: self.edges.iter().cloned().map(ArrowData::from).collect(),
+            bookmarks: self.bookmarks.clone(),
+            metadata: self.metadata.clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for GraphicsData {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let diagram = DiagramData::deserialize(deserializer)?;
+
+        let mut by_id = HashMap::new();
+        let mut data = GraphicsData::default();
+        data.zoom_factor = diagram.zoom_factor;
+        data.scroll_delta = diagram.scroll_delta;
+        data.figures = diagram
+            .figures
+            .into_iter()
+            .map(|figure| figure.into_figure(&mut by_id))
+            .collect();
+        data.edges = diagram
+            .edges
+            .into_iter()
+            .map(|edge| edge.into_arrow(&by_id))
+            .collect();
+        data.bookmarks = diagram.bookmarks;
+        data.metadata = diagram.metadata;
+
+        Ok(data)
+    }
+}
+
+/// Named page of the multi-page document
+#[derive(Serialize)]
+struct PageRef<'a> {
+    name: &'a str,
+    diagram: &'a GraphicsData,
+}
+
+#[derive(Deserialize)]
+struct Page {
+    name: String,
+    diagram: GraphicsData,
+}
+
+/// Implies saving and loading of diagrams
+impl Graphics {
+    /// Serialize diagram into JSON
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(&self.graphics_data)
+    }
+
+    /// Load diagram from JSON produced by `to_json`. Edges are connected to figures by identifiers
+    pub fn from_json(s: &str) -> Result<Self, serde_json::Error> {
+        Ok(Self {
+            graphics_data: serde_json::from_str(s)?,
+        })
+    }
+
+    /// Serialize named pages into JSON array
+    pub fn pages_to_json<'a>(
+        pages: impl IntoIterator<Item = (&'a str, &'a Graphics)>,
+    ) -> Result<String, serde_json::Error> {
+        serde_json::to_string(
+            &pages
+                .into_iter()
+                .map(|(name, page)| PageRef {
+                    name,
+                    diagram: &page.graphics_data,
+                })
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Load named pages from JSON produced by `pages_to_json`
+    pub fn pages_from_json(s: &str) -> Result<Vec<(String, Graphics)>, serde_json::Error> {
+        Ok(serde_json::from_str::<Vec<Page>>(s)?
+            .into_iter()
+            .map(|page| {
+                (
+                    page.name,
+                    Self {
+                        graphics_data: page.diagram,
+                    },
+                )
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use eframe::emath::{pos2, Rect};
+
+    use crate::graph::{
+        ArrowFigure, ConnectionPoint, DiamondFigure, EllipseFigure, GraphFigure, Graphics,
+        GroupFigure, RectFigure,
+    };
+
+    #[test]
+    fn test_json_round_trip() {
+        let mut graphics = Graphics::default();
+
+        let id = graphics.generate_id();
+        let rect = Rc::new(RefCell::new(Box::new(RectFigure::new(
+            id,
+            Rect::from_min_max(pos2(0., 0.), pos2(100., 50.)),
+            "Start".to_owned(),
+        )) as Box<dyn GraphFigure>));
+        graphics.add_figure(Rc::clone(&rect));
+
+        let id = graphics.generate_id();
+        let diamond = Rc::new(RefCell::new(Box::new(DiamondFigure::new(
+            id,
+            Rect::from_min_max(pos2(200., 0.), pos2(300., 100.)),
+            "Check".to_owned(),
+        )) as Box<dyn GraphFigure>));
+        graphics.add_figure(Rc::clone(&diamond));
+
+        let members: Vec<Rc<RefCell<Box<dyn GraphFigure>>>> =
+            vec![Rc::new(RefCell::new(Box::new(EllipseFigure::new(
+                graphics.generate_id(),
+                Rect::from_min_max(pos2(0., 200.), pos2(100., 250.)),
+                "End".to_owned(),
+            ))))];
+        let id = graphics.generate_id();
+        graphics.add_figure(Rc::new(RefCell::new(Box::new(GroupFigure::new(
+            id, members,
+        )))));
+
+        let id = graphics.generate_id();
+        let mut edge = ArrowFigure::new([pos2(100., 25.), pos2(200., 50.)], id);
+        edge.connect_start(ConnectionPoint::new(Rc::clone(&rect), 2));
+        edge.connect_end(ConnectionPoint::new(Rc::clone(&diamond), 3));
+        graphics.add_edge(edge);
+
+        let json = graphics.to_json().unwrap();
+        let restored = Graphics::from_json(&json).unwrap();
+        assert_eq!(restored.statistics(), (3, 1));
+        assert_eq!(restored.to_json().unwrap(), json);
+
+        let figures = restored.figures();
+        let types: Vec<_> = figures
+            .iter()
+            .map(|fig| RefCell::borrow(fig).type_name())
+            .collect();
+        assert_eq!(types, vec!["rect", "diamond", "group"]);
+        assert_eq!(RefCell::borrow(&figures[1]).label(), "Check");
+        assert_eq!(
+            RefCell::borrow(&figures[2]).rect(),
+            Rect::from_min_max(pos2(0., 200.), pos2(100., 250.))
+        );
+
+        let edge = &restored.edges()[0];
+        let start = edge.get_start_connection().as_ref().unwrap();
+        let end = edge.get_end_connection().as_ref().unwrap();
+        assert!(Rc::ptr_eq(start.get_figure(), &figures[0]));
+        assert!(Rc::ptr_eq(end.get_figure(), &figures[1]));
+        assert_eq!(edge.compute_end_point(), pos2(200., 50.));
+    }
+
+    #[test]
+    fn test_pages_round_trip() {
+        let mut design = Graphics::default();
+        let id = design.generate_id();
+        design.add_figure(Rc::new(RefCell::new(Box::new(RectFigure::new(
+            id,
+            Rect::from_min_max(pos2(0., 0.), pos2(100., 50.)),
+            "Service".to_owned(),
+        )))));
+        let pages = vec![
+            ("Design".to_owned(), design),
+            ("Deployment".to_owned(), Graphics::default()),
+        ];
+
+        let json = Graphics::pages_to_json(pages.iter().map(|(name, page)| (name.as_str(), page)))
+            .unwrap();
+        let restored = Graphics::pages_from_json(&json).unwrap();
+        let names: Vec<_> = restored.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["Design", "Deployment"]);
+        assert_eq!(restored[0].1.statistics(), (1, 0));
+        assert_eq!(restored[1].1.statistics(), (0, 0));
+        let restored_json =
+            Graphics::pages_to_json(restored.iter().map(|(name, page)| (name.as_str(), page)));
+        assert_eq!(restored_json.unwrap(), json);
+    }
+
+    #[test]
+    fn test_tooltips_round_trip() {
+        let mut graphics = Graphics::default();
+        let rect = Rect::from_min_max(pos2(0., 0.), pos2(100., 50.));
+        let mut figures: Vec<Box<dyn GraphFigure>> = vec![
+            Box::new(RectFigure::new(graphics.generate_id(), rect, "".to_owned())),
+            Box::new(EllipseFigure::new(
+                graphics.generate_id(),
+                rect,
+                "".to_owned(),
+            )),
+        ];
+        figures[0].set_tooltip(Some("Entry point".to_owned()));
+        for figure in figures {
+            graphics.add_figure(Rc::new(RefCell::new(figure)));
+        }
+
+        let mut edge = ArrowFigure::new([pos2(0., 0.), pos2(100., 0.)], graphics.generate_id());
+        edge.set_tooltip(Some("Request".to_owned()));
+        graphics.add_edge(edge);
+
+        let restored = Graphics::from_json(&graphics.to_json().unwrap()).unwrap();
+        let tooltips: Vec<_> = restored
+            .figures()
+            .iter()
+            .map(|fig| RefCell::borrow(fig).tooltip().map(str::to_owned))
+            .collect();
+        assert_eq!(tooltips, vec![Some("Entry point".to_owned()), None]);
+        assert_eq!(restored.edges()[0].tooltip(), Some("Request"));
+    }
+}
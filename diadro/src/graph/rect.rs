@@ -1,28 +1,61 @@
+use super::fill::{rounded_rect_outline, FillStyle};
+use super::persist::FigureKind;
 use super::shapes::{
-    FigureBasics, SelectMode, SELECT_MODE_HOVER, SELECT_MODE_NONE, SELECT_MODE_SELECTED,
+    draw_selection_border, zoom_rect, FigureBasics, SelectMode, SELECT_MODE_HOVER,
+    SELECT_MODE_NONE, SELECT_MODE_SELECTED,
 };
 use super::text::TextOps;
 use super::utils::{PointMath, TwoPosLine};
-use super::Zoom;
 use super::{DragMode, GraphFigure};
+use crate::rgraph::CanvasCommand;
 use eframe::egui::CursorIcon;
 use eframe::{
     egui::{Id, PointerButton, Ui},
-    epaint::{Color32, Pos2, Rect, Rounding, Vec2},
+    epaint::{Color32, FontFamily, Pos2, Rect, Rounding, Shape, Stroke, Vec2},
 };
+use serde::{Deserialize, Serialize};
+use std::f32::consts::FRAC_PI_2;
 
-#[derive(Clone, Debug)]
+/// How size of the rectangle is defined
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SizeMode {
+    /// Size is set by dragging, font is adjusted to the rectangle
+    Manual,
+    /// Font size is kept and rectangle grows to fit the text
+    AutoFitText,
+}
+
+impl Default for SizeMode {
+    fn default() -> Self {
+        SizeMode::Manual
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct RectFigure {
     id: Id,
     rect: Rect,
     fb: FigureBasics,
+    #[serde(skip)]
     selected: SelectMode,
     zoom_factor: f32,
     scroll_delta: Vec2,
+    #[serde(skip)]
     drag_mode: DragMode,
     text: TextOps,
+    #[serde(skip)]
     text_edit: bool,
+    #[serde(skip)]
     connection_points: Vec<Pos2>,
+    /// Offset of the selection border dash pattern
+    #[serde(skip)]
+    selection_offset: f32,
+    size_mode: SizeMode,
+    /// Clockwise rotation around the center. Text is kept horizontal
+    rotation_radians: f32,
+    /// Text shown when cursor is over the figure
+    tooltip: Option<String>,
 }
 
 impl Default for RectFigure {
@@ -41,13 +74,31 @@ impl Default for RectFigure {
             text_edit: false,
             fb: Default::default(),
             connection_points: Default::default(),
+            selection_offset: 0.,
+            size_mode: SizeMode::Manual,
+            rotation_radians: 0.,
+            tooltip: None,
         }
     }
 }
 
 const MARGIN: f32 = 10.;
+/// Size of the resize handle square
+const RESIZE_HANDLE_SIZE: f32 = 6.;
+/// Rectangles dragged smaller than this size are fitted to the text
+const AUTO_FIT_THRESHOLD: Vec2 = Vec2::splat(30.);
+/// Font size of the text in rectangles fitted to the text
+const AUTO_FIT_FONT_SIZE: f32 = 20.;
+/// Distance of the rotation handle from the top side
+const ROTATION_HANDLE_OFFSET: f32 = 20.;
+/// Radius of the rotation handle circle
+const ROTATION_HANDLE_RADIUS: f32 = 4.;
 
 impl GraphFigure for RectFigure {
+    fn clone_box(&self) -> Box<dyn GraphFigure> {
+        Box::new(self.clone())
+    }
+
     fn set_id(&mut self, id: Id) {
         self.id = id;
     }
@@ -59,22 +110,43 @@ impl GraphFigure for RectFigure {
     fn draw(&mut self, ui: &mut Ui, zoom_factor: f32, scroll_delta: Vec2) {
         self.zoom(zoom_factor, scroll_delta);
 
-        ui.painter().rect(
-            self.rect,
-            Rounding::from(10.),
-            Color32::TRANSPARENT,
-            self.fb.stroke,
-        );
-
         let bg_color = match self.selected {
             x if x & SELECT_MODE_HOVER > 0 => self.fb.selected_fill_color,
-            _ => self.fb.fill_color,
+            _ => self.fb.fill.base_color(),
         };
 
-        ui.painter()
-            .rect_filled(self.rect.expand(-1.0), Rounding::from(10.), bg_color);
+        if self.rotation_radians == 0. {
+            ui.painter().rect(
+                self.rect,
+                Rounding::from(10.),
+                Color32::TRANSPARENT,
+                self.fb.stroke,
+            );
+
+            match self.fb.fill {
+                fill if fill.is_gradient() && self.selected & SELECT_MODE_HOVER == 0 => {
+                    let outline = rounded_rect_outline(self.rect.expand(-1.0), 10.);
+                    ui.painter()
+                        .add(Shape::Mesh(fill.mesh(self.rect, &outline)));
+                }
+                _ => {
+                    ui.painter()
+                        .rect_filled(self.rect.expand(-1.0), Rounding::from(10.), bg_color);
+                }
+            }
+
+            let shadow_color = self.fb.shadow.shadow_color;
+            for shadow in self.fb.shadow_rects(self.rect) {
+                ui.painter()
+                    .rect_filled(shadow, Rounding::none(), shadow_color);
+            }
+        } else {
+            self.draw_rotated(ui, bg_color);
+        }
 
-        self.text.draw(
+        self.text
+            .set_fixed_font(self.size_mode == SizeMode::AutoFitText);
+        let text_size = self.text.draw(
             self.rect,
             ui,
             self.id(),
@@ -83,7 +155,12 @@ impl GraphFigure for RectFigure {
             &mut self.text_edit,
         );
 
+        if let (SizeMode::AutoFitText, Some(size)) = (self.size_mode, text_size) {
+            self.fit_text(size);
+        }
+
         if self.selected & SELECT_MODE_SELECTED > 0 {
+            self.draw_selection_border(ui);
             self.draw_resize_controls(ui);
         }
     }
@@ -93,6 +170,11 @@ impl GraphFigure for RectFigure {
     }
 
     fn contains(&self, point: Pos2) -> Option<CursorIcon> {
+        if self.selected & SELECT_MODE_SELECTED > 0 && point.over(self.rotation_handle(), MARGIN) {
+            return Some(CursorIcon::Grab);
+        }
+
+        let point = self.unrotated(point);
         match self.rect.contains(point) {
             true if point.over(self.rect.right_top(), MARGIN)
                 || point.over(self.rect.left_bottom(), MARGIN) =>
@@ -134,51 +216,47 @@ impl GraphFigure for RectFigure {
     fn drag_start(&mut self, hover_pos: Pos2, _button: PointerButton, zoom_factor: f32) {
         match self.selected {
             x if x & SELECT_MODE_HOVER > 0 || x & SELECT_MODE_SELECTED > 0 => {
+                // Resize handles are checked in the unrotated figure coordinates
+                let local = self.unrotated(hover_pos);
                 self.drag_mode = match self.contains(hover_pos) {
+                    Some(CursorIcon::Grab) => DragMode::Rotate,
                     Some(CursorIcon::ResizeHorizontal)
-                        if hover_pos
+                        if local
                             .in_line([self.rect.left_top(), self.rect.left_bottom()], MARGIN) =>
                     {
                         DragMode::ResizeLtoR(hover_pos)
                     }
                     Some(CursorIcon::ResizeHorizontal)
-                        if hover_pos
+                        if local
                             .in_line([self.rect.right_top(), self.rect.right_bottom()], MARGIN) =>
                     {
                         DragMode::ResizeRtoL(hover_pos)
                     }
                     Some(CursorIcon::ResizeVertical)
-                        if hover_pos
-                            .in_line([self.rect.left_top(), self.rect.right_top()], MARGIN) =>
+                        if local.in_line([self.rect.left_top(), self.rect.right_top()], MARGIN) =>
                     {
                         DragMode::ResizeTtoB(hover_pos)
                     }
                     Some(CursorIcon::ResizeVertical)
-                        if hover_pos.in_line(
+                        if local.in_line(
                             [self.rect.left_bottom(), self.rect.right_bottom()],
                             MARGIN,
                         ) =>
                     {
                         DragMode::ResizeBtoT(hover_pos)
                     }
-                    Some(CursorIcon::ResizeNwSe)
-                        if hover_pos.over(self.rect.left_top(), MARGIN) =>
-                    {
+                    Some(CursorIcon::ResizeNwSe) if local.over(self.rect.left_top(), MARGIN) => {
                         DragMode::ResizeTLtoBR(hover_pos)
                     }
                     Some(CursorIcon::ResizeNwSe)
-                        if hover_pos.over(self.rect.right_bottom(), MARGIN) =>
+                        if local.over(self.rect.right_bottom(), MARGIN) =>
                     {
                         DragMode::ResizeBRtoTL(hover_pos)
                     }
-                    Some(CursorIcon::ResizeNeSw)
-                        if hover_pos.over(self.rect.right_top(), MARGIN) =>
-                    {
+                    Some(CursorIcon::ResizeNeSw) if local.over(self.rect.right_top(), MARGIN) => {
                         DragMode::ResizeTRtoBL(hover_pos)
                     }
-                    Some(CursorIcon::ResizeNeSw)
-                        if hover_pos.over(self.rect.left_bottom(), MARGIN) =>
-                    {
+                    Some(CursorIcon::ResizeNeSw) if local.over(self.rect.left_bottom(), MARGIN) => {
                         DragMode::ResizeBLtoTR(hover_pos)
                     }
                     _ => DragMode::Move(hover_pos),
@@ -199,38 +277,13 @@ impl GraphFigure for RectFigure {
                 self.move_to(hover_pos, drag_started);
                 self.drag_mode = DragMode::Move(hover_pos);
             }
-            DragMode::Extend => {
-                self.rect.set_bottom(hover_pos.y);
-                self.rect.set_right(hover_pos.x);
-            }
-            DragMode::ResizeLtoR(_) => {
-                self.rect.set_left(hover_pos.x);
-            }
-            DragMode::ResizeRtoL(_) => {
-                self.rect.set_right(hover_pos.x);
-            }
-            DragMode::ResizeTtoB(_) => {
-                self.rect.set_top(hover_pos.y);
-            }
-            DragMode::ResizeBtoT(_) => {
-                self.rect.set_bottom(hover_pos.y);
-            }
-            DragMode::ResizeTLtoBR(_) => {
-                self.rect.set_left(hover_pos.x);
-                self.rect.set_top(hover_pos.y);
-            }
-            DragMode::ResizeBRtoTL(_) => {
-                self.rect.set_right(hover_pos.x);
-                self.rect.set_bottom(hover_pos.y);
-            }
-            DragMode::ResizeTRtoBL(_) => {
-                self.rect.set_right(hover_pos.x);
-                self.rect.set_top(hover_pos.y);
-            }
-            DragMode::ResizeBLtoTR(_) => {
-                self.rect.set_left(hover_pos.x);
-                self.rect.set_bottom(hover_pos.y);
+            DragMode::Rotate => {
+                // Rotation handle is above the center when figure is not rotated
+                let delta = hover_pos - self.rect.center();
+                self.rotation_radians = delta.y.atan2(delta.x) + FRAC_PI_2;
+                self.compute_connection_points();
             }
+            drag_mode => drag_mode.resize(&mut self.rect, self.unrotated(hover_pos)),
         }
 
         // Compute connection points if empty
@@ -242,6 +295,13 @@ impl GraphFigure for RectFigure {
 
     fn drag_released(&mut self, hover_pos: Pos2, _button: PointerButton) {
         self.dragged_by(hover_pos, _button);
+
+        // Moved or rotated rectangle keeps its size mode
+        let resized = !matches!(self.drag_mode, DragMode::Move(_) | DragMode::Rotate);
+        let size = self.rect.size();
+        if resized && size.x < AUTO_FIT_THRESHOLD.x && size.y < AUTO_FIT_THRESHOLD.y {
+            self.set_size_mode(SizeMode::AutoFitText);
+        }
     }
 
     fn double_click(&mut self) {
@@ -252,20 +312,257 @@ impl GraphFigure for RectFigure {
         self.rect
     }
 
+    fn bounding_rect(&self) -> Rect {
+        let corners = [
+            self.rect.left_top(),
+            self.rect.right_top(),
+            self.rect.right_bottom(),
+            self.rect.left_bottom(),
+        ];
+        Rect::from_points(&self.rotated_points(&corners))
+    }
+
+    fn set_rect(&mut self, rect: Rect) {
+        self.rect = rect;
+        self.compute_connection_points();
+    }
+
     fn connection_points(&self) -> &Vec<Pos2> {
         &self.connection_points
     }
+
+    fn type_name(&self) -> &'static str {
+        "rect"
+    }
+
+    fn label(&self) -> String {
+        self.text.text().to_owned()
+    }
+
+    fn set_text(&mut self, s: String) {
+        self.text.replace_text(s);
+    }
+
+    fn text_edited(&self) -> bool {
+        self.text_edit
+    }
+
+    fn font_family(&self) -> Option<FontFamily> {
+        Some(self.text.font_family().clone())
+    }
+
+    fn set_font_family(&mut self, family: FontFamily) {
+        self.text.set_font_family(family);
+    }
+
+    fn size_mode(&self) -> Option<SizeMode> {
+        Some(self.size_mode)
+    }
+
+    fn set_size_mode(&mut self, size_mode: SizeMode) {
+        if size_mode == SizeMode::AutoFitText && self.size_mode != size_mode {
+            // Text drawn into too small rectangle must become readable
+            self.text.set_font_size(AUTO_FIT_FONT_SIZE);
+            let size = self.rect.size().max(AUTO_FIT_THRESHOLD);
+            self.set_rect(Rect::from_min_size(self.rect.min, size));
+        }
+
+        self.size_mode = size_mode;
+    }
+
+    fn shadow(&self) -> Option<(Vec2, Color32)> {
+        Some((self.fb.shadow_offset, self.fb.shadow.shadow_color))
+    }
+
+    fn set_shadow(&mut self, offset: Vec2, color: Color32) {
+        self.fb.shadow_offset = offset;
+        self.fb.shadow.shadow_color = color;
+    }
+
+    fn tooltip(&self) -> Option<&str> {
+        self.tooltip.as_deref()
+    }
+
+    fn set_tooltip(&mut self, tooltip: Option<String>) {
+        self.tooltip = tooltip;
+    }
+
+    fn fill_color(&self) -> Color32 {
+        self.fb.fill.base_color()
+    }
+
+    fn to_canvas_commands(&self) -> Vec<CanvasCommand> {
+        vec![
+            CanvasCommand::FillRect(self.rect, self.fb.fill.base_color()),
+            CanvasCommand::StrokeRect(self.rect, self.fb.stroke),
+            CanvasCommand::DrawText {
+                pos: self.rect.shrink(10.).left_top(),
+                text: self.text.text().to_owned(),
+                font_size: self.text.font_size(),
+                color: Color32::BLUE,
+            },
+        ]
+    }
+
+    fn set_selection_offset(&mut self, offset: f32) {
+        self.selection_offset = offset;
+    }
+
+    fn to_kind(&self) -> Option<FigureKind> {
+        Some(FigureKind::Rect(self.clone()))
+    }
 }
 
 impl RectFigure {
-    fn zoom(&mut self, zoom_factor: f32, scroll_delta: Vec2) {
-        self.rect = self.rect.zoom(zoom_factor / self.zoom_factor);
-        self.zoom_factor = zoom_factor;
-        if self.scroll_delta != scroll_delta {
-            self.rect = self.rect.translate(scroll_delta);
-            self.scroll_delta = scroll_delta;
+    /// Construct figure with given position and text
+    pub fn new(id: Id, rect: Rect, text: String) -> Self {
+        let mut figure = Self {
+            id,
+            rect,
+            text: TextOps::new(text),
+            ..Default::default()
+        };
+        figure.compute_connection_points();
+        figure
+    }
+
+    /// Set style made by `FigureBasics` builder
+    pub fn with_basics(mut self, fb: FigureBasics) -> Self {
+        self.fb = fb;
+        self
+    }
+
+    /// Set fill style
+    pub fn with_fill(mut self, fill: FillStyle) -> Self {
+        self.fb.fill = fill;
+        self
+    }
+
+    /// Fill figure with color
+    pub fn with_fill_color(self, color: Color32) -> Self {
+        self.with_fill(FillStyle::Solid(color))
+    }
+
+    /// Fill figure with linear gradient
+    /// ### Arguments
+    /// * start_color - color at the start of gradient direction
+    /// * end_color - color at the end of gradient direction
+    /// * angle_deg - gradient direction, 0 means from left to right, 90 - from top to bottom
+    pub fn with_linear_gradient(
+        self,
+        start_color: Color32,
+        end_color: Color32,
+        angle_deg: f32,
+    ) -> Self {
+        self.with_fill(FillStyle::LinearGradient {
+            start_color,
+            end_color,
+            angle_deg,
+        })
+    }
+
+    /// Fill figure with radial gradient from its center to the border
+    pub fn with_radial_gradient(self, center_color: Color32, edge_color: Color32) -> Self {
+        self.with_fill(FillStyle::RadialGradient {
+            center_color,
+            edge_color,
+        })
+    }
+
+    /// Rotate figure clockwise around its center
+    pub fn with_rotation(mut self, radians: f32) -> Self {
+        self.rotation_radians = radians;
+        self.compute_connection_points();
+        self
+    }
+
+    /// Return fill style
+    pub fn fill(&self) -> FillStyle {
+        self.fb.fill
+    }
+
+    /// Return clockwise rotation around the center in radians
+    pub fn rotation(&self) -> f32 {
+        self.rotation_radians
+    }
+
+    /// Rotate point around the figure center by the figure rotation
+    fn rotated(&self, point: Pos2) -> Pos2 {
+        point.rotate_around(self.rect.center(), self.rotation_radians)
+    }
+
+    /// Rotate point around the figure center back into the unrotated figure
+    fn unrotated(&self, point: Pos2) -> Pos2 {
+        point.rotate_around(self.rect.center(), -self.rotation_radians)
+    }
+
+    /// Rotate points around the figure center by the figure rotation
+    fn rotated_points(&self, points: &[Pos2]) -> Vec<Pos2> {
+        points.iter().map(|point| self.rotated(*point)).collect()
+    }
+
+    /// Draw border, fill and shadow of the rotated figure. Shapes are built for the unrotated
+    /// figure and rotation is applied to each vertex
+    fn draw_rotated(&self, ui: &mut Ui, bg_color: Color32) {
+        let outline = rounded_rect_outline(self.rect.expand(-1.0), 10.);
+        match self.fb.fill {
+            fill if fill.is_gradient() && self.selected & SELECT_MODE_HOVER == 0 => {
+                let mut mesh = fill.mesh(self.rect, &outline);
+                for vertex in &mut mesh.vertices {
+                    vertex.pos = self.rotated(vertex.pos);
+                }
+                ui.painter().add(Shape::Mesh(mesh));
+            }
+            _ => {
+                ui.painter().add(Shape::convex_polygon(
+                    self.rotated_points(&outline),
+                    bg_color,
+                    Stroke::none(),
+                ));
+            }
+        }
+
+        let outline = rounded_rect_outline(self.rect, 10.);
+        ui.painter().add(Shape::closed_line(
+            self.rotated_points(&outline),
+            self.fb.stroke,
+        ));
+
+        let shadow_color = self.fb.shadow.shadow_color;
+        for shadow in self.fb.shadow_rects(self.rect) {
+            let corners = [
+                shadow.left_top(),
+                shadow.right_top(),
+                shadow.right_bottom(),
+                shadow.left_bottom(),
+            ];
+            ui.painter().add(Shape::convex_polygon(
+                self.rotated_points(&corners),
+                shadow_color,
+                Stroke::none(),
+            ));
         }
+    }
+
+    /// Enlarge rectangle so the text with padding fits inside
+    /// ### Arguments
+    /// * text_size - size of the laid out text
+    fn fit_text(&mut self, text_size: Vec2) {
+        let size = text_size + Vec2::splat(2. * self.text.padding());
+        if size.x > self.rect.width() || size.y > self.rect.height() {
+            self.rect.max = self.rect.min + self.rect.size().max(size);
+            self.compute_connection_points();
+        }
+    }
 
+    fn zoom(&mut self, zoom_factor: f32, scroll_delta: Vec2) {
+        self.rect = zoom_rect(
+            self.rect,
+            (self.zoom_factor, self.scroll_delta),
+            (zoom_factor, scroll_delta),
+        );
+        self.zoom_factor = zoom_factor;
+        self.scroll_delta = scroll_delta;
         self.compute_connection_points();
     }
 
@@ -283,43 +580,228 @@ impl RectFigure {
             let line = TwoPosLine::new([self.rect.left_bottom(), self.rect.left_top()]);
             self.connection_points
                 .extend_from_slice(&line.split(4)[1..4]);
+
+            if self.rotation_radians != 0. {
+                self.connection_points = self.rotated_points(&self.connection_points);
+            }
         }
     }
 
-    fn draw_resize_controls(&self, _ui: &mut Ui) {
-        // let rect = self.rect;
-        // let margin = MARGIN;
-        // let rect_pos = Pos2::new(rect.min.x - MARGIN / 2., rect.min.y - MARGIN / 2.);
-        // let rect_size = rect.size();
-        // let rect_right = rect_pos.x + rect_size.x;
-        // let rect_bottom = rect_pos.y + rect_size.y;
-        // let rect_center = rect_pos + rect_size / 2.0;
-
-        // let nw = rect_pos;
-        // let n = Pos2::new(rect_center.x, nw.y);
-        // let ne = Pos2::new(rect_right, nw.y);
-        // let e = Pos2::new(ne.x, rect_center.y);
-        // let se = Pos2::new(rect_right, rect_bottom);
-        // let s = Pos2::new(rect_center.x, se.y);
-        // let sw = Pos2::new(nw.x, se.y);
-        // let w = Pos2::new(nw.x, rect_center.y);
-
-        // let nw_rect = Rect::from_two_pos(nw, nw + Vec2::new(margin, margin));
-        // let n_rect = Rect::from_two_pos(n, n + Vec2::new(margin, margin));
-        // let ne_rect = Rect::from_two_pos(ne, ne + Vec2::new(margin, margin));
-        // let e_rect = Rect::from_two_pos(e, e + Vec2::new(margin, margin));
-        // let se_rect = Rect::from_two_pos(se, se + Vec2::new(margin, margin));
-        // let s_rect = Rect::from_two_pos(s, s + Vec2::new(margin, margin));
-        // let sw_rect = Rect::from_two_pos(sw, sw + Vec2::new(margin, margin));
-        // let w_rect = Rect::from_two_pos(w, w + Vec2::new(margin, margin));
-
-        // [
-        //     nw_rect, n_rect, ne_rect, e_rect, se_rect, s_rect, sw_rect, w_rect,
-        // ]
-        // .iter()
-        // .for_each(|r| {
-        //     ui.painter()
-        //         .rect_filled(*r, Rounding::none(), Color32::WHITE);
-        // });
+    /// Draw animated dashed border around selected figure
+    fn draw_selection_border(&self, ui: &mut Ui) {
+        let rect = self.rect.expand(3.);
+        let border = self.rotated_points(&[
+            rect.left_top(),
+            rect.right_top(),
+            rect.right_bottom(),
+            rect.left_bottom(),
+            rect.left_top(),
+        ]);
+
+        draw_selection_border(ui, &border, self.selection_offset, self.fb.selected_stroke);
+    }
+
+    /// Centers of the eight resize handles: corners and middles of the sides clockwise
+    /// starting from the left top corner
+    fn resize_handles(&self) -> [Pos2; 8] {
+        let rect = self.rect;
+        [
+            rect.left_top(),
+            rect.center_top(),
+            rect.right_top(),
+            rect.right_center(),
+            rect.right_bottom(),
+            rect.center_bottom(),
+            rect.left_bottom(),
+            rect.left_center(),
+        ]
+    }
+
+    /// Center of the rotation handle above the top side
+    fn rotation_handle(&self) -> Pos2 {
+        self.rotated(self.rect.center_top() - Vec2::new(0., ROTATION_HANDLE_OFFSET))
+    }
+
+    /// Draw small squares over resize handles and circle over rotation handle
+    fn draw_resize_controls(&self, ui: &mut Ui) {
+        for handle in self.resize_handles() {
+            ui.painter().rect_filled(
+                Rect::from_center_size(self.rotated(handle), Vec2::splat(RESIZE_HANDLE_SIZE)),
+                Rounding::none(),
+                Color32::WHITE,
+            );
+        }
+
+        ui.painter().circle(
+            self.rotation_handle(),
+            ROTATION_HANDLE_RADIUS,
+            Color32::WHITE,
+            self.fb.selected_stroke,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eframe::{
+        egui::{CentralPanel, Context, Id, PointerButton, RawInput},
+        emath::{pos2, vec2, Rect, Vec2},
+        epaint::{Color32, Shape},
+    };
+
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::{RectFigure, SizeMode, AUTO_FIT_FONT_SIZE, AUTO_FIT_THRESHOLD};
+    use crate::graph::{
+        shapes::{FigureBasics, SELECT_MODE_SELECTED},
+        DragMode, FillStyle, GraphFigure,
+    };
+
+    #[test]
+    fn test_drag_start_on_resize_handles() {
+        let mut figure = RectFigure::new(
+            Id::new(1),
+            Rect::from_min_max(pos2(100., 100.), pos2(300., 200.)),
+            "".to_owned(),
+        );
+        figure.select(SELECT_MODE_SELECTED);
+
+        let handles = figure.resize_handles();
+        let mut modes = vec![];
+        for handle in handles {
+            figure.drag_start(handle, PointerButton::Primary, 1.);
+            modes.push(figure.drag_mode);
+        }
+
+        assert!(matches!(modes[0], DragMode::ResizeTLtoBR(_)));
+        assert!(matches!(modes[1], DragMode::ResizeTtoB(_)));
+        assert!(matches!(modes[2], DragMode::ResizeTRtoBL(_)));
+        assert!(matches!(modes[3], DragMode::ResizeRtoL(_)));
+        assert!(matches!(modes[4], DragMode::ResizeBRtoTL(_)));
+        assert!(matches!(modes[5], DragMode::ResizeBtoT(_)));
+        assert!(matches!(modes[6], DragMode::ResizeBLtoTR(_)));
+        assert!(matches!(modes[7], DragMode::ResizeLtoR(_)));
+
+        figure.drag_start(pos2(200., 150.), PointerButton::Primary, 1.);
+        assert!(matches!(figure.drag_mode, DragMode::Move(_)));
+    }
+
+    #[test]
+    fn test_fill_serialization() {
+        let figure = RectFigure::new(
+            Id::new(1),
+            Rect::from_min_max(pos2(0., 0.), pos2(100., 50.)),
+            "".to_owned(),
+        )
+        .with_linear_gradient(Color32::RED, Color32::BLUE, 45.);
+
+        let json = serde_json::to_string(&figure).unwrap();
+        let restored: RectFigure = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.fill(), figure.fill());
+        assert_eq!(restored.fill_color(), Color32::RED);
+
+        // Figures saved before fill style was added get default fill
+        let restored: RectFigure = serde_json::from_str("{}").unwrap();
+        assert_eq!(restored.fill(), FillStyle::default());
+    }
+
+    #[test]
+    fn test_auto_fit_text() {
+        let mut figure = RectFigure::new(
+            Id::new(1),
+            Rect::from_min_max(pos2(0., 0.), pos2(100., 50.)),
+            "Text".to_owned(),
+        );
+        assert_eq!(figure.size_mode(), Some(SizeMode::Manual));
+
+        // Small rectangle is switched to fit text on drag release
+        figure.drag_start(pos2(200., 200.), PointerButton::Primary, 1.);
+        figure.drag_released(pos2(210., 205.), PointerButton::Primary);
+        assert_eq!(figure.size_mode(), Some(SizeMode::AutoFitText));
+        assert_eq!(figure.rect().size(), AUTO_FIT_THRESHOLD);
+        assert_eq!(figure.text.font_size(), AUTO_FIT_FONT_SIZE);
+
+        // Rectangle grows to fit text with padding and never shrinks
+        figure.fit_text(vec2(100., 20.));
+        assert_eq!(figure.rect().size(), vec2(120., 40.));
+        assert_eq!(figure.connection_points()[2], pos2(260., 200.));
+        figure.fit_text(vec2(10., 10.));
+        assert_eq!(figure.rect().size(), vec2(120., 40.));
+
+        let json = serde_json::to_string(&figure).unwrap();
+        let restored: RectFigure = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.size_mode(), Some(SizeMode::AutoFitText));
+
+        // Large rectangle keeps manual size
+        figure.set_size_mode(SizeMode::Manual);
+        figure.drag_start(pos2(400., 400.), PointerButton::Primary, 1.);
+        figure.drag_released(pos2(500., 450.), PointerButton::Primary);
+        assert_eq!(figure.size_mode(), Some(SizeMode::Manual));
+
+        // Small rectangle is not switched when it is just moved
+        figure.set_rect(Rect::from_min_size(pos2(0., 0.), vec2(28., 28.)));
+        figure.select(SELECT_MODE_SELECTED);
+        figure.drag_start(pos2(14., 14.), PointerButton::Primary, 1.);
+        assert!(matches!(figure.drag_mode, DragMode::Move(_)));
+        figure.drag_released(pos2(50., 50.), PointerButton::Primary);
+        assert_eq!(figure.size_mode(), Some(SizeMode::Manual));
+        assert_eq!(figure.rect().size(), vec2(28., 28.));
+    }
+
+    #[test]
+    fn test_draw_with_basics() {
+        let fill = Color32::from_rgb(10, 20, 30);
+        let mut figure = RectFigure::new(
+            Id::new(1),
+            Rect::from_min_max(pos2(10., 10.), pos2(110., 60.)),
+            "".to_owned(),
+        )
+        .with_basics(FigureBasics::new().fill_color(fill));
+        assert_eq!(figure.fill_color(), fill);
+
+        let output = Context::default().run(RawInput::default(), |ctx| {
+            CentralPanel::default().show(ctx, |ui| figure.draw(ui, 1., Vec2::ZERO));
+        });
+        let filled = |color: Color32| {
+            output
+                .shapes
+                .iter()
+                .any(|clipped| matches!(&clipped.1, Shape::Rect(rect) if rect.fill == color))
+        };
+        assert!(filled(fill));
+        assert!(!filled(FillStyle::default().base_color()));
+    }
+
+    #[test]
+    fn test_rotation() {
+        let mut figure = RectFigure::new(
+            Id::new(1),
+            Rect::from_min_max(pos2(0., 0.), pos2(100., 50.)),
+            "".to_owned(),
+        );
+        figure.select(SELECT_MODE_SELECTED);
+
+        // Drag rotation handle to the right of the center
+        figure.drag_start(figure.rotation_handle(), PointerButton::Primary, 1.);
+        assert!(matches!(figure.drag_mode, DragMode::Rotate));
+        figure.drag_released(pos2(150., 25.), PointerButton::Primary);
+        assert!((figure.rotation() - FRAC_PI_2).abs() < 1e-5);
+        assert_eq!(figure.rect().size(), vec2(100., 50.));
+
+        // Hit test is done in the unrotated figure
+        assert!(figure.contains(pos2(50., 70.)).is_some());
+        assert!(figure.contains(pos2(20., 25.)).is_none());
+
+        // Left top corner is rotated around the center
+        assert!(figure.connection_points()[0].distance(pos2(75., -25.)) < 1e-3);
+
+        // Bounding rectangle contains rotated figure
+        let bounds = figure.bounding_rect();
+        assert!(bounds.min.distance(pos2(25., -25.)) < 1e-3);
+        assert!(bounds.max.distance(pos2(75., 75.)) < 1e-3);
+
+        let json = serde_json::to_string(&figure).unwrap();
+        let restored: RectFigure = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.rotation(), figure.rotation());
     }
 }
@@ -0,0 +1,198 @@
+use std::cmp::Ordering;
+
+use eframe::emath::{pos2, Pos2, Rect};
+use serde::{Deserialize, Serialize};
+
+/// Distance kept between the route and figures it goes around
+const ROUTE_MARGIN: f32 = 20.;
+/// Penalty added to the route length for each bend, makes routes with less bends preferable
+const BEND_PENALTY: f32 = 10.;
+
+/// Defines how edge is drawn between its start and end points
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EdgeRouting {
+    /// Straight line
+    Straight,
+    /// Only horizontal and vertical segments
+    Orthogonal,
+}
+
+impl Default for EdgeRouting {
+    fn default() -> Self {
+        EdgeRouting::Straight
+    }
+}
+
+/// Remove repeated points and middle points of collinear segments. Result always contains
+/// at least two points
+fn simplify(points: Vec<Pos2>) -> Vec<Pos2> {
+    let mut result: Vec<Pos2> = Vec::with_capacity(points.len());
+    for point in points {
+        if result.last() == Some(&point) {
+            continue;
+        }
+
+        if let [.., a, b] = result[..] {
+            if (a.x == b.x && b.x == point.x) || (a.y == b.y && b.y == point.y) {
+                result.pop();
+            }
+        }
+        result.push(point);
+    }
+
+    if result.len() == 1 {
+        result.push(result[0]);
+    }
+    result
+}
+
+/// Count route segments crossing rectangles. Segments lying on the rectangle's border are allowed,
+/// edges start and end on the borders of connected figures
+fn crossings(points: &[Pos2], avoid: &[Rect]) -> usize {
+    points
+        .windows(2)
+        .map(|segment| {
+            let segment = Rect::from_two_pos(segment[0], segment[1]);
+            avoid
+                .iter()
+                .filter(|rect| segment.intersects(rect.shrink(1.)))
+                .count()
+        })
+        .sum()
+}
+
+/// Route cost: number of crossed rectangles first, then length with bend penalties
+fn cost(points: &[Pos2], avoid: &[Rect]) -> (usize, f32) {
+    let length: f32 = points
+        .windows(2)
+        .map(|segment| segment[0].distance(segment[1]))
+        .sum();
+    let bends = points.len().saturating_sub(2) as f32;
+    (crossings(points, avoid), length + bends * BEND_PENALTY)
+}
+
+/// Compute route between two points using only horizontal and vertical segments.
+/// Candidate routes are built through the middle point and around the given rectangles,
+/// the one crossing less rectangles, then the shortest one is chosen
+/// ### Arguments
+/// * start - route start point
+/// * end - route end point
+/// * avoid - rectangles route should not cross, usually connected figures
+/// ### Return
+/// route points including start and end points
+pub fn orthogonal_route(start: Pos2, end: Pos2, avoid: &[Rect]) -> Vec<Pos2> {
+    let middle = start + (end - start) / 2.;
+    let xs: Vec<f32> = std::iter::once(middle.x)
+        .chain(
+            avoid
+                .iter()
+                .flat_map(|rect| [rect.min.x - ROUTE_MARGIN, rect.max.x + ROUTE_MARGIN]),
+        )
+        .collect();
+    let ys: Vec<f32> = std::iter::once(middle.y)
+        .chain(
+            avoid
+                .iter()
+                .flat_map(|rect| [rect.min.y - ROUTE_MARGIN, rect.max.y + ROUTE_MARGIN]),
+        )
+        .collect();
+    let (xs, ys) = (xs.as_slice(), ys.as_slice());
+
+    // Horizontal, vertical, horizontal routes go first to be preferred when costs are equal
+    let simple = xs
+        .iter()
+        .map(|x| vec![start, pos2(*x, start.y), pos2(*x, end.y), end])
+        .chain(
+            ys.iter()
+                .map(|y| vec![start, pos2(start.x, *y), pos2(end.x, *y), end]),
+        )
+        .chain([
+            vec![start, pos2(end.x, start.y), end],
+            vec![start, pos2(start.x, end.y), end],
+        ]);
+
+    // Routes with four bends go around figures when simple routes cross them
+    let detours = xs.iter().flat_map(|x1| {
+        xs.iter().flat_map(move |x2| {
+            ys.iter().map(move |y| {
+                vec![
+                    start,
+                    pos2(*x1, start.y),
+                    pos2(*x1, *y),
+                    pos2(*x2, *y),
+                    pos2(*x2, end.y),
+                    end,
+                ]
+            })
+        })
+    });
+    let vertical_detours = ys.iter().flat_map(|y1| {
+        ys.iter().flat_map(move |y2| {
+            xs.iter().map(move |x| {
+                vec![
+                    start,
+                    pos2(start.x, *y1),
+                    pos2(*x, *y1),
+                    pos2(*x, *y2),
+                    pos2(end.x, *y2),
+                    end,
+                ]
+            })
+        })
+    });
+
+    simple
+        .chain(detours)
+        .chain(vertical_detours)
+        .map(simplify)
+        .map(|points| (cost(&points, avoid), points))
+        .min_by(|(a, _), (b, _)| {
+            a.0.cmp(&b.0)
+                .then_with(|| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
+        })
+        .map(|(_, points)| points)
+        .unwrap_or_else(|| vec![start, end])
+}
+
+#[cfg(test)]
+mod tests {
+    use eframe::emath::{pos2, Pos2, Rect};
+
+    use super::{crossings, orthogonal_route};
+
+    fn is_orthogonal(points: &[Pos2]) -> bool {
+        points
+            .windows(2)
+            .all(|s| s[0].x == s[1].x || s[0].y == s[1].y)
+    }
+
+    #[test]
+    fn test_orthogonal_route_without_obstacles() {
+        let route = orthogonal_route(pos2(0., 0.), pos2(100., 0.), &[]);
+        assert_eq!(route, vec![pos2(0., 0.), pos2(100., 0.)]);
+
+        let route = orthogonal_route(pos2(0., 0.), pos2(100., 50.), &[]);
+        assert_eq!(route.len(), 3);
+        assert_eq!(route.first(), Some(&pos2(0., 0.)));
+        assert_eq!(route.last(), Some(&pos2(100., 50.)));
+        assert!(is_orthogonal(&route));
+
+        let route = orthogonal_route(pos2(10., 10.), pos2(10., 10.), &[]);
+        assert_eq!(route, vec![pos2(10., 10.), pos2(10., 10.)]);
+    }
+
+    #[test]
+    fn test_orthogonal_route_avoids_figures() {
+        // Edge goes from the right side of the source to the left side of the target
+        // located to the left of the source
+        let source = Rect::from_min_max(pos2(200., 0.), pos2(300., 50.));
+        let target = Rect::from_min_max(pos2(0., 100.), pos2(100., 150.));
+        let avoid = [source, target];
+
+        let route = orthogonal_route(source.right_center(), target.left_center(), &avoid);
+        assert!(is_orthogonal(&route));
+        assert_eq!(crossings(&route, &avoid), 0);
+        assert_eq!(route.first(), Some(&source.right_center()));
+        assert_eq!(route.last(), Some(&target.left_center()));
+    }
+}
@@ -1,9 +1,21 @@
 use std::{cell::RefCell, ops, rc::Rc};
 
 use eframe::{
-    egui::{Color32, CursorIcon, Id, PointerButton, Pos2, Stroke, Ui, Vec2},
+    egui::{Color32, CursorIcon, FontFamily, Id, PointerButton, Pos2, Stroke, Ui, Vec2},
     emath::Rect,
+    epaint::{PathShape, Shape},
 };
+use serde::{Deserialize, Serialize};
+
+use super::{
+    dash::DashPattern,
+    fill::FillStyle,
+    group::GroupFigure,
+    persist::FigureKind,
+    utils::{dashed_path, PointMath},
+    SizeMode, Zoom,
+};
+use crate::rgraph::{CanvasCommand, PointAlgoritm};
 
 pub trait GraphUi {
     fn add_figure(&mut self, figure: Rc<RefCell<Box<dyn GraphFigure>>>);
@@ -38,8 +50,9 @@ pub const SELECT_MODE_HOVER: SelectMode = 1;
 pub const SELECT_MODE_SELECTED: SelectMode = 2;
 
 /// Trait Shape used to represents any shape to drawing into Graph
-/// derived from ShapeClone trait to implement Clone behaviour
 pub trait GraphFigure {
+    /// Make a copy of the figure, used because Box<dyn GraphFigure> can not be cloned
+    fn clone_box(&self) -> Box<dyn GraphFigure>;
     /// Set identifier of the shape
     fn set_id(&mut self, id: Id);
     /// Element identifier
@@ -62,36 +75,118 @@ pub trait GraphFigure {
     fn dragged_by(&mut self, hover_pos: Pos2, button: PointerButton);
     fn drag_released(&mut self, hover_pos: Pos2, button: PointerButton);
     fn double_click(&mut self);
+    /// Double click at the cursor position, figures which don't depend on the position
+    /// handle it as plain double click
+    fn double_click_at(&mut self, _pos: Pos2) {
+        self.double_click();
+    }
 
     /// Rectangle contained figure
     fn rect(&self) -> Rect;
 
+    /// Rectangle contained figure as it is drawn, differs from `rect` for rotated figures.
+    /// Used to select figures by area, compute export bounds and route edges around figure
+    fn bounding_rect(&self) -> Rect {
+        self.rect()
+    }
+
+    /// Replace rectangle contained figure. Used to restore figure size on undo,
+    /// figures which can not be resized ignore it
+    fn set_rect(&mut self, _rect: Rect) {}
+
     /// Point which can be used to connect to other figures. Only from these points lines can be drawn
     fn connection_points(&self) -> &Vec<Pos2>;
-}
 
-// /// Need to make Box<dyn Shape> cloneable
-// pub trait ShapeClone {
-//     /// Defines Box<dyn Shape> as cloneable
-//     fn clone_box(&self) -> Box<dyn GraphFigure>;
-// }
+    /// Short name of the figure type, used in exports
+    fn type_name(&self) -> &'static str {
+        "figure"
+    }
 
-// /// Implements clone_box to make Box<dyn Shape> cloneable
-// impl<T> ShapeClone for T
-// where
-//     T: 'static + GraphFigure + Clone,
-// {
-//     fn clone_box(&self) -> Box<dyn GraphFigure> {
-//         Box::new(self.clone())
-//     }
-// }
+    /// Text label of the figure
+    fn label(&self) -> String {
+        String::new()
+    }
 
-// /// Implements Clone for Box<dyn Shape>
-// impl Clone for Box<dyn GraphFigure> {
-//     fn clone(&self) -> Box<dyn GraphFigure> {
-//         self.clone_box()
-//     }
-// }
+    /// Replace text label of the figure, figures without label ignore it
+    fn set_text(&mut self, _s: String) {}
+
+    /// Return true while text label of the figure is edited
+    fn text_edited(&self) -> bool {
+        false
+    }
+
+    /// Font family of the text label, None for figures without label
+    fn font_family(&self) -> Option<FontFamily> {
+        None
+    }
+
+    /// Change font family of the text label, figures without label ignore it
+    fn set_font_family(&mut self, _family: FontFamily) {}
+
+    /// How figure size is defined, None for figures always sized by dragging
+    fn size_mode(&self) -> Option<SizeMode> {
+        None
+    }
+
+    /// Change how figure size is defined, figures without size modes ignore it
+    fn set_size_mode(&mut self, _size_mode: SizeMode) {}
+
+    /// Shadow offset and color, None for figures without shadow
+    fn shadow(&self) -> Option<(Vec2, Color32)> {
+        None
+    }
+
+    /// Change shadow of the figure, figures without shadow ignore it
+    fn set_shadow(&mut self, _offset: Vec2, _color: Color32) {}
+
+    /// Text shown when cursor is over the figure, None if figure has no tooltip
+    fn tooltip(&self) -> Option<&str> {
+        None
+    }
+
+    /// Change tooltip of the figure, figures without tooltip ignore it
+    fn set_tooltip(&mut self, _tooltip: Option<String>) {}
+
+    /// Figure fill color
+    fn fill_color(&self) -> Color32 {
+        Color32::TRANSPARENT
+    }
+
+    /// Convert figure into backend independent drawing commands. Used to export figure
+    fn to_canvas_commands(&self) -> Vec<CanvasCommand> {
+        let rect = self.rect();
+        let mut commands = vec![CanvasCommand::FillRect(rect, self.fill_color())];
+        let label = self.label();
+        if !label.is_empty() {
+            commands.push(CanvasCommand::DrawText {
+                pos: rect.left_top(),
+                text: label,
+                font_size: 14.,
+                color: Color32::BLACK,
+            });
+        }
+
+        commands
+    }
+
+    /// Set offset of the selection border dash pattern. Used to animate selection
+    fn set_selection_offset(&mut self, _offset: f32) {}
+
+    /// Return reference to group if figure is a group
+    fn as_group(&self) -> Option<&GroupFigure> {
+        None
+    }
+
+    /// Return mutable reference to group if figure is a group
+    fn as_group_mut(&mut self) -> Option<&mut GroupFigure> {
+        None
+    }
+
+    /// Convert figure into serializable form. Figures which can not be saved return None
+    fn to_kind(&self) -> Option<FigureKind> {
+        None
+    }
+}
 
 #[repr(u8)]
 #[derive(Clone, Copy, Debug)]
@@ -114,29 +209,211 @@ pub enum DragMode {
     ResizeTRtoBL(Pos2),
     /// BottomLeft to TopRight
     ResizeBLtoTR(Pos2),
+    /// Rotation around the center by rotation handle
+    Rotate,
 }
 
-#[derive(Clone, Debug)]
+impl DragMode {
+    /// Change bounding rectangle of the figure according to drag mode.
+    /// Move mode does not change rectangle, figure must be moved by itself
+    /// ### Arguments
+    /// * rect - bounding rectangle of the figure
+    /// * hover_pos - current cursor position
+    pub fn resize(&self, rect: &mut Rect, hover_pos: Pos2) {
+        match self {
+            DragMode::Move(_) | DragMode::Rotate => {}
+            DragMode::Extend => {
+                rect.set_bottom(hover_pos.y);
+                rect.set_right(hover_pos.x);
+            }
+            DragMode::ResizeLtoR(_) => {
+                rect.set_left(hover_pos.x);
+            }
+            DragMode::ResizeRtoL(_) => {
+                rect.set_right(hover_pos.x);
+            }
+            DragMode::ResizeTtoB(_) => {
+                rect.set_top(hover_pos.y);
+            }
+            DragMode::ResizeBtoT(_) => {
+                rect.set_bottom(hover_pos.y);
+            }
+            DragMode::ResizeTLtoBR(_) => {
+                rect.set_left(hover_pos.x);
+                rect.set_top(hover_pos.y);
+            }
+            DragMode::ResizeBRtoTL(_) => {
+                rect.set_right(hover_pos.x);
+                rect.set_bottom(hover_pos.y);
+            }
+            DragMode::ResizeTRtoBL(_) => {
+                rect.set_right(hover_pos.x);
+                rect.set_top(hover_pos.y);
+            }
+            DragMode::ResizeBLtoTR(_) => {
+                rect.set_left(hover_pos.x);
+                rect.set_bottom(hover_pos.y);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "FigureBasicsData")]
 pub struct FigureBasics {
-    pub fill_color: Color32,
+    /// Fill style, gradients are supported by rectangles only. Other figures are filled
+    /// with its base color
+    pub fill: FillStyle,
     pub selected_fill_color: Color32,
     pub stroke: Stroke,
+    /// Dash pattern of the stroke, supported by edges
+    #[serde(default)]
+    pub stroke_pattern: DashPattern,
     pub selected_stroke: Stroke,
     pub shadow: Shadow,
+    /// Offset of the shadow from the figure, supported by rectangles only
+    #[serde(default = "default_shadow_offset")]
+    pub shadow_offset: Vec2,
+}
+
+fn default_shadow_offset() -> Vec2 {
+    Vec2::new(4., 4.)
+}
+
+/// Serializable form of the figure style used for loading
+#[derive(Deserialize)]
+struct FigureBasicsData {
+    /// Kept to read styles saved before fill style was added
+    #[serde(default)]
+    fill_color: Option<Color32>,
+    #[serde(default)]
+    fill: Option<FillStyle>,
+    selected_fill_color: Color32,
+    stroke: Stroke,
+    #[serde(default)]
+    stroke_pattern: DashPattern,
+    selected_stroke: Stroke,
+    shadow: Shadow,
+    #[serde(default = "default_shadow_offset")]
+    shadow_offset: Vec2,
+}
+
+impl From<FigureBasicsData> for FigureBasics {
+    fn from(data: FigureBasicsData) -> Self {
+        Self {
+            fill: data
+                .fill
+                .or_else(|| data.fill_color.map(FillStyle::Solid))
+                .unwrap_or_default(),
+            selected_fill_color: data.selected_fill_color,
+            stroke: data.stroke,
+            stroke_pattern: data.stroke_pattern,
+            selected_stroke: data.selected_stroke,
+            shadow: data.shadow,
+            shadow_offset: data.shadow_offset,
+        }
+    }
 }
 
 impl Default for FigureBasics {
     fn default() -> Self {
         Self {
-            fill_color: Color32::from_rgba_premultiplied(100, 100, 50, 50),
+            fill: Default::default(),
             selected_fill_color: Color32::from_rgba_premultiplied(50, 100, 100, 50),
             stroke: Stroke::new(1., Color32::YELLOW),
-            selected_stroke: Default::default(),
+            stroke_pattern: Default::default(),
+            selected_stroke: Stroke::new(1., Color32::WHITE),
             shadow: Default::default(),
+            shadow_offset: default_shadow_offset(),
         }
     }
 }
 
+/// Builder methods for figure styling
+impl FigureBasics {
+    /// Create default style, same as `FigureBasics::default()`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set color used to fill the figure
+    pub fn fill_color(mut self, color: Color32) -> Self {
+        self.fill = FillStyle::Solid(color);
+        self
+    }
+
+    /// Set color of the figure border
+    pub fn stroke_color(mut self, color: Color32) -> Self {
+        self.stroke.color = color;
+        self
+    }
+
+    /// Set width of the figure border
+    pub fn stroke_width(mut self, width: f32) -> Self {
+        self.stroke.width = width;
+        self
+    }
+
+    /// Set color used to fill the selected figure
+    pub fn selected_fill(mut self, color: Color32) -> Self {
+        self.selected_fill_color = color;
+        self
+    }
+
+    /// Set shadow of the figure
+    /// ### Arguments
+    /// * place - shadow place flags, e.g. `ShadowPlace::Bottom | ShadowPlace::Right`
+    /// * color - shadow color
+    pub fn shadow(mut self, place: u8, color: Color32) -> Self {
+        self.shadow = Shadow {
+            shadow_color: color,
+            shadow_place: place,
+        };
+        self
+    }
+}
+
+impl FigureBasics {
+    /// Parts of the shadow visible around the figure. Shadow is the figure rectangle
+    /// moved by shadow offset, the part covered by the figure is cut out and only
+    /// the sides enabled in shadow place flags are kept
+    /// ### Arguments
+    /// * rect - rectangle of the figure
+    pub fn shadow_rects(&self, rect: Rect) -> Vec<Rect> {
+        let shadow = rect.translate(self.shadow_offset);
+        let flags = self.shadow.shadow_place;
+        let mut rects = vec![];
+        if ShadowPlace::check_flag(flags, ShadowPlace::Top) && shadow.min.y < rect.min.y {
+            rects.push(Rect::from_x_y_ranges(
+                shadow.x_range(),
+                shadow.min.y..=rect.min.y,
+            ));
+        }
+
+        if ShadowPlace::check_flag(flags, ShadowPlace::Bottom) && shadow.max.y > rect.max.y {
+            rects.push(Rect::from_x_y_ranges(
+                shadow.x_range(),
+                rect.max.y..=shadow.max.y,
+            ));
+        }
+
+        // Side parts are limited by the figure height, corners belong to top and bottom parts
+        let y_range = shadow.min.y.max(rect.min.y)..=shadow.max.y.min(rect.max.y);
+        if ShadowPlace::check_flag(flags, ShadowPlace::Left) && shadow.min.x < rect.min.x {
+            rects.push(Rect::from_x_y_ranges(
+                shadow.min.x..=rect.min.x,
+                y_range.clone(),
+            ));
+        }
+
+        if ShadowPlace::check_flag(flags, ShadowPlace::Right) && shadow.max.x > rect.max.x {
+            rects.push(Rect::from_x_y_ranges(rect.max.x..=shadow.max.x, y_range));
+        }
+
+        rects
+    }
+}
+
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ShadowPlace {
@@ -207,7 +484,7 @@ impl ops::BitOr for ShadowPlace {
 }
 
 #[repr(packed)]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Shadow {
     pub shadow_color: Color32,
     pub shadow_place: u8,
@@ -216,15 +493,125 @@ pub struct Shadow {
 impl Default for Shadow {
     fn default() -> Self {
         Self {
-            shadow_color: Default::default(),
+            shadow_color: Color32::from_black_alpha(60),
             shadow_place: ShadowPlace::Bottom | ShadowPlace::Right,
         }
     }
 }
 
+/// Length of the dash of the animated selection border
+pub const SELECTION_DASH: f32 = 6.;
+/// Length of the gap of the animated selection border
+pub const SELECTION_GAP: f32 = 4.;
+
+/// Compute rectangle of the figure for the new zoom factor and scroll delta
+/// ### Arguments
+/// * rect - current rectangle of the figure
+/// * from - zoom factor and scroll delta the rectangle is computed for
+/// * to - new zoom factor and scroll delta
+/// ### Return
+/// Zoomed and scrolled rectangle
+pub fn zoom_rect(rect: Rect, from: (f32, Vec2), to: (f32, Vec2)) -> Rect {
+    let rect = rect.zoom(to.0 / from.0);
+    if from.1 != to.1 {
+        rect.translate(to.1)
+    } else {
+        rect
+    }
+}
+
+/// Detect if point is near the border of the rectangle and compute resize cursor and drag
+/// mode. Corners resize the figure along both axes, sides - along one axis
+/// ### Arguments
+/// * rect - bounding rectangle of the figure
+/// * point - pointer position
+/// * margin - distance to the corner, distance to the side is a half of it
+/// ### Return
+/// Cursor icon and drag mode or None if point is not near the border
+pub fn resize_direction(rect: Rect, point: Pos2, margin: f32) -> Option<(CursorIcon, DragMode)> {
+    if !rect.expand(margin / 2.).contains(point) {
+        return None;
+    }
+
+    let corner = [
+        (
+            rect.left_top(),
+            CursorIcon::ResizeNwSe,
+            DragMode::ResizeTLtoBR(point),
+        ),
+        (
+            rect.right_bottom(),
+            CursorIcon::ResizeNwSe,
+            DragMode::ResizeBRtoTL(point),
+        ),
+        (
+            rect.right_top(),
+            CursorIcon::ResizeNeSw,
+            DragMode::ResizeTRtoBL(point),
+        ),
+        (
+            rect.left_bottom(),
+            CursorIcon::ResizeNeSw,
+            DragMode::ResizeBLtoTR(point),
+        ),
+    ]
+    .into_iter()
+    .find(|(corner, _, _)| point.over(*corner, margin))
+    .map(|(_, icon, drag_mode)| (icon, drag_mode));
+
+    corner.or_else(|| {
+        [
+            (
+                [rect.left_top(), rect.left_bottom()],
+                CursorIcon::ResizeHorizontal,
+                DragMode::ResizeLtoR(point),
+            ),
+            (
+                [rect.right_top(), rect.right_bottom()],
+                CursorIcon::ResizeHorizontal,
+                DragMode::ResizeRtoL(point),
+            ),
+            (
+                [rect.left_top(), rect.right_top()],
+                CursorIcon::ResizeVertical,
+                DragMode::ResizeTtoB(point),
+            ),
+            (
+                [rect.left_bottom(), rect.right_bottom()],
+                CursorIcon::ResizeVertical,
+                DragMode::ResizeBtoT(point),
+            ),
+        ]
+        .into_iter()
+        .find(|(line, _, _)| point.belong_line(line, margin / 2.))
+        .map(|(_, icon, drag_mode)| (icon, drag_mode))
+    })
+}
+
+/// Draw animated dashed border around selected figure
+/// ### Arguments
+/// * ui - egui object to draw into
+/// * border - closed polyline around the figure
+/// * offset - offset of the dash pattern, changed to animate the border
+/// * stroke - stroke of the border
+pub fn draw_selection_border(ui: &mut Ui, border: &[Pos2], offset: f32, stroke: Stroke) {
+    for dash in dashed_path(border, SELECTION_DASH, SELECTION_GAP, offset) {
+        ui.painter().add(Shape::Path(PathShape::line(dash, stroke)));
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::graph::shapes::ShadowPlace;
+    use eframe::{
+        egui::CursorIcon,
+        emath::{pos2, vec2, Rect},
+        epaint::{Color32, Stroke},
+    };
+
+    use crate::graph::{
+        shapes::{resize_direction, zoom_rect, FigureBasics, ShadowPlace},
+        DragMode, FillStyle,
+    };
 
     #[test]
     fn shadow_place_from() {
@@ -250,4 +637,86 @@ mod tests {
         assert!(!ShadowPlace::check_flag(flags, ShadowPlace::Right));
         assert!(!ShadowPlace::check_flag(flags, ShadowPlace::Left));
     }
+
+    #[test]
+    fn figure_basics_builder() {
+        let fb = FigureBasics::new()
+            .fill_color(Color32::BLUE)
+            .stroke_color(Color32::WHITE)
+            .stroke_width(2.)
+            .selected_fill(Color32::GREEN)
+            .shadow(ShadowPlace::Top.into_u8(), Color32::from_black_alpha(50));
+        assert_eq!(fb.fill, FillStyle::Solid(Color32::BLUE));
+        assert_eq!(fb.stroke, Stroke::new(2., Color32::WHITE));
+        assert_eq!(fb.selected_fill_color, Color32::GREEN);
+        assert_eq!(fb.shadow.shadow_place, ShadowPlace::Top.into_u8());
+        assert_eq!(fb.shadow.shadow_color, Color32::from_black_alpha(50));
+
+        // Not changed values are kept default
+        assert_eq!(fb.selected_stroke, FigureBasics::default().selected_stroke);
+    }
+
+    #[test]
+    fn figure_basics_legacy_fill_color() {
+        let mut json = serde_json::to_value(FigureBasics::default()).unwrap();
+        let fields = json.as_object_mut().unwrap();
+        fields.remove("fill");
+        fields.insert(
+            "fill_color".to_owned(),
+            serde_json::to_value(Color32::RED).unwrap(),
+        );
+
+        let fb: FigureBasics = serde_json::from_value(json).unwrap();
+        assert_eq!(fb.fill, FillStyle::Solid(Color32::RED));
+    }
+
+    #[test]
+    fn shadow_rects() {
+        let rect = Rect::from_min_max(pos2(0., 0.), pos2(100., 50.));
+        let mut fb = FigureBasics::default();
+        assert_eq!(
+            fb.shadow_rects(rect),
+            vec![
+                Rect::from_min_max(pos2(4., 50.), pos2(104., 54.)),
+                Rect::from_min_max(pos2(100., 4.), pos2(104., 50.)),
+            ]
+        );
+
+        // Sides opposite to the offset have no shadow
+        fb.shadow.shadow_place = ShadowPlace::Top | ShadowPlace::Right;
+        assert_eq!(
+            fb.shadow_rects(rect),
+            vec![Rect::from_min_max(pos2(100., 4.), pos2(104., 50.))]
+        );
+
+        fb.shadow_offset = vec2(-4., -4.);
+        assert_eq!(
+            fb.shadow_rects(rect),
+            vec![Rect::from_min_max(pos2(-4., -4.), pos2(96., 0.))]
+        );
+    }
+
+    #[test]
+    fn shared_rect_helpers() {
+        let rect = Rect::from_min_max(pos2(0., 0.), pos2(100., 50.));
+        assert!(matches!(
+            resize_direction(rect, pos2(1., 1.), 10.),
+            Some((CursorIcon::ResizeNwSe, DragMode::ResizeTLtoBR(_)))
+        ));
+        assert!(matches!(
+            resize_direction(rect, pos2(50., 49.), 10.),
+            Some((CursorIcon::ResizeVertical, DragMode::ResizeBtoT(_)))
+        ));
+        assert!(resize_direction(rect, pos2(50., 25.), 10.).is_none());
+        assert!(resize_direction(rect, pos2(200., 25.), 10.).is_none());
+
+        assert_eq!(
+            zoom_rect(rect, (1., vec2(0., 0.)), (2., vec2(0., 0.))),
+            Rect::from_min_max(pos2(0., 0.), pos2(200., 100.))
+        );
+        assert_eq!(
+            zoom_rect(rect, (1., vec2(0., 0.)), (1., vec2(10., 5.))),
+            Rect::from_min_max(pos2(10., 5.), pos2(110., 55.))
+        );
+    }
 }
@@ -6,38 +6,151 @@ use std::{
 use eframe::{
     egui::{Event, Id, Key, PointerButton, Sense, Ui},
     emath::Align2,
-    epaint::{text::cursor::CCursor, Color32, FontId, Galley, Pos2, Rect, Rounding, Stroke},
+    epaint::{
+        text::cursor::CCursor, Color32, FontFamily, FontId, Galley, Pos2, Rect, Rounding, Stroke,
+        Vec2,
+    },
 };
+use serde::{Deserialize, Serialize};
 
 const ADJ_RATIO: f32 = 1.3;
 
+/// Maximum number of text snapshots kept for undo
+const TEXT_HISTORY_CAPACITY: usize = 50;
+
+/// Serialize font with family as string tag: `Proportional`, `Monospace` or custom family name
+mod font_serde {
+    use std::sync::Arc;
+
+    use eframe::epaint::{FontFamily, FontId};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    const PROPORTIONAL: &str = "Proportional";
+    const MONOSPACE: &str = "Monospace";
+
+    #[derive(Serialize)]
+    struct FontRef<'a> {
+        size: f32,
+        family: &'a str,
+    }
+
+    /// Font family value. Fonts saved before families were tagged keep egui representation
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum FamilyValue {
+        Tag(String),
+        Family(FontFamily),
+    }
+
+    #[derive(Deserialize)]
+    struct FontValue {
+        size: f32,
+        family: FamilyValue,
+    }
+
+    pub fn serialize<S: Serializer>(font: &FontId, serializer: S) -> Result<S::Ok, S::Error> {
+        let family: &str = match &font.family {
+            FontFamily::Proportional => PROPORTIONAL,
+            FontFamily::Monospace => MONOSPACE,
+            FontFamily::Name(name) => name,
+        };
+
+        FontRef {
+            size: font.size,
+            family,
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<FontId, D::Error> {
+        let font = FontValue::deserialize(deserializer)?;
+        let family = match font.family {
+            FamilyValue::Tag(tag) if tag == PROPORTIONAL => FontFamily::Proportional,
+            FamilyValue::Tag(tag) if tag == MONOSPACE => FontFamily::Monospace,
+            FamilyValue::Tag(tag) => FontFamily::Name(Arc::from(tag)),
+            FamilyValue::Family(family) => family,
+        };
+        Ok(FontId::new(font.size, family))
+    }
+}
+
+/// Defines how text is laid out when it doesn't fit the figure width
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WordWrap {
+    /// Text is wrapped at the figure boundary and font is adjusted to the figure
+    Wrap,
+    /// Text is wrapped with the user font size, part out of the figure is not drawn
+    Clip,
+    /// Text is never wrapped and drawn with the user font size over the figure boundary
+    Expand,
+}
+
+impl Default for WordWrap {
+    fn default() -> Self {
+        WordWrap::Wrap
+    }
+}
+
 /// Text operations
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TextOps {
     text: Cow<'static, str>,
+    #[serde(with = "font_serde")]
     font: FontId,
     adj_ratio: f32,
+    #[serde(skip)]
     rect: Option<Rect>,
     edit_frame: bool,
     padding: f32,
+    #[serde(skip)]
     cursor_pos: usize,
+    /// Position where selection started, selection ends at cursor position
+    #[serde(skip)]
+    selection_start: Option<usize>,
     alignment: Align2,
+    /// Font size is not adjusted to the rectangle and text is not wrapped,
+    /// the owner fits its rectangle to the text instead
+    #[serde(skip)]
+    fixed_font: bool,
+    #[serde(default)]
+    word_wrap: WordWrap,
+    /// Font size used when the text is not wrapped to the figure
+    #[serde(default = "TextOps::default_font_size")]
+    user_font_size: f32,
+    /// Text and cursor position before each edit, independent of the diagram history
+    #[serde(skip)]
+    text_history: Vec<(String, usize)>,
+    /// Text and cursor position of the undone edits
+    #[serde(skip)]
+    text_redo: Vec<(String, usize)>,
 }
 
 impl TextOps {
-    pub fn new(text: &'static str) -> Self {
+    pub fn new(text: impl Into<Cow<'static, str>>) -> Self {
+        let text = text.into();
+        let cursor_pos = text.chars().count();
         Self {
-            text: Cow::Borrowed(text),
-            font: FontId::proportional(32.),
+            text,
+            font: FontId::proportional(Self::default_font_size()),
             adj_ratio: ADJ_RATIO,
             rect: None,
             edit_frame: true,
             padding: 10.,
-            cursor_pos: text.chars().count(),
+            cursor_pos,
+            selection_start: None,
             alignment: Align2::CENTER_CENTER,
+            fixed_font: false,
+            word_wrap: WordWrap::Wrap,
+            user_font_size: Self::default_font_size(),
+            text_history: vec![],
+            text_redo: vec![],
         }
     }
 
+    fn default_font_size() -> f32 {
+        32.
+    }
+
     #[allow(dead_code)]
     pub fn adj_ratio(mut self, r: f32) -> Self {
         self.adj_ratio = r;
@@ -56,9 +169,109 @@ impl TextOps {
         self
     }
 
+    /// Return current font size
+    pub fn font_size(&self) -> f32 {
+        self.font.size
+    }
+
+    /// Change font size, size is kept until the rectangle changes its aspect ratio.
+    /// If text is not wrapped the size is kept until changed by user
+    pub fn set_font_size(&mut self, size: f32) {
+        self.font.size = size;
+        self.user_font_size = size;
+        self.rect = None;
+    }
+
+    /// Return how text is laid out in the figure
+    #[allow(dead_code)]
+    pub fn word_wrap(&self) -> WordWrap {
+        self.word_wrap
+    }
+
+    /// Change how text is laid out in the figure. In `Clip` and `Expand` modes text is
+    /// drawn with the font size set by user
+    #[allow(dead_code)]
+    pub fn set_word_wrap(&mut self, mode: WordWrap) {
+        self.word_wrap = mode;
+        if mode != WordWrap::Wrap {
+            self.font.size = self.user_font_size;
+        }
+        self.rect = None;
+    }
+
+    /// Return padding between the text and the rectangle border
+    pub fn padding(&self) -> f32 {
+        self.padding
+    }
+
+    /// Keep font size and disable wrapping, so the galley size depends on the text only
+    pub fn set_fixed_font(&mut self, fixed_font: bool) {
+        self.fixed_font = fixed_font;
+    }
+
+    /// Return font family of the text
+    pub fn font_family(&self) -> &FontFamily {
+        &self.font.family
+    }
+
+    /// Change font family, font size is computed again on the next drawing
+    pub fn set_font_family(&mut self, family: FontFamily) {
+        self.font.family = family;
+        self.rect = None;
+    }
+
+    /// Return current text
+    pub fn text(&self) -> &str {
+        self.text.as_ref()
+    }
+
+    /// Return selected range of chars
+    /// ### Return
+    /// start and end char indexes, None if nothing selected
+    pub fn selection(&self) -> Option<(usize, usize)> {
+        self.selection_start
+            .filter(|start| *start != self.cursor_pos)
+            .map(|start| (start.min(self.cursor_pos), start.max(self.cursor_pos)))
+    }
+
+    /// Return selected part of the text, empty string if nothing selected
+    pub fn selected_text(&self) -> &str {
+        match self.selection() {
+            Some((start, end)) => {
+                let byte_index = |index: usize| {
+                    self.text
+                        .char_indices()
+                        .nth(index)
+                        .map(|(pos, _)| pos)
+                        .unwrap_or_else(|| self.text.len())
+                };
+                &self.text[byte_index(start)..byte_index(end)]
+            }
+            None => "",
+        }
+    }
+
+    /// Select whole text and move cursor to the end of it
+    pub fn select_all(&mut self) {
+        self.selection_start = Some(0);
+        self.cursor_pos = self.text.chars().count();
+    }
+
+    /// Replace text without ui, font size is computed again on the next drawing
+    pub fn replace_text(&mut self, text: String) {
+        self.cursor_pos = text.chars().count();
+        self.selection_start = None;
+        self.text = Cow::Owned(text);
+        self.rect = None;
+    }
+
     #[allow(dead_code)]
     pub fn set_text(&mut self, text: String, ui: &mut Ui, color: Color32) {
         self.text = Cow::Owned(text);
+        if self.word_wrap != WordWrap::Wrap {
+            return;
+        }
+
         // Compute new font size
         match self
             .rect
@@ -100,6 +313,8 @@ impl TextOps {
     /// - rc  -rectangle to drawing in
     /// - uui - egui object to drawing
     /// - color - text color
+    /// ### Return
+    /// size of the drawn text, None if the text is not drawn
     pub fn draw(
         &mut self,
         rc: Rect,
@@ -108,20 +323,25 @@ impl TextOps {
         color: Color32,
         bg_color: Color32,
         edited: &mut bool,
-    ) {
+    ) -> Option<Vec2> {
         // Skip drawing if region too small
         if rc.width() < 2. || rc.height() < 2. {
-            return;
+            return None;
         }
 
         // Load already adjasted font if possible
         let font = match self.rect {
+            _ if self.fixed_font || self.word_wrap != WordWrap::Wrap => Some(&self.font),
             Some(rect) if rc == rect => Some(&self.font),
             Some(rect) if rc.aspect_ratio() == rect.aspect_ratio() => Some(&self.font),
             _ => None,
         };
 
-        let width = rc.width();
+        let width = if self.fixed_font || self.word_wrap == WordWrap::Expand {
+            f32::INFINITY
+        } else {
+            rc.width()
+        };
 
         // Compute galley
         let galley = match font {
@@ -134,7 +354,7 @@ impl TextOps {
                 let font_size = self.compute_font_size(ui, rc, color);
                 if font_size < 1.0 {
                     // Font is too small to be drawing
-                    return;
+                    return None;
                 }
 
                 self.font.size = font_size;
@@ -166,7 +386,13 @@ impl TextOps {
             );
         }
 
-        ui.painter_at(rc).galley(rect.min, galley);
+        let size = galley.size();
+        // Expanded text is drawn over the figure boundary
+        match self.word_wrap {
+            WordWrap::Expand => ui.painter().galley(rect.min, galley),
+            WordWrap::Wrap | WordWrap::Clip => ui.painter_at(rc).galley(rect.min, galley),
+        }
+        Some(size)
     }
 
     /// Function add edit functional to text control
@@ -217,20 +443,66 @@ impl TextOps {
                 .line_segment([rect.right_bottom(), rect.left_bottom()], bg_stroke);
         }
 
+        self.draw_selection(ui, &galley, galley_pos);
         self.draw_cursor(ui, &galley, galley_pos);
 
         if resp.clicked_elsewhere() {
             ui.memory().lock_focus(id, false);
+            self.selection_start = None;
             *editable = false;
         }
 
+        let dragging = resp.is_pointer_button_down_on();
+        let mut copied = None;
         let add_text = ui
             .input()
             .events
             .iter()
             .fold(self.text.clone().into_owned(), |s, ev| match ev {
-                Event::Text(text) => self.insert_text(s, text),
-                Event::Paste(text) => self.insert_text(s, text),
+                Event::Text(text) | Event::Paste(text) => {
+                    let s = self.delete_selection(s);
+                    self.insert_text(s, text)
+                }
+                Event::Copy => {
+                    copied = Some(self.selected_text().to_owned());
+                    s
+                }
+                // Ctrl+X is handled by cut event only, so selection is cut once
+                Event::Cut => {
+                    copied = Some(self.selected_text().to_owned());
+                    self.delete_selection(s)
+                }
+                Event::Key {
+                    key: Key::Z,
+                    pressed: true,
+                    modifiers,
+                } if modifiers.command => self.undo_text(s),
+                Event::Key {
+                    key: Key::Y,
+                    pressed: true,
+                    modifiers,
+                } if modifiers.command => self.redo_text(s),
+                Event::Key {
+                    key: Key::A,
+                    pressed: true,
+                    modifiers,
+                } if modifiers.command => {
+                    self.select_all();
+                    s
+                }
+                Event::Key {
+                    key: Key::C,
+                    pressed: true,
+                    modifiers,
+                } if modifiers.command => {
+                    copied = Some(self.selected_text().to_owned());
+                    s
+                }
+                Event::Key {
+                    key: Key::Backspace | Key::Delete,
+                    pressed: true,
+                    ..
+                } if self.selection().is_some() => self.delete_selection(s),
                 Event::Key {
                     key: Key::Backspace,
                     pressed: true,
@@ -253,17 +525,39 @@ impl TextOps {
                     key: Key::Enter,
                     pressed: true,
                     ..
-                } => self.insert_text(s, "\n"),
+                } => {
+                    let s = self.delete_selection(s);
+                    self.insert_text(s, "\n")
+                }
                 Event::Key {
-                    key, pressed: true, ..
-                } => self.key_process(*key, &galley),
+                    key,
+                    pressed: true,
+                    modifiers,
+                } => {
+                    if Self::is_navigation_key(*key) {
+                        self.extend_selection(modifiers.shift);
+                    }
+                    self.key_process(*key, &galley)
+                }
                 Event::PointerButton {
                     pos,
                     button: PointerButton::Primary,
                     pressed: true,
-                    ..
+                    modifiers,
                 } => {
                     let cursor = galley.cursor_from_pos(*pos - galley_pos);
+                    if modifiers.shift {
+                        self.extend_selection(true);
+                    } else {
+                        // Selection starts here if pointer will be dragged
+                        self.selection_start = Some(cursor.ccursor.index);
+                    }
+                    self.cursor_pos = cursor.ccursor.index;
+                    s
+                }
+                Event::PointerMoved(pos) if dragging => {
+                    let cursor = galley.cursor_from_pos(*pos - galley_pos);
+                    self.extend_selection(true);
                     self.cursor_pos = cursor.ccursor.index;
                     s
                 }
@@ -273,11 +567,104 @@ impl TextOps {
                 }
             });
 
+        if let Some(text) = copied.filter(|text| !text.is_empty()) {
+            ui.output().copied_text = text;
+        }
+
         if self.text.borrow() != add_text {
             self.set_text(add_text, ui, color);
         }
     }
 
+    /// Return true if key moves cursor and can be used with Shift to select text
+    fn is_navigation_key(key: Key) -> bool {
+        matches!(
+            key,
+            Key::ArrowLeft | Key::ArrowRight | Key::ArrowUp | Key::ArrowDown | Key::Home | Key::End
+        )
+    }
+
+    /// Start or reset selection before cursor moves
+    /// ### Arguments
+    /// - extend - if true selection is started at current cursor position or extended if
+    ///   already started, otherwise selection is reset
+    fn extend_selection(&mut self, extend: bool) {
+        if !extend {
+            self.selection_start = None;
+        } else if self.selection_start.is_none() {
+            self.selection_start = Some(self.cursor_pos);
+        }
+    }
+
+    /// Remove selected chars and move cursor to the selection start. Text before removal
+    /// is stored in undo history
+    /// ### Arguments
+    /// - s - string from which selection will be removed
+    /// ### Returns
+    /// - string without selected chars
+    fn delete_selection(&mut self, s: String) -> String {
+        let res = match self.selection() {
+            Some((start, end)) => {
+                self.remember_text(&s);
+                self.cursor_pos = start;
+                s.chars()
+                    .enumerate()
+                    .filter(|(idx, _)| *idx < start || *idx >= end)
+                    .map(|(_, c)| c)
+                    .collect()
+            }
+            None => s,
+        };
+
+        self.selection_start = None;
+        res
+    }
+
+    /// Store text and cursor position before edit, redo history is cleared
+    /// ### Arguments
+    /// - s - text before edit
+    fn remember_text(&mut self, s: &str) {
+        if self.text_history.len() >= TEXT_HISTORY_CAPACITY {
+            self.text_history.remove(0);
+        }
+        self.text_history.push((s.to_owned(), self.cursor_pos));
+        self.text_redo.clear();
+    }
+
+    /// Restore text before the last edit
+    /// ### Arguments
+    /// - s - current text
+    /// ### Returns
+    /// - restored text, current text if there is nothing to undo
+    fn undo_text(&mut self, s: String) -> String {
+        match self.text_history.pop() {
+            Some((text, cursor_pos)) => {
+                self.text_redo.push((s, self.cursor_pos));
+                self.cursor_pos = cursor_pos;
+                self.selection_start = None;
+                text
+            }
+            None => s,
+        }
+    }
+
+    /// Restore text of the last undone edit
+    /// ### Arguments
+    /// - s - current text
+    /// ### Returns
+    /// - restored text, current text if there is nothing to redo
+    fn redo_text(&mut self, s: String) -> String {
+        match self.text_redo.pop() {
+            Some((text, cursor_pos)) => {
+                self.text_history.push((s, self.cursor_pos));
+                self.cursor_pos = cursor_pos;
+                self.selection_start = None;
+                text
+            }
+            None => s,
+        }
+    }
+
     /// Insert text at cursor
     /// ### Arguments
     /// - s - string to which new text will be inserted
@@ -285,6 +672,7 @@ impl TextOps {
     /// ### Returns
     /// - built string
     fn insert_text(&mut self, s: String, text: &str) -> String {
+        self.remember_text(&s);
         let count = s.chars().count();
         let s = if count > self.cursor_pos {
             let chars: Vec<char> = s.chars().collect();
@@ -305,11 +693,12 @@ impl TextOps {
     /// - s - string from which char will be removed
     /// - pos - posoition from which char will be removed
     /// new string
-    fn remove_char_at(&self, s: String, pos: usize) -> String {
+    fn remove_char_at(&mut self, s: String, pos: usize) -> String {
         let count = s.chars().count();
         if pos > count || (count == 0 || pos == 0) {
             s
         } else {
+            self.remember_text(&s);
             let mut sc = s.chars().collect::<Vec<char>>();
             if count == pos {
                 sc.truncate(sc.len() - 1);
@@ -351,6 +740,39 @@ impl TextOps {
         self.text.clone().into_owned()
     }
 
+    /// Fill rectangles of the selected text rows with highlight color
+    fn draw_selection(&self, ui: &mut Ui, galley: &Arc<Galley>, galley_pos: Pos2) {
+        let (start, end) = match self.selection() {
+            Some(selection) => selection,
+            None => return,
+        };
+
+        let start = galley.pos_from_ccursor(CCursor::new(start));
+        let end = galley.pos_from_ccursor(CCursor::new(end));
+        let color = ui.visuals().selection.bg_color.linear_multiply(0.5);
+        for row in &galley.rows {
+            // Skip rows out of the selection
+            if row.rect.bottom() <= start.top() || row.rect.top() >= end.bottom() {
+                continue;
+            }
+
+            let left = if row.rect.top() <= start.top() {
+                start.left()
+            } else {
+                row.rect.left()
+            };
+            let right = if row.rect.bottom() >= end.bottom() {
+                end.right()
+            } else {
+                row.rect.right()
+            };
+
+            let rect = Rect::from_x_y_ranges(left..=right, row.rect.y_range())
+                .translate(galley_pos.to_vec2());
+            ui.painter().rect_filled(rect, Rounding::none(), color);
+        }
+    }
+
     fn draw_cursor(&mut self, ui: &mut Ui, galley: &Arc<Galley>, galley_pos: Pos2) {
         let ccursor = CCursor::new(self.cursor_pos);
         let cursor = galley.from_ccursor(ccursor);
@@ -364,3 +786,165 @@ impl TextOps {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use eframe::epaint::{pos2, FontFamily, Rect};
+
+    use super::{TextOps, WordWrap};
+
+    #[test]
+    fn test_selected_text() {
+        let mut text = TextOps::new("Привет, world");
+        assert_eq!(text.selection(), None);
+        assert_eq!(text.selected_text(), "");
+
+        // Selection made backwards
+        text.selection_start = Some(13);
+        text.cursor_pos = 8;
+        assert_eq!(text.selection(), Some((8, 13)));
+        assert_eq!(text.selected_text(), "world");
+
+        text.selection_start = Some(0);
+        text.cursor_pos = 6;
+        assert_eq!(text.selected_text(), "Привет");
+
+        let s = text.delete_selection(text.text().to_owned());
+        assert_eq!(s, ", world");
+        assert_eq!(text.cursor_pos, 0);
+        assert_eq!(text.selection(), None);
+    }
+
+    #[test]
+    fn test_delete_selection_undo() {
+        let mut text = TextOps::new("Hello world");
+        text.selection_start = Some(5);
+        text.cursor_pos = 11;
+        let s = text.delete_selection(text.text().to_owned());
+        assert_eq!(s, "Hello");
+
+        let s = text.undo_text(s);
+        assert_eq!(s, "Hello world");
+        assert_eq!(text.cursor_pos, 11);
+
+        // Nothing is stored without selection
+        let s = text.delete_selection(s);
+        assert_eq!(s, "Hello world");
+        assert_eq!(text.undo_text(s), "Hello world");
+    }
+
+    #[test]
+    fn test_extend_selection() {
+        let mut text = TextOps::new("Hello");
+        text.extend_selection(true);
+        text.cursor_pos = 2;
+        text.extend_selection(true);
+        assert_eq!(text.selected_text(), "llo");
+
+        text.extend_selection(false);
+        assert_eq!(text.selection(), None);
+    }
+
+    #[test]
+    fn test_select_all() {
+        let mut text = TextOps::new("Привет");
+        text.cursor_pos = 2;
+        text.select_all();
+        assert_eq!(text.selection(), Some((0, 6)));
+        assert_eq!(text.selected_text(), "Привет");
+
+        let s = text.delete_selection(text.text().to_owned());
+        assert_eq!(s, "");
+        assert_eq!(text.cursor_pos, 0);
+    }
+
+    #[test]
+    fn test_text_undo() {
+        let mut text = TextOps::new("Hi");
+        let s = text.insert_text(text.text().to_owned(), "!");
+        let s = text.remove_char_at(s, 1);
+        assert_eq!(s, "i!");
+
+        let s = text.undo_text(s);
+        assert_eq!(s, "Hi!");
+        assert_eq!(text.cursor_pos, 3);
+        let s = text.undo_text(s);
+        assert_eq!(s, "Hi");
+        assert_eq!(text.cursor_pos, 2);
+        let s = text.undo_text(s);
+        assert_eq!(s, "Hi");
+
+        let s = text.redo_text(s);
+        assert_eq!(s, "Hi!");
+        assert_eq!(text.cursor_pos, 3);
+
+        // New edit clears redo history
+        let s = text.insert_text(s, "?");
+        assert_eq!(text.redo_text(s), "Hi!?");
+
+        for _ in 0..100 {
+            text.insert_text("".to_owned(), "a");
+        }
+        assert_eq!(text.text_history.len(), 50);
+    }
+
+    #[test]
+    fn test_word_wrap() {
+        let mut text = TextOps::new("Hello");
+        assert_eq!(text.word_wrap(), WordWrap::Wrap);
+        text.set_font_size(12.);
+
+        // Adjusted font size is replaced by the user one
+        text.font.size = 40.;
+        text.rect = Some(Rect::from_min_max(pos2(0., 0.), pos2(10., 10.)));
+        text.set_word_wrap(WordWrap::Clip);
+        assert_eq!(text.font_size(), 12.);
+        assert_eq!(text.rect, None);
+
+        let json = serde_json::to_value(&text).unwrap();
+        assert_eq!(json["word_wrap"], "Clip");
+        assert_eq!(json["user_font_size"], 12.);
+        let restored: TextOps = serde_json::from_value(json).unwrap();
+        assert_eq!(restored.word_wrap(), WordWrap::Clip);
+        assert_eq!(restored.user_font_size, 12.);
+
+        // Texts saved before word wrap mode was added
+        let mut json = serde_json::to_value(&text).unwrap();
+        json.as_object_mut().unwrap().remove("word_wrap");
+        json.as_object_mut().unwrap().remove("user_font_size");
+        let restored: TextOps = serde_json::from_value(json).unwrap();
+        assert_eq!(restored.word_wrap(), WordWrap::Wrap);
+        assert_eq!(restored.user_font_size, 32.);
+    }
+
+    #[test]
+    fn test_font_family_serialization() {
+        let mut text = TextOps::new("Hello");
+        text.rect = Some(Rect::from_min_max(pos2(0., 0.), pos2(10., 10.)));
+        text.set_font_family(FontFamily::Monospace);
+        assert_eq!(text.font_family(), &FontFamily::Monospace);
+        assert_eq!(text.rect, None);
+
+        let json = serde_json::to_value(&text).unwrap();
+        assert_eq!(json["font"]["family"], "Monospace");
+        let restored: TextOps = serde_json::from_value(json).unwrap();
+        assert_eq!(restored.font_family(), &FontFamily::Monospace);
+
+        text.set_font_family(FontFamily::Name(Arc::from("Hack")));
+        let json = serde_json::to_value(&text).unwrap();
+        assert_eq!(json["font"]["family"], "Hack");
+        let restored: TextOps = serde_json::from_value(json).unwrap();
+        assert_eq!(restored.font_family(), &FontFamily::Name(Arc::from("Hack")));
+
+        // Fonts saved by egui serialization
+        let mut json = serde_json::to_value(&text).unwrap();
+        json["font"]["family"] = serde_json::json!({ "Name": "Custom" });
+        let restored: TextOps = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            restored.font_family(),
+            &FontFamily::Name(Arc::from("Custom"))
+        );
+    }
+}
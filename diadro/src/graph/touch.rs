@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use eframe::{
+    egui::{TouchId, TouchPhase},
+    emath::{Pos2, Vec2},
+};
+
+/// Gesture recognized from the touch event
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TouchGesture {
+    /// One finger moved by the offset
+    Pan(Vec2),
+    /// Two fingers moved apart or together. Ratio is the distance between fingers after
+    /// the move divided by the distance before it
+    Pinch { ratio: f32, center: Pos2 },
+}
+
+/// What one finger does, decided by what was under the finger when it touched the canvas
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TouchMode {
+    /// Finger touched blank canvas, moving it scrolls the canvas
+    Pan,
+    /// Finger touched a figure or an edge, moving it drags them
+    Drag,
+}
+
+/// Positions of the fingers touching the screen
+#[derive(Clone, Debug, Default)]
+pub struct TouchState {
+    touches: HashMap<TouchId, Pos2>,
+    /// Mode of the current gesture, reset when the last finger leaves the screen
+    mode: Option<TouchMode>,
+}
+
+impl TouchState {
+    /// Return true if at least one finger touches the screen
+    pub fn is_active(&self) -> bool {
+        !self.touches.is_empty()
+    }
+
+    /// Return number of fingers touching the screen
+    pub fn count(&self) -> usize {
+        self.touches.len()
+    }
+
+    /// Return mode of the current gesture, None if no finger touches the screen
+    pub fn mode(&self) -> Option<TouchMode> {
+        self.mode
+    }
+
+    /// Set mode of the gesture started by the first finger
+    pub fn set_mode(&mut self, mode: TouchMode) {
+        self.mode = Some(mode);
+    }
+
+    /// Update finger positions by the touch event
+    /// ### Arguments
+    /// * id - finger identifier
+    /// * phase - touch phase of the event
+    /// * pos - finger position
+    /// ### Return
+    /// pan for one moved finger of the pan gesture, pinch for one of two moved fingers,
+    /// None otherwise
+    pub fn process(&mut self, id: TouchId, phase: TouchPhase, pos: Pos2) -> Option<TouchGesture> {
+        match phase {
+            TouchPhase::Start => {
+                self.touches.insert(id, pos);
+                None
+            }
+            TouchPhase::Move => {
+                let previous = self.touches.insert(id, pos)?;
+                match self.touches.len() {
+                    1 if self.mode == Some(TouchMode::Pan) => {
+                        Some(TouchGesture::Pan(pos - previous))
+                    }
+                    2 => {
+                        let other = self
+                            .touches
+                            .iter()
+                            .find(|(other_id, _)| **other_id != id)
+                            .map(|(_, other)| *other)?;
+                        let before = previous.distance(other);
+                        (before > 0.).then(|| TouchGesture::Pinch {
+                            ratio: pos.distance(other) / before,
+                            center: other + (pos - other) / 2.,
+                        })
+                    }
+                    _ => None,
+                }
+            }
+            TouchPhase::End | TouchPhase::Cancel => {
+                self.touches.remove(&id);
+                if self.touches.is_empty() {
+                    self.mode = None;
+                }
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eframe::{
+        egui::{TouchId, TouchPhase},
+        emath::{pos2, vec2},
+    };
+
+    use super::{TouchGesture, TouchMode, TouchState};
+
+    #[test]
+    fn test_pan() {
+        let mut touches = TouchState::default();
+        assert!(!touches.is_active());
+        touches.set_mode(TouchMode::Pan);
+        assert_eq!(
+            touches.process(TouchId(1), TouchPhase::Start, pos2(10., 10.)),
+            None
+        );
+        assert!(touches.is_active());
+        assert_eq!(
+            touches.process(TouchId(1), TouchPhase::Move, pos2(15., 8.)),
+            Some(TouchGesture::Pan(vec2(5., -2.)))
+        );
+        assert_eq!(
+            touches.process(TouchId(1), TouchPhase::End, pos2(15., 8.)),
+            None
+        );
+        assert!(!touches.is_active());
+        assert_eq!(touches.mode(), None);
+
+        // Finger dragging figure doesn't scroll
+        touches.set_mode(TouchMode::Drag);
+        touches.process(TouchId(1), TouchPhase::Start, pos2(10., 10.));
+        assert_eq!(
+            touches.process(TouchId(1), TouchPhase::Move, pos2(15., 8.)),
+            None
+        );
+        touches.process(TouchId(1), TouchPhase::End, pos2(15., 8.));
+
+        // Move of unknown finger is ignored
+        assert_eq!(
+            touches.process(TouchId(2), TouchPhase::Move, pos2(15., 8.)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_pinch() {
+        let mut touches = TouchState::default();
+        touches.process(TouchId(1), TouchPhase::Start, pos2(0., 0.));
+        touches.process(TouchId(2), TouchPhase::Start, pos2(100., 0.));
+        assert_eq!(touches.count(), 2);
+
+        assert_eq!(
+            touches.process(TouchId(2), TouchPhase::Move, pos2(200., 0.)),
+            Some(TouchGesture::Pinch {
+                ratio: 2.,
+                center: pos2(100., 0.)
+            })
+        );
+        assert_eq!(
+            touches.process(TouchId(1), TouchPhase::Move, pos2(100., 0.)),
+            Some(TouchGesture::Pinch {
+                ratio: 0.5,
+                center: pos2(150., 0.)
+            })
+        );
+
+        // The third finger doesn't change zoom
+        touches.process(TouchId(3), TouchPhase::Start, pos2(50., 50.));
+        assert_eq!(
+            touches.process(TouchId(3), TouchPhase::Move, pos2(60., 50.)),
+            None
+        );
+        touches.process(TouchId(3), TouchPhase::Cancel, pos2(60., 50.));
+        assert_eq!(touches.count(), 2);
+    }
+}
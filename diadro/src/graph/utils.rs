@@ -1,5 +1,6 @@
 use eframe::egui::{Pos2, Vec2};
 use eframe::emath::{pos2, Rect};
+use serde::{Deserialize, Serialize};
 
 /// Trait describes zoom functional
 pub trait Zoom {
@@ -48,6 +49,11 @@ pub trait PointMath {
     /// - pos - point to which current point is compared
     /// - tolerance - tolerance
     fn over(&self, pos: Pos2, tolerance: f32) -> bool;
+    /// Return point rotated around center
+    /// ### Arguments
+    /// - center - center of rotation
+    /// - angle - rotation angle in radians, positive angle rotates clockwise on screen
+    fn rotate_around(&self, center: Pos2, angle: f32) -> Pos2;
 }
 
 /// Implies point mathematics for Pos2
@@ -71,17 +77,31 @@ impl PointMath for Pos2 {
     fn over(&self, pos: Pos2, tolerance: f32) -> bool {
         self.distance(pos) < tolerance
     }
+
+    #[inline]
+    fn rotate_around(&self, center: Pos2, angle: f32) -> Pos2 {
+        let (sin, cos) = angle.sin_cos();
+        let delta = *self - center;
+        center + Vec2::new(cos * delta.x - sin * delta.y, sin * delta.x + cos * delta.y)
+    }
 }
 
 /// Struct defines two ppoints line
 #[allow(dead_code)]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TwoPosLine {
     start: Pos2,
     end: Pos2,
     angle: f32,
 }
 
+/// Zero-length line at origin
+impl Default for TwoPosLine {
+    fn default() -> Self {
+        Self::new([Pos2::ZERO, Pos2::ZERO])
+    }
+}
+
 /// Implies some functions for two point lines
 #[allow(dead_code)]
 impl TwoPosLine {
@@ -201,6 +221,35 @@ impl TwoPosLine {
         res.push(self.end);
         res
     }
+
+    /// Distance between start and end points
+    #[inline]
+    pub fn length(&self) -> f32 {
+        self.start.distance(self.end)
+    }
+
+    /// Point of the line at the given parameter
+    /// ### Arguments
+    /// * t - parameter in range [0, 1], 0 gives start point and 1 gives end point
+    #[inline]
+    pub fn interpolate(&self, t: f32) -> Pos2 {
+        self.start + (self.end - self.start) * t
+    }
+
+    /// Point of the segment nearest to the given point. Point is projected onto the line
+    /// and projection is clamped to the segment ends
+    /// ### Arguments
+    /// * point - point to project
+    pub fn closest_point(&self, point: Pos2) -> Pos2 {
+        let direction = self.end - self.start;
+        let length_sq = direction.length_sq();
+        if length_sq == 0. {
+            return self.start;
+        }
+
+        let t = (point - self.start).dot(direction) / length_sq;
+        self.interpolate(t.clamp(0., 1.))
+    }
 }
 
 /// Implies zoom functionality for two points line
@@ -248,6 +297,64 @@ pub fn pos_by_angle(start: Pos2, angle: f32, distance: f32) -> Pos2 {
     )
 }
 
+/// Split polyline into dashes
+/// ### Arguments
+///  - points - polyline points
+///  - dash - dash length
+///  - gap - gap length between dashes
+///  - offset - offset of the dash pattern from the polyline start
+/// ### Return
+///  list of polylines, one for each dash
+pub fn dashed_path(points: &[Pos2], dash: f32, gap: f32, offset: f32) -> Vec<Vec<Pos2>> {
+    let mut dashes = vec![];
+    let period = dash + gap;
+    if points.len() < 2 || dash <= 0. || gap < 0. {
+        return dashes;
+    }
+
+    // Position inside dash pattern
+    let mut phase = offset.rem_euclid(period);
+    let mut current: Vec<Pos2> = vec![];
+    for segment in points.windows(2) {
+        let (start, end) = (segment[0], segment[1]);
+        let length = start.distance(end);
+        let mut pos = 0.;
+        while pos < length {
+            let in_dash = phase < dash;
+            let step = if in_dash {
+                dash - phase
+            } else {
+                period - phase
+            }
+            .min(length - pos);
+            let dash_start = start.lerp(end, pos / length);
+            pos += step;
+            phase += step;
+
+            if in_dash {
+                if current.is_empty() {
+                    current.push(dash_start);
+                }
+                current.push(start.lerp(end, pos / length));
+
+                if phase >= dash {
+                    dashes.push(std::mem::take(&mut current));
+                }
+            }
+
+            if phase >= period {
+                phase -= period;
+            }
+        }
+    }
+
+    if current.len() > 1 {
+        dashes.push(current);
+    }
+
+    dashes
+}
+
 #[cfg(test)]
 mod tests {
     use std::{
@@ -262,7 +369,26 @@ mod tests {
 
     use crate::graph::utils::PointMath;
 
-    use super::{TwoPosLine, Zoom};
+    use super::{dashed_path, TwoPosLine, Zoom};
+
+    #[test]
+    fn test_line_geometry() {
+        let line = TwoPosLine::new([pos2(0., 0.), pos2(30., 40.)]);
+        assert_eq!(line.length(), 50.);
+        assert_eq!(line.interpolate(0.), pos2(0., 0.));
+        assert_eq!(line.interpolate(0.5), pos2(15., 20.));
+        assert_eq!(line.interpolate(1.), pos2(30., 40.));
+
+        let line = TwoPosLine::new([pos2(0., 0.), pos2(10., 0.)]);
+        assert_eq!(line.closest_point(pos2(4., 5.)), pos2(4., 0.));
+        assert_eq!(line.closest_point(pos2(-3., -1.)), pos2(0., 0.));
+        assert_eq!(line.closest_point(pos2(15., 2.)), pos2(10., 0.));
+
+        // Degenerate line
+        let line = TwoPosLine::new([pos2(1., 1.), pos2(1., 1.)]);
+        assert_eq!(line.length(), 0.);
+        assert_eq!(line.closest_point(pos2(5., 5.)), pos2(1., 1.));
+    }
 
     #[test]
     fn test_shift_line() {
@@ -349,6 +475,25 @@ mod tests {
         assert_eq!(slice.len(), 4);
     }
 
+    #[test]
+    fn test_dashed_path() {
+        let line = [pos2(0., 0.), pos2(10., 0.)];
+        let dashes = dashed_path(&line, 2., 2., 0.);
+        assert_eq!(dashes.len(), 3);
+        assert_eq!(dashes[0], vec![pos2(0., 0.), pos2(2., 0.)]);
+        assert_eq!(dashes[2], vec![pos2(8., 0.), pos2(10., 0.)]);
+
+        let dashes = dashed_path(&line, 2., 2., 1.);
+        assert_eq!(dashes.len(), 3);
+        assert_eq!(dashes[0], vec![pos2(0., 0.), pos2(1., 0.)]);
+        assert_eq!(dashes[1], vec![pos2(3., 0.), pos2(5., 0.)]);
+
+        // Dash continues over the corner
+        let corner = [pos2(0., 0.), pos2(3., 0.), pos2(3., 3.)];
+        let dashes = dashed_path(&corner, 4., 1., 0.);
+        assert_eq!(dashes[0], vec![pos2(0., 0.), pos2(3., 0.), pos2(3., 1.)]);
+    }
+
     #[test]
     fn test_read_mxgraph_compressed_xml() {
         use minidom::Element;
@@ -0,0 +1,586 @@
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::HashMap,
+    fmt::Write as _,
+    io::{Read, Write},
+    rc::Rc,
+};
+
+use eframe::{
+    egui::Id,
+    emath::{pos2, vec2, Pos2, Rect},
+    epaint::Color32,
+};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use minidom::Element;
+
+use super::ImportErrors;
+use crate::{
+    graph::{
+        ArrowFigure, ConnectionPoint, CylinderFigure, DiamondFigure, EllipseFigure, GraphFigure,
+        Graphics, RectFigure,
+    },
+    rgraph::xml_escape,
+};
+
+/// draw.io documents have no namespace, but minidom requires it
+const DRAWIO_NS: &str = "http://www.jgraph.com/drawio";
+
+/// Identifier of the layer all exported cells belong to
+const LAYER_ID: &str = "1";
+
+/// Convert color to draw.io hex string #RRGGBB
+#[inline]
+fn drawio_color(color: Color32) -> String {
+    let [r, g, b, _] = color.to_srgba_unmultiplied();
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Map figure type to draw.io style
+fn figure_style(figure: &dyn GraphFigure) -> String {
+    let shape = match figure.type_name() {
+        "rect" => "rounded=1;whiteSpace=wrap;html=1;",
+        "ellipse" => "ellipse;whiteSpace=wrap;html=1;",
+        "diamond" => "rhombus;whiteSpace=wrap;html=1;",
+        "cylinder" => "shape=cylinder3;whiteSpace=wrap;html=1;",
+        _ => "whiteSpace=wrap;html=1;",
+    };
+
+    let fill = figure.fill_color();
+    if fill.a() == 0 {
+        format!("{}fillColor=none;", shape)
+    } else {
+        format!("{}fillColor={};", shape, drawio_color(fill))
+    }
+}
+
+/// Compute relative position of the connection point inside figure rectangle
+fn connection_fraction(connection: &ConnectionPoint) -> Option<(f32, f32)> {
+    let rect = RefCell::borrow(connection.get_figure()).rect();
+    connection.get_connection_pos().map(|pos| {
+        (
+            (pos.x - rect.min.x) / rect.width(),
+            (pos.y - rect.min.y) / rect.height(),
+        )
+    })
+}
+
+/// Collect figures to export, group members are exported as separate cells
+fn flatten_figures(
+    figures: &[Rc<RefCell<Box<dyn GraphFigure>>>],
+    output: &mut Vec<Rc<RefCell<Box<dyn GraphFigure>>>>,
+) {
+    for figure in figures {
+        match RefCell::borrow(figure).as_group() {
+            Some(group) => flatten_figures(group.members(), output),
+            None => output.push(figure.clone()),
+        }
+    }
+}
+
+/// Build uncompressed `mxGraphModel` document
+fn graph_model(graphics: &Graphics) -> String {
+    let mut figures = vec![];
+    flatten_figures(graphics.figures(), &mut figures);
+
+    // draw.io identifiers are assigned in order of figures
+    let cell_ids: HashMap<Id, String> = figures
+        .iter()
+        .enumerate()
+        .map(|(idx, figure)| (RefCell::borrow(figure).id(), format!("v{}", idx)))
+        .collect();
+
+    let mut xml = format!(
+        r#"<mxGraphModel><root><mxCell id="0"/><mxCell id="{}" parent="0"/>"#,
+        LAYER_ID
+    );
+
+    // Writing into String never fails
+    for figure in &figures {
+        let figure = RefCell::borrow(figure);
+        let rect = figure.rect();
+        let _ = write!(
+            xml,
+            r#"<mxCell id="{}" value="{}" style="{}" vertex="1" parent="{}"><mxGeometry x="{}" y="{}" width="{}" height="{}" as="geometry"/></mxCell>"#,
+            cell_ids[&figure.id()],
+            xml_escape(&figure.label()),
+            xml_escape(&figure_style(&**figure)),
+            LAYER_ID,
+            rect.min.x,
+            rect.min.y,
+            rect.width(),
+            rect.height()
+        );
+    }
+
+    for (idx, edge) in graphics.edges().iter().enumerate() {
+        let mut style = format!(
+            "endArrow={};startArrow={};html=1;strokeColor={};",
+            if edge.end_arrow() { "classic" } else { "none" },
+            if edge.start_arrow() {
+                "classic"
+            } else {
+                "none"
+            },
+            drawio_color(edge.stroke().color)
+        );
+
+        let mut attrs = String::new();
+        let mut points = String::new();
+        for (connection, pos, attr, prefix) in [
+            (
+                edge.get_start_connection(),
+                edge.compute_start_point(),
+                "source",
+                "exit",
+            ),
+            (
+                edge.get_end_connection(),
+                edge.compute_end_point(),
+                "target",
+                "entry",
+            ),
+        ] {
+            let cell_id = connection
+                .as_ref()
+                .and_then(|cp| cell_ids.get(&RefCell::borrow(cp.get_figure()).id()));
+
+            match (connection, cell_id) {
+                (Some(connection), Some(cell_id)) => {
+                    let _ = write!(attrs, r#" {}="{}""#, attr, cell_id);
+                    if let Some((x, y)) = connection_fraction(connection) {
+                        let _ = write!(style, "{}X={};{}Y={};", prefix, x, prefix, y);
+                    }
+                }
+                _ => {
+                    let _ = write!(
+                        points,
+                        r#"<mxPoint x="{}" y="{}" as="{}Point"/>"#,
+                        pos.x, pos.y, attr
+                    );
+                }
+            }
+        }
+
+        let _ = write!(
+            xml,
+            r#"<mxCell id="e{}" value="{}" style="{}" edge="1" parent="{}"{}><mxGeometry relative="1" as="geometry">{}</mxGeometry></mxCell>"#,
+            idx,
+            xml_escape(&edge.label()),
+            xml_escape(&style),
+            LAYER_ID,
+            attrs,
+            points
+        );
+    }
+
+    xml.push_str("</root></mxGraphModel>");
+    xml
+}
+
+/// Compress diagram content the same way draw.io does: URL-encode, deflate, base64
+fn compress(content: &str) -> Result<String, std::io::Error> {
+    let mut encoder = DeflateEncoder::new(vec![], Compression::default());
+    encoder.write_all(urlencoding::encode(content).as_bytes())?;
+    Ok(base64::encode(encoder.finish()?))
+}
+
+/// Decompress diagram content: base64, inflate, URL-decode
+fn decompress(content: &str) -> Result<String, ImportErrors> {
+    let data = base64::decode(content.trim())?;
+    let mut decoder = DeflateDecoder::new(data.as_slice());
+    let mut text = String::new();
+    decoder.read_to_string(&mut text)?;
+    Ok(urlencoding::decode(&text)?.into_owned())
+}
+
+/// Export diagram into draw.io document with compressed content
+/// ### Arguments
+/// * graphics - exported diagram
+/// ### Return
+/// draw.io XML document
+pub fn export_drawio(graphics: &Graphics) -> String {
+    match compress(&graph_model(graphics)) {
+        Ok(content) => format!(
+            r#"<mxfile host="diadro"><diagram id="diadro" name="Page-1">{}</diagram></mxfile>"#,
+            content
+        ),
+        Err(err) => {
+            tracing::error!("Error compressing draw.io diagram: {}", err);
+            String::new()
+        }
+    }
+}
+
+/// Parse XML document adding namespace to the root element if it is missing
+fn parse_element(xml: &str) -> Result<Element, ImportErrors> {
+    // Root element is the first tag which is not declaration, comment or doctype
+    let root = xml
+        .match_indices('<')
+        .map(|(idx, _)| idx)
+        .find(|idx| {
+            xml[idx + 1..]
+                .chars()
+                .next()
+                .is_some_and(char::is_alphabetic)
+        })
+        .ok_or_else(|| ImportErrors::WrongFormat("No root element".to_owned()))?;
+
+    let tag_end = xml[root..].find('>').map_or(xml.len(), |end| root + end);
+    if xml[root..tag_end].contains("xmlns") {
+        return Ok(xml.parse()?);
+    }
+
+    let name_end = xml[root + 1..tag_end]
+        .find(|c: char| c.is_whitespace() || c == '/')
+        .map_or(tag_end, |end| root + 1 + end);
+
+    let xml = format!(
+        r#"{} xmlns="{}"{}"#,
+        &xml[..name_end],
+        DRAWIO_NS,
+        &xml[name_end..]
+    );
+    Ok(xml.parse()?)
+}
+
+/// Find `mxGraphModel` element in the document, decompressing diagram content if required
+fn graph_model_element(root: Element) -> Result<Element, ImportErrors> {
+    if root.name() == "mxGraphModel" {
+        return Ok(root);
+    }
+
+    let diagram = root
+        .children()
+        .find(|child| child.name() == "diagram")
+        .ok_or_else(|| ImportErrors::WrongFormat("No diagram element".to_owned()))?;
+
+    match diagram
+        .children()
+        .find(|child| child.name() == "mxGraphModel")
+    {
+        // Uncompressed content
+        Some(model) => Ok(model.clone()),
+        None => graph_model_element(parse_element(&decompress(&diagram.text())?)?),
+    }
+}
+
+/// Read float attribute
+#[inline]
+fn attr_f32(element: &Element, name: &str) -> f32 {
+    element
+        .attr(name)
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_default()
+}
+
+/// Parse style string `key=value;key=value` into map
+fn parse_style(style: &str) -> HashMap<&str, &str> {
+    style
+        .split(';')
+        .filter_map(|pair| pair.split_once('='))
+        .collect()
+}
+
+/// Create figure of the vertex shape. Shape is either bare style token like `ellipse`
+/// or value of the `shape` key, unknown shapes are imported as rectangles
+fn vertex_figure(style: &str, id: Id, rect: Rect, text: String) -> Box<dyn GraphFigure> {
+    let shape = style
+        .split(';')
+        .find_map(|token| match token.split_once('=') {
+            Some(("shape", value)) => Some(value),
+            Some(_) => None,
+            None => Some(token),
+        })
+        .unwrap_or_default();
+
+    match shape {
+        "ellipse" => Box::new(EllipseFigure::new(id, rect, text)),
+        "rhombus" => Box::new(DiamondFigure::new(id, rect, text)),
+        shape if shape.starts_with("cylinder") => Box::new(CylinderFigure::new(id, rect, text)),
+        _ => Box::new(RectFigure::new(id, rect, text)),
+    }
+}
+
+/// Find index of the figure's connection point nearest to the given point
+fn nearest_connection_point(figure: &dyn GraphFigure, point: Pos2) -> Option<usize> {
+    figure
+        .connection_points()
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| {
+            a.distance(point)
+                .partial_cmp(&b.distance(point))
+                .unwrap_or(Ordering::Equal)
+        })
+        .map(|(idx, _)| idx)
+}
+
+/// Import diagram from draw.io document. Vertices are imported as rectangles, ellipses,
+/// diamonds or cylinders depending on their style, edges connected to vertices keep their connections
+/// ### Arguments
+/// * xml - draw.io document, compressed or not
+/// ### Return
+/// imported diagram
+pub fn import_drawio(xml: &str) -> Result<Graphics, ImportErrors> {
+    let model = graph_model_element(parse_element(xml)?)?;
+    let root = model
+        .children()
+        .find(|child| child.name() == "root")
+        .ok_or_else(|| ImportErrors::WrongFormat("No root element in model".to_owned()))?;
+
+    let mut graphics = Graphics::default();
+    let mut vertices: HashMap<String, Rc<RefCell<Box<dyn GraphFigure>>>> = HashMap::new();
+    // Geometry of the cells is relative to the parent vertex
+    let mut origins: HashMap<String, Pos2> = HashMap::new();
+
+    let cells = root
+        .children()
+        .filter(|child| child.name() == "mxCell")
+        .collect::<Vec<_>>();
+
+    for cell in cells.iter().filter(|cell| cell.attr("vertex") == Some("1")) {
+        let geometry = match cell.children().find(|child| child.name() == "mxGeometry") {
+            Some(geometry) => geometry,
+            None => continue,
+        };
+
+        let origin = cell
+            .attr("parent")
+            .and_then(|parent| origins.get(parent))
+            .copied()
+            .unwrap_or(Pos2::ZERO);
+        let min = origin + vec2(attr_f32(geometry, "x"), attr_f32(geometry, "y"));
+        let rect = Rect::from_min_size(
+            min,
+            vec2(attr_f32(geometry, "width"), attr_f32(geometry, "height")),
+        );
+
+        let id = cell.attr("id").unwrap_or_default().to_owned();
+        let figure = Rc::new(RefCell::new(vertex_figure(
+            cell.attr("style").unwrap_or_default(),
+            graphics.generate_id(),
+            rect,
+            cell.attr("value").unwrap_or_default().to_owned(),
+        )));
+
+        origins.insert(id.clone(), min);
+        vertices.insert(id, figure.clone());
+        graphics.add_figure(figure);
+    }
+
+    for cell in cells.iter().filter(|cell| cell.attr("edge") == Some("1")) {
+        let style = parse_style(cell.attr("style").unwrap_or_default());
+        let geometry = cell.children().find(|child| child.name() == "mxGeometry");
+        let point = |name: &str| {
+            geometry
+                .and_then(|geometry| {
+                    geometry
+                        .children()
+                        .find(|child| child.name() == "mxPoint" && child.attr("as") == Some(name))
+                })
+                .map(|point| pos2(attr_f32(point, "x"), attr_f32(point, "y")))
+                .unwrap_or(Pos2::ZERO)
+        };
+
+        let source = cell.attr("source").and_then(|id| vertices.get(id));
+        let target = cell.attr("target").and_then(|id| vertices.get(id));
+
+        // Connection point is defined by relative position in style or is the nearest to
+        // the opposite end of the edge
+        let connect = |figure: &Rc<RefCell<Box<dyn GraphFigure>>>, prefix: &str, opposite: Pos2| {
+            let fig = RefCell::borrow(figure);
+            let rect = fig.rect();
+            let fraction = |axis: &str| {
+                style
+                    .get(format!("{}{}", prefix, axis).as_str())
+                    .and_then(|value| value.parse::<f32>().ok())
+            };
+            let point = match (fraction("X"), fraction("Y")) {
+                (Some(x), Some(y)) => rect.min + vec2(x * rect.width(), y * rect.height()),
+                _ => opposite,
+            };
+            nearest_connection_point(&**fig, point)
+                .map(|idx| ConnectionPoint::new(figure.clone(), idx))
+        };
+
+        let start_pos = source.map_or_else(|| point("sourcePoint"), |s| s.borrow().rect().center());
+        let end_pos = target.map_or_else(|| point("targetPoint"), |t| t.borrow().rect().center());
+
+        let mut edge = ArrowFigure::new([start_pos, end_pos], graphics.generate_id());
+        edge.set_end_arrow(style.get("endArrow") != Some(&"none"));
+        edge.set_start_arrow(
+            style
+                .get("startArrow")
+                .is_some_and(|arrow| *arrow != "none"),
+        );
+        if let Some(connection) = source.and_then(|figure| connect(figure, "exit", end_pos)) {
+            edge.connect_start(connection);
+        }
+
+        if let Some(connection) = target.and_then(|figure| connect(figure, "entry", start_pos)) {
+            edge.connect_end(connection);
+        }
+
+        graphics.add_edge(edge);
+    }
+
+    Ok(graphics)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use eframe::{
+        egui::Id,
+        emath::{pos2, vec2, Rect},
+    };
+
+    use super::{decompress, export_drawio, import_drawio, vertex_figure};
+    use crate::graph::{GraphFigure, Graphics};
+
+    const DIAGRAM: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<mxGraphModel dx="800" dy="600" grid="1">
+    <root>
+        <mxCell id="0"/>
+        <mxCell id="1" parent="0"/>
+        <mxCell id="a" value="Start" style="rounded=0;whiteSpace=wrap;html=1;" parent="1" vertex="1">
+            <mxGeometry x="40" y="80" width="120" height="60" as="geometry"/>
+        </mxCell>
+        <mxCell id="b" value="End &amp; more" style="rounded=1;" parent="1" vertex="1">
+            <mxGeometry x="280" y="80" width="120" height="60" as="geometry"/>
+        </mxCell>
+        <mxCell id="c" value="" style="endArrow=classic;html=1;exitX=1;exitY=0.5;entryX=0;entryY=0.5;" parent="1" source="a" target="b" edge="1">
+            <mxGeometry relative="1" as="geometry"/>
+        </mxCell>
+        <mxCell id="d" value="" style="endArrow=none;" parent="1" source="b" edge="1">
+            <mxGeometry relative="1" as="geometry">
+                <mxPoint x="500" y="300" as="targetPoint"/>
+            </mxGeometry>
+        </mxCell>
+    </root>
+</mxGraphModel>"#;
+
+    fn rects(graphics: &Graphics) -> Vec<Rect> {
+        graphics
+            .figures()
+            .iter()
+            .map(|figure| RefCell::borrow(figure).rect())
+            .collect()
+    }
+
+    /// Indexes of the figures connected by edges and connection points positions
+    fn connections(graphics: &Graphics) -> Vec<(Option<usize>, Option<usize>)> {
+        let figure_idx = |id| {
+            graphics
+                .figures()
+                .iter()
+                .position(|figure| RefCell::borrow(figure).id() == id)
+        };
+
+        graphics
+            .edges()
+            .iter()
+            .map(|edge| {
+                (
+                    edge.get_start_connection()
+                        .as_ref()
+                        .and_then(|cp| figure_idx(RefCell::borrow(cp.get_figure()).id())),
+                    edge.get_end_connection()
+                        .as_ref()
+                        .and_then(|cp| figure_idx(RefCell::borrow(cp.get_figure()).id())),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_import_drawio() {
+        let graphics = import_drawio(DIAGRAM).unwrap();
+        assert_eq!(
+            rects(&graphics),
+            vec![
+                Rect::from_min_max(pos2(40., 80.), pos2(160., 140.)),
+                Rect::from_min_max(pos2(280., 80.), pos2(400., 140.))
+            ]
+        );
+        assert_eq!(
+            RefCell::borrow(&graphics.figures()[1]).label(),
+            "End & more"
+        );
+
+        assert_eq!(
+            connections(&graphics),
+            vec![(Some(0), Some(1)), (Some(1), None)]
+        );
+        let edge = &graphics.edges()[0];
+        assert_eq!(edge.compute_start_point(), pos2(160., 110.));
+        assert_eq!(edge.compute_end_point(), pos2(280., 110.));
+        assert!(!graphics.edges()[1].end_arrow());
+        assert_eq!(graphics.edges()[1].compute_end_point(), pos2(500., 300.));
+    }
+
+    #[test]
+    fn test_drawio_round_trip() {
+        let imported = import_drawio(DIAGRAM).unwrap();
+        let exported = export_drawio(&imported);
+        assert!(exported.starts_with("<mxfile"));
+
+        // Content is compressed
+        let content = exported.split("<diagram").nth(1).unwrap();
+        let content = &content[content.find('>').unwrap() + 1..content.find("</diagram>").unwrap()];
+        assert!(decompress(content).unwrap().starts_with("<mxGraphModel>"));
+
+        let reimported = import_drawio(&exported).unwrap();
+        assert_eq!(rects(&reimported), rects(&imported));
+        assert_eq!(connections(&reimported), connections(&imported));
+        for (edge, original) in reimported.edges().iter().zip(imported.edges()) {
+            assert_eq!(edge.compute_start_point(), original.compute_start_point());
+            assert_eq!(edge.compute_end_point(), original.compute_end_point());
+            assert_eq!(edge.end_arrow(), original.end_arrow());
+        }
+        assert_eq!(
+            RefCell::borrow(&reimported.figures()[1]).label(),
+            "End & more"
+        );
+    }
+
+    #[test]
+    fn test_vertex_shapes() {
+        let rect = Rect::from_min_max(pos2(0., 0.), pos2(100., 50.));
+        for (style, type_name) in [
+            ("rounded=0;whiteSpace=wrap;html=1;", "rect"),
+            ("ellipse;whiteSpace=wrap;html=1;", "ellipse"),
+            ("whiteSpace=wrap;shape=ellipse;", "ellipse"),
+            ("rhombus;whiteSpace=wrap;html=1;", "diamond"),
+            ("shape=cylinder3;boundedLbl=1;", "cylinder"),
+            ("shape=hexagon;", "rect"),
+            ("", "rect"),
+        ] {
+            let figure = vertex_figure(style, Id::new(1), rect, "Label".to_owned());
+            assert_eq!(figure.type_name(), type_name, "style {}", style);
+            assert_eq!(figure.rect(), rect);
+            assert_eq!(figure.label(), "Label");
+        }
+
+        // Shapes survive export and import
+        let mut graphics = Graphics::default();
+        for (idx, style) in ["ellipse;", "rhombus;", "shape=cylinder3;"]
+            .iter()
+            .enumerate()
+        {
+            let rect = rect.translate(vec2(idx as f32 * 200., 0.));
+            let figure = vertex_figure(style, graphics.generate_id(), rect, String::new());
+            graphics.add_figure(Rc::new(RefCell::new(figure)));
+        }
+        let reimported = import_drawio(&export_drawio(&graphics)).unwrap();
+        let types: Vec<&str> = reimported
+            .figures()
+            .iter()
+            .map(|figure| RefCell::borrow(figure).type_name())
+            .collect();
+        assert_eq!(types, ["ellipse", "diamond", "cylinder"]);
+    }
+}
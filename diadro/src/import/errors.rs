@@ -0,0 +1,15 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ImportErrors {
+    #[error("XML error: {0}")]
+    Xml(#[from] minidom::Error),
+    #[error("Base64 decoding error: {0}")]
+    Base64(#[from] base64::DecodeError),
+    #[error("Decompression error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("URL decoding error: {0}")]
+    UrlDecode(#[from] std::string::FromUtf8Error),
+    #[error("Wrong diagram format: {0}")]
+    WrongFormat(String),
+}
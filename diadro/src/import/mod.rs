@@ -0,0 +1,5 @@
+mod drawio;
+mod errors;
+
+pub use self::drawio::{export_drawio, import_drawio};
+pub use self::errors::ImportErrors;
@@ -3,7 +3,10 @@
 #![warn(clippy::all)]
 
 mod app;
+pub mod auth;
+mod export;
 pub mod graph;
+pub mod import;
 pub mod rgraph;
 pub mod ws;
 
@@ -1,47 +1,77 @@
-use eframe::emath::Pos2;
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+};
+
+use eframe::{
+    egui::Id,
+    emath::{Pos2, Rect, Vec2},
+};
+use ordered_float::OrderedFloat;
+
+use super::errors::MxErrors;
 
 pub trait PointAlgoritm {
     /// Check if point lies on given line
+    /// Point belongs to the line if its distance to the line segment does not exceed epsilon
     fn belong_line(&self, line: &[Pos2; 2], epsilon: f32) -> bool;
     fn belong_path(&self, path: &[Pos2], epsilon: f32) -> bool;
+    /// Check if point lies on quadratic bezier curve defined by start, control and end points
+    fn belong_quadratic_bezier(&self, control_points: &[Pos2; 3], epsilon: f32) -> bool;
+    /// Check if point lies on cubic bezier curve defined by start, two control and end points
+    fn belong_cubic_bezier(&self, control_points: &[Pos2; 4], epsilon: f32) -> bool;
 }
 
-impl PointAlgoritm for Pos2 {
-    fn belong_line(&self, line: &[Pos2; 2], epsilon: f32) -> bool {
-        // Normalization (?)
-        let line_delta = line[0] - line[1];
-        let point_delta = line[0] - *self;
-
-        if point_delta.x == 0. && point_delta.y == 0. {
-            true
-        } else if line_delta.x == 0. {
-            // Check if point coordinates lies between start and end points
-            point_delta.y == 0.
-                || (line[0].x - epsilon <= self.x
-                    && line[0].x + epsilon >= self.x
-                    && line_delta.y.signum() == point_delta.y.signum()
-                    && line_delta.y <= point_delta.y)
-        } else if line_delta.y == 0. {
-            point_delta.x == 0.
-                || (line[0].y - epsilon <= self.y
-                    && line[0].y + epsilon >= self.y
-                    && line_delta.x.signum() == point_delta.x.signum()
-                    && line_delta.x <= point_delta.x)
-        } else {
-            let k = line_delta.y / line_delta.x;
-            let b = line[1].y - line[1].x * k;
+/// Minimal number of segments used to approximate bezier curve
+const BEZIER_MIN_SEGMENTS: usize = 4;
+/// Maximal number of segments used to approximate bezier curve
+const BEZIER_MAX_SEGMENTS: usize = 256;
+/// Approximate length of one segment of bezier curve approximation
+const BEZIER_SEGMENT_LENGTH: f32 = 4.;
+
+/// Approximate bezier curve by polyline. Number of segments depends on the length of
+/// the control polygon, which is never shorter than the curve
+/// ### Arguments
+/// * control_points - control points of the curve
+/// * point_at - function computing curve point by parameter in [0; 1]
+/// ### Return
+/// polyline points including both curve ends
+fn sample_bezier(control_points: &[Pos2], point_at: impl Fn(f32) -> Pos2) -> Vec<Pos2> {
+    let polygon_length: f32 = control_points
+        .windows(2)
+        .map(|segment| segment[0].distance(segment[1]))
+        .sum();
+    let segments = ((polygon_length / BEZIER_SEGMENT_LENGTH).ceil() as usize)
+        .max(BEZIER_MIN_SEGMENTS)
+        .min(BEZIER_MAX_SEGMENTS);
+
+    (0..=segments)
+        .map(|idx| point_at(idx as f32 / segments as f32))
+        .collect()
+}
 
-            // compute x
-            let y = k * self.x + b;
-            let x = (self.y - b) / k;
+/// Curve lies inside convex hull of its control points, so points out of
+/// control points bounding box can be rejected without sampling
+fn near_control_points(point: Pos2, control_points: &[Pos2], epsilon: f32) -> bool {
+    Rect::from_points(control_points)
+        .expand(epsilon)
+        .contains(point)
+}
 
-            (self.x - x).abs() <= epsilon
-                && (self.y - y).abs() <= epsilon
-                && (line_delta.x.signum() == point_delta.x.signum() || point_delta.x == 0.)
-                && line_delta.x <= point_delta.x - (epsilon * point_delta.x.signum())
-                && (line_delta.y.signum() == point_delta.y.signum() || point_delta.y == 0.)
-                && line_delta.y <= point_delta.y - (epsilon * point_delta.y.signum())
+impl PointAlgoritm for Pos2 {
+    fn belong_line(&self, line: &[Pos2; 2], epsilon: f32) -> bool {
+        let line_delta = line[1] - line[0];
+        let length_sq = line_delta.length_sq();
+        if length_sq == 0. {
+            // Zero-length line is a point
+            return self.distance(line[0]) <= epsilon;
         }
+
+        // Project point onto the line, projection is clamped by the line's ends
+        let point_delta = *self - line[0];
+        let t = ((point_delta.x * line_delta.x + point_delta.y * line_delta.y) / length_sq)
+            .clamp(0., 1.);
+        self.distance(line[0] + line_delta * t) <= epsilon
     }
 
     fn belong_path(&self, points: &[Pos2], epsilon: f32) -> bool {
@@ -54,10 +84,454 @@ impl PointAlgoritm for Pos2 {
         }
         false
     }
+
+    fn belong_quadratic_bezier(&self, control_points: &[Pos2; 3], epsilon: f32) -> bool {
+        if !near_control_points(*self, control_points, epsilon) {
+            return false;
+        }
+
+        let [p0, p1, p2] = *control_points;
+        let points = sample_bezier(control_points, |t| {
+            let s = 1. - t;
+            let (a, b, c) = (s * s, 2. * s * t, t * t);
+            Pos2::new(
+                a * p0.x + b * p1.x + c * p2.x,
+                a * p0.y + b * p1.y + c * p2.y,
+            )
+        });
+        self.belong_path(&points, epsilon)
+    }
+
+    fn belong_cubic_bezier(&self, control_points: &[Pos2; 4], epsilon: f32) -> bool {
+        if !near_control_points(*self, control_points, epsilon) {
+            return false;
+        }
+
+        let [p0, p1, p2, p3] = *control_points;
+        let points = sample_bezier(control_points, |t| {
+            let s = 1. - t;
+            let (a, b, c, d) = (s * s * s, 3. * s * s * t, 3. * s * t * t, t * t * t);
+            Pos2::new(
+                a * p0.x + b * p1.x + c * p2.x + d * p3.x,
+                a * p0.y + b * p1.y + c * p2.y + d * p3.y,
+            )
+        });
+        self.belong_path(&points, epsilon)
+    }
+}
+
+/// Maximal displacement of the vertex in the first iteration of force-directed layout.
+/// Displacement limit decreases linearly to zero with iterations
+const LAYOUT_MAX_DISPLACEMENT: f32 = 100.;
+/// Minimal distance between vertices used to compute forces, prevents infinite repulsion
+const LAYOUT_MIN_DISTANCE: f32 = 0.01;
+
+/// Arrange vertices using Fruchterman–Reingold force-directed algorithm. All pairs of vertices
+/// repel with force `repulsion / distance`, vertices connected by edge attract with force
+/// `attraction * distance^2`, so connected vertices tend to the distance
+/// `(repulsion / attraction)^(1/3)`
+/// ### Arguments
+/// * positions - initial positions of vertices
+/// * edges - list of edges as pairs of vertices indexes
+/// * iterations - number of simulation steps
+/// * repulsion - repulsion factor
+/// * attraction - attraction factor
+/// ### Return
+/// new positions of vertices
+pub fn force_directed_layout(
+    positions: &[Pos2],
+    edges: &[(usize, usize)],
+    iterations: u32,
+    repulsion: f32,
+    attraction: f32,
+) -> Vec<Pos2> {
+    let mut positions = positions.to_vec();
+    for iteration in 0..iterations {
+        let mut displacements = vec![Vec2::ZERO; positions.len()];
+
+        for i in 0..positions.len() {
+            for j in i + 1..positions.len() {
+                let delta = positions[i] - positions[j];
+                // Coincident vertices are pushed apart in the direction depending on their indexes
+                let (direction, distance) = match delta.length() {
+                    length if length < LAYOUT_MIN_DISTANCE => {
+                        (Vec2::angled((i + j) as f32), LAYOUT_MIN_DISTANCE)
+                    }
+                    length => (delta / length, length),
+                };
+                let force = direction * (repulsion / distance);
+                displacements[i] += force;
+                displacements[j] -= force;
+            }
+        }
+
+        for &(start, end) in edges {
+            if start == end || start >= positions.len() || end >= positions.len() {
+                continue;
+            }
+
+            let delta = positions[start] - positions[end];
+            let force = delta * delta.length() * attraction;
+            displacements[start] -= force;
+            displacements[end] += force;
+        }
+
+        // Displacement is clamped by temperature decreasing with iterations
+        let temperature = LAYOUT_MAX_DISPLACEMENT * (1. - iteration as f32 / iterations as f32);
+        for (position, displacement) in positions.iter_mut().zip(displacements) {
+            let length = displacement.length();
+            if length > 0. && length.is_finite() {
+                *position += displacement / length * length.min(temperature);
+            }
+        }
+    }
+
+    positions
+}
+
+/// Find shortest path between two vertices using breadth first search. Edges are treated as undirected
+/// ### Arguments
+/// * edges - list of edges as pairs of vertices identifiers
+/// * from - start vertex
+/// * to - end vertex
+/// ### Return
+/// indexes of the edges forming the path in order from start to end or None if there is no path
+pub fn shortest_path(edges: &[(Id, Id)], from: Id, to: Id) -> Option<Vec<usize>> {
+    if from == to {
+        return Some(vec![]);
+    }
+
+    let mut visited = HashSet::from([from]);
+    let mut parents: HashMap<Id, (Id, usize)> = HashMap::new();
+    let mut queue = VecDeque::from([from]);
+
+    while let Some(vertex) = queue.pop_front() {
+        for (idx, (start, end)) in edges.iter().enumerate() {
+            let next = match vertex {
+                v if v == *start => *end,
+                v if v == *end => *start,
+                _ => continue,
+            };
+
+            if !visited.insert(next) {
+                continue;
+            }
+
+            parents.insert(next, (vertex, idx));
+            if next == to {
+                // Restore path from end to start
+                let mut path = vec![];
+                let mut current = to;
+                while let Some((parent, edge)) = parents.get(&current) {
+                    path.push(*edge);
+                    current = *parent;
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            queue.push_back(next);
+        }
+    }
+
+    None
+}
+
+/// Find path with minimal total cost between two vertices using Dijkstra's algorithm.
+/// Edges are treated as undirected
+/// ### Arguments
+/// * edges - list of edges as pairs of vertices identifiers with edge cost
+/// * from - start vertex
+/// * to - end vertex
+/// ### Return
+/// vertices of the path from start to end with total cost, None if there is no path
+/// or error if any edge has negative cost
+pub fn shortest_path_weighted(
+    edges: &[(Id, Id, f32)],
+    from: Id,
+    to: Id,
+) -> Result<Option<(Vec<Id>, f32)>, MxErrors> {
+    if let Some((_, _, weight)) = edges
+        .iter()
+        .find(|(_, _, weight)| weight.is_nan() || *weight < 0.)
+    {
+        return Err(MxErrors::NegativeWeight(*weight));
+    }
+
+    let mut costs = HashMap::from([(from, 0.)]);
+    let mut parents: HashMap<Id, Id> = HashMap::new();
+    let mut visited = HashSet::new();
+    let mut queue = BinaryHeap::from([Reverse((OrderedFloat(0.), from))]);
+
+    while let Some(Reverse((OrderedFloat(cost), vertex))) = queue.pop() {
+        if !visited.insert(vertex) {
+            continue;
+        }
+
+        if vertex == to {
+            // Restore path from end to start
+            let mut path = vec![to];
+            let mut current = to;
+            while let Some(parent) = parents.get(&current) {
+                path.push(*parent);
+                current = *parent;
+            }
+            path.reverse();
+            return Ok(Some((path, cost)));
+        }
+
+        for (start, end, weight) in edges {
+            let next = match vertex {
+                v if v == *start => *end,
+                v if v == *end => *start,
+                _ => continue,
+            };
+
+            let next_cost = cost + weight;
+            if visited.contains(&next) || costs.get(&next).is_some_and(|c| *c <= next_cost) {
+                continue;
+            }
+
+            costs.insert(next, next_cost);
+            parents.insert(next, vertex);
+            queue.push(Reverse((OrderedFloat(next_cost), next)));
+        }
+    }
+
+    Ok(None)
 }
 
 #[cfg(test)]
 mod tests {
+    use eframe::{
+        egui::Id,
+        emath::{pos2, vec2, Pos2, Vec2},
+    };
+    use proptest::prelude::*;
+
+    use super::{force_directed_layout, shortest_path, shortest_path_weighted, PointAlgoritm};
+    use crate::rgraph::errors::MxErrors;
+
+    #[test]
+    fn test_shortest_path() {
+        let (a, b, c, d, e) = (Id::new(1), Id::new(2), Id::new(3), Id::new(4), Id::new(5));
+        let edges = [(a, b), (b, c), (c, d), (a, c), (d, b)];
+
+        assert_eq!(shortest_path(&edges, a, d), Some(vec![0, 4]));
+        assert_eq!(shortest_path(&edges, d, a), Some(vec![2, 3]));
+        assert_eq!(shortest_path(&edges, a, a), Some(vec![]));
+        assert_eq!(shortest_path(&edges, a, e), None);
+    }
+
+    #[test]
+    fn test_shortest_path_weighted() {
+        let (a, b, c, d) = (Id::new(1), Id::new(2), Id::new(3), Id::new(4));
+        let edges = [(a, b, 1.), (b, c, 1.), (c, d, 1.), (a, d, 5.)];
+
+        // Longer path by hops is cheaper
+        assert_eq!(
+            shortest_path_weighted(&edges, a, d).unwrap(),
+            Some((vec![a, b, c, d], 3.))
+        );
+        assert_eq!(
+            shortest_path_weighted(&edges, d, b).unwrap(),
+            Some((vec![d, c, b], 2.))
+        );
+
+        // Direct edge becomes cheaper
+        let edges = [(a, b, 1.), (b, c, 1.), (c, d, 1.), (a, d, 2.5)];
+        assert_eq!(
+            shortest_path_weighted(&edges, a, d).unwrap(),
+            Some((vec![a, d], 2.5))
+        );
+
+        // Zero cost edges are allowed
+        let edges = [(a, b, 0.), (b, c, 0.)];
+        assert_eq!(
+            shortest_path_weighted(&edges, a, c).unwrap(),
+            Some((vec![a, b, c], 0.))
+        );
+    }
+
+    #[test]
+    fn test_shortest_path_weighted_degenerate() {
+        let (a, b, c, d) = (Id::new(1), Id::new(2), Id::new(3), Id::new(4));
+
+        // Single node
+        assert_eq!(
+            shortest_path_weighted(&[], a, a).unwrap(),
+            Some((vec![a], 0.))
+        );
+
+        // Disconnected graph
+        let edges = [(a, b, 1.), (c, d, 1.)];
+        assert_eq!(shortest_path_weighted(&edges, a, d).unwrap(), None);
+        assert_eq!(shortest_path_weighted(&edges, a, Id::new(5)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_shortest_path_weighted_negative() {
+        let (a, b, c) = (Id::new(1), Id::new(2), Id::new(3));
+        let edges = [(a, b, 1.), (b, c, -2.)];
+
+        assert!(matches!(
+            shortest_path_weighted(&edges, a, c),
+            Err(MxErrors::NegativeWeight(w)) if w == -2.
+        ));
+
+        let edges = [(a, b, f32::NAN)];
+        assert!(shortest_path_weighted(&edges, a, b).is_err());
+    }
+
+    #[test]
+    fn test_belong_line_edge_cases() {
+        let horizontal = [pos2(0., 0.), pos2(10., 0.)];
+        assert!(pos2(5., 0.).belong_line(&horizontal, 1.));
+        assert!(pos2(5., 1.).belong_line(&horizontal, 1.));
+        assert!(pos2(10., 0.).belong_line(&horizontal, 1.));
+        assert!(pos2(10.5, 0.).belong_line(&horizontal, 1.));
+        assert!(!pos2(12., 0.).belong_line(&horizontal, 1.));
+        assert!(!pos2(-2., 0.).belong_line(&horizontal, 1.));
+
+        // Line direction does not matter
+        let vertical = [pos2(0., 10.), pos2(0., 0.)];
+        assert!(pos2(0., 5.).belong_line(&vertical, 1.));
+        assert!(pos2(-1., 5.).belong_line(&vertical, 1.));
+        assert!(!pos2(0., 12.).belong_line(&vertical, 1.));
+        assert!(!pos2(0., -2.).belong_line(&vertical, 1.));
+
+        let point = [pos2(3., 3.), pos2(3., 3.)];
+        assert!(pos2(3., 3.).belong_line(&point, 1.));
+        assert!(pos2(3.5, 3.5).belong_line(&point, 1.));
+        assert!(!pos2(5., 3.).belong_line(&point, 1.));
+    }
+
+    #[test]
+    fn test_belong_bezier() {
+        let quadratic = [pos2(0., 0.), pos2(50., 100.), pos2(100., 0.)];
+        assert!(pos2(0., 0.).belong_quadratic_bezier(&quadratic, 1.));
+        assert!(pos2(100., 0.).belong_quadratic_bezier(&quadratic, 1.));
+        // Middle of the curve
+        assert!(pos2(50., 51.).belong_quadratic_bezier(&quadratic, 2.));
+        assert!(!pos2(50., 60.).belong_quadratic_bezier(&quadratic, 2.));
+        assert!(!pos2(50., 0.).belong_quadratic_bezier(&quadratic, 2.));
+        assert!(!pos2(200., 200.).belong_quadratic_bezier(&quadratic, 2.));
+
+        let cubic = [
+            pos2(0., 0.),
+            pos2(0., 100.),
+            pos2(100., 100.),
+            pos2(100., 0.),
+        ];
+        assert!(pos2(0., 0.).belong_cubic_bezier(&cubic, 1.));
+        assert!(pos2(100., 0.).belong_cubic_bezier(&cubic, 1.));
+        assert!(pos2(50., 75.).belong_cubic_bezier(&cubic, 1.));
+        assert!(!pos2(50., 50.).belong_cubic_bezier(&cubic, 2.));
+        assert!(!pos2(50., 100.).belong_cubic_bezier(&cubic, 2.));
+        assert!(!pos2(-10., 50.).belong_cubic_bezier(&cubic, 2.));
+    }
+
+    #[test]
+    fn test_bezier_parametric_points() {
+        let [q0, q1, q2] = [pos2(10., 300.), pos2(400., -200.), pos2(600., 250.)];
+        let [c0, c1, c2, c3] = [
+            pos2(0., 0.),
+            pos2(500., 0.),
+            pos2(-200., 300.),
+            pos2(300., 300.),
+        ];
+
+        for step in 0..=50 {
+            let t = step as f32 / 50.;
+            let s = 1. - t;
+            let quadratic =
+                (q0.to_vec2() * s * s + q1.to_vec2() * 2. * s * t + q2.to_vec2() * t * t).to_pos2();
+            assert!(
+                quadratic.belong_quadratic_bezier(&[q0, q1, q2], 0.5),
+                "quadratic t = {}",
+                t
+            );
+
+            let cubic = (c0.to_vec2() * s * s * s
+                + c1.to_vec2() * 3. * s * s * t
+                + c2.to_vec2() * 3. * s * t * t
+                + c3.to_vec2() * t * t * t)
+                .to_pos2();
+            assert!(
+                cubic.belong_cubic_bezier(&[c0, c1, c2, c3], 0.5),
+                "cubic t = {}",
+                t
+            );
+        }
+    }
+
+    fn coordinate() -> impl Strategy<Value = f32> {
+        -1000f32..1000.
+    }
+
+    fn point() -> impl Strategy<Value = Pos2> {
+        (coordinate(), coordinate()).prop_map(|(x, y)| pos2(x, y))
+    }
+
+    /// Unit vector perpendicular to the line or arbitrary unit vector for zero-length line
+    fn normal(line: &[Pos2; 2], angle: f32) -> Vec2 {
+        let delta = line[1] - line[0];
+        if delta.length() > 0. {
+            delta.normalized().rot90()
+        } else {
+            vec2(angle.cos(), angle.sin())
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn prop_point_on_line_belongs(start in point(), end in point(), t in 0f32..=1., epsilon in 0.5f32..10.) {
+            let point = start + (end - start) * t;
+            prop_assert!(point.belong_line(&[start, end], epsilon));
+        }
+
+        #[test]
+        fn prop_far_point_does_not_belong(
+            start in point(),
+            end in point(),
+            t in 0f32..=1.,
+            epsilon in 0.5f32..10.,
+            factor in 2.1f32..100.,
+            angle in 0f32..std::f32::consts::TAU,
+        ) {
+            let line = [start, end];
+            let point = start + (end - start) * t + normal(&line, angle) * epsilon * factor;
+            prop_assert!(!point.belong_line(&line, epsilon));
+        }
+
+        #[test]
+        fn prop_point_beyond_end_does_not_belong(
+            start in point(),
+            length in 1f32..1000.,
+            angle in 0f32..std::f32::consts::TAU,
+            epsilon in 0.5f32..10.,
+            factor in 2.1f32..100.,
+        ) {
+            let direction = vec2(angle.cos(), angle.sin());
+            let end = start + direction * length;
+            prop_assert!(!(end + direction * epsilon * factor).belong_line(&[start, end], epsilon));
+            prop_assert!(!(start - direction * epsilon * factor).belong_line(&[start, end], epsilon));
+        }
+
+        #[test]
+        fn prop_axis_aligned_lines(x in coordinate(), y in coordinate(), length in -1000f32..1000., t in 0f32..=1., epsilon in 0.5f32..10.) {
+            let horizontal = [pos2(x, y), pos2(x + length, y)];
+            prop_assert!(pos2(x + length * t, y).belong_line(&horizontal, epsilon));
+            prop_assert!(pos2(x + length * t, y + epsilon * 0.9).belong_line(&horizontal, epsilon));
+            prop_assert!(!pos2(x + length * t, y + epsilon * 2.1).belong_line(&horizontal, epsilon));
+
+            let vertical = [pos2(x, y), pos2(x, y + length)];
+            prop_assert!(pos2(x, y + length * t).belong_line(&vertical, epsilon));
+            prop_assert!(pos2(x - epsilon * 0.9, y + length * t).belong_line(&vertical, epsilon));
+            prop_assert!(!pos2(x - epsilon * 2.1, y + length * t).belong_line(&vertical, epsilon));
+        }
+    }
+
     #[test]
     fn test_find_map() {
         let array = [1, 2, 3, 4, 2, 5, 6, 7, 8, 16, 18, 24];
@@ -72,4 +546,30 @@ mod tests {
             .find_map(|v| if v % 2 == 0 { Some(v) } else { None });
         assert_eq!(found, Some(&24));
     }
+
+    #[test]
+    fn test_force_directed_layout() {
+        // Two connected vertices and one isolated, all start at the same point
+        let positions = [Pos2::ZERO; 3];
+        let (repulsion, attraction) = (1000., 1.);
+        let result = force_directed_layout(&positions, &[(0, 1)], 200, repulsion, attraction);
+
+        // Connected vertices settle near the distance where forces are balanced
+        let ideal = (repulsion / attraction).powf(1. / 3.);
+        let distance = result[0].distance(result[1]);
+        assert!(
+            (distance - ideal).abs() < ideal * 0.2,
+            "distance: {}",
+            distance
+        );
+        // Isolated vertex is pushed farther away than connected one
+        assert!(result[2].distance(result[0]) > distance);
+        assert!(result[2].distance(result[1]) > distance);
+
+        assert!(force_directed_layout(&[], &[], 10, repulsion, attraction).is_empty());
+        assert_eq!(
+            force_directed_layout(&positions, &[], 0, repulsion, attraction),
+            positions.to_vec()
+        );
+    }
 }
@@ -0,0 +1,242 @@
+use eframe::{
+    emath::{Pos2, Rect, Vec2},
+    epaint::{
+        Color32, CubicBezierShape, Mesh, PathShape, QuadraticBezierShape, RectShape, Rounding,
+        Stroke, TextShape, Vertex,
+    },
+};
+
+use super::Figure;
+
+/// Approximate equality. Coordinates and sizes are compared within tolerance,
+/// colors and other discrete values are compared exactly
+pub trait ApproxEq {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool;
+}
+
+impl ApproxEq for f32 {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        (self - other).abs() <= tolerance
+    }
+}
+
+impl ApproxEq for Pos2 {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self.x.approx_eq(&other.x, tolerance) && self.y.approx_eq(&other.y, tolerance)
+    }
+}
+
+impl ApproxEq for Vec2 {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self.x.approx_eq(&other.x, tolerance) && self.y.approx_eq(&other.y, tolerance)
+    }
+}
+
+impl ApproxEq for Rect {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self.min.approx_eq(&other.min, tolerance) && self.max.approx_eq(&other.max, tolerance)
+    }
+}
+
+impl ApproxEq for Color32 {
+    fn approx_eq(&self, other: &Self, _tolerance: f32) -> bool {
+        self == other
+    }
+}
+
+impl ApproxEq for Stroke {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self.width.approx_eq(&other.width, tolerance) && self.color == other.color
+    }
+}
+
+impl ApproxEq for Rounding {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self.nw.approx_eq(&other.nw, tolerance)
+            && self.ne.approx_eq(&other.ne, tolerance)
+            && self.sw.approx_eq(&other.sw, tolerance)
+            && self.se.approx_eq(&other.se, tolerance)
+    }
+}
+
+impl<T: ApproxEq> ApproxEq for [T] {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .zip(other.iter())
+                .all(|(a, b)| a.approx_eq(b, tolerance))
+    }
+}
+
+impl<T: ApproxEq, const N: usize> ApproxEq for [T; N] {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self[..].approx_eq(&other[..], tolerance)
+    }
+}
+
+impl<T: ApproxEq> ApproxEq for Vec<T> {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self[..].approx_eq(&other[..], tolerance)
+    }
+}
+
+impl<T: ApproxEq> ApproxEq for Option<T> {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        match (self, other) {
+            (Some(a), Some(b)) => a.approx_eq(b, tolerance),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+impl ApproxEq for PathShape {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self.points.approx_eq(&other.points, tolerance)
+            && self.closed == other.closed
+            && self.fill == other.fill
+            && self.stroke.approx_eq(&other.stroke, tolerance)
+    }
+}
+
+impl ApproxEq for RectShape {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self.rect.approx_eq(&other.rect, tolerance)
+            && self.rounding.approx_eq(&other.rounding, tolerance)
+            && self.fill == other.fill
+            && self.stroke.approx_eq(&other.stroke, tolerance)
+    }
+}
+
+impl ApproxEq for TextShape {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self.pos.approx_eq(&other.pos, tolerance)
+            && self.galley.text() == other.galley.text()
+            && self.galley.rect.approx_eq(&other.galley.rect, tolerance)
+            && self.underline.approx_eq(&other.underline, tolerance)
+            && self.override_text_color == other.override_text_color
+            && self.angle.approx_eq(&other.angle, tolerance)
+    }
+}
+
+impl ApproxEq for Vertex {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self.pos.approx_eq(&other.pos, tolerance)
+            && self.uv.approx_eq(&other.uv, tolerance)
+            && self.color == other.color
+    }
+}
+
+impl ApproxEq for Mesh {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self.indices == other.indices
+            && self.vertices.approx_eq(&other.vertices, tolerance)
+            && self.texture_id == other.texture_id
+    }
+}
+
+impl ApproxEq for QuadraticBezierShape {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self.points.approx_eq(&other.points, tolerance)
+            && self.closed == other.closed
+            && self.fill == other.fill
+            && self.stroke.approx_eq(&other.stroke, tolerance)
+    }
+}
+
+impl ApproxEq for CubicBezierShape {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self.points.approx_eq(&other.points, tolerance)
+            && self.closed == other.closed
+            && self.fill == other.fill
+            && self.stroke.approx_eq(&other.stroke, tolerance)
+    }
+}
+
+impl ApproxEq for Figure {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        match (self, other) {
+            (Figure::Vec(a), Figure::Vec(b)) => a.approx_eq(b, tolerance),
+            (
+                Figure::LineSegment { points, stroke },
+                Figure::LineSegment {
+                    points: other_points,
+                    stroke: other_stroke,
+                },
+            ) => {
+                points.approx_eq(other_points, tolerance)
+                    && stroke.approx_eq(other_stroke, tolerance)
+            }
+            (Figure::Path(a), Figure::Path(b)) => a.approx_eq(b, tolerance),
+            (Figure::Rect(a), Figure::Rect(b)) => a.approx_eq(b, tolerance),
+            (Figure::Text(a), Figure::Text(b)) => a.approx_eq(b, tolerance),
+            (Figure::Mesh(a), Figure::Mesh(b)) => a.approx_eq(b, tolerance),
+            (Figure::QuadraticBezier(a), Figure::QuadraticBezier(b)) => a.approx_eq(b, tolerance),
+            (Figure::CubicBezier(a), Figure::CubicBezier(b)) => a.approx_eq(b, tolerance),
+            _ => false,
+        }
+    }
+}
+
+impl Figure {
+    /// Compare figures approximately. Used in tests to avoid floating point issues
+    /// ### Arguments
+    /// * other - figure to compare with
+    /// * tolerance - maximal allowed difference of coordinates, sizes and stroke widths
+    /// ### Return
+    /// true if figures have the same type and geometry within tolerance, colors are equal exactly
+    pub fn approx_eq(&self, other: &Figure, tolerance: f32) -> bool {
+        ApproxEq::approx_eq(self, other, tolerance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eframe::{
+        emath::{pos2, Rect},
+        epaint::{Color32, PathShape, RectShape, Rounding, Stroke},
+    };
+
+    use crate::rgraph::Figure;
+
+    #[test]
+    fn test_figure_approx_eq() {
+        let stroke = Stroke::new(1., Color32::RED);
+        let line = Figure::LineSegment {
+            points: [pos2(0., 0.), pos2(0.1 + 0.2, 10.)],
+            stroke,
+        };
+        let same = Figure::LineSegment {
+            points: [pos2(0., 0.), pos2(0.3, 10.)],
+            stroke: Stroke::new(1.005, Color32::RED),
+        };
+        assert!(line.approx_eq(&same, 0.01));
+        assert!(!line.approx_eq(&same, 0.001));
+
+        // Colors are compared exactly
+        let other_color = Figure::LineSegment {
+            points: [pos2(0., 0.), pos2(0.3, 10.)],
+            stroke: Stroke::new(1., Color32::BLUE),
+        };
+        assert!(!line.approx_eq(&other_color, 0.01));
+
+        let rect = Figure::Rect(RectShape {
+            rect: Rect::from_two_pos(pos2(0., 0.), pos2(10., 20.)),
+            rounding: Rounding::none(),
+            fill: Color32::BLUE,
+            stroke,
+        });
+        assert!(!line.approx_eq(&rect, 0.01));
+
+        let path = |last: f32| {
+            Figure::Vec(vec![Figure::Path(PathShape::line(
+                vec![pos2(0., 0.), pos2(5., 5.), pos2(last, 0.)],
+                stroke,
+            ))])
+        };
+        assert!(path(10.).approx_eq(&path(10.005), 0.01));
+        assert!(!path(10.).approx_eq(&path(10.5), 0.01));
+        assert!(!path(10.).approx_eq(&Figure::Vec(vec![]), 0.01));
+    }
+}
@@ -0,0 +1,569 @@
+use std::fmt::Write;
+
+use eframe::{
+    emath::{Pos2, Rect, Vec2},
+    epaint::{Color32, Stroke},
+};
+
+use super::{errors::MxErrors, Figure};
+
+/// Tolerance used to flatten bezier curves into polylines
+const BEZIER_TOLERANCE: f32 = 0.5;
+
+/// Backend independent drawing primitive. Used to render figures without egui painter
+#[derive(Clone, Debug, PartialEq)]
+pub enum CanvasCommand {
+    FillRect(Rect, Color32),
+    StrokeRect(Rect, Stroke),
+    DrawLine([Pos2; 2], Stroke),
+    FillPolygon(Vec<Pos2>, Color32),
+    DrawPath {
+        points: Vec<Pos2>,
+        closed: bool,
+        fill: Color32,
+        stroke: Stroke,
+    },
+    DrawText {
+        pos: Pos2,
+        text: String,
+        font_size: f32,
+        color: Color32,
+    },
+}
+
+/// Convert path into path command. Invisible paths are skipped
+/// ### Arguments
+/// * points - path points
+/// * closed - if true then last point will be connected with first one
+/// * fill - fill color, used only for closed paths
+/// * stroke - stroke used to draw path
+fn path_to_commands(
+    points: Vec<Pos2>,
+    closed: bool,
+    fill: Color32,
+    stroke: Stroke,
+) -> Vec<CanvasCommand> {
+    let has_stroke = stroke.width > 0. && stroke.color != Color32::TRANSPARENT;
+    let has_fill = closed && fill != Color32::TRANSPARENT;
+    if points.len() < 2 || !(has_stroke || has_fill) {
+        return vec![];
+    }
+
+    vec![CanvasCommand::DrawPath {
+        points,
+        closed,
+        fill,
+        stroke,
+    }]
+}
+
+impl Figure {
+    /// Convert figure to the list of backend independent drawing commands
+    pub fn to_canvas_commands(&self) -> Vec<CanvasCommand> {
+        match self {
+            Figure::Vec(figures) => figures
+                .iter()
+                .flat_map(|figure| figure.to_canvas_commands())
+                .collect(),
+            Figure::LineSegment { points, stroke } => {
+                vec![CanvasCommand::DrawLine(*points, *stroke)]
+            }
+            Figure::Path(path) => {
+                path_to_commands(path.points.clone(), path.closed, path.fill, path.stroke)
+            }
+            Figure::Rect(rect) => {
+                let mut commands = vec![];
+                if rect.fill != Color32::TRANSPARENT {
+                    commands.push(CanvasCommand::FillRect(rect.rect, rect.fill));
+                }
+                if rect.stroke.width > 0. && rect.stroke.color != Color32::TRANSPARENT {
+                    commands.push(CanvasCommand::StrokeRect(rect.rect, rect.stroke));
+                }
+                commands
+            }
+            Figure::Text(text) => {
+                let section = text.galley.job.sections.first();
+                let font_size = section.map(|s| s.format.font_id.size).unwrap_or(14.);
+                let color = text
+                    .override_text_color
+                    .or_else(|| section.map(|s| s.format.color))
+                    .unwrap_or(Color32::BLACK);
+
+                vec![CanvasCommand::DrawText {
+                    pos: text.pos,
+                    text: text.galley.text().to_owned(),
+                    font_size,
+                    color,
+                }]
+            }
+            Figure::Mesh(mesh) => mesh
+                .indices
+                .chunks_exact(3)
+                .map(|tri| {
+                    let vertices: Vec<_> =
+                        tri.iter().map(|idx| mesh.vertices[*idx as usize]).collect();
+                    CanvasCommand::FillPolygon(
+                        vertices.iter().map(|v| v.pos).collect(),
+                        vertices[0].color,
+                    )
+                })
+                .collect(),
+            Figure::QuadraticBezier(qb) => path_to_commands(
+                qb.flatten(Some(BEZIER_TOLERANCE)),
+                qb.closed,
+                qb.fill,
+                qb.stroke,
+            ),
+            Figure::CubicBezier(cb) => path_to_commands(
+                cb.flatten(Some(BEZIER_TOLERANCE)),
+                cb.closed,
+                cb.fill,
+                cb.stroke,
+            ),
+        }
+    }
+}
+
+/// Convert color into svg compatible color string and opacity
+#[inline]
+fn svg_color(color: Color32) -> (String, f32) {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    (format!("rgb({},{},{})", r, g, b), a as f32 / 255.)
+}
+
+/// Convert path points into svg path data
+/// ### Arguments
+/// * points - path points
+/// * closed - if true then path will be closed
+fn svg_path_data(points: &[Pos2], closed: bool) -> String {
+    let mut data = points
+        .iter()
+        .enumerate()
+        .map(|(idx, p)| format!("{} {} {}", if idx == 0 { 'M' } else { 'L' }, p.x, p.y))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if closed {
+        data.push_str(" Z");
+    }
+    data
+}
+
+/// Escape text to be placed inside xml document
+pub(crate) fn xml_escape(text: &str) -> String {
+    text.chars()
+        .fold(String::with_capacity(text.len()), |mut s, c| {
+            match c {
+                '&' => s.push_str("&amp;"),
+                '<' => s.push_str("&lt;"),
+                '>' => s.push_str("&gt;"),
+                '"' => s.push_str("&quot;"),
+                '\'' => s.push_str("&apos;"),
+                _ => s.push(c),
+            }
+            s
+        })
+}
+
+/// Renders canvas commands into SVG document
+pub struct SvgRenderer {
+    width: f32,
+    height: f32,
+    /// Offset applied to all commands before scaling
+    translate: Vec2,
+    scale: f32,
+}
+
+impl SvgRenderer {
+    pub fn new(width: f32, height: f32) -> Self {
+        Self {
+            width,
+            height,
+            translate: Vec2::ZERO,
+            scale: 1.,
+        }
+    }
+
+    /// Set transformation of the root group. Commands are translated first and then scaled
+    pub fn transform(mut self, translate: Vec2, scale: f32) -> Self {
+        self.translate = translate;
+        self.scale = scale;
+        self
+    }
+
+    /// Render commands into svg document
+    /// ### Arguments
+    /// * commands - list of commands to render
+    /// ### Return
+    /// svg document as string
+    pub fn render(&self, commands: &[CanvasCommand]) -> String {
+        let mut svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+            w = self.width,
+            h = self.height
+        );
+        // Writing into String never fails
+        let _ = write!(
+            svg,
+            r#"<g transform="scale({}) translate({} {})">"#,
+            self.scale, self.translate.x, self.translate.y
+        );
+
+        for command in commands {
+            // Writing into String never fails
+            let _ = match command {
+                CanvasCommand::FillRect(rect, color) => {
+                    let (fill, opacity) = svg_color(*color);
+                    write!(
+                        svg,
+                        r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}" fill-opacity="{}"/>"#,
+                        rect.min.x,
+                        rect.min.y,
+                        rect.width(),
+                        rect.height(),
+                        fill,
+                        opacity
+                    )
+                }
+                CanvasCommand::StrokeRect(rect, stroke) => {
+                    let (color, opacity) = svg_color(stroke.color);
+                    write!(
+                        svg,
+                        r#"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="{}" stroke-opacity="{}" stroke-width="{}"/>"#,
+                        rect.min.x,
+                        rect.min.y,
+                        rect.width(),
+                        rect.height(),
+                        color,
+                        opacity,
+                        stroke.width
+                    )
+                }
+                CanvasCommand::DrawLine(points, stroke) => {
+                    let (color, opacity) = svg_color(stroke.color);
+                    write!(
+                        svg,
+                        r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-opacity="{}" stroke-width="{}"/>"#,
+                        points[0].x,
+                        points[0].y,
+                        points[1].x,
+                        points[1].y,
+                        color,
+                        opacity,
+                        stroke.width
+                    )
+                }
+                CanvasCommand::FillPolygon(points, color) => {
+                    let (fill, opacity) = svg_color(*color);
+                    let points = points
+                        .iter()
+                        .map(|p| format!("{},{}", p.x, p.y))
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    write!(
+                        svg,
+                        r#"<polygon points="{}" fill="{}" fill-opacity="{}"/>"#,
+                        points, fill, opacity
+                    )
+                }
+                CanvasCommand::DrawPath {
+                    points,
+                    closed,
+                    fill,
+                    stroke,
+                } => {
+                    let (fill, fill_opacity) = if *closed {
+                        svg_color(*fill)
+                    } else {
+                        ("none".to_owned(), 0.)
+                    };
+                    let (color, opacity) = svg_color(stroke.color);
+                    write!(
+                        svg,
+                        r#"<path d="{}" fill="{}" fill-opacity="{}" stroke="{}" stroke-opacity="{}" stroke-width="{}"/>"#,
+                        svg_path_data(points, *closed),
+                        fill,
+                        fill_opacity,
+                        color,
+                        opacity,
+                        stroke.width
+                    )
+                }
+                CanvasCommand::DrawText {
+                    pos,
+                    text,
+                    font_size,
+                    color,
+                } => {
+                    let (fill, opacity) = svg_color(*color);
+                    write!(
+                        svg,
+                        r#"<text x="{}" y="{}" font-size="{}" dominant-baseline="hanging" fill="{}" fill-opacity="{}">{}</text>"#,
+                        pos.x,
+                        pos.y,
+                        font_size,
+                        fill,
+                        opacity,
+                        xml_escape(text)
+                    )
+                }
+            };
+        }
+
+        svg.push_str("</g></svg>");
+        svg
+    }
+}
+
+/// Renders canvas commands into PNG image using tiny-skia
+pub struct PngRenderer {
+    width: u32,
+    height: u32,
+    background: Color32,
+}
+
+impl PngRenderer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            background: Color32::TRANSPARENT,
+        }
+    }
+
+    pub fn background(mut self, background: Color32) -> Self {
+        self.background = background;
+        self
+    }
+
+    #[inline]
+    fn paint(color: Color32) -> tiny_skia::Paint<'static> {
+        let [r, g, b, a] = color.to_srgba_unmultiplied();
+        let mut paint = tiny_skia::Paint::default();
+        paint.set_color_rgba8(r, g, b, a);
+        paint.anti_alias = true;
+        paint
+    }
+
+    #[inline]
+    fn stroke(stroke: Stroke) -> tiny_skia::Stroke {
+        tiny_skia::Stroke {
+            width: stroke.width,
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    fn rect_path(rect: Rect) -> Option<tiny_skia::Path> {
+        tiny_skia::Rect::from_ltrb(rect.min.x, rect.min.y, rect.max.x, rect.max.y)
+            .map(tiny_skia::PathBuilder::from_rect)
+    }
+
+    fn polyline_path(points: &[Pos2], closed: bool) -> Option<tiny_skia::Path> {
+        let (first, rest) = points.split_first()?;
+        let mut pb = tiny_skia::PathBuilder::new();
+        pb.move_to(first.x, first.y);
+        for p in rest {
+            pb.line_to(p.x, p.y);
+        }
+        if closed {
+            pb.close();
+        }
+        pb.finish()
+    }
+
+    /// Render commands into PNG image. Text commands are skipped because tiny-skia
+    /// is not able to draw text
+    /// ### Arguments
+    /// * commands - list of commands to render
+    /// ### Return
+    /// encoded png image bytes
+    pub fn render(&self, commands: &[CanvasCommand]) -> Result<Vec<u8>, MxErrors> {
+        let mut pixmap = tiny_skia::Pixmap::new(self.width, self.height).ok_or_else(|| {
+            MxErrors::RenderError(format!("Wrong image size {}x{}", self.width, self.height))
+        })?;
+
+        let [r, g, b, a] = self.background.to_srgba_unmultiplied();
+        pixmap.fill(tiny_skia::Color::from_rgba8(r, g, b, a));
+
+        let transform = tiny_skia::Transform::identity();
+        for command in commands {
+            match command {
+                CanvasCommand::FillRect(rect, color) => {
+                    if let Some(path) = Self::rect_path(*rect) {
+                        pixmap.fill_path(
+                            &path,
+                            &Self::paint(*color),
+                            tiny_skia::FillRule::Winding,
+                            transform,
+                            None,
+                        );
+                    }
+                }
+                CanvasCommand::StrokeRect(rect, stroke) => {
+                    if let Some(path) = Self::rect_path(*rect) {
+                        pixmap.stroke_path(
+                            &path,
+                            &Self::paint(stroke.color),
+                            &Self::stroke(*stroke),
+                            transform,
+                            None,
+                        );
+                    }
+                }
+                CanvasCommand::DrawLine(points, stroke) => {
+                    if let Some(path) = Self::polyline_path(points, false) {
+                        pixmap.stroke_path(
+                            &path,
+                            &Self::paint(stroke.color),
+                            &Self::stroke(*stroke),
+                            transform,
+                            None,
+                        );
+                    }
+                }
+                CanvasCommand::FillPolygon(points, color) => {
+                    if let Some(path) = Self::polyline_path(points, true) {
+                        pixmap.fill_path(
+                            &path,
+                            &Self::paint(*color),
+                            tiny_skia::FillRule::Winding,
+                            transform,
+                            None,
+                        );
+                    }
+                }
+                CanvasCommand::DrawPath {
+                    points,
+                    closed,
+                    fill,
+                    stroke,
+                } => {
+                    if let Some(path) = Self::polyline_path(points, *closed) {
+                        if *closed {
+                            pixmap.fill_path(
+                                &path,
+                                &Self::paint(*fill),
+                                tiny_skia::FillRule::Winding,
+                                transform,
+                                None,
+                            );
+                        }
+                        pixmap.stroke_path(
+                            &path,
+                            &Self::paint(stroke.color),
+                            &Self::stroke(*stroke),
+                            transform,
+                            None,
+                        );
+                    }
+                }
+                CanvasCommand::DrawText { text, .. } => {
+                    tracing::debug!("Text is not supported by png renderer: {}", text);
+                }
+            }
+        }
+
+        pixmap
+            .encode_png()
+            .map_err(|err| MxErrors::RenderError(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eframe::{
+        emath::{pos2, vec2, Rect},
+        epaint::{Color32, PathShape, RectShape, Rounding, Stroke},
+    };
+
+    use super::{CanvasCommand, PngRenderer, SvgRenderer};
+    use crate::rgraph::Figure;
+
+    #[test]
+    fn test_rect_to_canvas_commands() {
+        let rect = Rect::from_two_pos(pos2(0., 0.), pos2(10., 20.));
+        let stroke = Stroke::new(1., Color32::RED);
+        let figure = Figure::Rect(RectShape {
+            rect,
+            rounding: Rounding::none(),
+            fill: Color32::BLUE,
+            stroke,
+        });
+
+        let commands = figure.to_canvas_commands();
+        assert_eq!(
+            commands,
+            vec![
+                CanvasCommand::FillRect(rect, Color32::BLUE),
+                CanvasCommand::StrokeRect(rect, stroke)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_vec_to_canvas_commands() {
+        let stroke = Stroke::new(1., Color32::RED);
+        let figure = Figure::Vec(vec![
+            Figure::LineSegment {
+                points: [pos2(0., 0.), pos2(10., 10.)],
+                stroke,
+            },
+            Figure::LineSegment {
+                points: [pos2(10., 10.), pos2(20., 0.)],
+                stroke,
+            },
+        ]);
+
+        assert_eq!(figure.to_canvas_commands().len(), 2);
+    }
+
+    #[test]
+    fn test_svg_renderer() {
+        let svg = SvgRenderer::new(100., 50.).render(&[
+            CanvasCommand::FillRect(
+                Rect::from_two_pos(pos2(0., 0.), pos2(10., 20.)),
+                Color32::BLUE,
+            ),
+            CanvasCommand::DrawText {
+                pos: pos2(1., 1.),
+                text: "a < b".to_owned(),
+                font_size: 12.,
+                color: Color32::BLACK,
+            },
+        ]);
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.ends_with("</svg>"));
+        assert!(svg.contains(r#"<rect x="0" y="0" width="10" height="20""#));
+        assert!(svg.contains("a &lt; b"));
+    }
+
+    #[test]
+    fn test_svg_path_and_transform() {
+        let stroke = Stroke::new(2., Color32::RED);
+        let figure = Figure::Path(PathShape::line(
+            vec![pos2(0., 0.), pos2(10., 0.), pos2(10., 10.)],
+            stroke,
+        ));
+        let svg = SvgRenderer::new(100., 50.)
+            .transform(vec2(-5., 0.), 0.5)
+            .render(&figure.to_canvas_commands());
+
+        assert!(svg.contains(r#"<g transform="scale(0.5) translate(-5 0)">"#));
+        assert!(svg.contains(r#"<path d="M 0 0 L 10 0 L 10 10" fill="none""#));
+        assert!(svg.ends_with("</g></svg>"));
+    }
+
+    #[test]
+    fn test_png_renderer() {
+        let png = PngRenderer::new(20, 20)
+            .render(&[CanvasCommand::FillPolygon(
+                vec![pos2(0., 0.), pos2(20., 0.), pos2(20., 20.)],
+                Color32::GREEN,
+            )])
+            .unwrap();
+
+        assert_eq!(&png[1..4], b"PNG");
+        assert!(PngRenderer::new(0, 0).render(&[]).is_err());
+    }
+}
@@ -71,6 +71,40 @@ impl Figure {
         }
     }
 
+    /// Bounding box of the shape. Bezier curves are bounded by their control points
+    /// ### Return
+    /// * None for shapes without points, e.g. empty path or empty vector of shapes
+    pub fn bounding_box(&self) -> Option<Rect> {
+        match self {
+            Figure::Vec(shapes) => shapes
+                .iter()
+                .filter_map(Figure::bounding_box)
+                .reduce(Rect::union),
+            Figure::LineSegment { points, .. } => Some(Rect::from_two_pos(points[0], points[1])),
+            Figure::Path(path_shape) => Self::points_box(&path_shape.points),
+            Figure::Rect(rect_shape) => Some(rect_shape.rect),
+            Figure::Text(text_shape) => Some(text_shape.visual_bounding_rect()),
+            Figure::Mesh(mesh) if mesh.vertices.is_empty() => None,
+            Figure::Mesh(mesh) => Some(mesh.calc_bounds()),
+            Figure::QuadraticBezier(bezier_shape) => Some(Rect::from_points(&bezier_shape.points)),
+            Figure::CubicBezier(cubie_curve) => Some(Rect::from_points(&cubie_curve.points)),
+        }
+    }
+
+    /// Bounding box of the points, None if there are no points
+    fn points_box(points: &[Pos2]) -> Option<Rect> {
+        if points.is_empty() {
+            None
+        } else {
+            Some(Rect::from_points(points))
+        }
+    }
+
+    /// Rectangle containing the shape, `Rect::NOTHING` if the shape has no points
+    pub fn rect(&self) -> Rect {
+        self.bounding_box().unwrap_or(Rect::NOTHING)
+    }
+
     /// Zoom shape
     pub fn zoom(&mut self, zoom_factor: f32) {
         match self {
@@ -122,7 +156,7 @@ impl Figure {
     /// * epsilone - tolerance
     /// ### Return
     /// * Contained enum or None
-    fn contains_in_rect(rect: Rect, point: Pos2, epsilon: f32) -> Option<Contained> {
+    pub(crate) fn contains_in_rect(rect: Rect, point: Pos2, epsilon: f32) -> Option<Contained> {
         match rect.contains(point) {
             true if point.distance(rect.right_top()) <= epsilon => {
                 Some(Contained::ResizeTRtoBL(point))
@@ -156,6 +190,11 @@ impl Figure {
     /// Check if the figure contains given point
     /// TODO: Transform to Contains trait and implements the trait for each figure independently
     pub fn contains(&self, point: Pos2, epsilon: f32) -> Option<Contained> {
+        // Points far from the bounding box cannot belong to the figure
+        if !self.bounding_box()?.expand(epsilon).contains(point) {
+            return None;
+        }
+
         match self {
             Figure::Vec(shapes) => shapes
                 .iter()
@@ -175,8 +214,20 @@ impl Figure {
                     None
                 }
             }
-            Figure::Text(text) => {
-                Self::contains_in_rect(text.visual_bounding_rect(), point, epsilon)
+            Figure::Text(_) => Self::contains_in_rect(self.bounding_box()?, point, epsilon),
+            Figure::QuadraticBezier(bezier) => {
+                if point.belong_quadratic_bezier(&bezier.points, epsilon) {
+                    Some(Contained::InArea)
+                } else {
+                    None
+                }
+            }
+            Figure::CubicBezier(bezier) => {
+                if point.belong_cubic_bezier(&bezier.points, epsilon) {
+                    Some(Contained::InArea)
+                } else {
+                    None
+                }
             }
             _ => {
                 tracing::error!("Sorry, I don't know how to determine belonging ath the moment");
@@ -219,6 +270,15 @@ impl MxCell {
         self
     }
 
+    /// Rectangle containing all the shapes, `Rect::NOTHING` if cell has no shapes
+    pub fn rect(&self) -> Rect {
+        self.shapes
+            .iter()
+            .filter_map(Figure::bounding_box)
+            .reduce(Rect::union)
+            .unwrap_or(Rect::NOTHING)
+    }
+
     /// Zoom all the shapes using given zoom_factor
     pub fn zoom(&mut self, zoom_factor: f32) -> &mut Self {
         self.shapes.iter_mut().for_each(|shape| {
@@ -261,15 +321,74 @@ impl MxCell {
             CellType::Edge(edge) => edge.contains(point),
             _ => match self.state {
                 MxCellState::Selected => self.find_cp(point, epsilon),
-
-                // check all shapes in
-                // ! REVERSE order
-                _ => self
-                    .shapes
-                    .iter()
-                    .rev()
-                    .find_map(|figure| figure.contains(point, epsilon)),
+                _ => self.shapes_contain(point, epsilon),
             },
         }
     }
+
+    /// Check if given point belongs to any shape of the cell regardless of the cell state
+    /// ### Arguments
+    /// * point - position to check
+    /// * epsilon - ± tolerance over which point will be determined
+    /// ### Return
+    /// * Option of Contained enum
+    pub fn shapes_contain(&self, point: Pos2, epsilon: f32) -> Option<Contained> {
+        // check all shapes in
+        // ! REVERSE order
+        self.shapes
+            .iter()
+            .rev()
+            .find_map(|figure| figure.contains(point, epsilon))
+    }
+
+    /// Find index of the connection point nearest to the given point
+    pub fn nearest_cp(&self, point: Pos2) -> Option<usize> {
+        self.connection_points
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                a.distance(point)
+                    .partial_cmp(&b.distance(point))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|(idx, _)| idx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eframe::{
+        emath::{pos2, Rect},
+        epaint::{Color32, PathShape, RectShape, Rounding, Stroke},
+    };
+
+    use crate::rgraph::Figure;
+
+    #[test]
+    fn test_bounding_box() {
+        let stroke = Stroke::new(1., Color32::RED);
+        let rect = Figure::Rect(RectShape {
+            rect: Rect::from_min_max(pos2(0., 0.), pos2(10., 20.)),
+            rounding: Rounding::none(),
+            fill: Color32::WHITE,
+            stroke,
+        });
+        let line = Figure::LineSegment {
+            points: [pos2(30., 40.), pos2(20., 5.)],
+            stroke,
+        };
+        assert_eq!(
+            line.bounding_box(),
+            Some(Rect::from_min_max(pos2(20., 5.), pos2(30., 40.)))
+        );
+        assert_eq!(
+            Figure::Vec(vec![rect, line]).bounding_box(),
+            Some(Rect::from_min_max(pos2(0., 0.), pos2(30., 40.)))
+        );
+
+        let empty_path = Figure::Path(PathShape::line(vec![], stroke));
+        assert_eq!(empty_path.bounding_box(), None);
+        assert_eq!(Figure::Vec(vec![empty_path]).bounding_box(), None);
+        assert_eq!(Figure::Vec(vec![]).rect(), Rect::NOTHING);
+    }
 }
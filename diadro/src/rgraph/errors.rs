@@ -7,4 +7,16 @@ pub enum MxErrors {
     WrongMxCellType,
     #[error("Cell not found")]
     MxCellNotFound,
+    #[error("Render error: {0}")]
+    RenderError(String),
+    #[error("Negative edge weight: {0}")]
+    NegativeWeight(f32),
+    #[error("Waypoint index {index} is out of bounds, line has {len} points")]
+    WaypointIndexOutOfBounds { index: usize, len: usize },
+    #[error("Deserialized graph is incomplete: {field}")]
+    DeserializationIncomplete { field: String },
+    #[error("Connection rejected: {reason}")]
+    ConnectionRejected { reason: String },
+    #[error("Serialization failed: {0}")]
+    SerializationFailed(String),
 }
@@ -1,10 +1,29 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use eframe::emath::{Pos2, Vec2};
+use eframe::{
+    egui::{Id, InnerResponse, Sense, Ui},
+    emath::{Pos2, Vec2},
+    epaint::{Color32, Shape, Stroke},
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
-use super::{ucell::UnMxEdge, MxCell};
+use crate::{
+    graph::{id_to_string, Zoom},
+    ws::{AddArrow, AddFigure, FigureShape, RequestInfo, WsMessages},
+};
 
-#[allow(dead_code)]
+use super::{
+    algo,
+    errors::MxErrors,
+    ucell::{EdgeVertex, UnMxEdge},
+    validator::ConnectionValidator,
+    Contained, Figure, MxCell, MxCellState,
+};
+
+/// Zoom and scroll of the graph view. Cells and edges are kept in graph coordinates and
+/// transformed when drawn
+#[derive(Clone, Copy)]
 pub(crate) struct Transform {
     pub(crate) scroll_delta: Vec2,
     pub(crate) zoom_factor: f32,
@@ -19,32 +38,116 @@ impl Default for Transform {
     }
 }
 
+impl Transform {
+    /// Screen position of the graph point
+    fn apply(&self, point: Pos2) -> Pos2 {
+        point.zoom(self.zoom_factor) + self.scroll_delta
+    }
+
+    /// Graph point shown at the screen position
+    fn invert(&self, point: Pos2) -> Pos2 {
+        (point - self.scroll_delta).unzoom(self.zoom_factor)
+    }
+
+    /// Shape of the figure transformed the same way as edges are
+    fn figure_shape(&self, figure: &Figure) -> Shape {
+        let mut figure = figure.clone();
+        figure.zoom(self.zoom_factor);
+        figure.translate(self.scroll_delta);
+        figure.to_shape()
+    }
+}
+
 #[allow(dead_code)]
 pub enum GraphState {
+    /// Cell placed by the next click
     Tool(MxCell),
+    /// Edge being drawn from connection point of the cell
     Arrow(UnMxEdge),
-    Dragged(MxCell),
+    /// Cell being dragged with the last drag position
+    Dragged(Rc<RefCell<MxCell>>, Pos2),
     Nothing,
 }
 
 #[allow(dead_code)]
 pub struct GraphUI {
-    last_id: usize,
     transform: Transform,
     state: GraphState,
     cells: Vec<Rc<RefCell<MxCell>>>,
     edges: Vec<UnMxEdge>,
+    /// Index of the cell under cursor
+    hovered: Option<usize>,
+    /// Index of the cell highlighted as the end of the drawn edge
+    candidate: Option<usize>,
+    /// Rule checked when drawn edge is connected to the end cell
+    validator: Option<Box<dyn ConnectionValidator>>,
+    /// Left top corner of the dragged cell at the drag start
+    drag_origin: Option<Pos2>,
 }
 
 impl Default for GraphUI {
     fn default() -> Self {
         Self {
-            last_id: Default::default(),
             transform: Default::default(),
             state: GraphState::Nothing,
             cells: Default::default(),
             edges: Default::default(),
+            hovered: None,
+            candidate: None,
+            validator: None,
+            drag_origin: None,
+        }
+    }
+}
+
+/// Serializable form of the graph used for saving
+#[derive(Serialize)]
+struct GraphDataRef<'a> {
+    cells: Vec<&'a RefCell<MxCell>>,
+    edges: &'a [UnMxEdge],
+}
+
+/// Serializable form of the graph used for loading
+#[derive(Deserialize)]
+struct GraphData {
+    cells: Vec<MxCell>,
+    edges: Vec<UnMxEdge>,
+}
+
+/// Implies saving and loading of graphs
+impl GraphUI {
+    /// Serialize graph into JSON. Edges refer to cells by identifiers
+    pub fn to_json(&self) -> Result<String, MxErrors> {
+        serde_json::to_string(&GraphDataRef {
+            cells: self.cells.iter().map(|cell| cell.as_ref()).collect(),
+            edges: &self.edges,
+        })
+        .map_err(|err| MxErrors::SerializationFailed(err.to_string()))
+    }
+
+    /// Load graph from JSON produced by `to_json`. Edges are reconnected to the loaded cells,
+    /// edges connected to missing cells are kept with their stub ends
+    pub fn from_json(s: &str) -> Result<Self, MxErrors> {
+        let data: GraphData = serde_json::from_str(s)
+            .map_err(|err| MxErrors::SerializationFailed(err.to_string()))?;
+        let cells: Vec<_> = data
+            .cells
+            .into_iter()
+            .map(|cell| Rc::new(RefCell::new(cell)))
+            .collect();
+
+        let mut edges = data.edges;
+        for edge in &mut edges {
+            if let Err(err) = edge.reconnect(&cells) {
+                tracing::warn!("{}", err);
+            }
         }
+
+        Ok(Self {
+            cells,
+            edges,
+            ..Default::default()
+        })
     }
 }
 
@@ -53,19 +156,478 @@ impl GraphUI {
     /// Tolerance for detect cursor in point
     const EPSILON: f32 = 5.0;
 
+    /// Add cell into graph
+    pub fn add_cell(&mut self, cell: MxCell) -> Rc<RefCell<MxCell>> {
+        let cell = Rc::new(RefCell::new(cell));
+        self.cells.push(cell.clone());
+        cell
+    }
+
+    /// Set rule checked when drawn edge is connected to the end cell
+    pub fn set_validator(&mut self, validator: Option<Box<dyn ConnectionValidator>>) {
+        self.validator = validator;
+    }
+
+    /// Choose cell placed by the next click
+    pub fn set_tool(&mut self, cell: MxCell) {
+        self.state = GraphState::Tool(cell);
+    }
+
+    /// Set zoom and scroll of the graph view
+    pub fn set_transform(&mut self, zoom_factor: f32, scroll_delta: Vec2) {
+        self.transform = Transform {
+            scroll_delta,
+            zoom_factor,
+        };
+    }
+
+    /// Generate identifier of the placed cell. Identifiers are random, so cells placed by
+    /// different clients never share identifier
+    fn generate_id() -> Id {
+        Id::new(Uuid::new_v4())
+    }
+
+    /// Move cell shapes and its connection points
+    fn move_cell(cell: &mut MxCell, delta: Vec2) {
+        cell.translate(delta);
+        cell.connection_points
+            .iter_mut()
+            .for_each(|point| *point += delta);
+    }
+
+    /// Recompute points of the edges after cells were moved
+    fn compute_edges(&mut self) {
+        self.edges
+            .iter_mut()
+            .filter(|edge| !edge.points().is_empty())
+            .for_each(UnMxEdge::compute_points);
+    }
+
+    /// Find topmost cell containing given point. Cell state is not taken into account,
+    /// so selected cells are found by their shapes too
+    fn cell_at(&self, point: Pos2, skip: Option<&Rc<RefCell<MxCell>>>) -> Option<usize> {
+        self.cells.iter().rposition(|cell| {
+            !skip.is_some_and(|skip| Rc::ptr_eq(skip, cell))
+                && cell.borrow().shapes_contain(point, Self::EPSILON).is_some()
+        })
+    }
+
+    /// Set state of the cell found by index, out of range indexes are ignored
+    fn set_cell_state(&self, idx: Option<usize>, state: MxCellState) {
+        if let Some(cell) = idx.and_then(|idx| self.cells.get(idx)) {
+            cell.borrow_mut().set_state(state);
+        }
+    }
+
+    /// Handle cursor movement. Cell under cursor is selected to show its connection points,
+    /// while edge is drawn the cell under cursor is highlighted as the edge end
+    /// ### Arguments
+    /// * hover_point - cursor position
     pub fn on_hover(&mut self, hover_point: Pos2) {
-        match self.state {
-            // Do nothing because
+        match &self.state {
             GraphState::Nothing => {
-                // Trying to find selected figure
-                let _ = self.cells.iter().find_map(|cell| {
-                    let mut_cell = cell.borrow_mut();
-                    mut_cell.contains(hover_point, Self::EPSILON)
-                });
+                // Hovered cell keeps selection while cursor is over its shapes or
+                // connection points
+                let keep = self
+                    .hovered
+                    .and_then(|idx| self.cells.get(idx))
+                    .is_some_and(|cell| {
+                        let cell = cell.borrow();
+                        cell.contains(hover_point, Self::EPSILON).is_some()
+                            || cell.shapes_contain(hover_point, Self::EPSILON).is_some()
+                    });
+                if keep {
+                    return;
+                }
+
+                let hovered = self.hovered.take();
+                self.set_cell_state(hovered, MxCellState::Free);
+                self.hovered = self
+                    .cells
+                    .iter()
+                    .rposition(|cell| cell.borrow().contains(hover_point, Self::EPSILON).is_some());
+                self.set_cell_state(self.hovered, MxCellState::Selected);
+            }
+            GraphState::Arrow(edge) => {
+                let candidate = self.cell_at(hover_point, edge.get_start_rc().as_ref());
+                if candidate != self.candidate {
+                    self.set_cell_state(self.candidate, MxCellState::Free);
+                    self.set_cell_state(candidate, MxCellState::Selected);
+                    self.candidate = candidate;
+                }
             }
             _ => {}
         }
     }
+
+    /// Handle click. Chosen tool cell is placed with its center at the click position and gets
+    /// new identifier, otherwise cell under cursor becomes the only selected cell
+    /// ### Arguments
+    /// * point - click position
+    pub fn on_click(&mut self, point: Pos2) {
+        match std::mem::replace(&mut self.state, GraphState::Nothing) {
+            GraphState::Tool(mut cell) => {
+                cell.id = Self::generate_id();
+                let delta = point - cell.rect().center();
+                Self::move_cell(&mut cell, delta);
+                self.add_cell(cell);
+            }
+            GraphState::Nothing => {
+                let clicked = self.cell_at(point, None);
+                for (idx, cell) in self.cells.iter().enumerate() {
+                    cell.borrow_mut().set_state(if Some(idx) == clicked {
+                        MxCellState::Selected
+                    } else {
+                        MxCellState::Free
+                    });
+                }
+                self.hovered = clicked;
+            }
+            state => self.state = state,
+        }
+    }
+
+    /// Handle drag start. Dragging from connection point of the hovered cell starts
+    /// drawing edge, dragging the cell itself moves it
+    /// ### Arguments
+    /// * point - drag start position
+    pub fn on_drag_start(&mut self, point: Pos2) {
+        if !matches!(self.state, GraphState::Nothing) {
+            return;
+        }
+
+        let cell = match self.hovered.and_then(|idx| self.cells.get(idx)) {
+            Some(cell) => cell.clone(),
+            None => match self.cell_at(point, None) {
+                Some(idx) => self.cells[idx].clone(),
+                None => return,
+            },
+        };
+
+        let contained = cell.borrow().contains(point, Self::EPSILON);
+        self.state = match contained {
+            Some(Contained::ConnectionPoint(idx)) => {
+                let mut edge =
+                    UnMxEdge::from_vertices(EdgeVertex::Cell(cell, idx), EdgeVertex::Pos(point));
+                edge.compute_points();
+                GraphState::Arrow(edge)
+            }
+            _ => {
+                cell.borrow_mut().set_state(MxCellState::Dragging);
+                GraphState::Dragged(cell, point)
+            }
+        };
+    }
+
+    /// Handle dragging: move dragged cell or the free end of the drawn edge
+    /// ### Arguments
+    /// * point - cursor position
+    pub fn on_drag(&mut self, point: Pos2) {
+        match &mut self.state {
+            GraphState::Dragged(cell, last) => {
+                Self::move_cell(&mut cell.borrow_mut(), point - *last);
+                *last = point;
+                self.compute_edges();
+            }
+            GraphState::Arrow(edge) => {
+                edge.set_end_pos(point);
+                self.on_hover(point);
+            }
+            _ => {}
+        }
+    }
+
+    /// Handle drag end. Dragged cell stays selected, drawn edge is connected to the nearest
+    /// connection point of the cell under cursor or keeps its free end if there is no cell
+    /// or validator rejects the connection
+    /// ### Arguments
+    /// * point - drag end position
+    pub fn on_drag_end(&mut self, point: Pos2) {
+        self.on_drag(point);
+        match std::mem::replace(&mut self.state, GraphState::Nothing) {
+            GraphState::Dragged(cell, _) => {
+                cell.borrow_mut().set_state(MxCellState::Selected);
+            }
+            GraphState::Arrow(mut edge) => {
+                if let Some(cell) = self.candidate.and_then(|idx| self.cells.get(idx)) {
+                    let cp = cell.borrow().nearest_cp(point);
+                    if let Some(cp) = cp {
+                        if let Err(err) = edge.set_end(cell.clone(), cp, self.validator.as_deref())
+                        {
+                            tracing::warn!("{}", err);
+                        }
+                    }
+                }
+                let candidate = self.candidate.take();
+                self.set_cell_state(candidate, MxCellState::Free);
+                self.edges.push(edge);
+            }
+            state => self.state = state,
+        }
+    }
+
+    /// Handle double click: inserts waypoint when clicked on edge line and removes waypoint
+    /// when clicked on it
+    /// ### Arguments
+    /// * point - double click position
+    /// ### Return
+    /// true if some edge was changed
+    pub fn on_double_click(&mut self, point: Pos2) -> bool {
+        self.edges.iter_mut().any(|edge| edge.double_click(point))
+    }
+
+    /// Handle pointer input and draw the graph. Allows using cell model as the primary drawing
+    /// path instead of `GraphicsData`
+    /// ### Arguments
+    /// * ui - egui object to draw in
+    /// ### Return
+    /// response of the graph area and messages describing changes made by the user
+    pub fn ui(&mut self, ui: &mut Ui) -> InnerResponse<Vec<WsMessages>> {
+        let (response, painter) = ui.allocate_painter(ui.available_size(), Sense::click_and_drag());
+
+        // Pointer is handled in graph coordinates
+        let transform = self.transform;
+        let hover = response.hover_pos().map(|point| transform.invert(point));
+        let pointer = response
+            .interact_pointer_pos()
+            .map(|point| transform.invert(point))
+            .or(hover);
+
+        let mut messages = vec![];
+        if let Some(point) = hover {
+            self.on_hover(point);
+        }
+
+        if let Some(point) = pointer {
+            if response.drag_started() {
+                self.handle_drag_start(point);
+            } else if response.dragged() {
+                self.on_drag(point);
+            }
+
+            if response.drag_released() {
+                messages.extend(self.handle_drag_end(point));
+            }
+
+            if response.double_clicked() {
+                self.on_double_click(point);
+            } else if response.clicked() {
+                messages.extend(self.handle_click(point));
+            }
+        }
+
+        for cell in &self.cells {
+            let cell = cell.borrow();
+            for figure in &cell.shapes {
+                painter.add(transform.figure_shape(figure));
+            }
+
+            if matches!(cell.state, MxCellState::Selected) {
+                let stroke = Stroke::new(1., Color32::BLUE);
+                for point in &cell.connection_points {
+                    painter.circle_stroke(transform.apply(*point), Self::EPSILON, stroke);
+                }
+            }
+        }
+
+        let (zoom_factor, scroll_delta) = (transform.zoom_factor, transform.scroll_delta);
+        for edge in &mut self.edges {
+            edge.draw(ui, zoom_factor, scroll_delta);
+        }
+
+        if let GraphState::Arrow(edge) = &mut self.state {
+            edge.draw(ui, zoom_factor, scroll_delta);
+        }
+
+        InnerResponse::new(messages, response)
+    }
+
+    /// Handle click and describe its result
+    /// ### Arguments
+    /// * point - click position
+    /// ### Return
+    /// `AddFigure` if tool cell was placed, `SelectionChange` otherwise
+    fn handle_click(&mut self, point: Pos2) -> Vec<WsMessages> {
+        let placed = matches!(self.state, GraphState::Tool(_));
+        self.on_click(point);
+
+        if placed {
+            self.cells
+                .last()
+                .map(|cell| {
+                    let cell = cell.borrow();
+                    let text = cell
+                        .shapes
+                        .iter()
+                        .filter_map(|figure| match figure {
+                            Figure::Text(text) => Some(text.galley.text()),
+                            _ => None,
+                        })
+                        .collect();
+                    WsMessages::AddFigure(AddFigure {
+                        rq: RequestInfo::default(),
+                        figure_id: id_to_string(cell.id),
+                        shape: FigureShape::Rect,
+                        rect: cell.rect(),
+                        text,
+                    })
+                })
+                .into_iter()
+                .collect()
+        } else {
+            vec![WsMessages::SelectionChange {
+                rq: RequestInfo::default(),
+                selected_ids: self
+                    .hovered
+                    .and_then(|idx| self.cells.get(idx))
+                    .map(|cell| id_to_string(cell.borrow().id))
+                    .into_iter()
+                    .collect(),
+            }]
+        }
+    }
+
+    /// Handle drag start remembering position of the dragged cell
+    /// ### Arguments
+    /// * point - drag start position
+    fn handle_drag_start(&mut self, point: Pos2) {
+        self.on_drag_start(point);
+        self.drag_origin = match &self.state {
+            GraphState::Dragged(cell, _) => Some(cell.borrow().rect().min),
+            _ => None,
+        };
+    }
+
+    /// Handle drag end and describe its result
+    /// ### Arguments
+    /// * point - drag end position
+    /// ### Return
+    /// `MoveFigure` if cell was moved, `AddArrow` if edge connecting two cells was drawn
+    fn handle_drag_end(&mut self, point: Pos2) -> Vec<WsMessages> {
+        let moved = match &self.state {
+            GraphState::Dragged(cell, _) => Some(cell.clone()),
+            _ => None,
+        };
+        let edges = self.edges.len();
+        self.on_drag_end(point);
+
+        let mut messages = vec![];
+        if let (Some(cell), Some(origin)) = (moved, self.drag_origin.take()) {
+            let cell = cell.borrow();
+            let delta = cell.rect().min - origin;
+            if delta != Vec2::ZERO {
+                messages.push(WsMessages::MoveFigure {
+                    rq: RequestInfo::default(),
+                    figure_id: id_to_string(cell.id),
+                    dx: delta.x,
+                    dy: delta.y,
+                });
+            }
+        }
+
+        if let Some(edge) = self.edges.get(edges) {
+            if let (Some(start), Some(end)) = (edge.get_start(), edge.get_end()) {
+                messages.push(WsMessages::AddArrow(AddArrow {
+                    rq: RequestInfo::default(),
+                    start_id: id_to_string(start.id),
+                    end_id: id_to_string(end.id),
+                }));
+            }
+        }
+
+        messages
+    }
+
+    /// Find shortest path between two cells
+    /// ### Arguments
+    /// * from - start cell identifier
+    /// * to - end cell identifier
+    /// ### Return
+    /// indexes of the edges forming the path or None if cells are not connected
+    pub fn shortest_path(&self, from: Id, to: Id) -> Option<Vec<usize>> {
+        // Only edges connected on both sides take part in the path
+        let (indexes, edges): (Vec<usize>, Vec<(Id, Id)>) = self
+            .edges
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, edge)| match (edge.get_start(), edge.get_end()) {
+                (Some(start), Some(end)) => Some((idx, (start.id, end.id))),
+                _ => None,
+            })
+            .unzip();
+
+        algo::shortest_path(&edges, from, to)
+            .map(|path| path.into_iter().map(|idx| indexes[idx]).collect())
+    }
+
+    /// Find path with minimal total weight between two cells. Edges without weight cost 1
+    /// ### Arguments
+    /// * from - start cell identifier
+    /// * to - end cell identifier
+    /// ### Return
+    /// identifiers of the cells forming the path with total cost or None if cells are not
+    /// connected, NegativeWeight error if some edge has negative weight
+    pub fn shortest_path_weighted(
+        &self,
+        from: Id,
+        to: Id,
+    ) -> Result<Option<(Vec<Id>, f32)>, MxErrors> {
+        let edges: Vec<(Id, Id, f32)> = self
+            .edges
+            .iter()
+            .filter_map(|edge| match (edge.get_start(), edge.get_end()) {
+                (Some(start), Some(end)) => Some((start.id, end.id, edge.weight().unwrap_or(1.))),
+                _ => None,
+            })
+            .collect();
+
+        algo::shortest_path_weighted(&edges, from, to)
+    }
+
+    /// Arrange cells using force-directed layout. Cells repel each other and edges connected
+    /// on both sides act as springs. Cells are moved by translating their shapes
+    /// ### Arguments
+    /// * iterations - number of simulation steps
+    /// * repulsion - repulsion factor between all cells
+    /// * attraction - attraction factor of the edges
+    pub fn force_directed_layout(&mut self, iterations: u32, repulsion: f32, attraction: f32) {
+        let positions: Vec<Pos2> = self
+            .cells
+            .iter()
+            .map(|cell| {
+                let rect = cell.borrow().rect();
+                if rect.is_positive() {
+                    rect.center()
+                } else {
+                    Pos2::ZERO
+                }
+            })
+            .collect();
+        let indexes: HashMap<Id, usize> = self
+            .cells
+            .iter()
+            .enumerate()
+            .map(|(idx, cell)| (cell.borrow().id, idx))
+            .collect();
+        let edges: Vec<(usize, usize)> = self
+            .edges
+            .iter()
+            .filter_map(|edge| match (edge.get_start(), edge.get_end()) {
+                (Some(start), Some(end)) => {
+                    Some((*indexes.get(&start.id)?, *indexes.get(&end.id)?))
+                }
+                _ => None,
+            })
+            .collect();
+
+        let layout =
+            algo::force_directed_layout(&positions, &edges, iterations, repulsion, attraction);
+        for ((cell, from), to) in self.cells.iter().zip(positions).zip(layout) {
+            Self::move_cell(&mut cell.borrow_mut(), to - from);
+        }
+
+        self.compute_edges();
+    }
 }
 
 #[allow(dead_code)]
@@ -74,3 +636,224 @@ pub struct Graph {
 }
 
 impl Graph {}
+
+#[cfg(test)]
+mod tests {
+    use eframe::{
+        egui::Id,
+        emath::{pos2, vec2, Pos2, Rect},
+        epaint::{Color32, RectShape, Rounding, Shape, Stroke},
+    };
+
+    use std::rc::Rc;
+
+    use super::{GraphState, GraphUI};
+    use crate::{
+        graph::id_to_string,
+        rgraph::{errors::MxErrors, Figure, MxCell, MxCellState},
+        ws::{AddFigure, WsMessages},
+    };
+
+    /// Rectangle cell with connection points in the middles of the sides
+    fn rect_cell(id: u64, min: Pos2) -> MxCell {
+        let rect = Rect::from_min_size(min, vec2(100., 50.));
+        let mut cell = MxCell::new(Id::new(id));
+        cell.shapes.push(Figure::Rect(RectShape {
+            rect,
+            rounding: Rounding::none(),
+            fill: Color32::WHITE,
+            stroke: Stroke::new(1., Color32::BLACK),
+        }));
+        cell.connection_points = vec![
+            rect.center_top(),
+            rect.right_center(),
+            rect.center_bottom(),
+            rect.left_center(),
+        ];
+        cell
+    }
+
+    #[test]
+    fn test_hover_and_drag() {
+        let mut graph = GraphUI::default();
+        let cell = graph.add_cell(rect_cell(1, pos2(0., 0.)));
+
+        graph.on_hover(pos2(50., 25.));
+        assert!(matches!(cell.borrow().state, MxCellState::Selected));
+        assert_eq!(graph.hovered, Some(0));
+
+        // Selected cell keeps hover while cursor is inside
+        graph.on_hover(pos2(60., 25.));
+        assert_eq!(graph.hovered, Some(0));
+        graph.on_hover(pos2(300., 300.));
+        assert!(matches!(cell.borrow().state, MxCellState::Free));
+        assert_eq!(graph.hovered, None);
+
+        graph.on_hover(pos2(50., 25.));
+        graph.on_drag_start(pos2(50., 25.));
+        assert!(matches!(graph.state, GraphState::Dragged(..)));
+        graph.on_drag(pos2(60., 35.));
+        graph.on_drag_end(pos2(70., 45.));
+        assert!(matches!(graph.state, GraphState::Nothing));
+        assert_eq!(cell.borrow().rect().min, pos2(20., 20.));
+        assert_eq!(cell.borrow().connection_points[1], pos2(120., 45.));
+        assert!(matches!(cell.borrow().state, MxCellState::Selected));
+    }
+
+    #[test]
+    fn test_draw_edge() {
+        let mut graph = GraphUI::default();
+        let start = graph.add_cell(rect_cell(1, pos2(0., 0.)));
+        let end = graph.add_cell(rect_cell(2, pos2(200., 0.)));
+
+        // Drag from the right connection point of the first cell
+        graph.on_hover(pos2(50., 25.));
+        graph.on_drag_start(pos2(100., 25.));
+        assert!(matches!(graph.state, GraphState::Arrow(_)));
+
+        graph.on_drag(pos2(220., 30.));
+        assert_eq!(graph.candidate, Some(1));
+        assert!(matches!(end.borrow().state, MxCellState::Selected));
+
+        graph.on_drag_end(pos2(210., 25.));
+        assert!(matches!(end.borrow().state, MxCellState::Free));
+        assert_eq!(graph.edges.len(), 1);
+        let edge = &graph.edges[0];
+        assert_eq!(
+            edge.get_start().map(|cell| cell.id),
+            Some(start.borrow().id)
+        );
+        assert_eq!(edge.get_end().map(|cell| cell.id), Some(Id::new(2)));
+        assert_eq!(edge.points(), &[pos2(100., 25.), pos2(200., 25.)]);
+    }
+
+    #[test]
+    fn test_shortest_path_weighted() {
+        let mut graph = GraphUI::default();
+        graph.add_cell(rect_cell(1, pos2(0., 0.)));
+        graph.add_cell(rect_cell(2, pos2(200., 0.)));
+        graph.on_hover(pos2(50., 25.));
+        graph.on_drag_start(pos2(100., 25.));
+        graph.on_drag(pos2(220., 30.));
+        graph.on_drag_end(pos2(210., 25.));
+
+        let (from, to) = (Id::new(1), Id::new(2));
+        assert!(matches!(
+            graph.shortest_path_weighted(from, to),
+            Ok(Some((path, cost))) if path == [from, to] && cost == 1.
+        ));
+        graph.add_cell(rect_cell(3, pos2(400., 0.)));
+        assert!(matches!(
+            graph.shortest_path_weighted(from, Id::new(3)),
+            Ok(None)
+        ));
+
+        // Negative weight is reported to the caller instead of looking like missing path
+        graph.edges[0].set_weight(Some(-2.));
+        assert!(matches!(
+            graph.shortest_path_weighted(from, to),
+            Err(MxErrors::NegativeWeight(w)) if w == -2.
+        ));
+    }
+
+    #[test]
+    fn test_json_reconnects_edges() {
+        let mut graph = GraphUI::default();
+        graph.add_cell(rect_cell(1, pos2(0., 0.)));
+        graph.add_cell(rect_cell(2, pos2(200., 0.)));
+        graph.on_hover(pos2(50., 25.));
+        graph.on_drag_start(pos2(100., 25.));
+        graph.on_drag(pos2(220., 30.));
+        graph.on_drag_end(pos2(210., 25.));
+
+        let json = graph.to_json().unwrap();
+        let restored = GraphUI::from_json(&json).unwrap();
+        assert_eq!(restored.cells.len(), 2);
+        let edge = &restored.edges[0];
+        assert!(Rc::ptr_eq(
+            &edge.get_start_rc().unwrap(),
+            &restored.cells[0]
+        ));
+        assert!(Rc::ptr_eq(&edge.get_end_rc().unwrap(), &restored.cells[1]));
+        assert_eq!(restored.to_json().unwrap(), json);
+    }
+
+    #[test]
+    fn test_ui_messages() {
+        let mut graph = GraphUI::default();
+        graph.set_tool(rect_cell(1, pos2(0., 0.)));
+        assert!(matches!(
+            graph.handle_click(pos2(50., 25.)).as_slice(),
+            [WsMessages::AddFigure(AddFigure { rect, figure_id, .. })]
+                if rect.min == pos2(0., 0.)
+                    && *figure_id == id_to_string(graph.cells[0].borrow().id)
+        ));
+        graph.add_cell(rect_cell(2, pos2(200., 0.)));
+
+        assert!(matches!(
+            graph.handle_click(pos2(50., 25.)).as_slice(),
+            [WsMessages::SelectionChange { selected_ids, .. }] if selected_ids.len() == 1
+        ));
+
+        // Moved cell
+        graph.on_hover(pos2(50., 25.));
+        graph.handle_drag_start(pos2(50., 25.));
+        graph.on_drag(pos2(60., 35.));
+        assert!(matches!(
+            graph.handle_drag_end(pos2(60., 35.)).as_slice(),
+            [WsMessages::MoveFigure { dx, dy, .. }] if *dx == 10. && *dy == 10.
+        ));
+
+        // Edge connecting two cells
+        graph.on_hover(pos2(60., 35.));
+        graph.handle_drag_start(pos2(110., 35.));
+        graph.on_drag(pos2(220., 30.));
+        assert!(matches!(
+            graph.handle_drag_end(pos2(210., 25.)).as_slice(),
+            [WsMessages::AddArrow(_)]
+        ));
+    }
+
+    #[test]
+    fn test_place_tool() {
+        let mut graph = GraphUI::default();
+        graph.set_tool(rect_cell(1, pos2(0., 0.)));
+        graph.on_click(pos2(300., 300.));
+        assert!(matches!(graph.state, GraphState::Nothing));
+        assert_eq!(graph.cells.len(), 1);
+        assert_eq!(graph.cells[0].borrow().rect().center(), pos2(300., 300.));
+
+        graph.on_click(pos2(300., 300.));
+        assert!(matches!(
+            graph.cells[0].borrow().state,
+            MxCellState::Selected
+        ));
+        graph.on_click(pos2(0., 0.));
+        assert!(matches!(graph.cells[0].borrow().state, MxCellState::Free));
+
+        // Each placed cell gets its own identifier
+        graph.set_tool(rect_cell(1, pos2(0., 0.)));
+        graph.on_click(pos2(500., 500.));
+        assert_ne!(graph.cells[0].borrow().id, graph.cells[1].borrow().id);
+        assert_ne!(graph.cells[1].borrow().id, Id::new(1));
+    }
+
+    #[test]
+    fn test_transform() {
+        let mut graph = GraphUI::default();
+        graph.set_transform(2., vec2(10., 20.));
+        let transform = graph.transform;
+        assert_eq!(transform.apply(pos2(50., 25.)), pos2(110., 70.));
+        assert_eq!(transform.invert(pos2(110., 70.)), pos2(50., 25.));
+
+        // Cell shapes are transformed the same way as edge points
+        let cell = rect_cell(1, pos2(0., 0.));
+        match transform.figure_shape(&cell.shapes[0]) {
+            Shape::Rect(shape) => assert_eq!(
+                shape.rect,
+                Rect::from_min_max(pos2(10., 20.), pos2(210., 120.))
+            ),
+            shape => panic!("Unexpected shape {:?}", shape),
+        }
+    }
+}
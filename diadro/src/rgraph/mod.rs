@@ -1,10 +1,18 @@
 mod algo;
+mod approx;
+mod canvas;
 mod cell;
 mod errors;
 mod graph;
 mod ucell;
+mod validator;
 
-pub use self::ucell::UnMxEdge;
+pub use self::algo::{force_directed_layout, shortest_path, shortest_path_weighted, PointAlgoritm};
+pub use self::approx::ApproxEq;
+pub(crate) use self::canvas::xml_escape;
+pub use self::canvas::{CanvasCommand, PngRenderer, SvgRenderer};
+pub use self::ucell::{EdgeStyle, UnMxEdge};
+pub use self::validator::{ConnectionValidator, NoSelfLoopValidator, TypedConnectionValidator};
 
 use eframe::{
     egui::Id,
@@ -26,7 +34,7 @@ pub enum CellType {
     Connectable(MxConnectable),
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum Figure {
     Vec(Vec<Figure>),
     LineSegment { points: [Pos2; 2], stroke: Stroke },
@@ -1,14 +1,14 @@
 use std::{
     cell::{Ref, RefCell, RefMut},
-    f32::consts::PI,
+    f32::consts::TAU,
     fmt::Debug,
     rc::Rc,
 };
 
 use eframe::{
     egui::{Id, Ui},
-    emath::{pos2, Pos2, Vec2},
-    epaint::{Color32, PathShape, Shape, Stroke},
+    emath::{pos2, vec2, Pos2, Vec2},
+    epaint::{Color32, CubicBezierShape, PathShape, QuadraticBezierShape, Shape, Stroke},
 };
 use serde::de::{Deserialize, Visitor};
 use serde::{
@@ -16,9 +16,95 @@ use serde::{
     ser::{Serialize, SerializeStruct},
 };
 
-use crate::graph::Zoom;
+use crate::graph::{
+    apply_dash, draw_head, outward_normal, ArrowHeadStyle, DashPattern, Zoom, DEFAULT_DASH_LENGTH,
+    LOOP_RADIUS,
+};
+
+use super::{
+    algo::PointAlgoritm, errors::MxErrors, validator::ConnectionValidator, Contained, MxCell,
+};
+
+/// Maximum distance between the dashed curve and its flattened line
+const DASH_TOLERANCE: f32 = 0.5;
+
+/// Defines how edge is drawn through its points
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum EdgeStyle {
+    /// Straight segments between points
+    Polyline,
+    /// Only horizontal and vertical segments between points
+    OrthogonalPolyline,
+    /// Cubic bezier curve from the first to the last point, waypoints define control points
+    CubicBezier,
+    /// Quadratic bezier curve from the first to the last point, waypoints define control point
+    QuadraticBezier,
+}
+
+impl Default for EdgeStyle {
+    fn default() -> Self {
+        EdgeStyle::Polyline
+    }
+}
+
+/// Build path of horizontal and vertical segments. Bend is inserted between points
+/// which are not on the same horizontal or vertical line
+fn orthogonal_path(points: &[Pos2]) -> Vec<Pos2> {
+    let mut path = Vec::with_capacity(points.len() * 2);
+    for (idx, point) in points.iter().enumerate() {
+        if let Some(prev) = idx.checked_sub(1).map(|prev| points[prev]) {
+            if prev.x != point.x && prev.y != point.y {
+                path.push(pos2(point.x, prev.y));
+            }
+        }
+        path.push(*point);
+    }
+    path
+}
+
+/// Compute control points of cubic bezier curve. The first and the last waypoints are used
+/// as control points, single waypoint defines both. Without waypoints curve leaves and enters
+/// ends along the major axis of the edge
+fn cubic_control_points(points: &[Pos2]) -> [Pos2; 4] {
+    let start = points[0];
+    let end = points[points.len() - 1];
+    match points.len() {
+        0..=2 => {
+            let delta = end - start;
+            let tangent = if delta.x.abs() >= delta.y.abs() {
+                vec2(delta.x / 2., 0.)
+            } else {
+                vec2(0., delta.y / 2.)
+            };
+            [start, start + tangent, end - tangent, end]
+        }
+        // Quadratic curve with the waypoint as control point raised to cubic one
+        3 => [
+            start,
+            start + (points[1] - start) * 2. / 3.,
+            end + (points[1] - end) * 2. / 3.,
+            end,
+        ],
+        len => [start, points[1], points[len - 2], end],
+    }
+}
 
-use super::{algo::PointAlgoritm, Contained, MxCell};
+/// Compute control points of quadratic bezier curve. Control point is the middle of the
+/// waypoints, without waypoints it is the corner of the edge's bounding rectangle
+fn quadratic_control_points(points: &[Pos2]) -> [Pos2; 3] {
+    let start = points[0];
+    let end = points[points.len() - 1];
+    let control = if points.len() > 2 {
+        let waypoints = &points[1..points.len() - 1];
+        let sum = waypoints
+            .iter()
+            .fold(Vec2::ZERO, |sum, point| sum + point.to_vec2());
+        (sum / waypoints.len() as f32).to_pos2()
+    } else {
+        pos2(end.x, start.y)
+    };
+    [start, control, end]
+}
 
 /// Defines edge with reference to figures at the start and end of edge
 pub struct UnMxEdge {
@@ -32,12 +118,17 @@ pub struct UnMxEdge {
     points: Vec<Pos2>,
     /// Default epsilon (tolerance)
     epsilon: f32,
-
-    zoom_factor: f32,
-    scroll_delta: Vec2,
     stroke: Stroke,
-    arrow_start: bool,
-    arrow_end: bool,
+    /// Head drawn at the start of the line
+    arrow_start: ArrowHeadStyle,
+    /// Head drawn at the end of the line
+    arrow_end: ArrowHeadStyle,
+    /// Cost of the edge used to find weighted shortest path
+    weight: Option<f32>,
+    /// How line is drawn through its points
+    style: EdgeStyle,
+    /// Dash pattern of the line
+    stroke_pattern: DashPattern,
 }
 
 impl Debug for UnMxEdge {
@@ -82,10 +173,40 @@ impl Serialize for UnMxEdge {
         state.serialize_field("arrow_start", &self.arrow_start)?;
         state.serialize_field("arrow_end", &self.arrow_end)?;
 
+        if let Some(weight) = &self.weight {
+            state.serialize_field("weight", weight)?;
+        }
+
+        if self.style != EdgeStyle::Polyline {
+            state.serialize_field("style", &self.style)?;
+        }
+
+        if self.stroke_pattern != DashPattern::Solid {
+            state.serialize_field("stroke_pattern", &self.stroke_pattern)?;
+        }
+
         state.end()
     }
 }
 
+/// Arrow head value. Edges saved before head styles were added have boolean flags
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum HeadValue {
+    Flag(bool),
+    Style(ArrowHeadStyle),
+}
+
+impl From<HeadValue> for ArrowHeadStyle {
+    fn from(value: HeadValue) -> Self {
+        match value {
+            HeadValue::Flag(true) => ArrowHeadStyle::Filled,
+            HeadValue::Flag(false) => ArrowHeadStyle::None,
+            HeadValue::Style(style) => style,
+        }
+    }
+}
+
 struct UnMxEdgeVisitor;
 
 /// Part of the deserialization UnMxEdge
@@ -107,8 +228,11 @@ impl<'de> Visitor<'de> for UnMxEdgeVisitor {
         let mut points: Vec<Pos2> = vec![];
         let mut epsilon: f32 = UnMxEdge::EPSILON;
         let mut stroke = UnMxEdge::default_stroke();
-        let mut arrow_start = false;
-        let mut arrow_end = false;
+        let mut arrow_start = ArrowHeadStyle::None;
+        let mut arrow_end = ArrowHeadStyle::None;
+        let mut weight: Option<f32> = None;
+        let mut style = EdgeStyle::default();
+        let mut stroke_pattern = DashPattern::Solid;
 
         while let Some(key) = map.next_key()? {
             match key {
@@ -152,8 +276,11 @@ impl<'de> Visitor<'de> for UnMxEdgeVisitor {
                 "stroke" => {
                     stroke = map.next_value()?;
                 }
-                "arrow_start" => arrow_start = map.next_value()?,
-                "arrow_end" => arrow_end = map.next_value()?,
+                "arrow_start" => arrow_start = map.next_value::<HeadValue>()?.into(),
+                "arrow_end" => arrow_end = map.next_value::<HeadValue>()?.into(),
+                "weight" => weight = Some(map.next_value()?),
+                "style" => style = map.next_value()?,
+                "stroke_pattern" => stroke_pattern = map.next_value()?,
                 _ => {}
             }
         }
@@ -165,11 +292,12 @@ impl<'de> Visitor<'de> for UnMxEdgeVisitor {
             end_point,
             points,
             epsilon,
-            zoom_factor: 1.,
-            scroll_delta: Vec2::ZERO,
             stroke,
             arrow_start,
             arrow_end,
+            weight,
+            style,
+            stroke_pattern,
         })
     }
 }
@@ -208,11 +336,12 @@ impl UnMxEdge {
             end_point: None,
             points: vec![],
             epsilon: Self::EPSILON,
-            zoom_factor: 1.,
-            scroll_delta: Vec2::ZERO,
             stroke: Self::default_stroke(),
-            arrow_start: false,
-            arrow_end: false,
+            arrow_start: ArrowHeadStyle::None,
+            arrow_end: ArrowHeadStyle::None,
+            weight: None,
+            style: EdgeStyle::Polyline,
+            stroke_pattern: DashPattern::Solid,
         }
     }
 
@@ -225,11 +354,12 @@ impl UnMxEdge {
                 end_point: Some(ep),
                 points: vec![],
                 epsilon: Self::EPSILON,
-                zoom_factor: 1.,
-                scroll_delta: Vec2::ZERO,
                 stroke: Self::default_stroke(),
-                arrow_start: false,
-                arrow_end: false,
+                arrow_start: ArrowHeadStyle::None,
+                arrow_end: ArrowHeadStyle::None,
+                weight: None,
+                style: EdgeStyle::Polyline,
+                stroke_pattern: DashPattern::Solid,
             },
             (EdgeVertex::Cell(s, sp), EdgeVertex::Pos(pos)) => Self {
                 start: Some(s),
@@ -238,11 +368,12 @@ impl UnMxEdge {
                 end_point: None,
                 points: vec![pos2(f32::NAN, f32::NAN), pos],
                 epsilon: Self::EPSILON,
-                zoom_factor: 1.,
-                scroll_delta: Vec2::ZERO,
                 stroke: Self::default_stroke(),
-                arrow_start: false,
-                arrow_end: false,
+                arrow_start: ArrowHeadStyle::None,
+                arrow_end: ArrowHeadStyle::None,
+                weight: None,
+                style: EdgeStyle::Polyline,
+                stroke_pattern: DashPattern::Solid,
             },
             (EdgeVertex::Pos(pos), EdgeVertex::Cell(e, ep)) => Self {
                 start: None,
@@ -251,11 +382,12 @@ impl UnMxEdge {
                 end_point: Some(ep),
                 points: vec![pos, pos2(f32::NAN, f32::NAN)],
                 epsilon: Self::EPSILON,
-                zoom_factor: 1.,
-                scroll_delta: Vec2::ZERO,
                 stroke: Self::default_stroke(),
-                arrow_start: false,
-                arrow_end: false,
+                arrow_start: ArrowHeadStyle::None,
+                arrow_end: ArrowHeadStyle::None,
+                weight: None,
+                style: EdgeStyle::Polyline,
+                stroke_pattern: DashPattern::Solid,
             },
             (EdgeVertex::Pos(spos), EdgeVertex::Pos(epos)) => Self {
                 start: None,
@@ -264,11 +396,12 @@ impl UnMxEdge {
                 end_point: None,
                 points: vec![spos, epos],
                 epsilon: Self::EPSILON,
-                zoom_factor: 1.,
-                scroll_delta: Vec2::ZERO,
                 stroke: Self::default_stroke(),
-                arrow_start: false,
-                arrow_end: false,
+                arrow_start: ArrowHeadStyle::None,
+                arrow_end: ArrowHeadStyle::None,
+                weight: None,
+                style: EdgeStyle::Polyline,
+                stroke_pattern: DashPattern::Solid,
             },
         }
     }
@@ -293,11 +426,81 @@ impl UnMxEdge {
         self.end.as_ref().map(|e| e.borrow_mut())
     }
 
+    /// Return reference to the start figure
+    pub fn get_start_rc(&self) -> Option<Rc<RefCell<MxCell>>> {
+        self.start.as_ref().cloned()
+    }
+
     /// Return reference to the end figure
     pub fn get_end_rc(&self) -> Option<Rc<RefCell<MxCell>>> {
         self.end.as_ref().cloned()
     }
 
+    /// Replace stub cells created by deserialization with the cells of the diagram.
+    /// Ends without matching cell keep their stubs
+    /// ### Arguments
+    /// * cells - cells of the diagram, ends are matched by identifiers
+    /// ### Return
+    /// DeserializationIncomplete error naming the end without matching cell
+    pub fn reconnect(&mut self, cells: &[Rc<RefCell<MxCell>>]) -> Result<(), MxErrors> {
+        let mut missing = vec![];
+        for (field, end) in [("start", &mut self.start), ("end", &mut self.end)] {
+            let id = match end.as_ref() {
+                Some(end) => end.borrow().id,
+                None => continue,
+            };
+
+            match cells.iter().find(|cell| cell.borrow().id == id) {
+                Some(cell) => *end = Some(cell.clone()),
+                None => missing.push(format!("{} cell {:?}", field, id)),
+            }
+        }
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(MxErrors::DeserializationIncomplete {
+                field: missing.join(", "),
+            })
+        }
+    }
+
+    /// Set heads drawn at the start and the end of the line
+    pub fn set_heads(&mut self, start: ArrowHeadStyle, end: ArrowHeadStyle) {
+        self.arrow_start = start;
+        self.arrow_end = end;
+    }
+
+    /// Return cost of the edge
+    pub fn weight(&self) -> Option<f32> {
+        self.weight
+    }
+
+    /// Set cost of the edge. None means unit cost
+    pub fn set_weight(&mut self, weight: Option<f32>) {
+        self.weight = weight;
+    }
+
+    /// Return how line is drawn through its points
+    pub fn style(&self) -> EdgeStyle {
+        self.style
+    }
+
+    /// Set how line is drawn through its points
+    pub fn set_style(&mut self, style: EdgeStyle) {
+        self.style = style;
+    }
+
+    /// Return dash pattern of the line
+    pub fn stroke_pattern(&self) -> DashPattern {
+        self.stroke_pattern
+    }
+
+    /// Set dash pattern of the line
+    pub fn set_stroke_pattern(&mut self, pattern: DashPattern) {
+        self.stroke_pattern = pattern;
+    }
+
     /// Set start figure fot the edge
     pub fn set_start(&mut self, mx_cell: Rc<RefCell<MxCell>>, point: usize) {
         self.start = Some(mx_cell);
@@ -305,26 +508,94 @@ impl UnMxEdge {
         self.compute_points();
     }
 
+    /// Move end of the line which is not connected to figure
+    pub fn set_end_pos(&mut self, pos: Pos2) {
+        if self.end.is_none() {
+            if let Some(last) = self.points.last_mut() {
+                *last = pos;
+            }
+        }
+    }
+
     /// Set end figure fot the edge
-    pub fn set_end(&mut self, mx_cell: Rc<RefCell<MxCell>>, point: usize) {
+    /// ### Arguments
+    /// * mx_cell - end figure
+    /// * point - index of the figure connection point
+    /// * validator - rule checked when the edge has start figure
+    /// ### Return
+    /// ConnectionRejected error if validator rejects the connection, the edge is not changed then
+    pub fn set_end(
+        &mut self,
+        mx_cell: Rc<RefCell<MxCell>>,
+        point: usize,
+        validator: Option<&dyn ConnectionValidator>,
+    ) -> Result<(), MxErrors> {
+        if let (Some(validator), Some(start)) = (validator, self.start.as_ref()) {
+            validator
+                .validate(&start.borrow(), &mx_cell.borrow())
+                .map_err(|reason| MxErrors::ConnectionRejected { reason })?;
+        }
+
         self.end = Some(mx_cell);
         self.end_point = Some(point);
         self.compute_points();
+        Ok(())
     }
 }
 
 /// Implies geometry logic
 impl UnMxEdge {
+    /// Number of segments of the self-loop circle
+    const LOOP_SEGMENTS: usize = 16;
+
     /// Get position from figure and connection point
     #[inline]
     fn get_figure_pos(mx: &Option<Rc<RefCell<MxCell>>>, pos: Option<usize>) -> Option<Pos2> {
         match (mx, pos) {
-            (Some(c), Some(p)) => Some(c.borrow().connection_points[p]),
+            (Some(c), Some(p)) => c.borrow().connection_points.get(p).copied(),
             _ => None,
         }
     }
 
-    /// Compute line points
+    /// Check if edge starts and ends at the same connection point of the same cell
+    fn is_loop(&self) -> bool {
+        match (&self.start, &self.end) {
+            (Some(start), Some(end)) => {
+                Rc::ptr_eq(start, end)
+                    && self.start_point.is_some()
+                    && self.start_point == self.end_point
+            }
+            _ => false,
+        }
+    }
+
+    /// Points of the circle drawn when edge starts and ends at the same connection point.
+    /// Circle touches the connection point and lies along its outward normal. Circle is
+    /// built for drawing and hit testing only and is not stored in the line points
+    fn loop_points(&self) -> Option<Vec<Pos2>> {
+        if !self.is_loop() {
+            return None;
+        }
+
+        let cell = self.start.as_ref()?.borrow();
+        let point = *cell.connection_points.get(self.start_point?)?;
+        let normal = outward_normal(cell.rect().center(), point);
+        let center = point + normal * LOOP_RADIUS;
+        let start_angle = (-normal).angle();
+        let mut points: Vec<Pos2> = (0..=Self::LOOP_SEGMENTS)
+            .map(|idx| {
+                let angle = start_angle + TAU * idx as f32 / Self::LOOP_SEGMENTS as f32;
+                center + Vec2::angled(angle) * LOOP_RADIUS
+            })
+            .collect();
+
+        // Both ends are exactly at the connection point
+        points[0] = point;
+        points[Self::LOOP_SEGMENTS] = point;
+        Some(points)
+    }
+
+    /// Compute start and end line points from the connected cells
     pub fn compute_points(&mut self) {
         let last = self.points.len() - 1;
         let start =
@@ -357,6 +628,18 @@ impl UnMxEdge {
             return None;
         }
 
+        // Self-loop is drawn as circle instead of the line points
+        if let Some(circle) = self.loop_points() {
+            return point
+                .belong_path(&circle, self.epsilon)
+                .then(|| Contained::InArea);
+        }
+
+        // Self-loop is always drawn as polyline
+        if self.style != EdgeStyle::Polyline && !self.is_loop() {
+            return self.curve_contains(point);
+        }
+
         for idx in 1..self.points.len() {
             let start = self.points[idx - 1];
             let end = self.points[idx];
@@ -373,108 +656,199 @@ impl UnMxEdge {
 
         None
     }
-}
 
-/// Implies drawing functionsl
-impl UnMxEdge {
-    const ARROW_WING_ANGLE: f32 = 15.;
-    const ARROW_WING_SIZE: f32 = 20.;
+    /// Check if curved or orthogonal line contains given point. Waypoints are treated
+    /// as connection points even if they don't lie on the line
+    fn curve_contains(&self, point: Pos2) -> Option<Contained> {
+        if let Some(idx) = self
+            .points
+            .iter()
+            .position(|pos| point.distance(*pos) <= self.epsilon)
+        {
+            return Some(Contained::ConnectionPoint(idx));
+        }
 
-    #[inline]
-    fn compute_angle(start: Pos2, end: Pos2) -> f32 {
-        (end.x - start.x).atan2(end.y - start.y)
+        let on_line = match self.style {
+            EdgeStyle::Polyline => point.belong_path(&self.points, self.epsilon),
+            EdgeStyle::OrthogonalPolyline => {
+                point.belong_path(&orthogonal_path(&self.points), self.epsilon)
+            }
+            EdgeStyle::CubicBezier => {
+                point.belong_cubic_bezier(&cubic_control_points(&self.points), self.epsilon)
+            }
+            EdgeStyle::QuadraticBezier => {
+                point.belong_quadratic_bezier(&quadratic_control_points(&self.points), self.epsilon)
+            }
+        };
+
+        if on_line {
+            Some(Contained::InArea)
+        } else {
+            None
+        }
+    }
+
+    /// Return line points including start and end
+    pub fn points(&self) -> &[Pos2] {
+        &self.points
     }
 
-    /// Compute point coordinates based on start point line angle and distance
+    /// Insert intermediate point between start and end points of the line
     /// ### Arguments
-    ///  - start - starting point
-    ///  - angle - line angle in radians
-    ///  - distance - distance from start to desired point
+    /// * index - position of the new point, clamped to keep start and end points in place
+    /// * point - waypoint coordinates
     /// ### Return
-    ///  new point coordinates
-    /// ### Example
-    /// ```
-    /// use eframe::egui::Pos2;
-    ///
-    /// let start = Pos2::new(0., 0.);
-    /// let angle = 3.14159 / 2.0; // 90 degrees
-    /// let end = diadro::graph::pos_by_angle(start, angle, 10.0);
-    ///
-    /// assert_eq!(end.x.round(), 10.);
-    /// ```
-    #[inline]
-    fn pos_by_angle(start: Pos2, angle: f32, distance: f32) -> Pos2 {
-        pos2(
-            start.x + distance * angle.sin(),
-            start.y + distance * angle.cos(),
-        )
+    /// WaypointIndexOutOfBounds error if line has no start and end points yet
+    pub fn insert_waypoint(&mut self, index: usize, point: Pos2) -> Result<(), MxErrors> {
+        let len = self.points.len();
+        if len < 2 {
+            return Err(MxErrors::WaypointIndexOutOfBounds { index, len });
+        }
+
+        let index = index.max(1).min(len - 1);
+        self.points.insert(index, point);
+        self.compute_points();
+        Ok(())
+    }
+
+    /// Remove intermediate point of the line. Start and end points can't be removed
+    /// ### Arguments
+    /// * index - position of the point to remove
+    /// ### Return
+    /// WaypointIndexOutOfBounds error if index doesn't point to intermediate point
+    pub fn remove_waypoint(&mut self, index: usize) -> Result<(), MxErrors> {
+        let len = self.points.len();
+        if index == 0 || index + 1 >= len {
+            return Err(MxErrors::WaypointIndexOutOfBounds { index, len });
+        }
+
+        self.points.remove(index);
+        self.compute_points();
+        Ok(())
+    }
+
+    /// Find line segment containing given point
+    /// ### Arguments
+    /// * point - point to check
+    /// ### Return
+    /// index of the segment start point or None if point doesn't lie on line
+    fn find_segment(&self, point: Pos2) -> Option<usize> {
+        self.points
+            .windows(2)
+            .position(|segment| point.belong_line(&[segment[0], segment[1]], self.epsilon))
     }
 
+    /// Handle double click on the line: clicking on line inserts waypoint at the middle of
+    /// the clicked segment, clicking on waypoint removes it
+    /// ### Arguments
+    /// * point - double click position
+    /// ### Return
+    /// true if line was changed
+    pub fn double_click(&mut self, point: Pos2) -> bool {
+        match self.contains(point) {
+            Some(Contained::ConnectionPoint(idx)) => self.remove_waypoint(idx).is_ok(),
+            Some(Contained::InArea) => match self.find_segment(point) {
+                Some(idx) => {
+                    let middle = self.points[idx] + (self.points[idx + 1] - self.points[idx]) / 2.;
+                    self.insert_waypoint(idx + 1, middle).is_ok()
+                }
+                None => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Implies drawing functionsl
+impl UnMxEdge {
     pub fn draw(&mut self, ui: &mut Ui, zoom_factor: f32, scroll_delta: Vec2) {
         // Recompute points each time when drawing
         self.compute_points();
 
+        // Points are kept in graph coordinates, the same as shapes of the connected cells
         let transformed: Vec<Pos2> = self
-            .points
+            .loop_points()
+            .unwrap_or_else(|| self.points.clone())
             .iter()
-            .map(|p| {
-                let np = p.zoom(zoom_factor / self.zoom_factor);
-                if self.scroll_delta != scroll_delta {
-                    np + scroll_delta
-                } else {
-                    np
-                }
-            })
+            .map(|p| p.zoom(zoom_factor) + scroll_delta)
             .collect();
 
-        self.zoom_factor = zoom_factor;
-        self.scroll_delta = scroll_delta;
-
-        let last = transformed.len() - 1;
-        let start_line = [transformed[0], transformed[1]];
-        let end_line = [transformed[last - 1], transformed[last]];
-
-        ui.painter()
-            .add(Shape::Path(PathShape::line(transformed, self.stroke)));
-
-        if self.arrow_start {
-            ui.painter().add(Shape::convex_polygon(
-                Self::arrow_for_line(start_line, Self::ARROW_WING_ANGLE, Self::ARROW_WING_SIZE),
-                self.stroke.color,
-                self.stroke,
-            ));
-        }
-
-        if self.arrow_end {
-            ui.painter().add(Shape::convex_polygon(
-                Self::arrow_for_line(end_line, Self::ARROW_WING_ANGLE, Self::ARROW_WING_SIZE),
-                self.stroke.color,
-                self.stroke,
-            ));
+        // Heads are directed along the line at its ends, for curves it is direction
+        // to the nearest control point
+        let style = if self.is_loop() {
+            EdgeStyle::Polyline
+        } else {
+            self.style
+        };
+        let (shape, directions) = match style {
+            EdgeStyle::Polyline => (
+                Shape::Path(PathShape::line(transformed.clone(), self.stroke)),
+                transformed,
+            ),
+            EdgeStyle::OrthogonalPolyline => {
+                let path = orthogonal_path(&transformed);
+                (
+                    Shape::Path(PathShape::line(path.clone(), self.stroke)),
+                    path,
+                )
+            }
+            EdgeStyle::CubicBezier => {
+                let points = cubic_control_points(&transformed);
+                (
+                    Shape::CubicBezier(CubicBezierShape::from_points_stroke(
+                        points,
+                        false,
+                        Color32::TRANSPARENT,
+                        self.stroke,
+                    )),
+                    points.to_vec(),
+                )
+            }
+            EdgeStyle::QuadraticBezier => {
+                let points = quadratic_control_points(&transformed);
+                (
+                    Shape::QuadraticBezier(QuadraticBezierShape::from_points_stroke(
+                        points,
+                        false,
+                        Color32::TRANSPARENT,
+                        self.stroke,
+                    )),
+                    points.to_vec(),
+                )
+            }
+        };
+
+        let last = directions.len() - 1;
+        let start_line = [directions[1], directions[0]];
+        let end_line = [directions[last - 1], directions[last]];
+
+        if self.stroke_pattern == DashPattern::Solid {
+            ui.painter().add(shape);
+        } else {
+            // Curves are flattened to apply dash pattern along them
+            let line = match shape {
+                Shape::Path(path) => path.points,
+                Shape::CubicBezier(curve) => curve.flatten(Some(DASH_TOLERANCE)),
+                Shape::QuadraticBezier(curve) => curve.flatten(Some(DASH_TOLERANCE)),
+                _ => directions.clone(),
+            };
+            for segment in apply_dash(&line, self.stroke_pattern, DEFAULT_DASH_LENGTH) {
+                ui.painter().line_segment(segment, self.stroke);
+            }
         }
-    }
 
-    #[inline]
-    fn arrow_for_line(line: [Pos2; 2], angle_grad: f32, distance: f32) -> Vec<Pos2> {
-        let start = line[0];
-        let end = line[1];
-        let line_angle = Self::compute_angle(start, end);
-        let rotate = PI;
-
-        let angle = angle_grad * PI / 180.;
-        let left_angle = line_angle + angle + rotate;
-        let left_pos = Self::pos_by_angle(end, left_angle, distance);
-        let right_angle = line_angle - angle + rotate;
-        let right_pos = Self::pos_by_angle(end, right_angle, distance);
-        let center_pos = Self::pos_by_angle(end, line_angle, -distance / 1.5);
-        vec![end, left_pos, center_pos, right_pos, end]
+        draw_head(self.arrow_start, start_line, ui.painter(), self.stroke);
+        draw_head(self.arrow_end, end_line, ui.painter(), self.stroke);
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::UnMxEdge;
-    use crate::rgraph::{Contained, MxCell};
+    use super::{cubic_control_points, orthogonal_path, EdgeStyle, EdgeVertex, UnMxEdge};
+    use crate::{
+        graph::{ArrowHeadStyle, DashPattern, LOOP_RADIUS},
+        rgraph::{errors::MxErrors, Contained, MxCell},
+    };
     use eframe::{
         egui::Id,
         emath::{pos2, Pos2},
@@ -493,7 +867,7 @@ mod tests {
 
         let json = serde_json::to_string(&edge).unwrap();
         assert_eq!(
-            r#"{"start":15326068958072818760,"end":16069757468406242631,"points":[{"x":1.0,"y":2.0}],"epsilon":3.0,"stroke":{"width":1.0,"color":[255,255,0,255]},"arrow_start":false,"arrow_end":false}"#,
+            r#"{"start":15326068958072818760,"end":16069757468406242631,"points":[{"x":1.0,"y":2.0}],"epsilon":3.0,"stroke":{"width":1.0,"color":[255,255,0,255]},"arrow_start":"None","arrow_end":"None"}"#,
             json
         );
 
@@ -513,6 +887,38 @@ mod tests {
         assert_eq!(json, json_2);
     }
 
+    #[test]
+    fn test_reconnect() {
+        let cells = vec![
+            Rc::new(RefCell::new(MxCell::new(Id::new(1)))),
+            Rc::new(RefCell::new(MxCell::new(Id::new(2)))),
+        ];
+        let edge = UnMxEdge::new(Some(cells[0].clone()), Some(cells[1].clone()));
+
+        let json = serde_json::to_string(&edge).unwrap();
+        let mut edge: UnMxEdge = serde_json::from_str(&json).unwrap();
+        assert!(!Rc::ptr_eq(edge.start.as_ref().unwrap(), &cells[0]));
+
+        assert!(edge.reconnect(&cells).is_ok());
+        assert!(Rc::ptr_eq(edge.start.as_ref().unwrap(), &cells[0]));
+        assert!(Rc::ptr_eq(edge.end.as_ref().unwrap(), &cells[1]));
+
+        // Edge sees changes of the diagram cells
+        cells[1].borrow_mut().connection_points = vec![pos2(10., 20.)];
+        assert_eq!(
+            edge.get_end().unwrap().connection_points,
+            vec![pos2(10., 20.)]
+        );
+
+        // Missing end cell is reported, start is still reconnected
+        let mut edge: UnMxEdge = serde_json::from_str(&json).unwrap();
+        assert!(matches!(
+            edge.reconnect(&cells[..1]),
+            Err(MxErrors::DeserializationIncomplete { .. })
+        ));
+        assert!(Rc::ptr_eq(edge.start.as_ref().unwrap(), &cells[0]));
+    }
+
     #[test]
     #[should_panic]
     fn test_refs_wrong() {
@@ -663,4 +1069,150 @@ mod tests {
 
         assert_eq!(idx, Some(1));
     }
+    #[test]
+    fn test_waypoints() {
+        let mut edge = UnMxEdge::from_vertices(
+            EdgeVertex::Pos(pos2(0., 0.)),
+            EdgeVertex::Pos(pos2(100., 0.)),
+        );
+
+        // Double click on line inserts waypoint at the middle of segment
+        assert!(edge.double_click(pos2(20., 1.)));
+        assert_eq!(
+            edge.points(),
+            &[pos2(0., 0.), pos2(50., 0.), pos2(100., 0.)]
+        );
+
+        // Start and end points are kept in place
+        edge.insert_waypoint(0, pos2(10., 10.)).unwrap();
+        edge.insert_waypoint(10, pos2(90., 10.)).unwrap();
+        assert_eq!(
+            edge.points(),
+            &[
+                pos2(0., 0.),
+                pos2(10., 10.),
+                pos2(50., 0.),
+                pos2(90., 10.),
+                pos2(100., 0.)
+            ]
+        );
+        assert!(matches!(
+            edge.remove_waypoint(0),
+            Err(MxErrors::WaypointIndexOutOfBounds { index: 0, len: 5 })
+        ));
+        assert!(edge.remove_waypoint(4).is_err());
+        assert_eq!(edge.points().len(), 5);
+
+        // Double click on waypoint removes it, on end point does nothing
+        assert!(edge.double_click(pos2(50., 0.)));
+        assert!(!edge.double_click(pos2(100., 0.)));
+        assert_eq!(edge.points().len(), 4);
+
+        // Waypoints survive serialization
+        let json = serde_json::to_string(&edge).unwrap();
+        let edge_de: UnMxEdge = serde_json::from_str(&json).unwrap();
+        assert_eq!(edge_de.points(), edge.points());
+    }
+
+    #[test]
+    fn test_head_styles_serialization() {
+        let mut edge = UnMxEdge::from_vertices(
+            EdgeVertex::Pos(pos2(0., 0.)),
+            EdgeVertex::Pos(pos2(100., 0.)),
+        );
+        edge.set_heads(ArrowHeadStyle::Circle, ArrowHeadStyle::OpenDiamond);
+
+        let json = serde_json::to_string(&edge).unwrap();
+        let edge_de: UnMxEdge = serde_json::from_str(&json).unwrap();
+        assert_eq!(edge_de.arrow_start, ArrowHeadStyle::Circle);
+        assert_eq!(edge_de.arrow_end, ArrowHeadStyle::OpenDiamond);
+
+        // Boolean flags are read as filled heads
+        let edge_de: UnMxEdge =
+            serde_json::from_str(r#"{"points":[],"arrow_start":false,"arrow_end":true}"#).unwrap();
+        assert_eq!(edge_de.arrow_start, ArrowHeadStyle::None);
+        assert_eq!(edge_de.arrow_end, ArrowHeadStyle::Filled);
+    }
+
+    #[test]
+    fn test_edge_styles() {
+        let mut edge = UnMxEdge::from_vertices(
+            EdgeVertex::Pos(pos2(0., 0.)),
+            EdgeVertex::Pos(pos2(100., 100.)),
+        );
+        assert_eq!(edge.style(), EdgeStyle::Polyline);
+        assert!(edge.contains(pos2(50., 50.)).is_some());
+
+        assert_eq!(
+            orthogonal_path(edge.points()),
+            [pos2(0., 0.), pos2(100., 0.), pos2(100., 100.)]
+        );
+        edge.set_style(EdgeStyle::OrthogonalPolyline);
+        assert!(edge.contains(pos2(100., 50.)).is_some());
+        assert!(edge.contains(pos2(50., 50.)).is_none());
+
+        // Without waypoints curve leaves start horizontally
+        assert_eq!(
+            cubic_control_points(edge.points()),
+            [
+                pos2(0., 0.),
+                pos2(50., 0.),
+                pos2(50., 100.),
+                pos2(100., 100.)
+            ]
+        );
+        edge.set_style(EdgeStyle::CubicBezier);
+        assert!(edge.contains(pos2(50., 50.)).is_some());
+        assert!(edge.contains(pos2(20., 50.)).is_none());
+
+        // Quadratic curve goes through the middle of the line and its corner
+        edge.set_style(EdgeStyle::QuadraticBezier);
+        assert!(edge.contains(pos2(75., 25.)).is_some());
+        assert!(edge.contains(pos2(50., 50.)).is_none());
+
+        // Waypoint pulls the curve and is treated as connection point
+        edge.insert_waypoint(1, pos2(0., 100.)).unwrap();
+        assert!(edge.contains(pos2(25., 75.)).is_some());
+        assert!(matches!(
+            edge.contains(pos2(0., 99.)),
+            Some(Contained::ConnectionPoint(1))
+        ));
+
+        edge.set_stroke_pattern(DashPattern::DashDot);
+        let json = serde_json::to_string(&edge).unwrap();
+        assert!(json.contains(r#""style":"QuadraticBezier""#));
+        let edge_de: UnMxEdge = serde_json::from_str(&json).unwrap();
+        assert_eq!(edge_de.style(), EdgeStyle::QuadraticBezier);
+        assert_eq!(edge_de.stroke_pattern(), DashPattern::DashDot);
+    }
+
+    #[test]
+    fn test_self_loop() {
+        let mut cell = MxCell::new(Id::new(1));
+        cell.connection_points = vec![pos2(50., 0.)];
+        let cell = Rc::new(RefCell::new(cell));
+
+        let mut edge = UnMxEdge::from_vertices(
+            EdgeVertex::Cell(Rc::clone(&cell), 0),
+            EdgeVertex::Pos(pos2(100., 100.)),
+        );
+        assert!(!edge.is_loop());
+        edge.set_end(Rc::clone(&cell), 0, None).unwrap();
+        assert!(edge.is_loop());
+
+        // Both ends are at the connection point, loop is drawn even for curved style
+        edge.set_style(EdgeStyle::CubicBezier);
+        assert_eq!(edge.points(), &[pos2(50., 0.), pos2(50., 0.)]);
+        assert!(edge
+            .contains(pos2(50. + LOOP_RADIUS, -LOOP_RADIUS))
+            .is_some());
+        assert!(edge.contains(pos2(50., -2. * LOOP_RADIUS)).is_some());
+        assert!(edge.contains(pos2(50., -LOOP_RADIUS)).is_none());
+
+        // Circle points don't become waypoints when the loop is broken
+        cell.borrow_mut().connection_points.push(pos2(100., 25.));
+        edge.set_end(Rc::clone(&cell), 1, None).unwrap();
+        assert!(!edge.is_loop());
+        assert_eq!(edge.points(), &[pos2(50., 0.), pos2(100., 25.)]);
+    }
 }
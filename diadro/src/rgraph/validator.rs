@@ -0,0 +1,143 @@
+use std::collections::{HashMap, HashSet};
+
+use eframe::egui::Id;
+
+use super::MxCell;
+
+/// Rule checked before edge connects two cells
+pub trait ConnectionValidator {
+    /// Check if edge may go from one cell to another
+    /// ### Arguments
+    /// * from - cell at the start of the edge
+    /// * to - cell at the end of the edge
+    /// ### Return
+    /// error message if connection is not allowed
+    fn validate(&self, from: &MxCell, to: &MxCell) -> Result<(), String>;
+}
+
+/// Forbids edges starting and ending at the same cell
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoSelfLoopValidator;
+
+impl ConnectionValidator for NoSelfLoopValidator {
+    fn validate(&self, from: &MxCell, to: &MxCell) -> Result<(), String> {
+        if from.id == to.id {
+            Err("Cell can not be connected to itself".to_owned())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Allows edges only between cells of compatible types. Types are assigned to cells
+/// by identifiers, cells without type can't be connected
+#[derive(Clone, Debug, Default)]
+pub struct TypedConnectionValidator {
+    /// Type of the cell by cell identifier
+    types: HashMap<Id, String>,
+    /// Allowed pairs of the start and end cell types
+    allowed: HashSet<(String, String)>,
+}
+
+impl TypedConnectionValidator {
+    /// Create validator from the compatibility matrix
+    /// ### Arguments
+    /// * allowed - pairs of start and end types which may be connected
+    pub fn new<S: Into<String>>(allowed: impl IntoIterator<Item = (S, S)>) -> Self {
+        Self {
+            types: HashMap::new(),
+            allowed: allowed
+                .into_iter()
+                .map(|(from, to)| (from.into(), to.into()))
+                .collect(),
+        }
+    }
+
+    /// Assign type to the cell
+    pub fn set_type(&mut self, id: Id, cell_type: impl Into<String>) {
+        self.types.insert(id, cell_type.into());
+    }
+
+    fn cell_type(&self, cell: &MxCell) -> Result<&str, String> {
+        self.types
+            .get(&cell.id)
+            .map(String::as_str)
+            .ok_or_else(|| format!("Cell {:?} has no type", cell.id))
+    }
+}
+
+impl ConnectionValidator for TypedConnectionValidator {
+    fn validate(&self, from: &MxCell, to: &MxCell) -> Result<(), String> {
+        let from_type = self.cell_type(from)?;
+        let to_type = self.cell_type(to)?;
+        if self
+            .allowed
+            .contains(&(from_type.to_owned(), to_type.to_owned()))
+        {
+            Ok(())
+        } else {
+            Err(format!("{} can not be connected to {}", from_type, to_type))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use eframe::{egui::Id, emath::pos2};
+
+    use super::{ConnectionValidator, NoSelfLoopValidator, TypedConnectionValidator};
+    use crate::rgraph::{
+        ucell::{EdgeVertex, UnMxEdge},
+        MxCell,
+    };
+
+    fn cell(id: u64) -> Rc<RefCell<MxCell>> {
+        let mut cell = MxCell::new(Id::new(id));
+        cell.connection_points = vec![pos2(id as f32, 0.)];
+        Rc::new(RefCell::new(cell))
+    }
+
+    #[test]
+    fn test_no_self_loop() {
+        let (a, b) = (cell(1), cell(2));
+        let validator = NoSelfLoopValidator;
+        assert!(validator.validate(&a.borrow(), &b.borrow()).is_ok());
+        assert!(validator.validate(&a.borrow(), &a.borrow()).is_err());
+
+        let mut edge = UnMxEdge::from_vertices(
+            EdgeVertex::Cell(a.clone(), 0),
+            EdgeVertex::Pos(pos2(5., 5.)),
+        );
+        assert!(edge.set_end(a, 0, Some(&validator)).is_err());
+        assert!(edge.get_end().is_none());
+        assert!(edge.set_end(b, 0, Some(&validator)).is_ok());
+        assert_eq!(edge.get_end().map(|end| end.id), Some(Id::new(2)));
+    }
+
+    #[test]
+    fn test_typed_connections() {
+        let (service, database, client) = (cell(1), cell(2), cell(3));
+        let mut validator =
+            TypedConnectionValidator::new([("service", "database"), ("client", "service")]);
+        validator.set_type(Id::new(1), "service");
+        validator.set_type(Id::new(2), "database");
+
+        assert!(validator
+            .validate(&service.borrow(), &database.borrow())
+            .is_ok());
+        assert!(validator
+            .validate(&database.borrow(), &service.borrow())
+            .is_err());
+        // Cell without type
+        assert!(validator
+            .validate(&client.borrow(), &service.borrow())
+            .is_err());
+
+        validator.set_type(Id::new(3), "client");
+        assert!(validator
+            .validate(&client.borrow(), &service.borrow())
+            .is_ok());
+    }
+}
@@ -0,0 +1,71 @@
+use std::{cell::RefCell, rc::Rc};
+
+use diadro::graph::{ArrowFigure, ConnectionPoint, GraphFigure, Graphics, RectFigure};
+use eframe::emath::{pos2, Rect};
+use regex::Regex;
+use resvg::usvg;
+
+/// Add rectangle node into diagram
+fn node(graphics: &mut Graphics, rect: Rect, text: &str) -> Rc<RefCell<Box<dyn GraphFigure>>> {
+    let id = graphics.generate_id();
+    let figure: Rc<RefCell<Box<dyn GraphFigure>>> = Rc::new(RefCell::new(Box::new(
+        RectFigure::new(id, rect, text.to_owned()),
+    )));
+    graphics.add_figure(Rc::clone(&figure));
+    figure
+}
+
+/// Build diagram with two nodes connected by one edge
+fn two_nodes_diagram() -> Graphics {
+    let mut graphics = Graphics::default();
+    let start = node(
+        &mut graphics,
+        Rect::from_min_max(pos2(0., 0.), pos2(100., 50.)),
+        "Start",
+    );
+    let end = node(
+        &mut graphics,
+        Rect::from_min_max(pos2(200., 0.), pos2(300., 50.)),
+        "End",
+    );
+
+    let id = graphics.generate_id();
+    let mut edge = ArrowFigure::new([pos2(100., 25.), pos2(200., 25.)], id);
+    edge.connect_start(ConnectionPoint::new(start, 6));
+    edge.connect_end(ConnectionPoint::new(end, 14));
+    graphics.add_edge(edge);
+
+    graphics
+}
+
+fn count(svg: &str, element: &str) -> usize {
+    Regex::new(&format!(r"<{}[\s/>]", element))
+        .unwrap()
+        .find_iter(svg)
+        .count()
+}
+
+#[test]
+fn test_export_svg_two_nodes_one_edge() {
+    let graphics = two_nodes_diagram();
+    let svg = graphics.export_svg();
+
+    // Each node is a filled and a stroked rect with text, edge is a line with arrow head
+    assert_eq!(count(&svg, "svg"), 1);
+    assert_eq!(count(&svg, "g"), 1);
+    assert_eq!(count(&svg, "rect"), 4);
+    assert_eq!(count(&svg, "text"), 2);
+    assert_eq!(count(&svg, "line"), 1);
+    assert_eq!(count(&svg, "polygon"), 1);
+    assert!(
+        Regex::new(r#"<g transform="scale\(1\) translate\(-?0 -?0\)">"#)
+            .unwrap()
+            .is_match(&svg)
+    );
+
+    assert!(usvg::Tree::from_str(&svg, &usvg::Options::default()).is_ok());
+
+    // Diagram restored from JSON is exported the same way
+    let restored = Graphics::from_json(&graphics.to_json().unwrap()).unwrap();
+    assert_eq!(restored.export_svg(), svg);
+}
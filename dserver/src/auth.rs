@@ -0,0 +1,130 @@
+use std::env;
+
+use jsonwebtoken::{decode, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Environment variable containing secret used to validate tokens
+pub const JWT_SECRET_ENV: &str = "JWT_SECRET";
+
+/// Environment variable enabling test mode. When set, its value is accepted as a valid token.
+/// Read only by servers built with `test-token` feature
+#[cfg(feature = "test-token")]
+pub const JWT_TEST_TOKEN_ENV: &str = "JWT_TEST_TOKEN";
+
+/// Subject of the user authenticated by test token
+#[cfg(feature = "test-token")]
+pub const TEST_USER: &str = "test";
+
+/// Role of the users allowed to manage boards access
+pub const ADMIN_ROLE: &str = "admin";
+
+#[derive(Error, Debug)]
+pub enum AuthError {
+    #[error("Token is not provided")]
+    MissingToken,
+    #[error("{} environment variable is not set", JWT_SECRET_ENV)]
+    MissingSecret,
+    #[error("Invalid token: {0}")]
+    InvalidToken(#[from] jsonwebtoken::errors::Error),
+}
+
+/// Claims of the token used by server
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Claims {
+    /// User identifier
+    pub sub: String,
+    /// User display name
+    #[serde(default)]
+    pub name: Option<String>,
+    /// User role
+    #[serde(default)]
+    pub role: Option<String>,
+    /// Expiration time, seconds since epoch
+    pub exp: usize,
+}
+
+impl Claims {
+    /// Name shown to other users, subject is used if token has no name
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.sub)
+    }
+
+    /// Return true if user can manage boards access
+    pub fn is_admin(&self) -> bool {
+        self.role.as_deref() == Some(ADMIN_ROLE)
+    }
+}
+
+/// Validate token signed with secret from `JWT_SECRET` environment variable
+/// ### Arguments
+/// * token - token received from client, None if client has not sent it
+/// ### Return
+/// token claims or error if token is missing or invalid
+pub fn validate_token(token: Option<&str>) -> Result<Claims, AuthError> {
+    let token = token
+        .filter(|token| !token.is_empty())
+        .ok_or(AuthError::MissingToken)?;
+
+    #[cfg(feature = "test-token")]
+    if let Ok(test_token) = env::var(JWT_TEST_TOKEN_ENV) {
+        if !test_token.is_empty() && token == test_token {
+            return Ok(Claims {
+                sub: TEST_USER.to_owned(),
+                name: Some("Test user".to_owned()),
+                role: None,
+                exp: usize::MAX,
+            });
+        }
+    }
+
+    let secret = env::var(JWT_SECRET_ENV).map_err(|_| AuthError::MissingSecret)?;
+    let data = decode::<Claims>(
+        token,
+        &DecodingKey::from_secret(secret.as_bytes()),
+        &Validation::default(),
+    )?;
+    Ok(data.claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::{encode, EncodingKey, Header};
+
+    use super::{validate_token, AuthError, Claims, JWT_SECRET_ENV};
+
+    #[test]
+    fn test_validate_token() {
+        std::env::set_var(JWT_SECRET_ENV, "secret");
+        let claims = Claims {
+            sub: "user-1".to_owned(),
+            name: Some("Alice".to_owned()),
+            role: Some("admin".to_owned()),
+            // Far in the future
+            exp: 4_000_000_000,
+        };
+        let token = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+
+        let validated = validate_token(Some(&token)).unwrap();
+        assert_eq!(validated.sub, "user-1");
+        assert_eq!(validated.display_name(), "Alice");
+        assert!(validated.is_admin());
+
+        let forged = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(b"other"),
+        )
+        .unwrap();
+        assert!(matches!(
+            validate_token(Some(&forged)),
+            Err(AuthError::InvalidToken(_))
+        ));
+        assert!(matches!(validate_token(None), Err(AuthError::MissingToken)));
+    }
+}
@@ -0,0 +1,37 @@
+use std::time::Duration;
+
+/// Default interval of heartbeat pings
+pub const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Default time without client response before connection is dropped
+pub const DEFAULT_CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default maximum size of the websocket frame in bytes
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 64 * 1024;
+
+/// Default number of messages accepted from the client per second
+pub const DEFAULT_RATE_LIMIT: f32 = 1000.;
+
+/// Runtime settings of websocket sessions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ServerConfig {
+    /// How often heartbeat pings are sent
+    pub heartbeat_interval: Duration,
+    /// How long before lack of client response causes a timeout
+    pub client_timeout: Duration,
+    /// Maximum size of the websocket frame in bytes
+    pub max_frame_size: usize,
+    /// Number of messages accepted from the client per second, also the maximum burst size
+    pub rate_limit: f32,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            heartbeat_interval: DEFAULT_HEARTBEAT_INTERVAL,
+            client_timeout: DEFAULT_CLIENT_TIMEOUT,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            rate_limit: DEFAULT_RATE_LIMIT,
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::env;
+
+use actix_cors::Cors;
+
+/// Comma-separated origins allowed to call REST endpoints, `*` allows any origin. If the
+/// variable is not set or blank, any origin is allowed in development builds and cross-origin
+/// requests are denied in release builds
+pub const ALLOWED_ORIGINS_ENV: &str = "ALLOWED_ORIGINS";
+
+/// How long browsers may cache preflight responses, seconds
+const PREFLIGHT_MAX_AGE: usize = 3600;
+
+/// Parse value of the `ALLOWED_ORIGINS` variable
+/// ### Arguments
+/// * value - comma-separated origins
+/// * development - whether any origin is allowed if no origins are set
+/// ### Return
+/// allowed origins or None if any origin is allowed, empty list denies cross-origin requests
+pub fn parse_allowed_origins(value: Option<&str>, development: bool) -> Option<Vec<String>> {
+    let origins: Vec<String> = value
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|origin| !origin.is_empty())
+        .map(str::to_owned)
+        .collect();
+
+    if origins.iter().any(|origin| origin == "*") || (origins.is_empty() && development) {
+        None
+    } else {
+        Some(origins)
+    }
+}
+
+/// Build CORS middleware for the given origins
+/// ### Arguments
+/// * origins - allowed origins, None allows any origin, empty list denies cross-origin requests
+pub fn build_cors(origins: Option<Vec<String>>) -> Cors {
+    let cors = Cors::default()
+        .allowed_methods(vec!["GET", "POST", "OPTIONS"])
+        .allow_any_header()
+        .max_age(PREFLIGHT_MAX_AGE);
+
+    match origins {
+        Some(origins) => origins
+            .iter()
+            .fold(cors, |cors, origin| cors.allowed_origin(origin)),
+        None => cors.allow_any_origin(),
+    }
+}
+
+/// Build CORS middleware for the origins from `ALLOWED_ORIGINS` variable
+pub fn cors() -> Cors {
+    build_cors(parse_allowed_origins(
+        env::var(ALLOWED_ORIGINS_ENV).ok().as_deref(),
+        cfg!(debug_assertions),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_allowed_origins;
+
+    #[test]
+    fn test_parse_allowed_origins() {
+        assert_eq!(parse_allowed_origins(None, true), None);
+        assert_eq!(parse_allowed_origins(Some(" , "), true), None);
+        assert_eq!(parse_allowed_origins(Some("*"), true), None);
+        assert_eq!(parse_allowed_origins(Some("*"), false), None);
+
+        // Missing or blank value denies cross-origin requests in release builds
+        assert_eq!(parse_allowed_origins(None, false), Some(vec![]));
+        assert_eq!(parse_allowed_origins(Some(" , "), false), Some(vec![]));
+
+        for development in [true, false] {
+            assert_eq!(
+                parse_allowed_origins(
+                    Some("https://cdn.example.com, https://app.example.com"),
+                    development
+                ),
+                Some(vec![
+                    "https://cdn.example.com".to_owned(),
+                    "https://app.example.com".to_owned()
+                ])
+            );
+        }
+    }
+}
@@ -0,0 +1,10 @@
+pub mod auth;
+pub mod config;
+pub mod cors;
+pub mod messages;
+pub mod metrics;
+pub mod routes;
+pub mod server;
+pub mod session;
+pub mod wasm_msg;
+pub mod webhooks;
@@ -1,45 +1,213 @@
-mod messages;
-mod server;
-mod session;
-mod wasm_msg;
-
 use std::fs::File;
 use std::io::{BufReader, Read};
-use std::time::Instant;
+use std::path::PathBuf;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, UNIX_EPOCH};
 use std::{env, fs};
 
-use actix::Addr;
-use actix_web::http::header::ContentEncoding;
-use actix_web::web::Path;
+use actix_web::http::header::{self, ContentEncoding};
 use actix_web::{middleware, web, App, HttpRequest, HttpResponse, HttpServer, Result};
 
-use actix_web_actors::ws;
-use cached::proc_macro::cached;
+use cached::{Cached, SizedCache};
+use once_cell::sync::Lazy;
 
 use actix::prelude::*;
-use session::WsChatSession;
+use dserver::config::ServerConfig;
+use dserver::cors::cors;
+use dserver::routes::{add_webhook, health_live, health_ready, metrics_route, ws_route};
+use dserver::server;
+
+/// Compression of the static files sent to the client
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum FileEncoding {
+    Brotli,
+    Gzip,
+    Identity,
+}
+
+impl FileEncoding {
+    /// Choose compression with the highest quality accepted by the client, brotli is preferred
+    /// if qualities are equal
+    /// ### Arguments
+    /// * req - http request with `Accept-Encoding` header
+    fn from_request(req: &HttpRequest) -> Self {
+        let codings = req
+            .headers()
+            .get(header::ACCEPT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .map(parse_accept_encoding)
+            .unwrap_or_default();
+
+        // Coding not listed by the client is accepted with the quality of `*`
+        let quality = |name: &str| {
+            codings
+                .iter()
+                .find(|(coding, _)| coding.eq_ignore_ascii_case(name))
+                .or_else(|| codings.iter().find(|(coding, _)| *coding == "*"))
+                .map_or(0., |(_, quality)| *quality)
+        };
+
+        let (brotli, gzip) = (quality("br"), quality("gzip"));
+        if brotli > 0. && brotli >= gzip {
+            FileEncoding::Brotli
+        } else if gzip > 0. {
+            FileEncoding::Gzip
+        } else {
+            FileEncoding::Identity
+        }
+    }
+
+    /// Name of the coding, differs entity tags of the file compressed in different ways
+    fn name(self) -> &'static str {
+        match self {
+            FileEncoding::Brotli => "br",
+            FileEncoding::Gzip => "gzip",
+            FileEncoding::Identity => "identity",
+        }
+    }
+
+    /// Value of the `Content-Encoding` response header
+    fn content_encoding(self) -> ContentEncoding {
+        match self {
+            FileEncoding::Brotli => ContentEncoding::Brotli,
+            FileEncoding::Gzip => ContentEncoding::Gzip,
+            FileEncoding::Identity => ContentEncoding::Identity,
+        }
+    }
+}
+
+/// Parse value of `Accept-Encoding` header into codings with their quality values. Coding
+/// without `q` parameter has quality 1, coding with malformed quality is skipped
+fn parse_accept_encoding(value: &str) -> Vec<(&str, f32)> {
+    value
+        .split(',')
+        .filter_map(|coding| {
+            let mut params = coding.split(';');
+            let name = params.next()?.trim();
+            if name.is_empty() {
+                return None;
+            }
+
+            let quality = params
+                .filter_map(|param| param.split_once('='))
+                .find(|(key, _)| key.trim().eq_ignore_ascii_case("q"))
+                .map_or(Some(1.), |(_, quality)| quality.trim().parse::<f32>().ok())?;
+            Some((name, quality))
+        })
+        .collect()
+}
+
+/// Number of compressed static files kept in memory, least recently used file is dropped
+const FILE_CACHE_SIZE: usize = 64;
+
+/// Entity tag of the cached file content with the compressed content itself
+type CachedFile = (String, Vec<u8>);
 
-/// Cached static files compressed using brotli compression codec. Must be using only for files not larger than 5Mb
-#[cached(result = true)]
-fn load_file(name: String) -> Result<Vec<u8>> {
-    tracing::debug!("start reading file: {}", &name);
-    let file = fs::File::open(&name)?;
+/// Compressed static files keyed by file name and compression. Entity tag of the cached content
+/// is kept with it, so file changed on disk replaces its previous version
+static FILE_CACHE: Lazy<Mutex<SizedCache<(String, FileEncoding), CachedFile>>> =
+    Lazy::new(|| Mutex::new(SizedCache::with_size(FILE_CACHE_SIZE)));
+
+/// Read static file compressed using codec accepted by the client
+fn load_file(name: &str, encoding: FileEncoding) -> Result<Vec<u8>> {
+    tracing::debug!("start reading file: {} {:?}", &name, encoding);
+    let mut file = fs::File::open(name)?;
     let mut buffer = Vec::new();
-    let mut input = brotli::CompressorReader::new(file, 8192, 6, 22);
-    let size = input.read_to_end(&mut buffer)?;
+    let size = match encoding {
+        FileEncoding::Brotli => {
+            brotli::CompressorReader::new(file, 8192, 6, 22).read_to_end(&mut buffer)?
+        }
+        FileEncoding::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            std::io::copy(&mut file, &mut encoder)?;
+            buffer = encoder.finish()?;
+            buffer.len()
+        }
+        FileEncoding::Identity => file.read_to_end(&mut buffer)?,
+    };
     tracing::debug!("finish read file: {} size: {}", &name, size);
     Ok(buffer)
 }
 
+/// Entity tag of the file derived from its modification time. Bodies compressed in different
+/// ways are different representations, so compression is a part of the tag
+fn file_etag(name: &str, encoding: FileEncoding) -> Result<String> {
+    let modified = fs::metadata(name)?.modified()?;
+    let since_epoch = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+    Ok(format!(
+        "\"{:x}-{:x}-{}\"",
+        since_epoch.as_secs(),
+        since_epoch.subsec_nanos(),
+        encoding.name()
+    ))
+}
+
+/// Load cached compressed file, file is read again if it was changed on disk since previous
+/// loading. Must be used only for files not larger than 5Mb
+/// ### Arguments
+/// * name - file name
+/// * etag - entity tag of the file on disk
+/// * encoding - compression of the file content
+fn load_file_with_etag(name: &str, etag: &str, encoding: FileEncoding) -> Result<Vec<u8>> {
+    let key = (name.to_owned(), encoding);
+    if let Some((cached_etag, data)) = FILE_CACHE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .cache_get(&key)
+    {
+        if cached_etag == etag {
+            return Ok(data.clone());
+        }
+    }
+
+    let data = load_file(name, encoding)?;
+    FILE_CACHE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .cache_set(key, (etag.to_owned(), data.clone()));
+    Ok(data)
+}
+
+/// Check whether client already has the file with given entity tag
+fn etag_matches(req: &HttpRequest, etag: &str) -> bool {
+    req.headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+        })
+        .unwrap_or_default()
+}
+
 /// Serves static files
 /// ### Argiuments
 /// * req - http request
 /// * data - configuration data, containing path to static files
 async fn index(req: HttpRequest, data: web::Data<String>) -> Result<HttpResponse> {
     let filename = format!("{}/{}", data.as_str(), req.match_info().query("filename"));
-    match load_file(filename.clone()) {
-        Ok(data) => Ok(HttpResponse::Ok()
-            .append_header(ContentEncoding::Brotli)
+    let encoding = FileEncoding::from_request(&req);
+    // Entity tag is checked before the file is compressed, so not modified file costs nothing
+    let loaded = file_etag(&filename, encoding).and_then(|etag| {
+        if etag_matches(&req, &etag) {
+            Ok((None, etag))
+        } else {
+            load_file_with_etag(&filename, &etag, encoding).map(|data| (Some(data), etag))
+        }
+    });
+
+    match loaded {
+        Ok((None, etag)) => Ok(HttpResponse::NotModified()
+            .append_header((header::VARY, "Accept-Encoding"))
+            .append_header((header::ETAG, etag))
+            .finish()),
+        Ok((Some(data), etag)) => Ok(HttpResponse::Ok()
+            .append_header(encoding.content_encoding())
+            .append_header((header::VARY, "Accept-Encoding"))
+            .append_header((header::ETAG, etag))
             .body(data)),
         Err(err) => {
             tracing::error!("{}, file: {}", err, &filename);
@@ -53,37 +221,25 @@ async fn index(req: HttpRequest, data: web::Data<String>) -> Result<HttpResponse
 //     Ok(NamedFile::open(filename)?)
 // }
 
-/// Entry point for our websocket route
-async fn ws_route(
-    req: HttpRequest,
-    stream: web::Payload,
-    id: Path<String>,
-    srv: web::Data<Addr<server::DroServer>>,
-) -> Result<HttpResponse> {
-    tracing::info!("come to ws route: {:?}", req);
-    ws::start(
-        WsChatSession {
-            id: id.into_inner(),
-            hb: Instant::now(),
-            name: None,
-            addr: srv.get_ref().clone(),
-            board: "todo!()".to_owned(),
-        },
-        &req,
-        stream,
-    )
+/// Log lines are written as NDJSON if the variable is set to `json`
+const LOG_FORMAT_ENV: &str = "LOG_FORMAT";
+
+/// Install log subscriber, output format is chosen by `LOG_FORMAT` environment variable
+fn init_tracing() {
+    match env::var(LOG_FORMAT_ENV).as_deref() {
+        Ok("json") => tracing_subscriber::fmt().json().init(),
+        _ => tracing_subscriber::fmt().init(),
+    }
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    tracing_subscriber::fmt().init();
+    init_tracing();
     // let _ = tracing::subscriber::set_global_default(sbr)
     //     .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
 
-    let public_folder = match env::args().nth(1) {
-        Some(x) => x,
-        None => "diadro/docs".to_string(),
-    };
+    let (public_folder, snapshot_dir, config) = parse_args(env::args().skip(1));
+    let config = web::Data::new(config);
 
     let data = web::Data::new(public_folder);
 
@@ -91,14 +247,20 @@ async fn main() -> std::io::Result<()> {
     let tls_config = load_rustls_config();
 
     // Create DwoServer
-    let dro_srv = server::DroServer::new().start();
+    let dro_srv = server::DroServer::new(snapshot_dir).start();
     HttpServer::new(move || {
         App::new()
             .app_data(data.clone())
             .app_data(web::Data::new(dro_srv.clone()))
+            .app_data(config.clone())
             .wrap(middleware::Compress::default())
+            .wrap(cors())
+            .route("/health/live", web::get().to(health_live))
+            .route("/health/ready", web::get().to(health_ready))
             .route("/public/{filename:.*}", web::get().to(index))
             .route("/ws/{id}", web::get().to(ws_route))
+            .route("/api/boards/{name}/webhooks", web::post().to(add_webhook))
+            .route("/metrics", web::get().to(metrics_route))
     })
     .bind_rustls(("0.0.0.0", 8083), tls_config)?
     .workers(num_cpus::get_physical())
@@ -106,6 +268,58 @@ async fn main() -> std::io::Result<()> {
     .await
 }
 
+/// Parse command line arguments:
+/// `[public folder] [--snapshot-dir <dir>] [--heartbeat-secs <secs>] [--timeout-secs <secs>]
+/// [--rate-limit <messages per second>]`
+/// ### Return
+/// public folder, snapshot directory and websocket sessions configuration
+fn parse_args(mut args: impl Iterator<Item = String>) -> (String, Option<PathBuf>, ServerConfig) {
+    let mut public_folder = None;
+    let mut snapshot_dir = None;
+    let mut config = ServerConfig::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--snapshot-dir" => snapshot_dir = args.next().map(PathBuf::from),
+            "--heartbeat-secs" => {
+                if let Some(secs) = parse_secs(&arg, args.next()) {
+                    config.heartbeat_interval = secs;
+                }
+            }
+            "--timeout-secs" => {
+                if let Some(secs) = parse_secs(&arg, args.next()) {
+                    config.client_timeout = secs;
+                }
+            }
+            "--rate-limit" => match args.next().map(|value| value.parse::<f32>()) {
+                Some(Ok(rate)) if rate >= 1. => config.rate_limit = rate,
+                value => tracing::warn!("Invalid value of {}: {:?}, default is used", arg, value),
+            },
+            _ if public_folder.is_none() => public_folder = Some(arg),
+            _ => tracing::warn!("Unknown argument: {}", arg),
+        }
+    }
+
+    (
+        public_folder.unwrap_or_else(|| "diadro/docs".to_string()),
+        snapshot_dir,
+        config,
+    )
+}
+
+/// Parse value of the argument as positive number of seconds
+/// ### Arguments
+/// * arg - argument name, used in log message
+/// * value - argument value
+fn parse_secs(arg: &str, value: Option<String>) -> Option<Duration> {
+    match value.as_deref().map(str::parse::<u64>) {
+        Some(Ok(secs)) if secs > 0 => Some(Duration::from_secs(secs)),
+        _ => {
+            tracing::warn!("Invalid value of {}: {:?}, default is used", arg, value);
+            None
+        }
+    }
+}
+
 fn load_rustls_config() -> rustls::ServerConfig {
     let pk_file = env::var("PK_FILE").unwrap_or_else(|err| {
         tracing::warn!(
@@ -115,7 +329,7 @@ fn load_rustls_config() -> rustls::ServerConfig {
         "./keys/key.pem".to_string()
     });
 
-    let cert_file = env::var("PK_FILE").unwrap_or_else(|err| {
+    let cert_file = env::var("CERT_FILE").unwrap_or_else(|err| {
         tracing::warn!(
             "Error reading CERT_FILE. Standatd value will be used. Error: {}",
             err
@@ -152,3 +366,108 @@ fn load_rustls_config() -> rustls::ServerConfig {
 
     config.with_single_cert(cert_chain, keys.remove(0)).unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Read};
+
+    use actix_web::{
+        body,
+        http::{header, StatusCode},
+        test::TestRequest,
+        web,
+    };
+
+    use super::index;
+
+    #[actix_web::test]
+    async fn test_index_encoding() {
+        let folder = std::env::temp_dir().join(format!("dserver_public_{}", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        let content = "static content ".repeat(100);
+        fs::write(folder.join("index.html"), &content).unwrap();
+        let data = web::Data::new(folder.to_string_lossy().into_owned());
+
+        for (accept_encoding, content_encoding) in [
+            ("gzip, deflate, br", "br"),
+            ("gzip, deflate", "gzip"),
+            ("br;q=0, gzip", "gzip"),
+            ("br; q=0.000, gzip;q=0.5", "gzip"),
+            ("gzip;q=1, br;q=0.8", "gzip"),
+            ("*", "br"),
+            ("*, br;q=0", "gzip"),
+            ("gzip;q=0, deflate", "identity"),
+            ("deflate", "identity"),
+        ] {
+            let req = TestRequest::default()
+                .insert_header((header::ACCEPT_ENCODING, accept_encoding))
+                .param("filename", "index.html")
+                .to_http_request();
+            let resp = index(req, data.clone()).await.unwrap();
+            assert_eq!(
+                resp.headers().get(header::CONTENT_ENCODING).unwrap(),
+                content_encoding
+            );
+            assert_eq!(resp.headers().get(header::VARY).unwrap(), "Accept-Encoding");
+
+            // Each compression has its own entity tag
+            let etag = resp.headers().get(header::ETAG).unwrap().to_str().unwrap();
+            assert!(etag.ends_with(&format!("-{}\"", content_encoding)));
+
+            let bytes = body::to_bytes(resp.into_body()).await.unwrap();
+            let mut decoded = String::new();
+            match content_encoding {
+                "br" => brotli::Decompressor::new(bytes.as_ref(), 4096)
+                    .read_to_string(&mut decoded)
+                    .unwrap(),
+                "gzip" => {
+                    // Gzip magic number
+                    assert_eq!(&bytes[..2], &[0x1f, 0x8b]);
+                    flate2::read::GzDecoder::new(bytes.as_ref())
+                        .read_to_string(&mut decoded)
+                        .unwrap()
+                }
+                _ => {
+                    assert_eq!(&bytes[..6], b"static");
+                    bytes.as_ref().read_to_string(&mut decoded).unwrap()
+                }
+            };
+            assert_eq!(decoded, content);
+        }
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_index_not_modified() {
+        let folder = std::env::temp_dir().join(format!("dserver_etag_{}", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("app.js"), "console.log(1)").unwrap();
+        let data = web::Data::new(folder.to_string_lossy().into_owned());
+
+        let req = TestRequest::default()
+            .param("filename", "app.js")
+            .to_http_request();
+        let resp = index(req, data.clone()).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let etag = resp.headers().get(header::ETAG).unwrap().clone();
+
+        let req = TestRequest::default()
+            .insert_header((header::IF_NONE_MATCH, etag.clone()))
+            .param("filename", "app.js")
+            .to_http_request();
+        let resp = index(req, data.clone()).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(resp.headers().get(header::ETAG).unwrap(), &etag);
+
+        // Missing file is not found even if client sends any entity tag
+        let req = TestRequest::default()
+            .insert_header((header::IF_NONE_MATCH, "*"))
+            .param("filename", "missing.js")
+            .to_http_request();
+        let resp = index(req, data).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
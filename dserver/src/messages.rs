@@ -1,10 +1,31 @@
 use actix::prelude::*;
+use actix_web::web::Bytes;
 
-use crate::wasm_msg::WsMessages;
+use crate::{
+    wasm_msg::{self, EncodeError, WsMessages},
+    webhooks::{Webhook, WebhookError},
+};
 
-#[derive(Message)]
+/// Messages sent by server to sessions. Messages are encoded once by server, each session sends
+/// the encoding negotiated with its client
+#[derive(Message, Clone)]
 #[rtype(result = "()")]
-pub struct Message(pub String);
+pub struct Message {
+    /// Json array of envelopes
+    pub json: String,
+    /// Bincode encoded envelopes
+    pub bincode: Bytes,
+}
+
+impl Message {
+    /// Encode messages with all supported encodings
+    pub fn encode(messages: &[WsMessages]) -> Result<Self, EncodeError> {
+        Ok(Self {
+            json: wasm_msg::to_json(messages)?,
+            bincode: wasm_msg::to_bincode(messages)?.into(),
+        })
+    }
+}
 
 /// Message for chat server communications
 /// New chat session is created
@@ -12,6 +33,12 @@ pub struct Message(pub String);
 #[rtype(String)]
 pub struct Connect {
     pub user_id: String,
+    /// Authenticated user name
+    pub name: String,
+    /// Authenticated user identifier, used to check boards access
+    pub subject: String,
+    /// User can manage boards access
+    pub admin: bool,
     pub addr: Recipient<Message>,
 }
 
@@ -24,9 +51,28 @@ pub struct Disconnect {
 #[derive(Message, Debug)]
 #[rtype(result = "()")]
 pub struct ClientMessage {
+    /// Identifier of the sender session
+    pub user_id: String,
     pub message: Vec<WsMessages>,
 }
 
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct ListRooms;
+
+/// Register webhook for the board. Webhook with the same url replaces the registered one
+#[derive(Message)]
+#[rtype(result = "Result<(), WebhookError>")]
+pub struct AddWebhook {
+    pub board: String,
+    pub webhook: Webhook,
+    /// Authenticated identifier of the user registering webhook, must own the board
+    pub subject: String,
+    /// Administrators can register webhooks for any board
+    pub admin: bool,
+}
+
+/// Check that the server actor is reachable
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Ping;
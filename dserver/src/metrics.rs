@@ -0,0 +1,71 @@
+use once_cell::sync::Lazy;
+use prometheus::{Encoder, IntCounter, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder};
+
+/// Registry of all server metrics
+pub static REGISTRY: Lazy<Registry> = Lazy::new(Registry::new);
+
+pub static ACTIVE_SESSIONS: Lazy<IntGauge> = Lazy::new(|| {
+    register(IntGauge::new(
+        "diadro_active_sessions",
+        "Number of connected websocket sessions",
+    ))
+});
+
+pub static MESSAGES_RECEIVED: Lazy<IntCounter> = Lazy::new(|| {
+    register(IntCounter::new(
+        "diadro_messages_received_total",
+        "Number of messages received from clients",
+    ))
+});
+
+pub static MESSAGES_BROADCAST: Lazy<IntCounter> = Lazy::new(|| {
+    register(IntCounter::new(
+        "diadro_messages_broadcast_total",
+        "Number of packets sent to board members",
+    ))
+});
+
+pub static BOARDS: Lazy<IntGauge> =
+    Lazy::new(|| register(IntGauge::new("diadro_boards_total", "Number of boards")));
+
+pub static BOARD_FIGURES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register(IntGaugeVec::new(
+        Opts::new(
+            "diadro_board_figures",
+            "Number of figures on the board, updated when snapshot is saved",
+        ),
+        &["board"],
+    ))
+});
+
+/// Register metric in the global registry
+fn register<M>(metric: prometheus::Result<M>) -> M
+where
+    M: prometheus::core::Collector + Clone + 'static,
+{
+    let metric = metric.expect("Invalid metric definition");
+    if let Err(err) = REGISTRY.register(Box::new(metric.clone())) {
+        tracing::error!("Error registering metric: {}", err);
+    }
+    metric
+}
+
+/// Make sure all metrics are registered, so they are exported before the first change
+fn init() {
+    Lazy::force(&ACTIVE_SESSIONS);
+    Lazy::force(&MESSAGES_RECEIVED);
+    Lazy::force(&MESSAGES_BROADCAST);
+    Lazy::force(&BOARDS);
+    Lazy::force(&BOARD_FIGURES);
+}
+
+/// Encode all metrics in Prometheus text format
+/// ### Return
+/// content type and encoded metrics
+pub fn encode() -> prometheus::Result<(String, Vec<u8>)> {
+    init();
+    let encoder = TextEncoder::new();
+    let mut buffer = vec![];
+    encoder.encode(&REGISTRY.gather(), &mut buffer)?;
+    Ok((encoder.format_type().to_owned(), buffer))
+}
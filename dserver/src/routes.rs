@@ -0,0 +1,182 @@
+use std::time::{Duration, Instant};
+
+use actix::Addr;
+use actix_web::web::Path;
+use actix_web::{http::header, web, HttpRequest, HttpResponse, Result};
+use actix_web_actors::ws;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    auth,
+    config::ServerConfig,
+    messages::{AddWebhook, Ping},
+    metrics, server,
+    session::WsChatSession,
+    webhooks::{self, Webhook, WebhookError},
+};
+
+/// How long readiness check waits for the server actor reply
+const READY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Query parameters of the websocket route
+#[derive(Deserialize, Debug)]
+pub struct WsQuery {
+    /// Token authenticating the user
+    pub token: Option<String>,
+}
+
+/// Identifier of the websocket session. Session id from path is prefixed by the authenticated
+/// user, so a token of one user can't replace sessions of others
+/// ### Arguments
+/// * claims - claims of the validated token
+/// * id - session id from `/ws/{id}` path
+fn session_id(claims: &auth::Claims, id: &str) -> String {
+    format!("{}/{}", claims.sub, id)
+}
+
+/// Entry point for our websocket route. Connection is accepted for clients with valid token only
+pub async fn ws_route(
+    req: HttpRequest,
+    stream: web::Payload,
+    id: Path<String>,
+    query: web::Query<WsQuery>,
+    srv: web::Data<Addr<server::DroServer>>,
+    config: web::Data<ServerConfig>,
+) -> Result<HttpResponse> {
+    tracing::info!("come to ws route: {:?}", req);
+    let claims = match auth::validate_token(query.token.as_deref()) {
+        Ok(claims) => claims,
+        Err(err) => {
+            tracing::warn!("Websocket connection rejected: {}", err);
+            return Ok(HttpResponse::Unauthorized().finish());
+        }
+    };
+
+    ws::WsResponseBuilder::new(
+        WsChatSession {
+            id: session_id(&claims, &id),
+            hb: Instant::now(),
+            name: Some(claims.display_name().to_owned()),
+            subject: claims.sub.clone(),
+            admin: claims.is_admin(),
+            addr: srv.get_ref().clone(),
+            // Session is moved to another board by join message
+            board: server::DEFAULT_BOARD.to_owned(),
+            config: *config.get_ref(),
+            encoding: Default::default(),
+            handshake: false,
+            tokens: config.rate_limit,
+            last_refill: Instant::now(),
+            dropped_messages: 0,
+        },
+        &req,
+        stream,
+    )
+    .frame_size(config.max_frame_size)
+    .start()
+}
+
+/// Register webhook for the board. Request must be authenticated by `Authorization: Bearer`
+/// token of the board owner or administrator
+/// ### Arguments
+/// * name - board name
+/// * webhook - webhook url and list of events
+pub async fn add_webhook(
+    req: HttpRequest,
+    name: Path<String>,
+    webhook: web::Json<Webhook>,
+    srv: web::Data<Addr<server::DroServer>>,
+) -> Result<HttpResponse> {
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let claims = match auth::validate_token(token) {
+        Ok(claims) => claims,
+        Err(err) => {
+            tracing::warn!("Webhook registration rejected: {}", err);
+            return Ok(HttpResponse::Unauthorized().finish());
+        }
+    };
+
+    if let Err(err) = webhooks::validate_url(&webhook.url).await {
+        return Ok(HttpResponse::BadRequest().body(err.to_string()));
+    }
+
+    match srv
+        .send(AddWebhook {
+            board: name.into_inner(),
+            webhook: webhook.into_inner(),
+            admin: claims.is_admin(),
+            subject: claims.sub,
+        })
+        .await
+    {
+        Ok(Ok(_)) => Ok(HttpResponse::Created().finish()),
+        Ok(Err(err @ WebhookError::UnknownBoard(_))) => {
+            Ok(HttpResponse::NotFound().body(err.to_string()))
+        }
+        Ok(Err(err @ WebhookError::NotOwner(_))) => {
+            Ok(HttpResponse::Forbidden().body(err.to_string()))
+        }
+        Ok(Err(err)) => Ok(HttpResponse::BadRequest().body(err.to_string())),
+        Err(err) => {
+            tracing::error!("Error registering webhook: {}", err);
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+/// Export server metrics in Prometheus text format
+pub async fn metrics_route() -> Result<HttpResponse> {
+    match metrics::encode() {
+        Ok((content_type, body)) => Ok(HttpResponse::Ok().content_type(content_type).body(body)),
+        Err(err) => {
+            tracing::error!("Error encoding metrics: {}", err);
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+/// Body of the health check responses
+#[derive(Serialize, Debug)]
+pub struct HealthStatus {
+    /// `ok` or `error`
+    pub status: &'static str,
+    /// Failure reason
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl HealthStatus {
+    fn ok() -> Self {
+        Self {
+            status: "ok",
+            detail: None,
+        }
+    }
+
+    fn error(detail: String) -> Self {
+        Self {
+            status: "error",
+            detail: Some(detail),
+        }
+    }
+}
+
+/// Liveness check, always succeeds while the process is running
+pub async fn health_live() -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(HealthStatus::ok()))
+}
+
+/// Readiness check, succeeds only if server actor replies to ping within one second
+pub async fn health_ready(srv: web::Data<Addr<server::DroServer>>) -> Result<HttpResponse> {
+    match srv.send(Ping).timeout(READY_TIMEOUT).await {
+        Ok(_) => Ok(HttpResponse::Ok().json(HealthStatus::ok())),
+        Err(err) => {
+            tracing::error!("Readiness check failed: {}", err);
+            Ok(HttpResponse::ServiceUnavailable().json(HealthStatus::error(err.to_string())))
+        }
+    }
+}
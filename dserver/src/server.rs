@@ -1,40 +1,285 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
-use actix::{Actor, Context, Handler, Recipient};
+use actix::{Actor, AsyncContext, Context, Handler, Recipient};
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    messages::{ClientMessage, Connect, Disconnect, Message},
-    wasm_msg::{AddArrow, AddFigure, MousePosition},
+    messages::{AddWebhook, ClientMessage, Connect, Disconnect, Message, Ping},
+    metrics,
+    wasm_msg::{AddFigure, RequestInfo, WsMessages},
+    webhooks::{self, Webhook, WebhookError},
 };
 
+/// Board every session joins on connect
+pub const DEFAULT_BOARD: &str = "Main";
+
+/// Interval between board snapshots saving
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Number of the last message identifiers remembered for each user to drop duplicates
+const RECENT_IDS_CAPACITY: usize = 200;
+
+/// Time message identifiers of the disconnected user are kept, so frames replayed after
+/// reconnect are still dropped
+const RECENT_IDS_TTL: Duration = Duration::from_secs(600);
+
+/// Identifiers of the last messages received from the user
+#[derive(Debug)]
+struct RecentIds {
+    ids: VecDeque<String>,
+    /// Time of the last received message
+    updated: Instant,
+}
+
+/// Authenticated user of the session
+#[derive(Debug, Clone)]
+struct SessionUser {
+    /// Display name
+    name: String,
+    /// User identifier from token
+    subject: String,
+    /// User can manage boards access
+    admin: bool,
+}
+
+/// Board state stored on disk
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct BoardSnapshot {
+    /// Identifiers of the sessions connected to the board at the moment of saving
+    pub sessions: Vec<String>,
+    /// Messages changed the board diagram in order of receiving
+    pub messages: Vec<WsMessages>,
+    /// Pages of the board in order of adding
+    #[serde(default)]
+    pub pages: Vec<BoardPage>,
+    /// Title, author and description of the board diagram
+    #[serde(default)]
+    pub metadata: Option<BoardMetadata>,
+    /// Identifier of the user created the board
+    #[serde(default)]
+    pub owner: Option<String>,
+}
+
+/// Page of the board added by `AddPage` message
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BoardPage {
+    pub page_id: String,
+    pub page_name: String,
+}
+
+/// Diagram metadata of the board set by the last `UpdateMetadata` message
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BoardMetadata {
+    pub title: String,
+    pub author: String,
+    pub description: String,
+}
+
 /// `ChatServer` manages chat rooms and responsible for coordinating chat session.
 ///
 /// Implementation is very naïve.
 #[derive(Debug)]
 pub struct DroServer {
     sessions: HashMap<String, Recipient<Message>>,
+    /// Authenticated users by session identifiers
+    users: HashMap<String, SessionUser>,
+    /// Users allowed to access the board by board names. Boards without list are open to all users
+    board_acls: HashMap<String, HashSet<String>>,
     boards: HashMap<String, HashSet<String>>,
+    /// Webhooks registered for boards
+    webhooks: HashMap<String, Vec<Webhook>>,
+    /// Identifiers of the users created boards by board names, only owners can register webhooks
+    owners: HashMap<String, String>,
+    /// Messages changed diagrams of the boards, sent to clients joining the board
+    history: HashMap<String, Vec<WsMessages>>,
+    /// Pages of the boards, sent to clients joining the board before diagram messages
+    pages: HashMap<String, Vec<BoardPage>>,
+    /// Diagram metadata of the boards, sent to clients joining the board before diagram messages
+    metadata: HashMap<String, BoardMetadata>,
+    /// Directory where board snapshots are stored, snapshots are not saved if not set
+    snapshot_path: Option<PathBuf>,
+    /// Identifiers of the last messages received from users by token subjects
+    recent_ids: HashMap<String, RecentIds>,
+    /// Sessions which have selected figures shown to other users of the board
+    selecting: HashSet<String>,
 }
 
 impl DroServer {
-    pub fn new() -> DroServer {
+    /// Create server. Boards saved in the snapshot directory are restored
+    /// ### Arguments
+    /// * snapshot_path - directory of the board snapshots
+    pub fn new(snapshot_path: Option<PathBuf>) -> DroServer {
         // default room
         let mut boards = HashMap::new();
-        boards.insert("Main".to_owned(), HashSet::new());
+        boards.insert(DEFAULT_BOARD.to_owned(), HashSet::new());
 
+        let mut history = HashMap::new();
+        let mut pages = HashMap::new();
+        let mut metadata = HashMap::new();
+        let mut owners = HashMap::new();
+        let snapshots = snapshot_path
+            .as_deref()
+            .map(load_snapshots)
+            .unwrap_or_default();
+        for (board, snapshot) in snapshots {
+            boards.entry(board.clone()).or_default();
+            if !snapshot.pages.is_empty() {
+                pages.insert(board.clone(), snapshot.pages);
+            }
+            if let Some(board_metadata) = snapshot.metadata {
+                metadata.insert(board.clone(), board_metadata);
+            }
+            if let Some(owner) = snapshot.owner {
+                owners.insert(board.clone(), owner);
+            }
+            history.insert(board, snapshot.messages);
+        }
+
+        metrics::BOARDS.set(boards.len() as i64);
         DroServer {
             sessions: HashMap::new(),
+            users: HashMap::new(),
+            board_acls: HashMap::new(),
             boards,
+            webhooks: HashMap::new(),
+            owners,
+            history,
+            pages,
+            metadata,
+            snapshot_path,
+            recent_ids: HashMap::new(),
+            selecting: HashSet::new(),
+        }
+    }
+}
+
+impl Default for DroServer {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+/// Board name is used as file name, so only names without path separators can be stored
+fn is_valid_board_name(board: &str) -> bool {
+    !board.is_empty()
+        && board
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == ' ')
+}
+
+/// Count figures added and not deleted by diagram messages
+fn figures_count(messages: &[WsMessages]) -> i64 {
+    messages.iter().fold(0, |count, message| match message {
+        WsMessages::AddFigure(_) => count + 1,
+        WsMessages::DeleteFigure { .. } => count - 1,
+        _ => count,
+    })
+}
+
+/// Append diagram message to the board history. Moves and text edits are merged into the
+/// previous change of the same figure and changes of deleted figures are removed, so history
+/// doesn't grow while figures are dragged or edited. Figure added and deleted meanwhile
+/// disappears from history completely
+/// ### Arguments
+/// * history - diagram messages of the board
+/// * message - new diagram message
+fn append_history(history: &mut Vec<WsMessages>, message: WsMessages) {
+    if let WsMessages::DeleteFigure { figure_id, .. } = &message {
+        let added = history.iter().any(
+            |m| matches!(m, WsMessages::AddFigure(AddFigure { figure_id: id, .. }) if id == figure_id),
+        );
+        history.retain(|m| m.figure_id() != Some(figure_id));
+        if !added {
+            history.push(message);
+        }
+        return;
+    }
+
+    let last_change = message.figure_id().and_then(|figure_id| {
+        history
+            .iter()
+            .rposition(|m| m.figure_id() == Some(figure_id))
+    });
+    match (&message, last_change.and_then(|idx| history.get_mut(idx))) {
+        (WsMessages::MoveFigure { dx, dy, .. }, Some(WsMessages::AddFigure(figure))) => {
+            figure.rect.translate(*dx, *dy);
+            return;
+        }
+        (
+            WsMessages::MoveFigure { dx, dy, .. },
+            Some(WsMessages::MoveFigure {
+                dx: last_dx,
+                dy: last_dy,
+                ..
+            }),
+        ) => {
+            *last_dx += dx;
+            *last_dy += dy;
+            return;
         }
+        (WsMessages::UpdateFigureText { text, .. }, Some(WsMessages::AddFigure(figure))) => {
+            figure.text = text.clone();
+            return;
+        }
+        (
+            WsMessages::UpdateFigureText { text, .. },
+            Some(WsMessages::UpdateFigureText {
+                text: last_text, ..
+            }),
+        ) => {
+            *last_text = text.clone();
+            return;
+        }
+        _ => {}
     }
+
+    history.push(message);
+}
+
+/// Read all board snapshots from directory. Unreadable snapshots are skipped
+/// ### Return
+/// snapshots by board names
+fn load_snapshots(path: &Path) -> HashMap<String, BoardSnapshot> {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(err) => {
+            tracing::warn!("Snapshots are not loaded from {}: {}", path.display(), err);
+            return HashMap::new();
+        }
+    };
+
+    entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|file| file.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|file| {
+            let board = file.file_stem()?.to_str()?.to_owned();
+            let snapshot = fs::read(&file)
+                .map_err(|err| err.to_string())
+                .and_then(|data| {
+                    serde_json::from_slice::<BoardSnapshot>(&data).map_err(|err| err.to_string())
+                });
+            match snapshot {
+                Ok(snapshot) => Some((board, snapshot)),
+                Err(err) => {
+                    tracing::error!("Error loading snapshot {}: {}", file.display(), err);
+                    None
+                }
+            }
+        })
+        .collect()
 }
 
 impl DroServer {
     /// Broadcast message to all connected clients, except sender (skip_client)
-    fn broadcast(&self, _board: &str, message: &str, skip_client: &str) {
+    fn broadcast(&self, board: &str, message: &Message, skip_client: &str) {
         // tracing::info!("Sessions: {:?}", self.sessions);
         // tracing::info!("Boards: {:?}, board: {}", self.boards, board);
-        let _ = self.boards.get("Main").map(|clients| {
+        let _ = self.boards.get(board).map(|clients| {
             tracing::debug!("{:?}", self.sessions);
             clients
                 .iter()
@@ -42,15 +287,351 @@ impl DroServer {
                 .filter_map(|c| self.sessions.get(c))
                 .for_each(|addr| {
                     // tracing::info!("Send message to client: {:?}", addr);
-                    let _ = addr.do_send(Message(message.to_owned()));
+                    addr.do_send(message.clone());
+                    metrics::MESSAGES_BROADCAST.inc();
+                })
+        });
+    }
+
+    /// Check if message with the same identifier was already received from the user of the
+    /// session. Identifiers are kept by token subject, so frames replayed by the new session
+    /// after reconnect are dropped as well. Identifier of the new message is remembered,
+    /// messages without identifier and mouse positions are never duplicates
+    /// ### Arguments
+    /// * user_id - session identifier
+    /// * message - received message
+    fn is_duplicate(&mut self, user_id: &str, message: &WsMessages) -> bool {
+        // Mouse moves are frequent and harmless to replay, they would push edits out of window
+        if matches!(message, WsMessages::MousePosition(_)) {
+            return false;
+        }
+        let message_id = match message.request_info().and_then(|rq| rq.message_id.as_ref()) {
+            Some(message_id) => message_id,
+            None => return false,
+        };
+        let subject = match self.users.get(user_id) {
+            Some(user) => user.subject.clone(),
+            None => return false,
+        };
+
+        let recent = self.recent_ids.entry(subject).or_insert_with(|| RecentIds {
+            ids: VecDeque::new(),
+            updated: Instant::now(),
+        });
+        recent.updated = Instant::now();
+        if recent.ids.contains(message_id) {
+            return true;
+        }
+
+        if recent.ids.len() >= RECENT_IDS_CAPACITY {
+            recent.ids.pop_front();
+        }
+        recent.ids.push_back(message_id.clone());
+        false
+    }
+
+    /// Forget message identifiers of the users which sent nothing for `RECENT_IDS_TTL`
+    /// and have no connected sessions
+    fn prune_recent_ids(&mut self) {
+        let users = &self.users;
+        self.recent_ids.retain(|subject, recent| {
+            recent.updated.elapsed() < RECENT_IDS_TTL
+                || users.values().any(|user| &user.subject == subject)
+        });
+    }
+
+    /// Send messages to one client only
+    fn send_to(&self, user_id: &str, messages: &[WsMessages]) {
+        match (self.sessions.get(user_id), Message::encode(messages)) {
+            (Some(addr), Ok(message)) => addr.do_send(message),
+            (None, _) => tracing::warn!("Session {} not found", user_id),
+            (_, Err(err)) => tracing::error!("Error serialize: {}", err),
+        }
+    }
+
+    /// Create new empty board and confirm creation to the sender. Sender becomes owner of
+    /// the new board
+    fn create_board(&mut self, board_name: String, user_id: &str) {
+        if !self.boards.contains_key(&board_name) {
+            self.boards.insert(board_name.clone(), HashSet::new());
+            if let Some(user) = self.users.get(user_id) {
+                self.owners.insert(board_name.clone(), user.subject.clone());
+            }
+        }
+        metrics::BOARDS.set(self.boards.len() as i64);
+        self.send_to(user_id, &[WsMessages::BoardCreated { board_name }]);
+    }
+
+    /// Send names of all boards to the requesting client
+    fn list_boards(&self, user_id: &str) {
+        let mut boards: Vec<String> = self.boards.keys().cloned().collect();
+        boards.sort();
+        self.send_to(user_id, &[WsMessages::BoardList { boards }]);
+    }
+
+    /// Send display names of the users connected to the board to the requesting client.
+    /// Users without access to the board get no response
+    fn board_members(&self, board: String, user_id: &str) {
+        if !self.is_allowed(&board, user_id) {
+            tracing::debug!("User {} has no access to board {}", user_id, &board);
+            return;
+        }
+
+        let mut members: Vec<String> = self
+            .boards
+            .get(&board)
+            .map(|clients| {
+                clients
+                    .iter()
+                    .map(|id| {
+                        self.users
+                            .get(id)
+                            .map_or_else(|| id.to_owned(), |user| user.name.clone())
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        members.sort();
+        self.send_to(user_id, &[WsMessages::BoardMembers { board, members }]);
+    }
+
+    /// Board the session is connected to
+    fn session_board(&self, user_id: &str) -> Option<String> {
+        self.boards
+            .iter()
+            .find(|(_, clients)| clients.contains(user_id))
+            .map(|(board, _)| board.clone())
+    }
+
+    /// Check if session user is allowed to access the board
+    fn is_allowed(&self, board: &str, user_id: &str) -> bool {
+        match self.board_acls.get(board) {
+            Some(allowed) => self
+                .users
+                .get(user_id)
+                .is_some_and(|user| allowed.contains(&user.subject)),
+            None => true,
+        }
+    }
+
+    /// Replace board access list. Sessions of not allowed users are removed from the board
+    fn set_board_acl(&mut self, board: String, allowed_users: Vec<String>, user_id: &str) {
+        if !self.users.get(user_id).is_some_and(|user| user.admin) {
+            tracing::warn!(
+                "User {} is not allowed to change access to {}",
+                user_id,
+                board
+            );
+            return;
+        }
+
+        self.board_acls
+            .insert(board.clone(), allowed_users.into_iter().collect());
+        let denied: Vec<String> = self
+            .boards
+            .get(&board)
+            .map(|clients| {
+                clients
+                    .iter()
+                    .filter(|client| !self.is_allowed(&board, client))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        if let Some(clients) = self.boards.get_mut(&board) {
+            for client in denied {
+                clients.remove(&client);
+            }
+        }
+    }
+
+    /// Move user from its current board to the given one. Unknown boards and boards
+    /// the user has no access to are not joined
+    fn join_board(&mut self, board_name: &str, user_id: &str) {
+        if !self.boards.contains_key(board_name) {
+            tracing::warn!("User {} can't join unknown board {}", user_id, board_name);
+            return;
+        }
+
+        if !self.is_allowed(board_name, user_id) {
+            tracing::warn!("User {} has no access to board {}", user_id, board_name);
+            return;
+        }
+
+        if let Some(board) = self
+            .session_board(user_id)
+            .filter(|board| board != board_name)
+        {
+            self.clear_selection(&board, user_id);
+        }
+        for clients in self.boards.values_mut() {
+            clients.remove(user_id);
+        }
+
+        self.enter_board(board_name, user_id);
+    }
+
+    /// Add user to the board, send board state to the user and announce the user to the board
+    /// webhooks. Access to the board must be checked by caller
+    fn enter_board(&mut self, board: &str, user_id: &str) {
+        self.boards
+            .entry(board.to_owned())
+            .or_default()
+            .insert(user_id.to_owned());
+        self.send_history(board, user_id);
+
+        let name = self
+            .users
+            .get(user_id)
+            .map(|user| user.name.clone())
+            .unwrap_or_default();
+        self.notify_webhooks(
+            board,
+            webhooks::EVENT_USER_JOINED,
+            serde_json::json!({ "user": user_id, "name": name }),
+        );
+    }
+
+    /// Tell other users of the board that user has left it, so they forget figures selected
+    /// by the user. Nothing is sent if user has no selection
+    fn clear_selection(&mut self, board: &str, user_id: &str) {
+        if !self.selecting.remove(user_id) {
+            return;
+        }
+
+        let message = WsMessages::SelectionChange {
+            rq: RequestInfo {
+                board: board.to_owned(),
+                user: user_id.to_owned(),
+                message_id: None,
+                page_id: None,
+            },
+            selected_ids: vec![],
+        };
+        match Message::encode(&[message]) {
+            Ok(message) => self.broadcast(board, &message, user_id),
+            Err(err) => tracing::error!("Error serialize: {}", err),
+        }
+    }
+
+    /// Send pages, metadata and diagram messages of the board to the client, used to show current state
+    /// to the joined client
+    fn send_history(&self, board: &str, user_id: &str) {
+        if let Some(pages) = self.pages.get(board).filter(|pages| !pages.is_empty()) {
+            let pages: Vec<_> = pages
+                .iter()
+                .map(|page| WsMessages::AddPage {
+                    rq: RequestInfo {
+                        board: board.to_owned(),
+                        user: String::new(),
+                        message_id: None,
+                        page_id: None,
+                    },
+                    page_id: page.page_id.clone(),
+                    page_name: page.page_name.clone(),
                 })
+                .collect();
+            self.send_to(user_id, &pages);
+        }
+
+        if let Some(metadata) = self.metadata.get(board) {
+            let metadata = [WsMessages::UpdateMetadata {
+                rq: RequestInfo {
+                    board: board.to_owned(),
+                    user: String::new(),
+                    message_id: None,
+                    page_id: None,
+                },
+                title: metadata.title.clone(),
+                author: metadata.author.clone(),
+                description: metadata.description.clone(),
+            }];
+            self.send_to(user_id, &metadata);
+        }
+
+        match self.history.get(board) {
+            Some(messages) if !messages.is_empty() => self.send_to(user_id, messages),
+            _ => {}
+        }
+    }
+
+    /// Save board sessions and diagram messages into `snapshot_path/board.json`
+    pub fn save_snapshot(&self, board: &str) {
+        let dir = match &self.snapshot_path {
+            Some(dir) if is_valid_board_name(board) => dir,
+            Some(_) => {
+                tracing::warn!("Board {} can't be saved, invalid name", board);
+                return;
+            }
+            None => return,
+        };
+
+        let mut sessions: Vec<&String> = self
+            .boards
+            .get(board)
+            .map(|clients| clients.iter().collect())
+            .unwrap_or_default();
+        sessions.sort();
+
+        let snapshot = serde_json::json!({
+            "sessions": sessions,
+            "messages": self.history.get(board).map(Vec::as_slice).unwrap_or_default(),
+            "pages": self.pages.get(board).map(Vec::as_slice).unwrap_or_default(),
+            "metadata": self.metadata.get(board),
+            "owner": self.owners.get(board),
         });
+
+        metrics::BOARD_FIGURES
+            .with_label_values(&[board])
+            .set(figures_count(
+                self.history
+                    .get(board)
+                    .map(Vec::as_slice)
+                    .unwrap_or_default(),
+            ));
+
+        let file = dir.join(format!("{}.json", board));
+        let result =
+            fs::create_dir_all(dir).and_then(|_| fs::write(&file, snapshot.to_string().as_bytes()));
+        if let Err(err) = result {
+            tracing::error!("Error saving snapshot {}: {}", file.display(), err);
+        }
+    }
+
+    /// Save snapshots of all boards
+    fn save_snapshots(&self) {
+        for board in self.boards.keys() {
+            self.save_snapshot(board);
+        }
+    }
+
+    /// Notify all board webhooks subscribed to the event. Each delivery is spawned as separate task
+    fn notify_webhooks(&self, board: &str, event: &str, payload: serde_json::Value) {
+        if let Some(hooks) = self.webhooks.get(board) {
+            hooks
+                .iter()
+                .filter(|hook| hook.accepts(event))
+                .for_each(|hook| {
+                    actix::spawn(webhooks::deliver(
+                        hook.url.clone(),
+                        event.to_owned(),
+                        board.to_owned(),
+                        payload.clone(),
+                    ));
+                });
+        }
     }
 }
 
 /// Implies actor for Dro server
 impl Actor for DroServer {
     type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        if self.snapshot_path.is_some() {
+            ctx.run_interval(SNAPSHOT_INTERVAL, |act, _| act.save_snapshots());
+        }
+    }
 }
 
 /// Implies handler for connect message
@@ -60,11 +641,20 @@ impl Handler<Connect> for DroServer {
     fn handle(&mut self, msg: Connect, _ctx: &mut Self::Context) -> Self::Result {
         let id = msg.user_id.to_owned();
         // Just add new user to sessions
-        self.sessions.insert(id.clone(), msg.addr);
-        self.boards
-            .entry("Main".to_owned())
-            .or_insert_with(HashSet::new)
-            .insert(id.clone());
+        if self.sessions.insert(id.clone(), msg.addr).is_none() {
+            metrics::ACTIVE_SESSIONS.inc();
+        }
+        self.users.insert(
+            id.clone(),
+            SessionUser {
+                name: msg.name.clone(),
+                subject: msg.subject,
+                admin: msg.admin,
+            },
+        );
+        if self.is_allowed(DEFAULT_BOARD, &id) {
+            self.enter_board(DEFAULT_BOARD, &id);
+        }
         id
     }
 }
@@ -73,7 +663,19 @@ impl Handler<Disconnect> for DroServer {
     type Result = ();
 
     fn handle(&mut self, msg: Disconnect, _ctx: &mut Self::Context) -> Self::Result {
-        self.sessions.remove(&msg.user_id);
+        if self.sessions.remove(&msg.user_id).is_some() {
+            metrics::ACTIVE_SESSIONS.dec();
+        }
+        if let Some(user) = self.users.remove(&msg.user_id) {
+            tracing::info!("User {} disconnected", user.name);
+        }
+        self.prune_recent_ids();
+        if let Some(board) = self.session_board(&msg.user_id) {
+            self.clear_selection(&board, &msg.user_id);
+        }
+        for clients in self.boards.values_mut() {
+            clients.remove(&msg.user_id);
+        }
     }
 }
 
@@ -82,20 +684,186 @@ impl Handler<ClientMessage> for DroServer {
     type Result = ();
 
     fn handle(&mut self, msg: ClientMessage, _ctx: &mut Self::Context) -> Self::Result {
+        // Log lines of the handler carry sender, board and type of the first message
+        let span = tracing::info_span!(
+            "client_message",
+            board = tracing::field::Empty,
+            user_id = %msg.user_id,
+            message_type = msg.message.first().map_or("", WsMessages::type_name),
+        );
+        let _entered = span.enter();
+
         tracing::debug!("on client message: {:?}", &msg);
-        if !msg.message.is_empty() {
-            let (board, user_id) = match &msg.message[0] {
-                crate::wasm_msg::WsMessages::MousePosition(MousePosition { rq, .. })
-                | crate::wasm_msg::WsMessages::AddArrow(AddArrow { rq, .. })
-                | crate::wasm_msg::WsMessages::AddFigure(AddFigure { rq, .. }) => {
-                    (rq.board.to_owned(), rq.user.to_owned())
+        metrics::MESSAGES_RECEIVED.inc_by(msg.message.len() as u64);
+
+        // Board management messages are answered to the sender only, others are broadcast
+        let mut messages = Vec::with_capacity(msg.message.len());
+        for message in msg.message {
+            // Frames replayed by unreliable network are processed once
+            if self.is_duplicate(&msg.user_id, &message) {
+                tracing::debug!("Duplicate {} message dropped", message.type_name());
+                continue;
+            }
+
+            match message {
+                WsMessages::CreateBoard { board_name, .. } => {
+                    self.create_board(board_name, &msg.user_id)
+                }
+                WsMessages::ListBoards { .. } => self.list_boards(&msg.user_id),
+                WsMessages::GetBoardMembers { rq } => self.board_members(rq.board, &msg.user_id),
+                WsMessages::JoinBoard { board_name, .. } => {
+                    self.join_board(&board_name, &msg.user_id)
+                }
+                WsMessages::BoardAcl {
+                    board,
+                    allowed_users,
+                    ..
+                } => self.set_board_acl(board, allowed_users, &msg.user_id),
+                // Protocol messages are processed by session and server responses are never
+                // sent by clients, so only messages with request information are broadcast
+                message if message.request_info().is_some() => messages.push(message),
+                message => {
+                    tracing::debug!("Unexpected {} message dropped", message.type_name())
+                }
+            }
+        }
+
+        if !messages.is_empty() {
+            // Board and sender are taken from the session, so clients can't post to boards
+            // they haven't joined or on behalf of other users
+            let board = match self.session_board(&msg.user_id) {
+                Some(board) => board,
+                None => {
+                    tracing::debug!("User {} has not joined any board", &msg.user_id);
+                    return;
                 }
             };
+            for message in &mut messages {
+                if let Some(rq) = message.request_info_mut() {
+                    rq.board = board.clone();
+                    rq.user = msg.user_id.clone();
+                }
+            }
+
+            span.record("board", board.as_str());
+
+            // Messages of users without access to the board are dropped
+            if !self.is_allowed(&board, &msg.user_id) {
+                tracing::debug!("User {} has no access to board {}", &msg.user_id, &board);
+                return;
+            }
 
-            match serde_json::to_string(&msg.message) {
-                Ok(message) => self.broadcast(&board, &message, &user_id),
+            // Selection of the user is cleared for other users when user leaves the board
+            for message in &messages {
+                if let WsMessages::SelectionChange { selected_ids, .. } = message {
+                    if selected_ids.is_empty() {
+                        self.selecting.remove(&msg.user_id);
+                    } else {
+                        self.selecting.insert(msg.user_id.clone());
+                    }
+                }
+            }
+
+            match Message::encode(&messages) {
+                Ok(message) => self.broadcast(&board, &message, &msg.user_id),
                 Err(err) => tracing::error!("Error serialize: {}", err),
             }
+
+            if self.webhooks.contains_key(&board) {
+                for message in &messages {
+                    let event = match webhooks::event_name(message) {
+                        Some(event) => event,
+                        None => continue,
+                    };
+
+                    match serde_json::to_value(message) {
+                        Ok(payload) => self.notify_webhooks(&board, event, payload),
+                        Err(err) => tracing::error!("Error serialize: {}", err),
+                    }
+                }
+            }
+
+            // Board state is stored for known boards only
+            if !self.boards.contains_key(&board) {
+                tracing::warn!("Messages of unknown board {} are not stored", &board);
+                return;
+            }
+
+            for message in &messages {
+                match message {
+                    WsMessages::AddPage {
+                        page_id, page_name, ..
+                    } => {
+                        let pages = self.pages.entry(board.clone()).or_default();
+                        if !pages.iter().any(|page| page.page_id == *page_id) {
+                            pages.push(BoardPage {
+                                page_id: page_id.clone(),
+                                page_name: page_name.clone(),
+                            });
+                        }
+                    }
+                    WsMessages::UpdateMetadata {
+                        title,
+                        author,
+                        description,
+                        ..
+                    } => {
+                        self.metadata.insert(
+                            board.clone(),
+                            BoardMetadata {
+                                title: title.clone(),
+                                author: author.clone(),
+                                description: description.clone(),
+                            },
+                        );
+                    }
+                    _ => {}
+                }
+            }
+
+            let history = self.history.entry(board).or_default();
+            for message in messages.into_iter().filter(WsMessages::changes_diagram) {
+                append_history(history, message);
+            }
+        }
+    }
+}
+
+/// Implies handler for webhook registration
+impl Handler<AddWebhook> for DroServer {
+    type Result = Result<(), WebhookError>;
+
+    fn handle(&mut self, msg: AddWebhook, _ctx: &mut Self::Context) -> Self::Result {
+        if !self.boards.contains_key(&msg.board) {
+            return Err(WebhookError::UnknownBoard(msg.board));
         }
+
+        if !msg.admin && self.owners.get(&msg.board) != Some(&msg.subject) {
+            tracing::warn!(
+                "User {} is not allowed to register webhooks for {}",
+                &msg.subject,
+                &msg.board
+            );
+            return Err(WebhookError::NotOwner(msg.board));
+        }
+
+        tracing::info!(
+            "Register webhook {} for board {}",
+            &msg.webhook.url,
+            &msg.board
+        );
+        let hooks = self.webhooks.entry(msg.board).or_default();
+        match hooks.iter_mut().find(|hook| hook.url == msg.webhook.url) {
+            Some(hook) => hook.events = msg.webhook.events,
+            None => hooks.push(msg.webhook),
+        }
+        Ok(())
     }
 }
+
+/// Implies handler for readiness check, reply means that actor processes its mailbox
+impl Handler<Ping> for DroServer {
+    type Result = ();
+
+    fn handle(&mut self, _: Ping, _ctx: &mut Self::Context) -> Self::Result {}
+}
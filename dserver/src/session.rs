@@ -1,26 +1,21 @@
-use std::time::{Duration, Instant};
+use std::time::Instant;
 
 use actix::prelude::*;
 use actix_web_actors::ws;
 
 use crate::{
+    config::ServerConfig,
     messages::{self, ClientMessage, Connect, Disconnect},
     server,
-    wasm_msg::WsMessages,
+    wasm_msg::{self, MessageEncoding, WsMessages, PROTOCOL_VERSION},
 };
 
-/// How often heartbeat pings are sent
-const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
-
-/// How long before lack of client response causes a timeout
-const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
-
 #[derive(Debug)]
 pub struct WsChatSession {
     /// unique session id
     pub id: String,
 
-    /// Client must send ping at least once per 10 seconds (CLIENT_TIMEOUT),
+    /// Client must send ping at least once per `config.client_timeout`,
     /// otherwise we drop connection.
     pub hb: Instant,
 
@@ -30,25 +25,52 @@ pub struct WsChatSession {
     /// peer name
     pub name: Option<String>,
 
+    /// Authenticated user identifier
+    pub subject: String,
+
+    /// User can manage boards access
+    pub admin: bool,
+
     /// Chat server
     pub addr: Addr<server::DroServer>,
+
+    /// Heartbeat and timeout settings
+    pub config: ServerConfig,
+
+    /// Encoding of messages sent to the client, negotiated by `WsMessages::Hello`
+    pub encoding: MessageEncoding,
+
+    /// Client sent handshake with compatible protocol version. Other messages are dropped
+    /// until it is received
+    pub handshake: bool,
+
+    /// Tokens of the rate limiter bucket, each incoming message consumes one token
+    pub tokens: f32,
+
+    /// Last time the bucket was refilled
+    pub last_refill: Instant,
+
+    /// Number of messages dropped by the rate limiter
+    pub dropped_messages: u64,
 }
 
 impl WsChatSession {
-    /// helper method that sends ping to client every second.
+    /// helper method that sends ping to client every `config.heartbeat_interval`.
     ///
     /// also this method checks heartbeats from client
     fn hb(&self, ctx: &mut ws::WebsocketContext<Self>) {
-        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
+        ctx.run_interval(self.config.heartbeat_interval, |act, ctx| {
             // check client heartbeats
-            if Instant::now().duration_since(act.hb) > CLIENT_TIMEOUT {
+            if Instant::now().duration_since(act.hb) > act.config.client_timeout {
                 // heartbeat timed out
                 tracing::info!("Websocket Client heartbeat failed, disconnecting!");
 
                 // notify server
-                act.addr.do_send(Disconnect {
-                    user_id: act.id.clone(),
-                });
+                if act.handshake {
+                    act.addr.do_send(Disconnect {
+                        user_id: act.id.clone(),
+                    });
+                }
 
                 // stop actor
                 ctx.stop();
@@ -60,28 +82,61 @@ impl WsChatSession {
             ctx.ping(b"");
         });
     }
-}
 
-impl Actor for WsChatSession {
-    type Context = ws::WebsocketContext<Self>;
+    /// Check protocol version received in handshake. Connection with incompatible version
+    /// is closed after sending `WsMessages::VersionMismatch` to the client
+    /// ### Return
+    /// true if client protocol version is compatible
+    fn check_version(&self, client_version: u32, ctx: &mut ws::WebsocketContext<Self>) -> bool {
+        if client_version == PROTOCOL_VERSION {
+            return true;
+        }
 
-    /// Method is called on actor start.
-    /// We register ws session with ChatServer
-    fn started(&mut self, ctx: &mut Self::Context) {
-        tracing::debug!("WS session started: {}", &self.id);
+        tracing::warn!(
+            "Client {} uses protocol version {}, server version is {}",
+            &self.id,
+            client_version,
+            PROTOCOL_VERSION
+        );
 
-        // we'll start heartbeat process on session start.
-        self.hb(ctx);
+        match wasm_msg::to_json(&[WsMessages::VersionMismatch {
+            server_version: PROTOCOL_VERSION,
+            client_version,
+        }]) {
+            Ok(message) => ctx.text(message),
+            Err(err) => tracing::error!("Error serialize: {}", err),
+        }
+
+        ctx.close(Some(ws::CloseReason {
+            code: ws::CloseCode::Protocol,
+            description: Some("Incompatible protocol version".to_owned()),
+        }));
+        ctx.stop();
+        false
+    }
+
+    /// Confirm compatible protocol version to the client and register session in chat server.
+    /// Session receives board messages only after handshake, so client sends `WsMessages::Hello`
+    /// and board requests after acknowledgement
+    fn accept_handshake(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        match wasm_msg::to_json(&[WsMessages::HandshakeAck {
+            version: PROTOCOL_VERSION,
+        }]) {
+            Ok(message) => ctx.text(message),
+            Err(err) => tracing::error!("Error serialize: {}", err),
+        }
+        self.handshake = true;
 
         // register self in chat server. `AsyncContext::wait` register
         // future within context, but context waits until this future resolves
         // before processing any other events.
-        // HttpContext::state() is instance of WsChatSessionState, state is shared
-        // across all routes within application
         let addr = ctx.address();
         self.addr
             .send(Connect {
                 user_id: self.id.clone(),
+                name: self.name.clone().unwrap_or_else(|| self.id.clone()),
+                subject: self.subject.clone(),
+                admin: self.admin,
                 addr: addr.recipient(),
             })
             .into_actor(self)
@@ -96,12 +151,154 @@ impl Actor for WsChatSession {
             .wait(ctx);
     }
 
+    /// Switch to the client preferred encoding if it is supported, otherwise json is used.
+    /// Acknowledgement is always sent as json because client switches encoding after it
+    /// ### Arguments
+    /// * preferred_encoding - encoding name from `WsMessages::Hello`
+    fn negotiate_encoding(
+        &mut self,
+        preferred_encoding: &str,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) {
+        let encoding = MessageEncoding::from_name(preferred_encoding).unwrap_or_default();
+        match wasm_msg::to_json(&[WsMessages::HelloAck {
+            encoding: encoding.name().to_owned(),
+        }]) {
+            Ok(message) => {
+                ctx.text(message);
+                self.encoding = encoding;
+            }
+            Err(err) => tracing::error!("Error serialize: {}", err),
+        }
+    }
+
+    /// Refill rate limiter bucket by the time elapsed since the last refill and consume one token
+    /// ### Return
+    /// false if there are no tokens and message must be dropped
+    fn take_token(&mut self) -> bool {
+        let now = Instant::now();
+        let rate = self.config.rate_limit;
+        self.tokens =
+            (self.tokens + now.duration_since(self.last_refill).as_secs_f32() * rate).min(rate);
+        self.last_refill = now;
+
+        if self.tokens < 1. {
+            self.dropped_messages += 1;
+            false
+        } else {
+            self.tokens -= 1.;
+            true
+        }
+    }
+
+    /// Process messages received from the client. Protocol messages are processed by session,
+    /// others are sent to server
+    fn process_messages(
+        &mut self,
+        messages: Vec<WsMessages>,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) {
+        let received = messages.len();
+        let messages: Vec<_> = messages.into_iter().filter(|_| self.take_token()).collect();
+        if messages.len() < received {
+            tracing::warn!(
+                "Session {} is throttled, dropped {} of {} messages, {} dropped in total",
+                &self.id,
+                received - messages.len(),
+                received,
+                self.dropped_messages
+            );
+        }
+
+        let (protocol, messages): (Vec<_>, Vec<_>) = messages.into_iter().partition(|msg| {
+            matches!(
+                msg,
+                WsMessages::Handshake { .. }
+                    | WsMessages::HandshakeAck { .. }
+                    | WsMessages::VersionMismatch { .. }
+                    | WsMessages::Hello { .. }
+                    | WsMessages::HelloAck { .. }
+            )
+        });
+
+        for msg in protocol {
+            match msg {
+                WsMessages::Handshake { version } => {
+                    if !self.check_version(version, ctx) {
+                        return;
+                    }
+                    if !self.handshake {
+                        self.accept_handshake(ctx);
+                    }
+                }
+                WsMessages::Hello { preferred_encoding } if self.handshake => {
+                    self.negotiate_encoding(&preferred_encoding, ctx)
+                }
+                _ => (),
+            }
+        }
+
+        // Messages of the client which version is unknown could be misread
+        if !self.handshake {
+            if !messages.is_empty() {
+                tracing::warn!(
+                    "Session {} sent {} messages before handshake, messages are dropped",
+                    &self.id,
+                    messages.len()
+                );
+            }
+            return;
+        }
+
+        // Session board follows the last join message, server moves session to the board
+        if let Some(board) = messages.iter().rev().find_map(|msg| match msg {
+            WsMessages::JoinBoard { board_name, .. } => Some(board_name),
+            _ => None,
+        }) {
+            self.board = board.clone();
+        }
+
+        if messages.is_empty() {
+            return;
+        }
+
+        self.addr
+            .send(ClientMessage {
+                user_id: self.id.clone(),
+                message: messages,
+            })
+            .into_actor(self)
+            .then(|res, _, _ctx| {
+                match res {
+                    Ok(_) => tracing::debug!("Ok"),
+                    Err(err) => tracing::error!("Something is wrong {}", err),
+                }
+                fut::ready(())
+            })
+            .wait(ctx);
+    }
+}
+
+impl Actor for WsChatSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    /// Method is called on actor start.
+    /// Session is registered with ChatServer after handshake
+    fn started(&mut self, ctx: &mut Self::Context) {
+        tracing::debug!("WS session started: {}", &self.id);
+
+        // we'll start heartbeat process on session start.
+        self.hb(ctx);
+    }
+
     fn stopping(&mut self, _: &mut Self::Context) -> Running {
         tracing::debug!("WS session stopping");
-        // notify chat server
-        self.addr.do_send(Disconnect {
-            user_id: self.id.clone(),
-        });
+        // notify chat server, session is registered after handshake only
+        if self.handshake {
+            self.addr.do_send(Disconnect {
+                user_id: self.id.clone(),
+            });
+        }
         Running::Stop
     }
 }
@@ -111,7 +308,11 @@ impl Handler<messages::Message> for WsChatSession {
     type Result = ();
 
     fn handle(&mut self, msg: messages::Message, ctx: &mut Self::Context) {
-        ctx.text(msg.0);
+        // Server encodes messages once for all sessions
+        match self.encoding {
+            MessageEncoding::Json => ctx.text(msg.json),
+            MessageEncoding::Bincode => ctx.binary(msg.bincode),
+        }
     }
 }
 
@@ -138,29 +339,15 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsChatSession {
             ws::Message::Text(text) => {
                 let m = text.trim();
                 tracing::debug!("Receive message: {}", m);
-                let client_msg = match serde_json::from_str::<Vec<WsMessages>>(m) {
-                    Ok(msg) => Some(ClientMessage { message: msg }),
-                    Err(err) => {
-                        tracing::error!("Error parseing message: {:?}", err);
-                        None
-                    }
-                };
-
-                if let Some(msg) = client_msg {
-                    self.addr
-                        .send(msg)
-                        .into_actor(self)
-                        .then(|res, _, _ctx| {
-                            match res {
-                                Ok(_) => tracing::debug!("Ok"),
-                                Err(err) => tracing::error!("Something is wrong {}", err),
-                            }
-                            fut::ready(())
-                        })
-                        .wait(ctx);
+                match wasm_msg::from_json(m) {
+                    Ok(messages) => self.process_messages(messages, ctx),
+                    Err(err) => tracing::error!("Error parseing message: {:?}", err),
                 }
             }
-            ws::Message::Binary(_) => println!("Unexpected binary"),
+            ws::Message::Binary(data) => match wasm_msg::from_bincode(&data) {
+                Ok(messages) => self.process_messages(messages, ctx),
+                Err(err) => tracing::error!("Error decoding binary message: {:?}", err),
+            },
             ws::Message::Close(reason) => {
                 ctx.close(reason);
                 ctx.stop();
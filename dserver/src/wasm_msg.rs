@@ -1,4 +1,80 @@
-use serde::{Deserialize, Serialize};
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
+use thiserror::Error;
+
+/// Protocol version shared by workspace crates
+mod workspace {
+    include!("../../protocol.rs");
+}
+
+/// Version of the websocket protocol. Client sends it in `WsMessages::Handshake` on connect
+pub const PROTOCOL_VERSION: u32 = 2;
+
+// Server and client copies of the protocol version must be changed together
+const _: () = assert!(
+    PROTOCOL_VERSION == workspace::PROTOCOL_VERSION,
+    "PROTOCOL_VERSION differs from the workspace protocol version"
+);
+
+// Schema version
+// --------------
+// Every message is sent wrapped into `Envelope` which carries schema version of its payload:
+//
+//     [{"schema_version": 1, "payload": {"type": "ListBoards", "rq": {...}}}]
+//
+// Bincode frames keep the same order: version followed by the payload. Unlike
+// `PROTOCOL_VERSION`, which is checked once by handshake, schema version is checked for each
+// message, so payloads may evolve while old clients are still connected. Version is
+// increased when payload of any message is changed incompatibly, receiver drops messages of
+// versions it doesn't understand instead of misreading the payload, other messages of the
+// frame are still processed. Bincode payloads are length prefixed to be skipped this way.
+//
+// Clients preceding envelopes send bare `Handshake`, it is still decoded so server can
+// answer with `VersionMismatch`.
+
+/// Schema version of the message payloads sent by this build
+pub const SCHEMA_VERSION: u8 = 1;
+
+/// Message with schema version of its payload
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Envelope {
+    pub schema_version: u8,
+    pub payload: WsMessages,
+}
+
+impl From<WsMessages> for Envelope {
+    fn from(payload: WsMessages) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            payload,
+        }
+    }
+}
+
+/// Borrowed envelope used to serialize messages without copying
+#[derive(Serialize)]
+struct EnvelopeRef<'a> {
+    schema_version: u8,
+    payload: &'a WsMessages,
+}
+
+/// Schema version of the received message is not understood by this build
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error(
+    "Unsupported schema version {0}, supported version is {supported}",
+    supported = SCHEMA_VERSION
+)]
+pub struct UnsupportedVersion(pub u8);
+
+/// Take message out of the envelope
+/// ### Return
+/// UnsupportedVersion error if payload schema version differs from `SCHEMA_VERSION`
+pub fn try_from_envelope(env: Envelope) -> Result<WsMessages, UnsupportedVersion> {
+    if env.schema_version == SCHEMA_VERSION {
+        Ok(env.payload)
+    } else {
+        Err(UnsupportedVersion(env.schema_version))
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "type")]
@@ -6,12 +82,248 @@ pub enum WsMessages {
     MousePosition(MousePosition),
     AddFigure(AddFigure),
     AddArrow(AddArrow),
+    /// First message sent by client after connect
+    Handshake {
+        version: u32,
+    },
+    /// Server response to handshake with compatible protocol version
+    HandshakeAck {
+        version: u32,
+    },
+    /// Server response to handshake with incompatible protocol version
+    VersionMismatch {
+        server_version: u32,
+        client_version: u32,
+    },
+    /// Client preferred encoding of the websocket messages, sent after handshake
+    Hello {
+        preferred_encoding: String,
+    },
+    /// Server response to `Hello` with the encoding used for the following messages
+    HelloAck {
+        encoding: String,
+    },
+    /// Create new board
+    CreateBoard {
+        board_name: String,
+        rq: RequestInfo,
+    },
+    /// Server confirmation of the board creation, sent to the creator only
+    BoardCreated {
+        board_name: String,
+    },
+    /// Request names of all boards
+    ListBoards {
+        rq: RequestInfo,
+    },
+    /// Server response to `ListBoards`, sent to the requesting client only
+    BoardList {
+        boards: Vec<String>,
+    },
+    /// Request users connected to the board of the request
+    GetBoardMembers {
+        rq: RequestInfo,
+    },
+    /// Server response to `GetBoardMembers` with display names of the connected users,
+    /// sent to the requesting client only
+    BoardMembers {
+        board: String,
+        members: Vec<String>,
+    },
+    /// Join board. Client receives messages of the joined board only
+    JoinBoard {
+        board_name: String,
+        rq: RequestInfo,
+    },
+    /// Replace list of users allowed to access the board. Accepted from administrators only
+    BoardAcl {
+        rq: RequestInfo,
+        board: String,
+        allowed_users: Vec<String>,
+    },
+    /// Figure was deleted
+    DeleteFigure {
+        rq: RequestInfo,
+        figure_id: String,
+    },
+    /// Figure was moved by the offset
+    MoveFigure {
+        rq: RequestInfo,
+        figure_id: String,
+        dx: f32,
+        dy: f32,
+    },
+    /// Label of the figure was edited
+    UpdateFigureText {
+        rq: RequestInfo,
+        figure_id: String,
+        text: String,
+    },
+    /// Page was appended to the board
+    AddPage {
+        rq: RequestInfo,
+        /// Identifier of the page shared by all clients, messages refer page by it
+        page_id: String,
+        page_name: String,
+    },
+    /// Figures selected by the user were changed, shown to other users as ghost selection
+    SelectionChange {
+        rq: RequestInfo,
+        selected_ids: Vec<String>,
+    },
+    /// Title, author or description of the diagram was edited
+    UpdateMetadata {
+        rq: RequestInfo,
+        title: String,
+        author: String,
+        description: String,
+    },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl WsMessages {
+    /// Name of the message type, the same as `type` tag of json encoding
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            WsMessages::MousePosition(_) => "MousePosition",
+            WsMessages::AddFigure(_) => "AddFigure",
+            WsMessages::AddArrow(_) => "AddArrow",
+            WsMessages::Handshake { .. } => "Handshake",
+            WsMessages::HandshakeAck { .. } => "HandshakeAck",
+            WsMessages::VersionMismatch { .. } => "VersionMismatch",
+            WsMessages::Hello { .. } => "Hello",
+            WsMessages::HelloAck { .. } => "HelloAck",
+            WsMessages::CreateBoard { .. } => "CreateBoard",
+            WsMessages::BoardCreated { .. } => "BoardCreated",
+            WsMessages::ListBoards { .. } => "ListBoards",
+            WsMessages::BoardList { .. } => "BoardList",
+            WsMessages::GetBoardMembers { .. } => "GetBoardMembers",
+            WsMessages::BoardMembers { .. } => "BoardMembers",
+            WsMessages::JoinBoard { .. } => "JoinBoard",
+            WsMessages::BoardAcl { .. } => "BoardAcl",
+            WsMessages::DeleteFigure { .. } => "DeleteFigure",
+            WsMessages::MoveFigure { .. } => "MoveFigure",
+            WsMessages::UpdateFigureText { .. } => "UpdateFigureText",
+            WsMessages::AddPage { .. } => "AddPage",
+            WsMessages::SelectionChange { .. } => "SelectionChange",
+            WsMessages::UpdateMetadata { .. } => "UpdateMetadata",
+        }
+    }
+
+    /// Return request information of the messages sent by clients
+    pub fn request_info(&self) -> Option<&RequestInfo> {
+        match self {
+            WsMessages::MousePosition(MousePosition { rq, .. })
+            | WsMessages::AddFigure(AddFigure { rq, .. })
+            | WsMessages::AddArrow(AddArrow { rq, .. })
+            | WsMessages::CreateBoard { rq, .. }
+            | WsMessages::ListBoards { rq }
+            | WsMessages::GetBoardMembers { rq }
+            | WsMessages::JoinBoard { rq, .. }
+            | WsMessages::BoardAcl { rq, .. }
+            | WsMessages::DeleteFigure { rq, .. }
+            | WsMessages::MoveFigure { rq, .. }
+            | WsMessages::UpdateFigureText { rq, .. }
+            | WsMessages::AddPage { rq, .. }
+            | WsMessages::SelectionChange { rq, .. }
+            | WsMessages::UpdateMetadata { rq, .. } => Some(rq),
+            WsMessages::Handshake { .. }
+            | WsMessages::HandshakeAck { .. }
+            | WsMessages::VersionMismatch { .. }
+            | WsMessages::Hello { .. }
+            | WsMessages::HelloAck { .. }
+            | WsMessages::BoardCreated { .. }
+            | WsMessages::BoardList { .. }
+            | WsMessages::BoardMembers { .. } => None,
+        }
+    }
+
+    /// Return mutable request information of the messages sent by clients
+    pub fn request_info_mut(&mut self) -> Option<&mut RequestInfo> {
+        match self {
+            WsMessages::MousePosition(MousePosition { rq, .. })
+            | WsMessages::AddFigure(AddFigure { rq, .. })
+            | WsMessages::AddArrow(AddArrow { rq, .. })
+            | WsMessages::CreateBoard { rq, .. }
+            | WsMessages::ListBoards { rq }
+            | WsMessages::GetBoardMembers { rq }
+            | WsMessages::JoinBoard { rq, .. }
+            | WsMessages::BoardAcl { rq, .. }
+            | WsMessages::DeleteFigure { rq, .. }
+            | WsMessages::MoveFigure { rq, .. }
+            | WsMessages::UpdateFigureText { rq, .. }
+            | WsMessages::AddPage { rq, .. }
+            | WsMessages::SelectionChange { rq, .. }
+            | WsMessages::UpdateMetadata { rq, .. } => Some(rq),
+            WsMessages::Handshake { .. }
+            | WsMessages::HandshakeAck { .. }
+            | WsMessages::VersionMismatch { .. }
+            | WsMessages::Hello { .. }
+            | WsMessages::HelloAck { .. }
+            | WsMessages::BoardCreated { .. }
+            | WsMessages::BoardList { .. }
+            | WsMessages::BoardMembers { .. } => None,
+        }
+    }
+
+    /// Return identifier of the figure changed by the message
+    pub fn figure_id(&self) -> Option<&str> {
+        match self {
+            WsMessages::AddFigure(AddFigure { figure_id, .. })
+            | WsMessages::DeleteFigure { figure_id, .. }
+            | WsMessages::MoveFigure { figure_id, .. }
+            | WsMessages::UpdateFigureText { figure_id, .. } => Some(figure_id),
+            _ => None,
+        }
+    }
+
+    /// Return true if message changes diagram, such messages are kept in board snapshot
+    pub fn changes_diagram(&self) -> bool {
+        matches!(
+            self,
+            WsMessages::AddFigure(_)
+                | WsMessages::AddArrow(_)
+                | WsMessages::DeleteFigure { .. }
+                | WsMessages::MoveFigure { .. }
+                | WsMessages::UpdateFigureText { .. }
+        )
+    }
+}
+
+#[derive(Deserialize, Debug)]
 pub struct RequestInfo {
     pub board: String,
     pub user: String,
+    /// Identifier assigned by client to each sent message, used to drop duplicated frames
+    #[serde(default)]
+    pub message_id: Option<String>,
+    /// Identifier of the board page changed by the message, board level messages have no page
+    #[serde(default)]
+    pub page_id: Option<String>,
+}
+
+/// Json omits message and page identifiers if they are not set, so messages of the clients
+/// which don't assign identifiers are not changed. Bincode can't skip fields and always keeps them
+impl Serialize for RequestInfo {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let human_readable = serializer.is_human_readable();
+        let skip_id = human_readable && self.message_id.is_none();
+        let skip_page = human_readable && self.page_id.is_none();
+        let len = 2 + usize::from(!skip_id) + usize::from(!skip_page);
+        let mut state = serializer.serialize_struct("RequestInfo", len)?;
+        state.serialize_field("board", &self.board)?;
+        state.serialize_field("user", &self.user)?;
+        if skip_id {
+            state.skip_field("message_id")?;
+        } else {
+            state.serialize_field("message_id", &self.message_id)?;
+        }
+        if skip_page {
+            state.skip_field("page_id")?;
+        } else {
+            state.serialize_field("page_id", &self.page_id)?;
+        }
+        state.end()
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -26,6 +338,16 @@ pub struct Rect {
     max: Pos2,
 }
 
+impl Rect {
+    /// Move rectangle by the given offset
+    pub fn translate(&mut self, dx: f32, dy: f32) {
+        self.min.x += dx;
+        self.min.y += dy;
+        self.max.x += dx;
+        self.max.y += dy;
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename = "mp")]
 pub struct MousePosition {
@@ -38,13 +360,268 @@ pub struct MousePosition {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AddFigure {
     pub rq: RequestInfo,
+    /// Identifier of the figure shared by all clients, other messages refer figure by it
+    pub figure_id: String,
+    pub shape: FigureShape,
     pub rect: Rect,
+    /// Label of the figure
     pub text: String,
 }
 
+/// Shape of the figure added by `AddFigure`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FigureShape {
+    #[default]
+    Rect,
+    Ellipse,
+    Diamond,
+    Cylinder,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AddArrow {
     pub rq: RequestInfo,
     pub start_id: String,
     pub end_id: String,
 }
+
+/// Encoding of the websocket messages negotiated by `WsMessages::Hello`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MessageEncoding {
+    /// Text frames with json array of messages
+    #[default]
+    Json,
+    /// Binary frames with bincode encoded messages
+    Bincode,
+}
+
+impl MessageEncoding {
+    /// Find encoding by name used in `WsMessages::Hello`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(MessageEncoding::Json),
+            "bincode" => Some(MessageEncoding::Bincode),
+            _ => None,
+        }
+    }
+
+    /// Name of encoding used in `WsMessages::Hello`
+    pub fn name(&self) -> &'static str {
+        match self {
+            MessageEncoding::Json => "json",
+            MessageEncoding::Bincode => "bincode",
+        }
+    }
+}
+
+/// Error of decoding messages of the websocket frame
+#[derive(Error, Debug)]
+pub enum DecodeError {
+    #[error("Json decoding failed: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Bincode decoding failed: {0}")]
+    Bincode(#[from] bincode::Error),
+}
+
+/// Error of encoding messages sent to the clients
+#[derive(Error, Debug)]
+pub enum EncodeError {
+    #[error("Json encoding failed: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Bincode encoding failed: {0}")]
+    Bincode(#[from] bincode::Error),
+}
+
+/// Encode messages as json array of envelopes
+pub fn to_json(messages: &[WsMessages]) -> serde_json::Result<String> {
+    serde_json::to_string(
+        &messages
+            .iter()
+            .map(|payload| EnvelopeRef {
+                schema_version: SCHEMA_VERSION,
+                payload,
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Message of the received json frame, payload is decoded after schema version is checked
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonEnvelope {
+    Envelope {
+        schema_version: u8,
+        payload: serde_json::Value,
+    },
+    Bare(serde_json::Value),
+}
+
+/// Check schema version of the received message, message of unknown version is dropped
+/// ### Return
+/// true if payload of the message could be decoded
+fn is_supported(schema_version: u8) -> bool {
+    if schema_version == SCHEMA_VERSION {
+        true
+    } else {
+        tracing::warn!("{}, message is dropped", UnsupportedVersion(schema_version));
+        false
+    }
+}
+
+/// Decode json array of envelopes. Messages of unknown schema versions are dropped
+pub fn from_json(text: &str) -> Result<Vec<WsMessages>, DecodeError> {
+    let mut messages = vec![];
+    for env in serde_json::from_str::<Vec<JsonEnvelope>>(text)? {
+        match env {
+            JsonEnvelope::Envelope {
+                schema_version,
+                payload,
+            } => {
+                if is_supported(schema_version) {
+                    messages.push(serde_json::from_value(payload)?);
+                }
+            }
+            // Old clients send bare handshake, it is decoded to report version mismatch
+            JsonEnvelope::Bare(payload) => match serde_json::from_value(payload) {
+                Ok(msg @ WsMessages::Handshake { .. }) => messages.push(msg),
+                _ => tracing::warn!("Message without envelope is dropped"),
+            },
+        }
+    }
+    Ok(messages)
+}
+
+/// Encode messages with bincode, each payload is encoded separately to be skipped by receiver
+/// which doesn't understand its schema version
+pub fn to_bincode(messages: &[WsMessages]) -> bincode::Result<Vec<u8>> {
+    bincode::serialize(
+        &messages
+            .iter()
+            .map(|msg| Ok((SCHEMA_VERSION, bincode::serialize(&binary::Ref(msg))?)))
+            .collect::<bincode::Result<Vec<_>>>()?,
+    )
+}
+
+/// Decode messages encoded with bincode. Messages of unknown schema versions are dropped
+pub fn from_bincode(data: &[u8]) -> Result<Vec<WsMessages>, DecodeError> {
+    bincode::deserialize::<Vec<(u8, Vec<u8>)>>(data)?
+        .into_iter()
+        .filter(|(schema_version, _)| is_supported(*schema_version))
+        .map(|(_, payload)| {
+            bincode::deserialize::<binary::Owned>(&payload)
+                .map(|msg| msg.0)
+                .map_err(DecodeError::from)
+        })
+        .collect()
+}
+
+/// Bincode cannot decode internally tagged enums and flattened structs used for json,
+/// so binary encoding uses externally tagged copy of the messages
+mod binary {
+    use serde::{Deserialize, Serialize, Serializer};
+
+    use super::{AddArrow, AddFigure, MousePosition, Pos2, RequestInfo, WsMessages};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(remote = "MousePosition")]
+    struct MousePositionDef {
+        rq: RequestInfo,
+        position: Pos2,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(remote = "WsMessages")]
+    enum WsMessagesDef {
+        MousePosition(#[serde(with = "MousePositionDef")] MousePosition),
+        AddFigure(AddFigure),
+        AddArrow(AddArrow),
+        Handshake {
+            version: u32,
+        },
+        HandshakeAck {
+            version: u32,
+        },
+        VersionMismatch {
+            server_version: u32,
+            client_version: u32,
+        },
+        Hello {
+            preferred_encoding: String,
+        },
+        HelloAck {
+            encoding: String,
+        },
+        CreateBoard {
+            board_name: String,
+            rq: RequestInfo,
+        },
+        BoardCreated {
+            board_name: String,
+        },
+        ListBoards {
+            rq: RequestInfo,
+        },
+        BoardList {
+            boards: Vec<String>,
+        },
+        GetBoardMembers {
+            rq: RequestInfo,
+        },
+        BoardMembers {
+            board: String,
+            members: Vec<String>,
+        },
+        JoinBoard {
+            board_name: String,
+            rq: RequestInfo,
+        },
+        BoardAcl {
+            rq: RequestInfo,
+            board: String,
+            allowed_users: Vec<String>,
+        },
+        DeleteFigure {
+            rq: RequestInfo,
+            figure_id: String,
+        },
+        MoveFigure {
+            rq: RequestInfo,
+            figure_id: String,
+            dx: f32,
+            dy: f32,
+        },
+        UpdateFigureText {
+            rq: RequestInfo,
+            figure_id: String,
+            text: String,
+        },
+        AddPage {
+            rq: RequestInfo,
+            page_id: String,
+            page_name: String,
+        },
+        SelectionChange {
+            rq: RequestInfo,
+            selected_ids: Vec<String>,
+        },
+        UpdateMetadata {
+            rq: RequestInfo,
+            title: String,
+            author: String,
+            description: String,
+        },
+    }
+
+    /// Serializes borrowed message
+    pub(super) struct Ref<'a>(pub &'a WsMessages);
+
+    impl Serialize for Ref<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            WsMessagesDef::serialize(self.0, serializer)
+        }
+    }
+
+    /// Deserializes owned message
+    #[derive(Deserialize)]
+    pub(super) struct Owned(#[serde(with = "WsMessagesDef")] pub WsMessages);
+}
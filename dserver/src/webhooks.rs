@@ -0,0 +1,290 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use reqwest::{redirect::Policy, Response, Url};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::wasm_msg::WsMessages;
+
+/// Maximum number of delivery attempts for one webhook event
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Initial delay between delivery attempts. Doubled after each failed attempt
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+pub const EVENT_HEADER: &str = "X-Diadro-Event";
+pub const BOARD_HEADER: &str = "X-Diadro-Board";
+
+pub const EVENT_FIGURE_ADDED: &str = "figure_added";
+pub const EVENT_ARROW_ADDED: &str = "arrow_added";
+pub const EVENT_FIGURE_DELETED: &str = "figure_deleted";
+pub const EVENT_FIGURE_MOVED: &str = "figure_moved";
+pub const EVENT_FIGURE_TEXT_UPDATED: &str = "figure_text_updated";
+pub const EVENT_USER_JOINED: &str = "user_joined";
+
+/// Reason of the webhook registration rejection
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum WebhookError {
+    #[error("Webhook url must be http(s) url with host")]
+    InvalidUrl,
+    #[error("Webhook url must not point to private, loopback or link-local address")]
+    PrivateTarget,
+    #[error("Board {0} not found")]
+    UnknownBoard(String),
+    #[error("Only owner of the board {0} can register webhooks")]
+    NotOwner(String),
+}
+
+/// Webhook registered for the board
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Webhook {
+    /// Url to which events will be posted
+    pub url: String,
+    /// List of events the webhook subscribed to
+    pub events: Vec<String>,
+}
+
+impl Webhook {
+    /// Check if webhook subscribed to the event
+    pub fn accepts(&self, event: &str) -> bool {
+        self.events.iter().any(|e| e == event)
+    }
+}
+
+/// Check if address can be reached from the internet. Webhooks posting to other addresses
+/// would let clients send requests into the server network
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.segments() {
+            // IPv4-mapped address ::ffff:a.b.c.d
+            [0, 0, 0, 0, 0, 0xffff, high, low] => {
+                is_public_ipv4(Ipv4Addr::from((u32::from(high) << 16) | u32::from(low)))
+            }
+            _ => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    // Carrier-grade NAT range 100.64.0.0/10
+    let shared = ip.octets()[0] == 100 && (ip.octets()[1] & 0b1100_0000) == 64;
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || shared)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    // Unique local fc00::/7 and link-local fe80::/10 addresses
+    let unique_local = (first & 0xfe00) == 0xfc00;
+    let link_local = (first & 0xffc0) == 0xfe80;
+    !(ip.is_loopback() || ip.is_unspecified() || ip.is_multicast() || unique_local || link_local)
+}
+
+/// Webhook url along with the checked addresses of its host
+struct Target {
+    url: Url,
+    addrs: Vec<SocketAddr>,
+}
+
+/// Check that webhook url is http(s) url of the public host. Host names are resolved and
+/// rejected if any of their addresses is not public
+/// ### Arguments
+/// * url - webhook url
+pub async fn validate_url(url: &str) -> Result<(), WebhookError> {
+    resolve_target(url).await.map(|_| ())
+}
+
+/// Parse webhook url and resolve its host
+/// ### Return
+/// Target with all host addresses or error if url is invalid or any of the addresses is not public
+async fn resolve_target(url: &str) -> Result<Target, WebhookError> {
+    let url = Url::parse(url).map_err(|_| WebhookError::InvalidUrl)?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(WebhookError::InvalidUrl);
+    }
+
+    let host = url.host_str().ok_or(WebhookError::InvalidUrl)?;
+    let port = url
+        .port_or_known_default()
+        .ok_or(WebhookError::InvalidUrl)?;
+    let literal = host.trim_start_matches('[').trim_end_matches(']');
+    let addrs: Vec<SocketAddr> = match literal.parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|_| WebhookError::InvalidUrl)?
+            .collect(),
+    };
+
+    if !addrs.is_empty() && addrs.iter().all(|addr| is_public_ip(addr.ip())) {
+        Ok(Target { url, addrs })
+    } else {
+        Err(WebhookError::PrivateTarget)
+    }
+}
+
+/// Post event to the target. Client connects only to the checked target addresses, so host name
+/// rebound to a private address after the check is never reached
+async fn post(
+    target: Target,
+    event: &str,
+    board: &str,
+    payload: &serde_json::Value,
+) -> reqwest::Result<Response> {
+    // Redirects could lead webhook requests to private addresses
+    let builder = reqwest::Client::builder().redirect(Policy::none());
+    let builder = match target.url.domain() {
+        Some(domain) => builder.resolve_to_addrs(domain, &target.addrs),
+        None => builder,
+    };
+
+    builder
+        .build()?
+        .post(target.url)
+        .header(EVENT_HEADER, event)
+        .header(BOARD_HEADER, board)
+        .json(payload)
+        .send()
+        .await?
+        .error_for_status()
+}
+
+/// Return webhook event name for the message
+pub fn event_name(message: &WsMessages) -> Option<&'static str> {
+    match message {
+        WsMessages::AddFigure(_) => Some(EVENT_FIGURE_ADDED),
+        WsMessages::AddArrow(_) => Some(EVENT_ARROW_ADDED),
+        WsMessages::DeleteFigure { .. } => Some(EVENT_FIGURE_DELETED),
+        WsMessages::MoveFigure { .. } => Some(EVENT_FIGURE_MOVED),
+        WsMessages::UpdateFigureText { .. } => Some(EVENT_FIGURE_TEXT_UPDATED),
+        WsMessages::MousePosition(_)
+        | WsMessages::Handshake { .. }
+        | WsMessages::HandshakeAck { .. }
+        | WsMessages::VersionMismatch { .. }
+        | WsMessages::Hello { .. }
+        | WsMessages::HelloAck { .. }
+        | WsMessages::CreateBoard { .. }
+        | WsMessages::BoardCreated { .. }
+        | WsMessages::ListBoards { .. }
+        | WsMessages::BoardList { .. }
+        | WsMessages::GetBoardMembers { .. }
+        | WsMessages::BoardMembers { .. }
+        | WsMessages::JoinBoard { .. }
+        | WsMessages::BoardAcl { .. }
+        | WsMessages::AddPage { .. }
+        | WsMessages::SelectionChange { .. }
+        | WsMessages::UpdateMetadata { .. } => None,
+    }
+}
+
+/// Post event to webhook url. Delivery is retried up to MAX_ATTEMPTS times using
+/// exponential backoff. Url host is resolved and checked again before every attempt, event is
+/// dropped if the host does not resolve to public addresses anymore
+/// ### Arguments
+/// * url - webhook url
+/// * event - event name
+/// * board - board name
+/// * payload - event payload
+pub async fn deliver(url: String, event: String, board: String, payload: serde_json::Value) {
+    let mut backoff = INITIAL_BACKOFF;
+    for attempt in 1..=MAX_ATTEMPTS {
+        let result = match resolve_target(&url).await {
+            Ok(target) => post(target, &event, &board, &payload)
+                .await
+                .map_err(|err| err.to_string()),
+            Err(WebhookError::PrivateTarget) => {
+                tracing::error!("Webhook {} resolved to private address", &url);
+                break;
+            }
+            Err(err) => Err(err.to_string()),
+        };
+
+        match result {
+            Ok(_) => {
+                tracing::debug!("Webhook {} delivered event {}", &url, &event);
+                return;
+            }
+            Err(err) => tracing::warn!(
+                "Webhook {} failed to deliver event {}, attempt {}: {}",
+                &url,
+                &event,
+                attempt,
+                err
+            ),
+        }
+
+        if attempt < MAX_ATTEMPTS {
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+
+    tracing::error!("Webhook {} dropped event {}", &url, &event);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{deliver, resolve_target, validate_url, WebhookError};
+
+    #[actix_web::test]
+    async fn test_validate_url() {
+        assert_eq!(validate_url("http://8.8.8.8/hook").await, Ok(()));
+        assert_eq!(
+            validate_url("ftp://8.8.8.8/hook").await,
+            Err(WebhookError::InvalidUrl)
+        );
+        assert_eq!(
+            validate_url("not a url").await,
+            Err(WebhookError::InvalidUrl)
+        );
+        for url in [
+            "http://127.0.0.1:8083/",
+            "http://10.0.0.1/",
+            "http://192.168.1.1/",
+            "http://169.254.169.254/latest/meta-data",
+            "http://100.64.0.1/",
+            "http://0.0.0.0/",
+            "http://[::1]/",
+            "http://[fd00::1]/",
+            "http://[::ffff:127.0.0.1]/",
+            "http://localhost:8083/",
+        ] {
+            assert_eq!(
+                validate_url(url).await,
+                Err(WebhookError::PrivateTarget),
+                "{}",
+                url
+            );
+        }
+    }
+    #[actix_web::test]
+    async fn test_resolve_target() {
+        let target = resolve_target("http://8.8.8.8:8080/hook").await.unwrap();
+        assert_eq!(target.addrs, vec!["8.8.8.8:8080".parse().unwrap()]);
+    }
+
+    #[actix_web::test]
+    async fn test_deliver_private_target() {
+        // Private target is dropped at once instead of being retried
+        let delivery = deliver(
+            "http://127.0.0.1:8083/hook".to_owned(),
+            "figure_added".to_owned(),
+            "Main".to_owned(),
+            serde_json::json!({}),
+        );
+        assert!(tokio::time::timeout(Duration::from_millis(200), delivery)
+            .await
+            .is_ok());
+    }
+}
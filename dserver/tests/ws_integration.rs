@@ -0,0 +1,992 @@
+use std::{fs, path::PathBuf, time::Duration};
+
+use actix::Actor;
+use actix_web::{http::Method, web, App};
+use awc::ws::{Frame, Message, ProtocolError};
+use futures_util::{Sink, SinkExt, Stream, StreamExt};
+use serde_json::{json, Value};
+use tokio::time::{sleep, timeout};
+
+use jsonwebtoken::{encode, EncodingKey, Header};
+
+use dserver::{
+    auth::{Claims, JWT_SECRET_ENV},
+    config::ServerConfig,
+    cors::cors,
+    routes::{add_webhook, health_live, health_ready, metrics_route, ws_route},
+    server::DroServer,
+    wasm_msg::{from_bincode, to_bincode, WsMessages, PROTOCOL_VERSION, SCHEMA_VERSION},
+};
+
+/// Time to wait for the message delivery
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Start server with websocket, health and metrics routes
+fn start_server() -> actix_test::TestServer {
+    start_server_with_snapshots(None)
+}
+
+/// Subject of the test user
+const TEST_USER: &str = "test";
+
+/// Path of the websocket route for the client authenticated as test user
+fn ws_path(id: &str) -> String {
+    signed_ws_path(id, TEST_USER, None)
+}
+
+/// Secret used to sign tokens in tests
+const TEST_SECRET: &str = "integration-test-secret";
+
+/// Token of the user signed with test secret
+fn signed_token(subject: &str, role: Option<&str>) -> String {
+    let claims = Claims {
+        sub: subject.to_owned(),
+        name: None,
+        role: role.map(str::to_owned),
+        exp: 4_000_000_000,
+    };
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(TEST_SECRET.as_bytes()),
+    )
+    .unwrap()
+}
+
+/// Path of the websocket route for the client authenticated by signed token
+fn signed_ws_path(id: &str, subject: &str, role: Option<&str>) -> String {
+    format!("/ws/{}?token={}", id, signed_token(subject, role))
+}
+
+/// Start server restoring boards from the snapshot directory
+fn start_server_with_snapshots(snapshot_path: Option<PathBuf>) -> actix_test::TestServer {
+    start_server_with_config(snapshot_path, ServerConfig::default())
+}
+
+/// Start server with the websocket sessions configuration
+fn start_server_with_config(
+    snapshot_path: Option<PathBuf>,
+    config: ServerConfig,
+) -> actix_test::TestServer {
+    std::env::set_var(JWT_SECRET_ENV, TEST_SECRET);
+    let dro_srv = DroServer::new(snapshot_path).start();
+    actix_test::start(move || {
+        App::new()
+            .app_data(web::Data::new(dro_srv.clone()))
+            .app_data(web::Data::new(config))
+            .wrap(cors())
+            .route("/health/live", web::get().to(health_live))
+            .route("/health/ready", web::get().to(health_ready))
+            .route("/ws/{id}", web::get().to(ws_route))
+            .route("/api/boards/{name}/webhooks", web::post().to(add_webhook))
+            .route("/metrics", web::get().to(metrics_route))
+    })
+}
+
+/// Wrap messages into envelopes of the given schema version
+fn envelopes(messages: Value, schema_version: u8) -> Value {
+    match messages {
+        Value::Array(messages) => messages
+            .into_iter()
+            .map(|payload| json!({ "schema_version": schema_version, "payload": payload }))
+            .collect(),
+        messages => messages,
+    }
+}
+
+/// Take payloads out of the envelopes
+/// ### Return
+/// None if value is not an array of envelopes
+fn payloads(envelopes: Value) -> Option<Value> {
+    match envelopes {
+        Value::Array(envelopes) => envelopes
+            .into_iter()
+            .map(|mut env| env.get_mut("payload").map(Value::take))
+            .collect::<Option<Vec<_>>>()
+            .map(Value::Array),
+        _ => None,
+    }
+}
+
+/// Text frame with messages of the current schema version
+fn text_frame(messages: Value) -> Message {
+    Message::Text(envelopes(messages, SCHEMA_VERSION).to_string().into())
+}
+
+/// Send handshake and wait for acknowledgement, server drops other messages of the client
+/// until handshake is accepted
+async fn handshake<S>(conn: &mut S)
+where
+    S: Sink<Message> + Stream<Item = Result<Frame, ProtocolError>> + Unpin,
+    S::Error: std::fmt::Debug,
+{
+    let handshake = json!([{ "type": "Handshake", "version": PROTOCOL_VERSION }]);
+    conn.send(text_frame(handshake)).await.unwrap();
+    assert_eq!(
+        next_message(conn).await,
+        Some(json!([{ "type": "HandshakeAck", "version": PROTOCOL_VERSION }]))
+    );
+}
+
+/// Wait for the next text message skipping control frames
+/// ### Return
+/// payloads of the parsed message or None if nothing received within timeout
+async fn next_message<S>(conn: &mut S) -> Option<Value>
+where
+    S: Stream<Item = Result<Frame, ProtocolError>> + Unpin,
+{
+    loop {
+        match timeout(RECEIVE_TIMEOUT, conn.next()).await {
+            Ok(Some(Ok(Frame::Text(text)))) => {
+                return serde_json::from_slice(&text).ok().and_then(payloads)
+            }
+            Ok(Some(Ok(Frame::Ping(_) | Frame::Pong(_)))) => continue,
+            _ => return None,
+        }
+    }
+}
+
+/// Wait for the next binary message skipping control frames
+/// ### Return
+/// bincode decoded messages converted to json or None if nothing received within timeout
+async fn next_binary_message<S>(conn: &mut S) -> Option<Value>
+where
+    S: Stream<Item = Result<Frame, ProtocolError>> + Unpin,
+{
+    loop {
+        match timeout(RECEIVE_TIMEOUT, conn.next()).await {
+            Ok(Some(Ok(Frame::Binary(data)))) => {
+                return from_bincode(&data)
+                    .ok()
+                    .and_then(|messages| serde_json::to_value(messages).ok())
+            }
+            Ok(Some(Ok(Frame::Ping(_) | Frame::Pong(_)))) => continue,
+            _ => return None,
+        }
+    }
+}
+
+fn add_figure(user: &str) -> Value {
+    json!([{
+        "type": "AddFigure",
+        "rq": { "board": "Main", "user": user },
+        "figure_id": "0",
+        "shape": "Rect",
+        "rect": { "min": { "x": 10.0, "y": 20.0 }, "max": { "x": 110.0, "y": 70.0 } },
+        "text": "Figure"
+    }])
+}
+
+fn mouse_position(user: &str) -> Value {
+    json!([{
+        "type": "MousePosition",
+        "board": "Main",
+        "user": user,
+        "pos": { "x": 1.5, "y": 2.5 }
+    }])
+}
+
+fn board_request(message: &str, board: &str, user: &str) -> Value {
+    json!([{
+        "type": message,
+        "board_name": board,
+        "rq": { "board": board, "user": user }
+    }])
+}
+
+#[actix_web::test]
+async fn test_add_figure_is_broadcast() {
+    let srv = start_server();
+    let mut client_a = srv.ws_at(&ws_path("a")).await.unwrap();
+    handshake(&mut client_a).await;
+    let mut client_b = srv.ws_at(&ws_path("b")).await.unwrap();
+    handshake(&mut client_b).await;
+    // Sessions are registered in server asynchronously
+    sleep(Duration::from_millis(100)).await;
+
+    client_a.send(text_frame(add_figure("a"))).await.unwrap();
+
+    assert_eq!(
+        next_message(&mut client_b).await,
+        Some(add_figure("test/a"))
+    );
+}
+
+#[actix_web::test]
+async fn test_request_info_is_taken_from_session() {
+    let srv = start_server();
+    let mut client_a = srv.ws_at(&ws_path("a")).await.unwrap();
+    handshake(&mut client_a).await;
+    let mut client_b = srv.ws_at(&ws_path("b")).await.unwrap();
+    handshake(&mut client_b).await;
+    sleep(Duration::from_millis(100)).await;
+
+    client_b
+        .send(text_frame(board_request("CreateBoard", "Design", "b")))
+        .await
+        .unwrap();
+    assert!(next_message(&mut client_b).await.is_some());
+    client_b
+        .send(text_frame(board_request("JoinBoard", "Design", "b")))
+        .await
+        .unwrap();
+    sleep(Duration::from_millis(100)).await;
+
+    // Client can't post to the board it hasn't joined or on behalf of other user
+    let forged = json!([{
+        "type": "AddFigure",
+        "rq": { "board": "Design", "user": "b" },
+        "figure_id": "0",
+        "shape": "Rect",
+        "rect": { "min": { "x": 10.0, "y": 20.0 }, "max": { "x": 110.0, "y": 70.0 } },
+        "text": "Figure"
+    }]);
+    client_a.send(text_frame(forged)).await.unwrap();
+    assert_eq!(next_message(&mut client_b).await, None);
+}
+
+#[actix_web::test]
+async fn test_figure_changes_are_broadcast() {
+    let srv = start_server();
+    let mut client_a = srv.ws_at(&ws_path("a")).await.unwrap();
+    handshake(&mut client_a).await;
+    let mut client_b = srv.ws_at(&ws_path("b")).await.unwrap();
+    handshake(&mut client_b).await;
+    sleep(Duration::from_millis(100)).await;
+
+    let changes = |user: &str| {
+        json!([
+            {
+                "type": "MoveFigure",
+                "rq": { "board": "Main", "user": user },
+                "figure_id": "1",
+                "dx": 10.0,
+                "dy": -5.0
+            },
+            {
+                "type": "DeleteFigure",
+                "rq": { "board": "Main", "user": user },
+                "figure_id": "1"
+            }
+        ])
+    };
+    client_a.send(text_frame(changes("a"))).await.unwrap();
+
+    assert_eq!(next_message(&mut client_b).await, Some(changes("test/a")));
+}
+
+#[actix_web::test]
+async fn test_unsupported_schema_version_is_dropped() {
+    let srv = start_server();
+    let mut client_a = srv.ws_at(&ws_path("a")).await.unwrap();
+    handshake(&mut client_a).await;
+    let mut client_b = srv.ws_at(&ws_path("b")).await.unwrap();
+    handshake(&mut client_b).await;
+    sleep(Duration::from_millis(100)).await;
+
+    let message = envelopes(add_figure("a"), SCHEMA_VERSION + 1);
+    client_a
+        .send(Message::Text(message.to_string().into()))
+        .await
+        .unwrap();
+    assert_eq!(next_message(&mut client_b).await, None);
+
+    // Only the message of unknown version is dropped from the frame
+    let mut message = envelopes(add_figure("a"), SCHEMA_VERSION + 1);
+    if let (Value::Array(frame), Value::Array(known)) =
+        (&mut message, envelopes(add_figure("a"), SCHEMA_VERSION))
+    {
+        frame.extend(known);
+    }
+    client_a
+        .send(Message::Text(message.to_string().into()))
+        .await
+        .unwrap();
+    assert_eq!(
+        next_message(&mut client_b).await,
+        Some(add_figure("test/a"))
+    );
+}
+
+#[actix_web::test]
+async fn test_bare_handshake_version_mismatch() {
+    let srv = start_server();
+    let mut client = srv.ws_at(&ws_path("a")).await.unwrap();
+
+    // Clients preceding schema envelopes send handshake without envelope
+    let handshake = json!([{ "type": "Handshake", "version": 1 }]);
+    client
+        .send(Message::Text(handshake.to_string().into()))
+        .await
+        .unwrap();
+    assert_eq!(
+        next_message(&mut client).await,
+        Some(json!([{
+            "type": "VersionMismatch",
+            "server_version": PROTOCOL_VERSION,
+            "client_version": 1
+        }]))
+    );
+}
+
+#[actix_web::test]
+async fn test_duplicate_message_id_is_dropped() {
+    let srv = start_server();
+    let mut client_a = srv.ws_at(&ws_path("a")).await.unwrap();
+    handshake(&mut client_a).await;
+    let mut client_b = srv.ws_at(&ws_path("b")).await.unwrap();
+    handshake(&mut client_b).await;
+    sleep(Duration::from_millis(100)).await;
+
+    let move_figure = |user: &str, message_id: &str| {
+        json!([{
+            "type": "MoveFigure",
+            "rq": { "board": "Main", "user": user, "message_id": message_id },
+            "figure_id": "1",
+            "dx": 10.0,
+            "dy": -5.0
+        }])
+    };
+    client_a
+        .send(text_frame(move_figure("a", "1")))
+        .await
+        .unwrap();
+    assert_eq!(
+        next_message(&mut client_b).await,
+        Some(move_figure("test/a", "1"))
+    );
+
+    // Replayed frame is not broadcast again
+    client_a
+        .send(text_frame(move_figure("a", "1")))
+        .await
+        .unwrap();
+    assert_eq!(next_message(&mut client_b).await, None);
+
+    client_a
+        .send(text_frame(move_figure("a", "2")))
+        .await
+        .unwrap();
+    assert_eq!(
+        next_message(&mut client_b).await,
+        Some(move_figure("test/a", "2"))
+    );
+
+    // Frames replayed by the new session of the same user after reconnect are dropped
+    drop(client_a);
+    let mut client_c = srv.ws_at(&ws_path("c")).await.unwrap();
+    handshake(&mut client_c).await;
+    sleep(Duration::from_millis(100)).await;
+    client_c
+        .send(text_frame(move_figure("c", "2")))
+        .await
+        .unwrap();
+    assert_eq!(next_message(&mut client_b).await, None);
+}
+
+#[actix_web::test]
+async fn test_mouse_position_is_not_echoed() {
+    let srv = start_server();
+    let mut client_a = srv.ws_at(&ws_path("a")).await.unwrap();
+    handshake(&mut client_a).await;
+    let mut client_b = srv.ws_at(&ws_path("b")).await.unwrap();
+    handshake(&mut client_b).await;
+    sleep(Duration::from_millis(100)).await;
+
+    client_a
+        .send(text_frame(mouse_position("a")))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        next_message(&mut client_b).await,
+        Some(mouse_position("test/a"))
+    );
+    assert_eq!(next_message(&mut client_a).await, None);
+}
+
+#[actix_web::test]
+async fn test_handshake_version_mismatch() {
+    let srv = start_server();
+    let mut client = srv.ws_at(&ws_path("a")).await.unwrap();
+
+    let handshake = json!([{ "type": "Handshake", "version": PROTOCOL_VERSION }]);
+    client.send(text_frame(handshake)).await.unwrap();
+    assert_eq!(
+        next_message(&mut client).await,
+        Some(json!([{ "type": "HandshakeAck", "version": PROTOCOL_VERSION }]))
+    );
+
+    let handshake = json!([{ "type": "Handshake", "version": PROTOCOL_VERSION + 1 }]);
+    client.send(text_frame(handshake)).await.unwrap();
+    assert_eq!(
+        next_message(&mut client).await,
+        Some(json!([{
+            "type": "VersionMismatch",
+            "server_version": PROTOCOL_VERSION,
+            "client_version": PROTOCOL_VERSION + 1
+        }]))
+    );
+}
+
+#[actix_web::test]
+async fn test_messages_before_handshake_are_dropped() {
+    let srv = start_server();
+    let mut client_a = srv.ws_at(&ws_path("a")).await.unwrap();
+    let mut client_b = srv.ws_at(&ws_path("b")).await.unwrap();
+    handshake(&mut client_b).await;
+    sleep(Duration::from_millis(100)).await;
+
+    client_a.send(text_frame(add_figure("a"))).await.unwrap();
+    assert_eq!(next_message(&mut client_b).await, None);
+
+    // Session receives board messages after handshake only
+    client_b.send(text_frame(add_figure("b"))).await.unwrap();
+    assert_eq!(next_message(&mut client_a).await, None);
+
+    handshake(&mut client_a).await;
+    client_a.send(text_frame(add_figure("a"))).await.unwrap();
+    assert_eq!(
+        next_message(&mut client_b).await,
+        Some(add_figure("test/a"))
+    );
+}
+
+#[actix_web::test]
+async fn test_create_and_list_boards() {
+    let srv = start_server();
+    let mut client_a = srv.ws_at(&ws_path("a")).await.unwrap();
+    handshake(&mut client_a).await;
+    let mut client_b = srv.ws_at(&ws_path("b")).await.unwrap();
+    handshake(&mut client_b).await;
+    sleep(Duration::from_millis(100)).await;
+
+    client_a
+        .send(text_frame(board_request("CreateBoard", "Design", "a")))
+        .await
+        .unwrap();
+    assert_eq!(
+        next_message(&mut client_a).await,
+        Some(json!([{ "type": "BoardCreated", "board_name": "Design" }]))
+    );
+
+    let list = json!([{ "type": "ListBoards", "rq": { "board": "Main", "user": "a" } }]);
+    client_a.send(text_frame(list)).await.unwrap();
+    assert_eq!(
+        next_message(&mut client_a).await,
+        Some(json!([{ "type": "BoardList", "boards": ["Design", "Main"] }]))
+    );
+
+    // Board responses are sent to the requesting client only
+    assert_eq!(next_message(&mut client_b).await, None);
+}
+
+#[actix_web::test]
+async fn test_webhook_registration() {
+    let srv = start_server();
+    let token = signed_token(TEST_USER, None);
+    let register = |board: &str, token: Option<&str>, url: &str| {
+        let request = srv.post(format!("/api/boards/{}/webhooks", board));
+        let request = match token {
+            Some(token) => request.insert_header(("Authorization", format!("Bearer {}", token))),
+            None => request,
+        };
+        request.send_json(&json!({ "url": url, "events": ["figure_added"] }))
+    };
+
+    let response = register("Main", None, "http://8.8.8.8/hook").await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Board without owner is managed by administrators only
+    let response = register("Main", Some(token.as_str()), "http://8.8.8.8/hook")
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 403);
+
+    let mut client = srv.ws_at(&ws_path("a")).await.unwrap();
+    handshake(&mut client).await;
+    client
+        .send(text_frame(board_request("CreateBoard", "Design", "a")))
+        .await
+        .unwrap();
+    assert!(next_message(&mut client).await.is_some());
+
+    let response = register("Design", Some(token.as_str()), "http://127.0.0.1:8083/")
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+    let response = register("Unknown", Some(token.as_str()), "http://8.8.8.8/hook")
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+    let response = register("Design", Some(token.as_str()), "http://8.8.8.8/hook")
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+#[actix_web::test]
+async fn test_join_board() {
+    let srv = start_server();
+    let mut client_a = srv.ws_at(&ws_path("a")).await.unwrap();
+    handshake(&mut client_a).await;
+    let mut client_b = srv.ws_at(&ws_path("b")).await.unwrap();
+    handshake(&mut client_b).await;
+    sleep(Duration::from_millis(100)).await;
+
+    client_b
+        .send(text_frame(board_request("CreateBoard", "Design", "b")))
+        .await
+        .unwrap();
+    assert!(next_message(&mut client_b).await.is_some());
+    client_b
+        .send(text_frame(board_request("JoinBoard", "Design", "b")))
+        .await
+        .unwrap();
+    sleep(Duration::from_millis(100)).await;
+
+    // Client b left the main board
+    client_a.send(text_frame(add_figure("a"))).await.unwrap();
+    assert_eq!(next_message(&mut client_b).await, None);
+}
+
+#[actix_web::test]
+async fn test_history_is_compacted() {
+    let srv = start_server();
+    let mut client_a = srv.ws_at(&ws_path("a")).await.unwrap();
+    handshake(&mut client_a).await;
+    sleep(Duration::from_millis(100)).await;
+
+    let move_figure = |user: &str, figure_id: &str, dx: f32| {
+        json!({
+            "type": "MoveFigure",
+            "rq": { "board": "Main", "user": user },
+            "figure_id": figure_id,
+            "dx": dx,
+            "dy": -5.0
+        })
+    };
+    let update_text = |user: &str, text: &str| {
+        json!({
+            "type": "UpdateFigureText",
+            "rq": { "board": "Main", "user": user },
+            "figure_id": "1",
+            "text": text
+        })
+    };
+    let delete_figure = |user: &str, figure_id: &str| {
+        json!({
+            "type": "DeleteFigure",
+            "rq": { "board": "Main", "user": user },
+            "figure_id": figure_id
+        })
+    };
+    let added = |user: &str, figure_id: &str| {
+        let mut figure = add_figure(user)[0].take();
+        figure["figure_id"] = json!(figure_id);
+        figure
+    };
+    let changes = json!([
+        move_figure("a", "1", 10.),
+        move_figure("a", "2", 10.),
+        move_figure("a", "1", 10.),
+        update_text("a", "First"),
+        update_text("a", "Second"),
+        delete_figure("a", "2"),
+        added("a", "3"),
+        move_figure("a", "3", 10.),
+        added("a", "4"),
+        delete_figure("a", "4"),
+    ]);
+    client_a.send(text_frame(changes)).await.unwrap();
+    sleep(Duration::from_millis(100)).await;
+
+    // Moves and text edits are merged, changes of the deleted figure are removed. Figure
+    // added and deleted meanwhile is not replayed at all
+    let mut client_b = srv.ws_at(&ws_path("b")).await.unwrap();
+    handshake(&mut client_b).await;
+    let mut moved = move_figure("test/a", "1", 20.);
+    moved["dy"] = json!(-10.0);
+    let mut added_moved = added("test/a", "3");
+    added_moved["rect"] =
+        json!({ "min": { "x": 20.0, "y": 15.0 }, "max": { "x": 120.0, "y": 65.0 } });
+    assert_eq!(
+        next_message(&mut client_b).await,
+        Some(json!([
+            moved,
+            update_text("test/a", "Second"),
+            delete_figure("test/a", "2"),
+            added_moved
+        ]))
+    );
+}
+
+#[actix_web::test]
+async fn test_snapshot_is_replayed_on_connect() {
+    let dir = std::env::temp_dir().join(format!("dserver-snapshots-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let snapshot = json!({ "sessions": ["a"], "messages": add_figure("a") });
+    fs::write(dir.join("Main.json"), snapshot.to_string()).unwrap();
+
+    let srv = start_server_with_snapshots(Some(dir.clone()));
+    let mut client = srv.ws_at(&ws_path("b")).await.unwrap();
+    handshake(&mut client).await;
+    let received = next_message(&mut client).await;
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(received, Some(add_figure("a")));
+}
+
+#[actix_web::test]
+async fn test_connection_requires_token() {
+    let srv = start_server();
+    assert!(srv.ws_at("/ws/a").await.is_err());
+    assert!(srv.ws_at("/ws/a?token=wrong").await.is_err());
+    assert!(srv.ws_at(&ws_path("a")).await.is_ok());
+}
+
+#[actix_web::test]
+async fn test_session_id_is_bound_to_user() {
+    let srv = start_server();
+    let mut alice = srv
+        .ws_at(&signed_ws_path("a", "alice", None))
+        .await
+        .unwrap();
+    handshake(&mut alice).await;
+    // Session with the same id of other user doesn't replace the first one
+    let mut bob = srv.ws_at(&signed_ws_path("a", "bob", None)).await.unwrap();
+    handshake(&mut bob).await;
+    sleep(Duration::from_millis(100)).await;
+
+    alice.send(text_frame(add_figure("a"))).await.unwrap();
+    assert_eq!(next_message(&mut bob).await, Some(add_figure("alice/a")));
+    bob.send(text_frame(add_figure("a"))).await.unwrap();
+    assert_eq!(next_message(&mut alice).await, Some(add_figure("bob/a")));
+}
+
+#[actix_web::test]
+async fn test_board_members() {
+    let srv = start_server();
+    let mut client_a = srv
+        .ws_at(&signed_ws_path("a", "alice", None))
+        .await
+        .unwrap();
+    handshake(&mut client_a).await;
+    let mut client_b = srv.ws_at(&signed_ws_path("b", "bob", None)).await.unwrap();
+    handshake(&mut client_b).await;
+    sleep(Duration::from_millis(100)).await;
+
+    client_a
+        .send(text_frame(board_request("GetBoardMembers", "Main", "a")))
+        .await
+        .unwrap();
+    assert_eq!(
+        next_message(&mut client_a).await,
+        Some(json!([{ "type": "BoardMembers", "board": "Main", "members": ["alice", "bob"] }]))
+    );
+
+    // Members are sent to the requesting client only
+    assert_eq!(next_message(&mut client_b).await, None);
+}
+
+#[actix_web::test]
+async fn test_board_acl() {
+    let srv = start_server();
+    let mut admin = srv
+        .ws_at(&signed_ws_path("a", "alice", Some("admin")))
+        .await
+        .unwrap();
+    handshake(&mut admin).await;
+    let mut client_b = srv.ws_at(&signed_ws_path("b", "bob", None)).await.unwrap();
+    handshake(&mut client_b).await;
+    sleep(Duration::from_millis(100)).await;
+
+    // Not administrator can't change access
+    let acl = |user: &str| {
+        json!([{
+            "type": "BoardAcl",
+            "rq": { "board": "Main", "user": user },
+            "board": "Main",
+            "allowed_users": ["alice"]
+        }])
+    };
+    client_b.send(text_frame(acl("b"))).await.unwrap();
+    sleep(Duration::from_millis(100)).await;
+    client_b.send(text_frame(add_figure("b"))).await.unwrap();
+    assert_eq!(next_message(&mut admin).await, Some(add_figure("bob/b")));
+
+    admin.send(text_frame(acl("a"))).await.unwrap();
+    sleep(Duration::from_millis(100)).await;
+
+    // Messages of the user without access are dropped
+    client_b.send(text_frame(add_figure("b"))).await.unwrap();
+    assert_eq!(next_message(&mut admin).await, None);
+
+    // User without access is removed from the board
+    admin.send(text_frame(add_figure("a"))).await.unwrap();
+    assert_eq!(next_message(&mut client_b).await, None);
+}
+
+#[actix_web::test]
+async fn test_metrics() {
+    let srv = start_server();
+    let mut client = srv.ws_at(&ws_path("a")).await.unwrap();
+    handshake(&mut client).await;
+    client.send(text_frame(add_figure("a"))).await.unwrap();
+    sleep(Duration::from_millis(100)).await;
+
+    let mut response = srv.get("/metrics").send().await.unwrap();
+    assert!(response.status().is_success());
+    let body = response.body().await.unwrap();
+    let body = String::from_utf8_lossy(&body);
+    for name in [
+        "diadro_active_sessions",
+        "diadro_messages_received_total",
+        "diadro_messages_broadcast_total",
+        "diadro_boards_total",
+    ] {
+        assert!(body.contains(name), "metric {} is not exported", name);
+    }
+}
+
+#[actix_web::test]
+async fn test_health() {
+    let srv = start_server();
+    for path in ["/health/live", "/health/ready"] {
+        let mut response = srv.get(path).send().await.unwrap();
+        assert!(response.status().is_success(), "{} failed", path);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body, json!({"status": "ok"}));
+    }
+}
+
+#[actix_web::test]
+async fn test_cors_preflight() {
+    let srv = start_server();
+    let origin = "https://cdn.example.com";
+    let response = srv
+        .request(Method::OPTIONS, srv.url("/health/live"))
+        .insert_header(("Origin", origin))
+        .insert_header(("Access-Control-Request-Method", "GET"))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(
+        response
+            .headers()
+            .get("Access-Control-Allow-Origin")
+            .and_then(|value| value.to_str().ok()),
+        Some(origin)
+    );
+}
+
+#[actix_web::test]
+async fn test_bincode_encoding() {
+    let srv = start_server();
+    let mut client_a = srv.ws_at(&ws_path("a")).await.unwrap();
+    handshake(&mut client_a).await;
+    let mut client_b = srv.ws_at(&ws_path("b")).await.unwrap();
+    handshake(&mut client_b).await;
+    sleep(Duration::from_millis(100)).await;
+
+    let hello = json!([{ "type": "Hello", "preferred_encoding": "bincode" }]);
+    client_a.send(text_frame(hello)).await.unwrap();
+    assert_eq!(
+        next_message(&mut client_a).await,
+        Some(json!([{ "type": "HelloAck", "encoding": "bincode" }]))
+    );
+
+    // Json client message is delivered to bincode client as binary frame
+    client_b.send(text_frame(add_figure("b"))).await.unwrap();
+    assert_eq!(
+        next_binary_message(&mut client_a).await,
+        Some(add_figure("test/b"))
+    );
+
+    // Binary client message is delivered to json client as text frame
+    let messages: Vec<WsMessages> = serde_json::from_value(add_figure("a")).unwrap();
+    client_a
+        .send(Message::Binary(to_bincode(&messages).unwrap().into()))
+        .await
+        .unwrap();
+    assert_eq!(
+        next_message(&mut client_b).await,
+        Some(add_figure("test/a"))
+    );
+}
+
+#[actix_web::test]
+async fn test_unknown_encoding_falls_back_to_json() {
+    let srv = start_server();
+    let mut client = srv.ws_at(&ws_path("a")).await.unwrap();
+    handshake(&mut client).await;
+
+    let hello = json!([{ "type": "Hello", "preferred_encoding": "cbor" }]);
+    client.send(text_frame(hello)).await.unwrap();
+    assert_eq!(
+        next_message(&mut client).await,
+        Some(json!([{ "type": "HelloAck", "encoding": "json" }]))
+    );
+}
+
+#[actix_web::test]
+async fn test_rate_limit() {
+    let srv = start_server_with_config(
+        None,
+        ServerConfig {
+            rate_limit: 5.,
+            ..Default::default()
+        },
+    );
+    let mut client_a = srv.ws_at(&ws_path("a")).await.unwrap();
+    handshake(&mut client_a).await;
+    let mut client_b = srv.ws_at(&ws_path("b")).await.unwrap();
+    handshake(&mut client_b).await;
+    sleep(Duration::from_millis(100)).await;
+
+    let flood = Value::Array(vec![add_figure("a")[0].clone(); 20]);
+    client_a.send(text_frame(flood)).await.unwrap();
+
+    assert_eq!(
+        next_message(&mut client_b).await,
+        Some(Value::Array(vec![add_figure("test/a")[0].clone(); 5]))
+    );
+}
+
+#[actix_web::test]
+async fn test_pages_are_stored() {
+    let srv = start_server();
+    let mut client_a = srv.ws_at(&ws_path("a")).await.unwrap();
+    handshake(&mut client_a).await;
+    let mut client_b = srv.ws_at(&ws_path("b")).await.unwrap();
+    handshake(&mut client_b).await;
+    sleep(Duration::from_millis(100)).await;
+
+    let pages = |user: &str| {
+        json!([
+            {
+                "type": "AddPage",
+                "rq": { "board": "Main", "user": user },
+                "page_id": "deployment",
+                "page_name": "Deployment"
+            },
+            {
+                "type": "MoveFigure",
+                "rq": { "board": "Main", "user": user, "page_id": "deployment" },
+                "figure_id": "1",
+                "dx": 10.0,
+                "dy": -5.0
+            }
+        ])
+    };
+    client_a.send(text_frame(pages("a"))).await.unwrap();
+    assert_eq!(next_message(&mut client_b).await, Some(pages("test/a")));
+
+    // Joined client receives pages of the board before diagram messages, which keep the page
+    let mut client_c = srv.ws_at(&ws_path("c")).await.unwrap();
+    handshake(&mut client_c).await;
+    assert_eq!(
+        next_message(&mut client_c).await,
+        Some(json!([{
+            "type": "AddPage",
+            "rq": { "board": "Main", "user": "" },
+            "page_id": "deployment",
+            "page_name": "Deployment"
+        }]))
+    );
+    assert_eq!(
+        next_message(&mut client_c).await,
+        Some(json!([pages("test/a")[1]]))
+    );
+}
+
+#[actix_web::test]
+async fn test_selection_change_is_not_stored() {
+    let srv = start_server();
+    let mut client_a = srv.ws_at(&ws_path("a")).await.unwrap();
+    handshake(&mut client_a).await;
+    let mut client_b = srv.ws_at(&ws_path("b")).await.unwrap();
+    handshake(&mut client_b).await;
+    sleep(Duration::from_millis(100)).await;
+
+    let selection = |user: &str| {
+        json!([{
+            "type": "SelectionChange",
+            "rq": { "board": "Main", "user": user },
+            "selected_ids": ["1", "2"]
+        }])
+    };
+    client_a.send(text_frame(selection("a"))).await.unwrap();
+    assert_eq!(next_message(&mut client_b).await, Some(selection("test/a")));
+
+    // Selection is not a diagram change, so it is not replayed to joined clients
+    let mut client_c = srv.ws_at(&ws_path("c")).await.unwrap();
+    handshake(&mut client_c).await;
+    assert_eq!(next_message(&mut client_c).await, None);
+}
+
+#[actix_web::test]
+async fn test_selection_is_cleared_when_user_leaves() {
+    let srv = start_server();
+    let mut client_a = srv.ws_at(&ws_path("a")).await.unwrap();
+    handshake(&mut client_a).await;
+    let mut client_b = srv.ws_at(&ws_path("b")).await.unwrap();
+    handshake(&mut client_b).await;
+    sleep(Duration::from_millis(100)).await;
+
+    let selection = |user: &str, selected_ids: Value| {
+        json!([{
+            "type": "SelectionChange",
+            "rq": { "board": "Main", "user": user },
+            "selected_ids": selected_ids
+        }])
+    };
+    client_a
+        .send(text_frame(selection("a", json!(["1"]))))
+        .await
+        .unwrap();
+    assert_eq!(
+        next_message(&mut client_b).await,
+        Some(selection("test/a", json!(["1"])))
+    );
+
+    // Other users forget selection of the disconnected user
+    client_a.send(Message::Close(None)).await.unwrap();
+    assert_eq!(
+        next_message(&mut client_b).await,
+        Some(selection("test/a", json!([])))
+    );
+}
+
+#[actix_web::test]
+async fn test_metadata_is_stored() {
+    let srv = start_server();
+    let mut client_a = srv.ws_at(&ws_path("a")).await.unwrap();
+    handshake(&mut client_a).await;
+    sleep(Duration::from_millis(100)).await;
+
+    let metadata = json!([{
+        "type": "UpdateMetadata",
+        "rq": { "board": "Main", "user": "a" },
+        "title": "Deployment",
+        "author": "a",
+        "description": "Services and databases"
+    }]);
+    client_a.send(text_frame(metadata)).await.unwrap();
+    sleep(Duration::from_millis(100)).await;
+
+    // Joined client receives the last metadata of the board
+    let mut client_b = srv.ws_at(&ws_path("b")).await.unwrap();
+    handshake(&mut client_b).await;
+    assert_eq!(
+        next_message(&mut client_b).await,
+        Some(json!([{
+            "type": "UpdateMetadata",
+            "rq": { "board": "Main", "user": "" },
+            "title": "Deployment",
+            "author": "a",
+            "description": "Services and databases"
+        }]))
+    );
+}
@@ -0,0 +1,8 @@
+/// Version of the websocket protocol shared by the server and the client.
+/// Must be increased on any incompatible change of `WsMessages`
+///
+/// * 1 - initial handshake
+/// * 2 - boards, pages and page identifiers of the messages, selection and metadata messages,
+///   schema envelopes, encoding negotiated after handshake acknowledgement, length prefixed
+///   bincode payloads, shared figure identifiers, shapes and labels in `AddFigure`
+pub const PROTOCOL_VERSION: u32 = 2;